@@ -0,0 +1,18 @@
+-- Keep the SQL portable, the same migrations run on both SQLite and Postgres.
+
+CREATE TABLE karma (
+    chat_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    -- Name of the user when the karma was last given
+    username TEXT NOT NULL,
+    karma BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (chat_id, user_id)
+);
+
+CREATE INDEX karma_leaderboard ON karma (chat_id, karma DESC);
+
+CREATE TABLE global_admins (
+    user_id BIGINT PRIMARY KEY,
+    -- Unix timestamp in seconds
+    added_at BIGINT NOT NULL
+);
@@ -0,0 +1,9 @@
+CREATE TABLE reaction_rules (
+    chat_id BIGINT NOT NULL,
+    emoji TEXT NOT NULL,
+    -- `pin` or `delete`
+    action TEXT NOT NULL,
+    -- Reactions needed to trigger the action
+    threshold BIGINT NOT NULL,
+    PRIMARY KEY (chat_id, emoji)
+);
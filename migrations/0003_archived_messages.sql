@@ -0,0 +1,14 @@
+-- Messages kept by the opt-in archive module, for /export
+CREATE TABLE archived_messages (
+    chat_id BIGINT NOT NULL,
+    message_id BIGINT NOT NULL,
+    user_id BIGINT,
+    -- Name of the sender when the message was sent
+    username TEXT NOT NULL,
+    text TEXT NOT NULL,
+    -- Unix timestamp in seconds
+    sent_at BIGINT NOT NULL,
+    PRIMARY KEY (chat_id, message_id)
+);
+
+CREATE INDEX archived_messages_time ON archived_messages (chat_id, sent_at);
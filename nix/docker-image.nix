@@ -5,7 +5,7 @@
 , cacert
 , yt-dlp
 , ffmpeg
-, netcat-openbsd
+, curl
 
 , bash
 , writeShellScriptBin
@@ -29,7 +29,7 @@ let
       healthcheck = {
         test = [
           "CMD-SHELL"
-          "${netcat-openbsd}/bin/nc -z 127.0.0.1 11451 || exit 1"
+          "${curl}/bin/curl -fsS -m 10 http://127.0.0.1:11451/healthz || exit 1"
         ];
       };
     };
@@ -0,0 +1,173 @@
+use std::time::Duration;
+
+use redis::Commands;
+use serde::{Deserialize, Serialize};
+use teloxide::types::{ChatId, Message};
+
+use crate::app::AppData;
+
+/// The album is complete when no more part comes in this time. Telegram sends the parts of an
+/// album within a second.
+const SETTLE_TIME: Duration = Duration::from_millis(1500);
+/// Keep the parts for the commands replying to the album later
+const ALBUM_TTL: i64 = 60 * 60 * 24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaKind {
+    Photo,
+    Video,
+    Document,
+    Audio,
+}
+
+/// One message of the album.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AlbumPart {
+    pub message_id: i32,
+    pub kind: MediaKind,
+    /// File id of the media, the largest size for the photo
+    pub file_id: String,
+    pub caption: Option<String>,
+}
+
+impl AlbumPart {
+    fn of(msg: &Message) -> Option<Self> {
+        let (kind, file) = if let Some(sizes) = msg.photo() {
+            let largest = sizes.iter().max_by_key(|size| size.width)?;
+            (MediaKind::Photo, &largest.file)
+        } else if let Some(video) = msg.video() {
+            (MediaKind::Video, &video.file)
+        } else if let Some(document) = msg.document() {
+            (MediaKind::Document, &document.file)
+        } else if let Some(audio) = msg.audio() {
+            (MediaKind::Audio, &audio.file)
+        } else {
+            return None;
+        };
+        Some(Self {
+            message_id: msg.id.0,
+            kind,
+            file_id: file.id.to_string(),
+            caption: msg.caption().map(str::to_string),
+        })
+    }
+}
+
+/// The messages sharing a `media_group_id`, which Telegram delivers one by one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Album {
+    pub chat_id: ChatId,
+    pub media_group_id: String,
+    /// In the order of the messages
+    pub parts: Vec<AlbumPart>,
+}
+
+impl Album {
+    /// The caption of the album, Telegram shows the caption of the only part having one.
+    pub fn caption(&self) -> Option<&str> {
+        self.parts.iter().find_map(|part| part.caption.as_deref())
+    }
+
+    pub fn file_ids(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().map(|part| part.file_id.as_str())
+    }
+}
+
+fn media_group_of(msg: &Message) -> Option<String> {
+    msg.media_group_id().map(|id| id.to_string())
+}
+
+fn album_key(chat_id: ChatId, media_group_id: &str) -> String {
+    format!("MEDIA_GROUP:{chat_id}:{media_group_id}")
+}
+
+/// Keep the message if it is a part of an album, return the parts kept so far.
+pub fn push_part(data: &AppData, msg: &Message) -> anyhow::Result<Option<usize>> {
+    let (Some(media_group_id), Some(part)) = (media_group_of(msg), AlbumPart::of(msg)) else {
+        return Ok(None);
+    };
+    let key = album_key(msg.chat.id, &media_group_id);
+    let mut conn = data.cacher.get_conn();
+    // Keyed by the message id, so the redelivered update is not counted twice
+    let () = conn.hset(&key, part.message_id, serde_json::to_string(&part)?)?;
+    let () = conn.expire(&key, ALBUM_TTL)?;
+    let parts: usize = conn.hlen(&key)?;
+    Ok(Some(parts))
+}
+
+/// The parts of the album kept so far.
+pub fn get(data: &AppData, chat_id: ChatId, media_group_id: &str) -> anyhow::Result<Option<Album>> {
+    let encoded: Vec<String> = data
+        .cacher
+        .get_conn()
+        .hvals(album_key(chat_id, media_group_id))?;
+    if encoded.is_empty() {
+        return Ok(None);
+    }
+    let mut parts = encoded
+        .iter()
+        .map(|part| serde_json::from_str(part))
+        .collect::<Result<Vec<AlbumPart>, _>>()?;
+    parts.sort_by_key(|part| part.message_id);
+    Ok(Some(Album {
+        chat_id,
+        media_group_id: media_group_id.to_string(),
+        parts,
+    }))
+}
+
+/// The whole album of the message, for the commands replying to one photo of an album.
+pub fn of_message(data: &AppData, msg: &Message) -> anyhow::Result<Option<Album>> {
+    match media_group_of(msg) {
+        Some(media_group_id) => get(data, msg.chat.id, &media_group_id),
+        None => Ok(None),
+    }
+}
+
+/// Keep the part, and return the complete album when no more part comes in a while. Only the
+/// call with the last part gets the album, the others return none. This waits for a moment, so
+/// spawn it instead of blocking the updates of the chat.
+pub async fn collect(data: &AppData, msg: &Message) -> anyhow::Result<Option<Album>> {
+    let Some(parts) = push_part(data, msg)? else {
+        return Ok(None);
+    };
+    tokio::time::sleep(SETTLE_TIME).await;
+    let album = of_message(data, msg)?;
+    // Another part came during the wait, the call of that part takes over
+    Ok(album.filter(|album| album.parts.len() == parts))
+}
+
+#[tokio::test]
+async fn test_collect() {
+    let data = crate::testkit::app_data(teloxide::Bot::new("1000:fake-token")).await;
+    let part = |message_id: i32, caption: Option<&str>| -> Message {
+        serde_json::from_value(serde_json::json!({
+            "message_id": message_id,
+            "date": 0,
+            "chat": { "id": -100, "type": "supergroup", "title": "Group" },
+            "media_group_id": "album",
+            "photo": [{
+                "file_id": format!("photo-{message_id}"),
+                "file_unique_id": format!("unique-{message_id}"),
+                "width": 100,
+                "height": 100,
+            }],
+            "caption": caption,
+        }))
+        .unwrap()
+    };
+
+    let first = tokio::spawn({
+        let (data, msg) = (data.clone(), part(2, Some("trip")));
+        async move { collect(&data, &msg).await.unwrap() }
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let last = collect(&data, &part(1, None)).await.unwrap();
+
+    assert_eq!(first.await.unwrap(), None);
+    let album = last.unwrap();
+    assert_eq!(album.file_ids().collect::<Vec<_>>(), ["photo-1", "photo-2"]);
+    assert_eq!(album.caption(), Some("trip"));
+    assert_eq!(of_message(&data, &part(1, None)).unwrap(), Some(album));
+}
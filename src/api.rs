@@ -0,0 +1,218 @@
+use std::{any::Any, future::IntoFuture, time::Duration};
+
+use redis::Commands;
+use teloxide::{
+    types::{ChatId, Message, MessageId},
+    ApiError, RequestError,
+};
+
+use crate::{app::AppData, chat_migration, metrics, module::ModuleRegistry, send_queue::Priority};
+
+/// Give up after Telegram asks the direct call to retry for this many times
+const MAX_ATTEMPTS: u32 = 3;
+/// Fail the call instead of waiting longer than this for the flood limit
+const MAX_RETRY_WAIT: Duration = Duration::from_secs(60);
+/// Remember the messages sent by the bot as long as it can delete them
+const SENT_TTL: i64 = 60 * 60 * 48;
+
+/// The errors telling that the bot can't send to the chat anymore
+pub fn is_unreachable(err: &RequestError) -> bool {
+    matches!(
+        err,
+        RequestError::Api(
+            ApiError::BotBlocked
+                | ApiError::BotKicked
+                | ApiError::BotKickedFromSupergroup
+                | ApiError::ChatNotFound
+                | ApiError::UserDeactivated
+                | ApiError::GroupDeactivated
+        )
+    )
+}
+
+/// Time to wait before retrying the failed call, or none to give up. `attempts` counts the
+/// failed one.
+fn retry_wait(err: &RequestError, attempts: u32) -> Option<Duration> {
+    match err {
+        RequestError::RetryAfter(secs)
+            if attempts < MAX_ATTEMPTS && secs.duration() <= MAX_RETRY_WAIT =>
+        {
+            Some(secs.duration())
+        }
+        _ => None,
+    }
+}
+
+impl AppData {
+    /// Call the Bot API for the chat right away. The call sleeps and retries when hitting the
+    /// flood limit, and the chat is forgotten when it blocks or removes the bot. The `request`
+    /// might be called more than once.
+    ///
+    /// ```ignore
+    /// data.call(chat_id, || bot.send_message(chat_id, &text)).await?;
+    /// ```
+    pub async fn call<T, F, Req>(&self, chat_id: ChatId, request: F) -> anyhow::Result<T>
+    where
+        F: Fn() -> Req,
+        Req: IntoFuture<Output = Result<T, RequestError>>,
+    {
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let err = match request().await {
+                Ok(response) => return Ok(response),
+                Err(err) => err,
+            };
+            let Some(wait) = retry_wait(&err, attempts) else {
+                return Err(self.call_failed(chat_id, err.into()).await);
+            };
+            metrics::observe_api_failure(&err, "retried");
+            tracing::warn!(
+                "hit flood limit when calling for chat {chat_id}, retry after {}s",
+                wait.as_secs()
+            );
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Send through the [`crate::send_queue::SendQueue`], which handles the flood limit, and
+    /// forget the chat like [`AppData::call`] when it is unreachable. The messages sent are
+    /// remembered for [`sent_by_bot`].
+    pub async fn submit<T, F, Req>(
+        &self,
+        chat_id: ChatId,
+        priority: Priority,
+        request: F,
+    ) -> anyhow::Result<T>
+    where
+        T: Send + 'static,
+        F: Fn() -> Req + Send + 'static,
+        Req: IntoFuture<Output = Result<T, RequestError>>,
+        Req::IntoFuture: Send + 'static,
+    {
+        match self.send_queue.submit(chat_id, priority, request).await {
+            Ok(response) => {
+                if let Err(err) = remember_sent(self, &response) {
+                    tracing::error!("fail to remember the message sent to {chat_id}: {err}");
+                }
+                Ok(response)
+            }
+            Err(err) => Err(self.call_failed(chat_id, err).await),
+        }
+    }
+
+    /// Reply to the chat through [`AppData::submit`] as an interactive message. The request is
+    /// cloned when the queue sends it again.
+    ///
+    /// ```ignore
+    /// data.reply(msg.chat.id, bot.send_message_to(&msg, text)).await?;
+    /// ```
+    pub async fn reply<T, Req>(&self, chat_id: ChatId, request: Req) -> anyhow::Result<T>
+    where
+        T: Send + 'static,
+        Req: IntoFuture<Output = Result<T, RequestError>> + Clone + Send + 'static,
+        Req::IntoFuture: Send + 'static,
+    {
+        self.submit(chat_id, Priority::Interactive, move || request.clone())
+            .await
+    }
+
+    async fn call_failed(&self, chat_id: ChatId, err: anyhow::Error) -> anyhow::Error {
+        let Some(request_err) = err.downcast_ref::<RequestError>() else {
+            return err;
+        };
+        if let RequestError::MigrateToChatId(to) = request_err {
+            if let Err(err) = chat_migration::migrate_chat(self, chat_id, *to).await {
+                tracing::error!("fail to migrate chat {chat_id} to {to}: {err}");
+            }
+        }
+        if !is_unreachable(request_err) {
+            metrics::observe_api_failure(request_err, "returned");
+            return err;
+        }
+        metrics::observe_api_failure(request_err, "cleaned_up");
+        tracing::info!("chat {chat_id} is unreachable, forget it: {err}");
+        if let Err(err) = forget_chat(self, chat_id).await {
+            tracing::error!("fail to forget chat {chat_id}: {err}");
+        }
+        err
+    }
+}
+
+fn sent_key(chat_id: ChatId) -> String {
+    format!("BOT_SENT_MESSAGES:{chat_id}")
+}
+
+fn remember_sent(data: &AppData, response: &dyn Any) -> anyhow::Result<()> {
+    let messages = match response.downcast_ref::<Message>() {
+        Some(msg) => std::slice::from_ref(msg),
+        None => match response.downcast_ref::<Vec<Message>>() {
+            Some(messages) => messages.as_slice(),
+            None => return Ok(()),
+        },
+    };
+    let mut conn = data.cacher.get_conn();
+    for msg in messages {
+        let key = sent_key(msg.chat.id);
+        let () = conn.sadd(&key, msg.id.0)?;
+        let () = conn.expire(&key, SENT_TTL)?;
+    }
+    Ok(())
+}
+
+/// Whether the message is sent by the bot through [`AppData::submit`] in the last two days.
+pub fn sent_by_bot(data: &AppData, chat_id: ChatId, message_id: MessageId) -> anyhow::Result<bool> {
+    Ok(data
+        .cacher
+        .get_conn()
+        .sismember(sent_key(chat_id), message_id.0)?)
+}
+
+/// Remove the event subscriptions of the chat, and let every module clean up what it keeps for
+/// the chat in [`crate::module::BotModule::on_chat_unreachable`].
+pub async fn forget_chat(data: &AppData, chat_id: ChatId) -> anyhow::Result<()> {
+    let removed = data.cacher.unsubscribe_chat(chat_id.0)?;
+    tracing::info!("removed {removed} subscriptions of chat {chat_id}");
+    let Some(registry) = ModuleRegistry::try_global() else {
+        return Ok(());
+    };
+    for module in registry.modules() {
+        if let Err(err) = module.on_chat_unreachable(data, chat_id).await {
+            tracing::error!("[{}] fail to forget chat {chat_id}: {err}", module.name());
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_retry_wait() {
+    use teloxide::types::Seconds;
+
+    let retry = |secs| RequestError::RetryAfter(Seconds::from_seconds(secs));
+    assert_eq!(retry_wait(&retry(3), 1), Some(Duration::from_secs(3)));
+    assert_eq!(retry_wait(&retry(3), MAX_ATTEMPTS), None);
+    assert_eq!(retry_wait(&retry(600), 1), None);
+    let blocked = RequestError::Api(ApiError::BotBlocked);
+    assert_eq!(retry_wait(&blocked, 1), None);
+    assert!(is_unreachable(&blocked));
+    assert!(!is_unreachable(&retry(3)));
+}
+
+#[tokio::test]
+async fn test_sent_by_bot() {
+    use crate::testkit::{self, FakeTelegram};
+    use teloxide::prelude::*;
+
+    let telegram = FakeTelegram::start();
+    let bot = telegram.bot();
+    let data = testkit::app_data(bot.clone()).await;
+
+    let chat_id = ChatId(-100);
+    let sent: Message = data
+        .reply(chat_id, bot.send_message(chat_id, "hello"))
+        .await
+        .unwrap();
+    assert!(sent_by_bot(&data, chat_id, sent.id).unwrap());
+    assert!(!sent_by_bot(&data, chat_id, MessageId(sent.id.0 + 1)).unwrap());
+    assert!(!sent_by_bot(&data, ChatId(-200), sent.id).unwrap());
+}
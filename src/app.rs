@@ -1,10 +1,15 @@
+use std::collections::HashMap;
 use std::ops::Deref;
 use std::sync::Arc;
 
 use clearurl::UrlCleaner;
 use deepl::DeepLApi;
+use teloxide::Bot;
+use tokio::sync::watch;
 
-use crate::{cache::Cacher, http::HttpClient};
+use crate::{
+    cache::Cacher, config::MAIN_BOT, http::HttpClient, send_queue::SendQueue, storage::Storage,
+};
 
 pub struct AppData(Arc<RuntimeData>);
 
@@ -28,14 +33,68 @@ impl Deref for AppData {
     }
 }
 
+/// The bots of this process by name. Only the main bot receives updates, the others are used
+/// to send notifications.
+#[derive(Clone)]
+pub struct Bots(HashMap<String, Bot>);
+
+impl Bots {
+    pub fn new(main: Bot) -> Self {
+        Self(HashMap::from([(MAIN_BOT.to_string(), main)]))
+    }
+
+    pub fn with(mut self, name: impl Into<String>, bot: Bot) -> Self {
+        self.0.insert(name.into(), bot);
+        self
+    }
+
+    pub fn main(&self) -> &Bot {
+        &self.0[MAIN_BOT]
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Bot> {
+        self.0.get(name)
+    }
+
+    /// Name of the bot, matched by the token.
+    pub fn name_of(&self, bot: &Bot) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(_, known)| known.token() == bot.token())
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Get the bot by name, or the main bot when the name is not given or unknown.
+    pub fn pick(&self, name: Option<&str>) -> &Bot {
+        name.and_then(|name| self.get(name))
+            .unwrap_or_else(|| self.main())
+    }
+}
+
 #[derive(typed_builder::TypedBuilder)]
 pub struct RuntimeData {
+    pub bots: Bots,
     pub cacher: Cacher,
+    pub storage: Storage,
     pub requester: HttpClient,
 
-    pub deepl: DeepLApi,
+    /// Rebuilt when the API key or proxy is changed by config reload
+    pub deepl: watch::Receiver<DeepLApi>,
 
     pub quote_maker: make_quote::QuoteProducer<'static>,
 
     pub url_cleaner: UrlCleaner,
+
+    pub send_queue: SendQueue,
+}
+
+#[test]
+fn test_pick_bot() {
+    let bots = Bots::new(Bot::new("1:main")).with("announcer", Bot::new("2:announcer"));
+    assert_eq!(bots.pick(None).token(), "1:main");
+    assert_eq!(bots.pick(Some("announcer")).token(), "2:announcer");
+    assert_eq!(bots.pick(Some("unknown")).token(), "1:main");
+    assert!(bots.get("unknown").is_none());
+    assert_eq!(bots.name_of(&Bot::new("2:announcer")), Some("announcer"));
+    assert_eq!(bots.name_of(&Bot::new("3:other")), None);
 }
@@ -0,0 +1,442 @@
+//! Parse the arguments of a command. The text after the command is split by whitespace, with
+//! the quoted parts like `"New York"` kept together, and the handler takes the typed arguments
+//! one by one. An [`ArgError`] returned by the handler is replied with the usage of the command
+//! in the chat language, instead of being reported as a failure.
+
+use std::{collections::VecDeque, fmt::Display, str::FromStr, time::Duration};
+
+use teloxide::types::{ChatId, Message, MessageEntityKind, Recipient, UserId};
+
+/// The invalid input of a command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgError {
+    Missing {
+        name: &'static str,
+    },
+    Invalid {
+        name: &'static str,
+        value: String,
+        reason: String,
+    },
+    Unexpected {
+        value: String,
+    },
+    UnclosedQuote,
+}
+
+impl ArgError {
+    /// Describe the error in the language of the chat.
+    pub fn localize(&self, lang: &str) -> String {
+        match self {
+            Self::Missing { name } => crate::t!(lang, "args.missing", name = name),
+            Self::Invalid {
+                name,
+                value,
+                reason,
+            } => crate::t!(
+                lang,
+                "args.invalid",
+                name = name,
+                value = value,
+                reason = reason
+            ),
+            Self::Unexpected { value } => crate::t!(lang, "args.unexpected", value = value),
+            Self::UnclosedQuote => crate::t!(lang, "args.unclosed_quote"),
+        }
+    }
+}
+
+impl Display for ArgError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing { name } => write!(f, "missing {name}"),
+            Self::Invalid {
+                name,
+                value,
+                reason,
+            } => write!(f, "invalid {name} {value}: {reason}"),
+            Self::Unexpected { value } => write!(f, "unexpected argument {value}"),
+            Self::UnclosedQuote => write!(f, "the quote is not closed"),
+        }
+    }
+}
+
+impl std::error::Error for ArgError {}
+
+/// One argument of the command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Arg {
+    pub text: String,
+    /// The user of the text mention at the argument, which is how Telegram mentions the users
+    /// without a username
+    pub user: Option<UserId>,
+    /// Byte offset of the argument in the text after the command
+    start: usize,
+}
+
+/// Convert an argument to the typed value, or return why it is invalid.
+pub trait FromArg: Sized {
+    fn from_arg(arg: &Arg) -> Result<Self, String>;
+}
+
+impl<T> FromArg for T
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    fn from_arg(arg: &Arg) -> Result<Self, String> {
+        arg.text.parse().map_err(|err: T::Err| err.to_string())
+    }
+}
+
+/// The arguments of a command.
+///
+/// ```ignore
+/// let mut args = Args::parse(&msg)?;
+/// let days = args.optional::<u32>("days")?;
+/// let user = args.required::<UserRef>("user")?;
+/// args.finish()?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct Args {
+    /// The text after the command
+    text: String,
+    args: VecDeque<Arg>,
+}
+
+impl Args {
+    /// Parse the arguments of the command in the message, with the text mentions in it.
+    pub fn parse(msg: &Message) -> Result<Self, ArgError> {
+        let text = msg.text().unwrap_or_default();
+        let command_len = command_len(text);
+        let mut args = Self::from_text(text)?;
+
+        let mentions = msg.parse_entities().unwrap_or_default();
+        for arg in &mut args.args {
+            arg.user = mentions.iter().find_map(|entity| match entity.kind() {
+                MessageEntityKind::TextMention { user }
+                    if entity.start() == command_len + arg.start =>
+                {
+                    Some(user.id)
+                }
+                _ => None,
+            });
+        }
+        Ok(args)
+    }
+
+    /// Parse the arguments in the text, skipping the command if it starts with one.
+    pub fn from_text(text: &str) -> Result<Self, ArgError> {
+        let text = &text[command_len(text)..];
+        Ok(Self {
+            text: text.to_string(),
+            args: tokenize(text)?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.args.is_empty()
+    }
+
+    /// Look at the next argument without taking it, like a subcommand.
+    pub fn peek(&self) -> Option<&str> {
+        self.args.front().map(|arg| arg.text.as_str())
+    }
+
+    /// Take the next argument, and fail if there is none.
+    pub fn required<T: FromArg>(&mut self, name: &'static str) -> Result<T, ArgError> {
+        self.optional(name)?.ok_or(ArgError::Missing { name })
+    }
+
+    /// Take the next argument if there is one.
+    pub fn optional<T: FromArg>(&mut self, name: &'static str) -> Result<Option<T>, ArgError> {
+        let Some(arg) = self.args.pop_front() else {
+            return Ok(None);
+        };
+        T::from_arg(&arg)
+            .map(Some)
+            .map_err(|reason| ArgError::Invalid {
+                name,
+                value: arg.text,
+                reason,
+            })
+    }
+
+    /// Take the rest of the text as it is written, for the free text like a message.
+    pub fn rest(&mut self) -> Option<String> {
+        let start = self.args.front()?.start;
+        self.args.clear();
+        Some(self.text[start..].trim_end().to_string())
+    }
+
+    /// Fail if there are arguments left.
+    pub fn finish(&self) -> Result<(), ArgError> {
+        match self.args.front() {
+            Some(arg) => Err(ArgError::Unexpected {
+                value: arg.text.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Length of the `/command@bot` at the start of the text
+fn command_len(text: &str) -> usize {
+    if !text.starts_with('/') {
+        return 0;
+    }
+    text.find(char::is_whitespace).unwrap_or(text.len())
+}
+
+fn tokenize(text: &str) -> Result<VecDeque<Arg>, ArgError> {
+    let mut args = VecDeque::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let mut token = String::new();
+        if c == '"' {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some((_, '"')) => break,
+                    Some((_, c)) => token.push(c),
+                    None => return Err(ArgError::UnclosedQuote),
+                }
+            }
+        } else {
+            while let Some((_, c)) = chars.next_if(|(_, c)| !c.is_whitespace()) {
+                token.push(c);
+            }
+        }
+        args.push_back(Arg {
+            text: token,
+            user: None,
+            start,
+        });
+    }
+    Ok(args)
+}
+
+/// A span of time like `30s`, `5m`, `2h`, `1d` or `1h30m`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DurationArg(pub Duration);
+
+impl FromStr for DurationArg {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || anyhow::anyhow!("a duration should look like 30s, 5m, 2h or 1d");
+        let mut secs = 0_u64;
+        let mut number = String::new();
+        for c in s.chars() {
+            if c.is_ascii_digit() {
+                number.push(c);
+                continue;
+            }
+            let unit = match c.to_ascii_lowercase() {
+                's' => 1,
+                'm' => 60,
+                'h' => 60 * 60,
+                'd' => 24 * 60 * 60,
+                'w' => 7 * 24 * 60 * 60,
+                _ => return Err(invalid()),
+            };
+            let value: u64 = number.parse().map_err(|_| invalid())?;
+            secs = value
+                .checked_mul(unit)
+                .and_then(|value| secs.checked_add(value))
+                .ok_or_else(|| anyhow::anyhow!("the duration is too long"))?;
+            number.clear();
+        }
+        if !number.is_empty() || secs == 0 {
+            return Err(invalid());
+        }
+        Ok(Self(Duration::from_secs(secs)))
+    }
+}
+
+impl Display for DurationArg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut secs = self.0.as_secs();
+        if secs == 0 {
+            return write!(f, "0s");
+        }
+        for (unit, name) in [(24 * 60 * 60, "d"), (60 * 60, "h"), (60, "m"), (1, "s")] {
+            if secs >= unit {
+                write!(f, "{}{name}", secs / unit)?;
+                secs %= unit;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A user by the id, the `@username` or the text mention.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UserRef {
+    Id(UserId),
+    /// The username without `@`
+    Username(String),
+}
+
+impl UserRef {
+    /// The bot can't look up a user by the username, so only the id is usable for most APIs.
+    pub fn id(&self) -> Option<UserId> {
+        match self {
+            Self::Id(id) => Some(*id),
+            Self::Username(_) => None,
+        }
+    }
+}
+
+impl FromArg for UserRef {
+    fn from_arg(arg: &Arg) -> Result<Self, String> {
+        if let Some(user) = arg.user {
+            return Ok(Self::Id(user));
+        }
+        if let Some(name) = arg.text.strip_prefix('@') {
+            if is_username(name) {
+                return Ok(Self::Username(name.to_string()));
+            }
+        } else if let Ok(id) = arg.text.parse() {
+            return Ok(Self::Id(UserId(id)));
+        }
+        Err("a user should be a user id, a @username or a mention".to_string())
+    }
+}
+
+/// A chat by the id or the `@username` of a public chat.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChatRef {
+    Id(ChatId),
+    /// The username without `@`
+    Username(String),
+}
+
+impl FromStr for ChatRef {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(name) = s.strip_prefix('@') {
+            if is_username(name) {
+                return Ok(Self::Username(name.to_string()));
+            }
+        } else if let Ok(id) = s.parse() {
+            return Ok(Self::Id(ChatId(id)));
+        }
+        anyhow::bail!("a chat should be a chat id or a @username")
+    }
+}
+
+impl From<ChatRef> for Recipient {
+    fn from(chat: ChatRef) -> Self {
+        match chat {
+            ChatRef::Id(id) => Recipient::Id(id),
+            ChatRef::Username(name) => Recipient::ChannelUsername(format!("@{name}")),
+        }
+    }
+}
+
+fn is_username(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[test]
+fn test_args() {
+    let mut args = Args::from_text(r#"/weather@maid_bot "New York"  3d  extra words"#).unwrap();
+    assert_eq!(args.peek(), Some("New York"));
+    assert_eq!(args.required::<String>("city").unwrap(), "New York");
+    assert_eq!(
+        args.optional::<DurationArg>("days").unwrap(),
+        Some(DurationArg(Duration::from_secs(3 * 24 * 60 * 60)))
+    );
+    assert_eq!(
+        args.finish(),
+        Err(ArgError::Unexpected {
+            value: "extra".to_string()
+        })
+    );
+    assert_eq!(args.rest().as_deref(), Some("extra words"));
+    assert!(args.is_empty());
+    assert_eq!(args.optional::<u32>("count").unwrap(), None);
+    assert_eq!(
+        args.required::<u32>("count"),
+        Err(ArgError::Missing { name: "count" })
+    );
+
+    let mut args = Args::from_text("/retention many").unwrap();
+    assert!(matches!(
+        args.required::<u32>("days"),
+        Err(ArgError::Invalid { name: "days", value, .. }) if value == "many"
+    ));
+    assert_eq!(
+        Args::from_text(r#"/tr "unclosed"#).unwrap_err(),
+        ArgError::UnclosedQuote
+    );
+
+    let mut args = Args::from_text("add @alice 42 -100123 @news").unwrap();
+    assert_eq!(args.required::<String>("operation").unwrap(), "add");
+    assert_eq!(
+        args.required::<UserRef>("user").unwrap(),
+        UserRef::Username("alice".to_string())
+    );
+    assert_eq!(
+        args.required::<UserRef>("user").unwrap().id(),
+        Some(UserId(42))
+    );
+    assert_eq!(
+        args.required::<ChatRef>("chat").unwrap(),
+        ChatRef::Id(ChatId(-100123))
+    );
+    assert_eq!(
+        args.required::<ChatRef>("chat").unwrap(),
+        ChatRef::Username("news".to_string())
+    );
+}
+
+#[test]
+fn test_duration_arg() {
+    let parse = |s: &str| s.parse::<DurationArg>().map(|d| d.0.as_secs()).ok();
+    assert_eq!(parse("30s"), Some(30));
+    assert_eq!(parse("5m"), Some(300));
+    assert_eq!(parse("1h30m"), Some(5400));
+    assert_eq!(parse("2D"), Some(2 * 24 * 60 * 60));
+    assert_eq!(parse("1w"), Some(7 * 24 * 60 * 60));
+    assert_eq!(parse("90"), None);
+    assert_eq!(parse("0s"), None);
+    assert_eq!(parse("h"), None);
+    assert_eq!(parse("5x"), None);
+    assert_eq!(DurationArg(Duration::from_secs(5400)).to_string(), "1h30m");
+    assert_eq!(DurationArg(Duration::from_secs(86401)).to_string(), "1d1s");
+}
+
+#[test]
+fn test_text_mention() {
+    let msg: Message = serde_json::from_value(serde_json::json!({
+        "message_id": 1,
+        "date": 0,
+        "chat": { "id": -100123, "type": "supergroup", "title": "Group" },
+        "from": { "id": 1, "is_bot": false, "first_name": "Admin" },
+        "text": "/admin add 小明",
+        "entities": [
+            { "type": "bot_command", "offset": 0, "length": 6 },
+            {
+                "type": "text_mention",
+                "offset": 11,
+                "length": 2,
+                "user": { "id": 42, "is_bot": false, "first_name": "小明" }
+            }
+        ]
+    }))
+    .unwrap();
+    let mut args = Args::parse(&msg).unwrap();
+    assert_eq!(args.required::<String>("operation").unwrap(), "add");
+    assert_eq!(
+        args.required::<UserRef>("user").unwrap(),
+        UserRef::Id(UserId(42))
+    );
+}
@@ -0,0 +1,96 @@
+pub const USAGE: &str = "\
+Usage: tgbot [COMMAND]
+
+Commands:
+  run [--dry-run]                         Run the bot, the default command
+  check-config                            Validate the config and exit
+  export-state                            Print the database and Redis data as JSON
+  send --chat <ID> --text <TEXT> [--bot <NAME>]
+                                          Send a message by the bot
+  redis migrate                           Copy the Redis data of older versions missing in the database
+  help                                    Print this message";
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Run {
+        dry_run: bool,
+    },
+    CheckConfig,
+    ExportState,
+    Send {
+        chat: i64,
+        text: String,
+        /// Name in the `[bots]` section, default to the main bot
+        bot: Option<String>,
+    },
+    RedisMigrate,
+    Help,
+}
+
+/// Parse the arguments without the program name.
+pub fn parse(args: impl IntoIterator<Item = String>) -> anyhow::Result<Command> {
+    let args = args.into_iter().collect::<Vec<_>>();
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+
+    let command = match args.as_slice() {
+        [] | ["run"] => Command::Run { dry_run: false },
+        ["--dry-run"] | ["run", "--dry-run"] => Command::Run { dry_run: true },
+        ["check-config"] => Command::CheckConfig,
+        ["export-state"] => Command::ExportState,
+        ["send", options @ ..] => parse_send(options)?,
+        ["redis", "migrate"] => Command::RedisMigrate,
+        ["help" | "-h" | "--help"] => Command::Help,
+        _ => anyhow::bail!("unknown arguments `{}`\n\n{USAGE}", args.join(" ")),
+    };
+    Ok(command)
+}
+
+fn parse_send(mut options: &[&str]) -> anyhow::Result<Command> {
+    let (mut chat, mut text, mut bot) = (None, None, None);
+    while let [option, value, rest @ ..] = options {
+        match *option {
+            "--chat" => {
+                let id = value
+                    .parse::<i64>()
+                    .map_err(|_| anyhow::anyhow!("--chat `{value}` is not a chat id"))?;
+                chat = Some(id);
+            }
+            "--text" => text = Some(value.to_string()),
+            "--bot" => bot = Some(value.to_string()),
+            _ => anyhow::bail!("unknown option `{option}` for send\n\n{USAGE}"),
+        }
+        options = rest;
+    }
+    if let [option] = options {
+        anyhow::bail!("missing value for `{option}`\n\n{USAGE}");
+    }
+
+    match (chat, text) {
+        (Some(chat), Some(text)) => Ok(Command::Send { chat, text, bot }),
+        _ => anyhow::bail!("send needs both --chat and --text\n\n{USAGE}"),
+    }
+}
+
+#[test]
+fn test_parse_command() {
+    let parse = |args: &[&str]| parse(args.iter().map(|arg| arg.to_string()));
+
+    assert_eq!(parse(&[]).unwrap(), Command::Run { dry_run: false });
+    assert_eq!(
+        parse(&["run", "--dry-run"]).unwrap(),
+        Command::Run { dry_run: true }
+    );
+    assert_eq!(parse(&["redis", "migrate"]).unwrap(), Command::RedisMigrate);
+    assert_eq!(
+        parse(&["send", "--chat", "-100", "--text", "hello world"]).unwrap(),
+        Command::Send {
+            chat: -100,
+            text: "hello world".to_string(),
+            bot: None
+        }
+    );
+    assert!(parse(&["send", "--chat", "group", "--text", "hi"]).is_err());
+    assert!(parse(&["send", "--chat", "-100"]).is_err());
+    assert!(parse(&["send", "--chat", "-100", "--text"]).is_err());
+    assert!(parse(&["export"]).is_err());
+}
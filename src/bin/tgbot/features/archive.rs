@@ -0,0 +1,406 @@
+use anyhow::Result;
+use chrono::{Duration, Local, NaiveDate, TimeZone};
+use teloxide::{
+    prelude::*,
+    types::{InputFile, ParseMode},
+    utils::html,
+};
+
+use rusty_maid::{
+    app::AppData,
+    args::{ArgError, Args},
+    command::{ChatScope, CommandInfo, Permission},
+    config::Config,
+    i18n,
+    module::{BotModule, Command, UpdateFilter},
+    modules::archive::{self, ExportFormat},
+    t,
+    topic::SendTo,
+};
+
+/// Exports cover the last week when the range is not given
+const DEFAULT_EXPORT_DAYS: i64 = 7;
+/// Messages shown for a search
+const MAX_SEARCH_RESULTS: i64 = 10;
+/// Stats cover the last week when the days are not given
+const DEFAULT_STATS_DAYS: u32 = 7;
+const STATS_TOP_SENDERS: i64 = 5;
+/// How often the messages over the retention are deleted
+const PURGE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+pub struct Archive;
+
+#[async_trait::async_trait]
+impl BotModule for Archive {
+    fn name(&self) -> &'static str {
+        archive::MODULE
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Keep the chat history for /search, /stats and /export")
+    }
+
+    fn opt_in(&self) -> bool {
+        true
+    }
+
+    fn update_filter(&self) -> UpdateFilter {
+        UpdateFilter {
+            private_chats: false,
+            ..UpdateFilter::ALL
+        }
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![
+            Command::new(
+                CommandInfo::builder()
+                    .name("export")
+                    .permission(Permission::ChatAdmin)
+                    .build(),
+                dptree::endpoint(export_handler),
+            ),
+            Command::new(
+                CommandInfo::builder()
+                    .name("search")
+                    .scope(ChatScope::Group)
+                    .build(),
+                dptree::endpoint(search_handler),
+            ),
+            Command::new(
+                CommandInfo::builder()
+                    .name("stats")
+                    .scope(ChatScope::Group)
+                    .build(),
+                dptree::endpoint(stats_handler),
+            ),
+            Command::new(
+                CommandInfo::builder()
+                    .name("purgearchive")
+                    .permission(Permission::ChatAdmin)
+                    .build(),
+                dptree::endpoint(purge_handler),
+            ),
+            Command::new(
+                CommandInfo::builder()
+                    .name("retention")
+                    .permission(Permission::ChatAdmin)
+                    .build(),
+                dptree::endpoint(retention_handler),
+            ),
+            Command::new(
+                CommandInfo::builder().name("nolog").build(),
+                dptree::endpoint(opt_out_handler),
+            ),
+        ]
+    }
+
+    async fn on_message(&self, _bot: &Bot, data: &AppData, msg: &Message) -> anyhow::Result<()> {
+        archive_message(data, msg).await
+    }
+
+    async fn on_edited_message(
+        &self,
+        _bot: &Bot,
+        data: &AppData,
+        msg: &Message,
+    ) -> anyhow::Result<()> {
+        archive_message(data, msg).await
+    }
+
+    fn spawn_watchers(&self, _bot: &Bot, data: &AppData, _config: &Config) {
+        let data = data.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PURGE_INTERVAL);
+            loop {
+                interval.tick().await;
+                match archive::purge_expired(&data).await {
+                    Ok(0) => {}
+                    Ok(purged) => tracing::info!("[Archive] purged {purged} expired messages"),
+                    Err(err) => tracing::error!("[Archive] fail to purge expired messages: {err}"),
+                }
+            }
+        });
+    }
+}
+
+async fn archive_message(data: &AppData, msg: &Message) -> Result<()> {
+    if let Some(user) = msg.from.as_ref() {
+        if archive::is_opted_out(data, msg.chat.id.0, user.id.0)? {
+            return Ok(());
+        }
+    }
+    archive::archive(&data.storage, msg).await
+}
+
+struct ExportArgs {
+    from: NaiveDate,
+    /// The last day to export, inclusive
+    to: NaiveDate,
+    format: ExportFormat,
+    anonymous: bool,
+}
+
+fn parse_export_args<'a>(args: impl Iterator<Item = &'a str>) -> Option<ExportArgs> {
+    let mut dates = Vec::new();
+    let mut format = ExportFormat::Html;
+    let mut anonymous = false;
+    for arg in args {
+        match arg {
+            "html" => format = ExportFormat::Html,
+            "json" => format = ExportFormat::Json,
+            "anonymous" => anonymous = true,
+            date => dates.push(NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?),
+        }
+    }
+
+    let today = Local::now().date_naive();
+    let (from, to) = match dates.as_slice() {
+        [] => (today - Duration::days(DEFAULT_EXPORT_DAYS - 1), today),
+        [from] => (*from, today),
+        [from, to] => (*from, *to),
+        _ => return None,
+    };
+    (from <= to).then_some(ExportArgs {
+        from,
+        to,
+        format,
+        anonymous,
+    })
+}
+
+async fn export_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let text = msg.text().unwrap();
+    let Some(args) = parse_export_args(text.split_whitespace().skip(1)) else {
+        abort!(bot, data, msg, "{}", t!(lang, "archive.usage"));
+    };
+
+    let start_of = |date: NaiveDate| {
+        Local
+            .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+            .earliest()
+    };
+    let (Some(from), Some(to)) = (start_of(args.from), start_of(args.to + Duration::days(1)))
+    else {
+        abort!(bot, data, msg, "{}", t!(lang, "archive.usage"));
+    };
+
+    send_action!(@UploadDocument; msg, bot);
+    let mut messages = archive::messages(&data.storage, msg.chat.id.0, from, to).await?;
+    if messages.is_empty() {
+        abort!(bot, data, msg, "{}", t!(lang, "archive.empty"));
+    }
+    if args.anonymous {
+        messages = messages
+            .into_iter()
+            .map(archive::ArchivedMessage::anonymize)
+            .collect();
+    }
+
+    let title = msg.chat.title().unwrap_or("chat");
+    let content = archive::render(title, &messages, args.format);
+    let extension = match args.format {
+        ExportFormat::Html => "html",
+        ExportFormat::Json => "json",
+    };
+    let file_name = format!("{}-{}-{}.{extension}", msg.chat.id, args.from, args.to);
+    data.reply(
+        msg.chat.id,
+        bot.send_document_to(&msg, InputFile::memory(content).file_name(file_name))
+            .caption(t!(
+                lang,
+                "archive.exported",
+                count = messages.len(),
+                from = args.from,
+                to = args.to
+            )),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn retention_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let chat_id = msg.chat.id.0;
+    let mut args = Args::parse(&msg)?;
+    let Some(days) = args.optional::<u32>("days")? else {
+        let days = archive::get_retention(&data, chat_id)?;
+        abort!(
+            bot,
+            data,
+            msg,
+            "{}",
+            t!(lang, "archive.retention", days = days)
+        );
+    };
+    args.finish()?;
+    let updated = archive::set_retention(&data, chat_id, days).map(|_| days);
+    let reply = match updated {
+        Ok(days) => t!(lang, "archive.retention_updated", days = days),
+        Err(_) => t!(
+            lang,
+            "archive.retention_usage",
+            max = archive::MAX_RETENTION_DAYS
+        ),
+    };
+    data.reply(msg.chat.id, bot.send_message_to(&msg, reply))
+        .await?;
+    Ok(())
+}
+
+/// Whether the sender of the message is in the chat, the anonymous admins are
+async fn is_member(bot: &Bot, msg: &Message) -> Result<bool> {
+    if msg.sender_chat.as_ref().map(|chat| chat.id) == Some(msg.chat.id) {
+        return Ok(true);
+    }
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(false);
+    };
+    Ok(bot
+        .get_chat_member(msg.chat.id, user.id)
+        .await?
+        .is_present())
+}
+
+async fn search_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let mut args = Args::parse(&msg)?;
+    let Some(query) = args.rest() else {
+        return Err(ArgError::Missing { name: "text" }.into());
+    };
+    if !is_member(&bot, &msg).await? {
+        abort!(bot, data, msg, "{}", t!(lang, "archive.members_only"));
+    }
+
+    let query = query.trim_matches('"');
+    let found = archive::search(&data.storage, msg.chat.id.0, query, MAX_SEARCH_RESULTS).await?;
+    if found.is_empty() {
+        abort!(
+            bot,
+            data,
+            msg,
+            "{}",
+            t!(lang, "archive.not_found", query = query)
+        );
+    }
+    let mut lines = vec![t!(lang, "archive.found", query = html::escape(query))];
+    for message in &found {
+        let time = match archive::message_link(msg.chat.id.0, message.message_id) {
+            Some(link) => html::link(&link, &message.time()),
+            None => message.time(),
+        };
+        lines.push(format!(
+            "{time} <b>{}</b>: {}",
+            html::escape(&message.username),
+            html::escape(&message.snippet())
+        ));
+    }
+    data.reply(
+        msg.chat.id,
+        bot.send_message_to(&msg, lines.join("\n"))
+            .parse_mode(ParseMode::Html),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn stats_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let mut args = Args::parse(&msg)?;
+    let days = args
+        .optional::<u32>("days")?
+        .unwrap_or(DEFAULT_STATS_DAYS)
+        .clamp(1, archive::MAX_RETENTION_DAYS);
+    args.finish()?;
+
+    let since = Local::now() - Duration::days(days.into());
+    let stats = archive::stats(&data.storage, msg.chat.id.0, since, STATS_TOP_SENDERS).await?;
+    if stats.messages == 0 {
+        abort!(
+            bot,
+            data,
+            msg,
+            "{}",
+            t!(lang, "archive.stats_empty", days = days)
+        );
+    }
+    let mut lines = vec![t!(
+        lang,
+        "archive.stats",
+        messages = stats.messages,
+        senders = stats.senders,
+        days = days
+    )];
+    lines.extend(
+        stats
+            .top_senders
+            .iter()
+            .enumerate()
+            .map(|(rank, (name, sent))| format!("{}. {name}: {sent}", rank + 1)),
+    );
+    data.reply(msg.chat.id, bot.send_message_to(&msg, lines.join("\n")))
+        .await?;
+    Ok(())
+}
+
+async fn purge_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let chat_id = msg.chat.id.0;
+    let mut args = Args::parse(&msg)?;
+    let confirmed = match args.optional::<String>("confirm")? {
+        Some(arg) if arg == "confirm" => true,
+        Some(arg) => {
+            return Err(ArgError::Invalid {
+                name: "confirm",
+                value: arg,
+                reason: "send confirm to delete the messages".to_string(),
+            }
+            .into());
+        }
+        None => false,
+    };
+    args.finish()?;
+
+    let reply = if confirmed {
+        let purged = archive::purge_chat(&data.storage, chat_id).await?;
+        tracing::info!("[Archive] purged {purged} messages of chat {chat_id} on demand");
+        t!(lang, "archive.purged", count = purged)
+    } else {
+        let count = archive::count(&data.storage, chat_id).await?;
+        t!(lang, "archive.purge_confirm", count = count)
+    };
+    data.reply(msg.chat.id, bot.send_message_to(&msg, reply))
+        .await?;
+    Ok(())
+}
+
+async fn opt_out_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(());
+    };
+    let reply = if archive::toggle_opt_out(&data, msg.chat.id.0, user.id.0).await? {
+        t!(lang, "archive.opted_out")
+    } else {
+        t!(lang, "archive.opted_in")
+    };
+    data.reply(msg.chat.id, bot.send_message_to(&msg, reply))
+        .await?;
+    Ok(())
+}
+
+#[test]
+fn test_parse_export_args() {
+    let args = parse_export_args("2024-10-01 2024-10-07 json anonymous".split_whitespace());
+    let args = args.unwrap();
+    assert_eq!(args.from, NaiveDate::from_ymd_opt(2024, 10, 1).unwrap());
+    assert_eq!(args.to, NaiveDate::from_ymd_opt(2024, 10, 7).unwrap());
+    assert_eq!(args.format, ExportFormat::Json);
+    assert!(args.anonymous);
+
+    let args = parse_export_args(std::iter::empty()).unwrap();
+    assert_eq!((args.to - args.from).num_days(), DEFAULT_EXPORT_DAYS - 1);
+    assert!(parse_export_args("2024-10-07 2024-10-01".split_whitespace()).is_none());
+    assert!(parse_export_args("yesterday".split_whitespace()).is_none());
+}
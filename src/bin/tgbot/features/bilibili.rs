@@ -0,0 +1,16 @@
+use teloxide::prelude::*;
+
+use rusty_maid::{app::AppData, config::Config, module::BotModule, modules};
+
+pub struct Bilibili;
+
+#[async_trait::async_trait]
+impl BotModule for Bilibili {
+    fn name(&self) -> &'static str {
+        "bilibili"
+    }
+
+    fn spawn_watchers(&self, bot: &Bot, data: &AppData, config: &Config) {
+        modules::bilibili::spawn_bilibili_live_room_listener(bot.clone(), data.clone(), config);
+    }
+}
@@ -0,0 +1,103 @@
+use anyhow::Result;
+use teloxide::prelude::*;
+
+use rusty_maid::{
+    app::AppData,
+    command::{CommandInfo, Permission},
+    i18n,
+    module::{BotModule, Command},
+    modules::broadcast::{self, BroadcastReport},
+    t,
+    topic::SendTo,
+};
+
+/// Edit the progress message after this many chats
+const PROGRESS_EVERY: usize = 50;
+
+pub struct Broadcast;
+
+#[async_trait::async_trait]
+impl BotModule for Broadcast {
+    fn name(&self) -> &'static str {
+        broadcast::MODULE
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Announcements from the bot owner")
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![Command::new(
+            CommandInfo::builder()
+                .name("broadcast")
+                .permission(Permission::Owner)
+                .build(),
+            dptree::endpoint(broadcast_handler),
+        )]
+    }
+
+    async fn on_chat_unreachable(&self, data: &AppData, chat_id: ChatId) -> Result<()> {
+        broadcast::forget_chat(data, chat_id.0)
+    }
+}
+
+async fn broadcast_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let text = msg.text().unwrap();
+    let Some((_, announcement)) = text.split_once([' ', '\n']) else {
+        abort!(bot, data, msg, "{}", t!(lang, "broadcast.usage"));
+    };
+    let announcement = announcement.trim().to_string();
+    if announcement.is_empty() {
+        abort!(bot, data, msg, "{}", t!(lang, "broadcast.usage"));
+    }
+
+    let status = data
+        .reply(
+            msg.chat.id,
+            bot.send_message_to(&msg, t!(lang, "broadcast.started")),
+        )
+        .await?;
+
+    // It takes a while to go through the send queue, don't block the chat
+    tokio::spawn(async move {
+        let edit = |text: String| {
+            let request = bot.edit_message_text(status.chat.id, status.id, text);
+            async move {
+                if let Err(err) = request.await {
+                    tracing::warn!("[Broadcast] fail to update the progress: {err}");
+                }
+            }
+        };
+
+        let result = broadcast::broadcast(&data, &bot, &announcement, |report| {
+            if report.done() % PROGRESS_EVERY == 0 && report.done() < report.total {
+                tokio::spawn(edit(progress(lang, report)));
+            }
+        })
+        .await;
+
+        let summary = match result {
+            Ok(report) => t!(
+                lang,
+                "broadcast.finished",
+                sent = report.sent,
+                failed = report.failed,
+                unreachable = report.unreachable
+            ),
+            Err(err) => t!(lang, "broadcast.failed", error = format!("{err:#}")),
+        };
+        edit(summary).await;
+    });
+
+    Ok(())
+}
+
+fn progress(lang: &str, report: &BroadcastReport) -> String {
+    t!(
+        lang,
+        "broadcast.progress",
+        done = report.done(),
+        total = report.total
+    )
+}
@@ -0,0 +1,110 @@
+use anyhow::Result;
+use teloxide::{prelude::*, types::ParseMode, utils::html};
+
+use rusty_maid::{
+    app::AppData,
+    command::CommandInfo,
+    i18n,
+    module::{BotModule, Command},
+    modules::calc,
+    t,
+    topic::SendTo,
+};
+
+pub struct Calc;
+
+#[async_trait::async_trait]
+impl BotModule for Calc {
+    fn name(&self) -> &'static str {
+        "calc"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Offline calculator and unit conversion")
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![
+            Command::new(
+                CommandInfo::builder().name("calc").build(),
+                dptree::endpoint(calc_handler),
+            ),
+            Command::new(
+                CommandInfo::builder().name("convert").build(),
+                dptree::endpoint(convert_handler),
+            ),
+        ]
+    }
+}
+
+fn args_of(msg: &Message) -> &str {
+    msg.text()
+        .unwrap()
+        .split_once([' ', '\n'])
+        .map_or("", |(_, args)| args.trim())
+}
+
+async fn calc_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let expr = args_of(&msg);
+    if expr.is_empty() {
+        abort!(bot, data, msg, "{}", t!(lang, "calc.usage"));
+    }
+    let value = match calc::evaluate(expr) {
+        Ok(value) => value,
+        Err(err) => {
+            abort!(bot, data, msg, "{}", t!(lang, "calc.invalid", error = err));
+        }
+    };
+
+    data.reply(
+        msg.chat.id,
+        bot.send_message_to(
+            &msg,
+            format!(
+                "<code>{}</code> = <b>{}</b>",
+                html::escape(expr),
+                calc::format_number(value)
+            ),
+        )
+        .parse_mode(ParseMode::Html),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn convert_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let Some((amount, from, to)) = calc::parse_conversion(args_of(&msg)) else {
+        abort!(bot, data, msg, "{}", t!(lang, "calc.convert_usage"));
+    };
+    let value = match calc::convert(amount, &from, &to) {
+        Ok(value) => value,
+        Err(err) => {
+            abort!(
+                bot,
+                data,
+                msg,
+                "{}",
+                t!(lang, "calc.convert_failed", error = err)
+            );
+        }
+    };
+
+    data.reply(
+        msg.chat.id,
+        bot.send_message_to(
+            &msg,
+            format!(
+                "{} {} = <b>{} {}</b>",
+                calc::format_number(amount),
+                html::escape(&from),
+                calc::format_number(value),
+                html::escape(&to)
+            ),
+        )
+        .parse_mode(ParseMode::Html),
+    )
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,131 @@
+use anyhow::Result;
+use teloxide::{prelude::*, types::ParseMode, utils::html};
+
+use rusty_maid::{
+    app::AppData,
+    command::CommandInfo,
+    config::Config,
+    delayed_task, i18n,
+    module::{BotModule, Command},
+    modules::cert,
+    t,
+    topic::{ChatTarget, SendTo},
+};
+
+use crate::handlers::is_chat_admin;
+
+pub struct Cert;
+
+#[async_trait::async_trait]
+impl BotModule for Cert {
+    fn name(&self) -> &'static str {
+        "cert"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("TLS certificate expiry check")
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![Command::new(
+            CommandInfo::builder().name("cert").build(),
+            dptree::endpoint(cert_handler),
+        )]
+    }
+
+    fn spawn_watchers(&self, bot: &Bot, data: &AppData, _config: &Config) {
+        cert::spawn_cert_watcher(bot.clone(), data.clone());
+    }
+}
+
+async fn cert_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let text = msg.text().unwrap();
+    let args = text.split_whitespace().skip(1).collect::<Vec<_>>();
+    let (op, host) = match args.as_slice() {
+        [host] => ("check", *host),
+        [op @ ("check" | "watch" | "unwatch"), host] => (*op, *host),
+        _ => {
+            abort!(bot, data, msg, "{}", t!(lang, "cert.usage"));
+        }
+    };
+
+    if op != "check" && !is_chat_admin(&bot, &data, &msg).await? {
+        delayed_task::send_ephemeral(
+            &bot,
+            &data,
+            &msg,
+            t!(lang, "common.permission_denied"),
+            delayed_task::NOTICE_TTL,
+        )
+        .await?;
+        return Ok(());
+    }
+
+    send_action!(@Typing; msg, bot);
+    let target = ChatTarget::of(&msg);
+    let host_html = html::escape(host);
+    let result = match op {
+        "watch" => cert::watch(&data, target, host).await.map(|watched| {
+            if watched {
+                t!(lang, "cert.watched", host = host_html)
+            } else {
+                t!(lang, "cert.already_watched", host = host_html)
+            }
+        }),
+        "unwatch" => cert::unwatch(&data, target, host).map(|unwatched| {
+            if unwatched {
+                t!(lang, "cert.unwatched", host = host_html)
+            } else {
+                t!(lang, "cert.not_watched", host = host_html)
+            }
+        }),
+        _ => check(host, lang).await,
+    };
+    let reply = match result {
+        Ok(reply) => reply,
+        Err(err) => {
+            abort!(bot, data, msg, "{}: {err}", t!(lang, "cert.failed"));
+        }
+    };
+
+    data.reply(
+        msg.chat.id,
+        bot.send_message_to(&msg, reply).parse_mode(ParseMode::Html),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn check(input: &str, lang: &str) -> Result<String> {
+    let (host, port) = cert::parse_host(input)?;
+    let chain = cert::fetch_chain(&host, port).await?;
+    let now = chrono::Utc::now();
+    let mut lines = vec![format!("<b>{}:{port}</b>", html::escape(&host))];
+    for (index, cert) in chain.iter().enumerate() {
+        lines.push(format!(
+            "\n{}. <b>{}</b>",
+            index + 1,
+            html::escape(&cert.subject)
+        ));
+        lines.push(t!(lang, "cert.issuer", issuer = html::escape(&cert.issuer)));
+        lines.push(t!(
+            lang,
+            "cert.validity",
+            from = cert.not_before.format("%Y-%m-%d"),
+            to = cert.not_after.format("%Y-%m-%d"),
+            days = cert.days_left(now)
+        ));
+    }
+    if let Some(leaf) = chain.first().filter(|leaf| !leaf.dns_names.is_empty()) {
+        lines.push(format!(
+            "\n{}",
+            t!(
+                lang,
+                "cert.names",
+                names = html::escape(&leaf.dns_names.join(", "))
+            )
+        ));
+    }
+    Ok(lines.join("\n"))
+}
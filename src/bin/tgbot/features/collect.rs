@@ -0,0 +1,110 @@
+use anyhow::Result;
+use teloxide::prelude::*;
+
+use rusty_maid::{
+    app::AppData,
+    command::CommandInfo,
+    i18n,
+    module::{BotModule, Command},
+    modules, t,
+    topic::SendTo,
+};
+use rusty_maid::{
+    command::ChatScope,
+    dialogue::{DialogueRouter, Transition},
+    modules::Sendable,
+    sendable,
+};
+use teloxide::types::ParseMode;
+
+use crate::handlers::DIALOGUE_ROUTER;
+
+pub struct Collect;
+
+#[async_trait::async_trait]
+impl BotModule for Collect {
+    fn name(&self) -> &'static str {
+        "collect"
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![
+            Command::new(
+                CommandInfo::builder()
+                    .name("collect")
+                    .scope(ChatScope::Private)
+                    .build(),
+                dptree::endpoint(collect_handler),
+            ),
+            Command::new(
+                CommandInfo::builder()
+                    .name("collectdone")
+                    .scope(ChatScope::Private)
+                    .build(),
+                dptree::endpoint(collect_done_handler),
+            )
+            .stateful(),
+        ]
+    }
+
+    fn dialogues(&self, router: DialogueRouter) -> DialogueRouter {
+        router.route(
+            "collect",
+            std::time::Duration::from_secs(30 * 60),
+            |ctx| async move {
+                modules::collect::push_msg(ctx.data, ctx.msg).await?;
+                Ok(Transition::Stay)
+            },
+        )
+    }
+}
+
+/// handler for the collect command
+async fn collect_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    if let teloxide::types::ChatKind::Public(_) = msg.chat.kind {
+        abort!(bot, data, msg, "{}", t!(lang, "common.private_only"));
+    }
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(());
+    };
+    DIALOGUE_ROUTER.start(
+        &data,
+        msg.chat.id.0,
+        user.id.0,
+        "collect",
+        "forwarding",
+        &(),
+    )?;
+    data.reply(
+        msg.chat.id,
+        bot.send_message_to(&msg, t!(lang, "collect.start")),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn collect_done_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(());
+    };
+    let lang = i18n::lang_of(&data, &msg);
+    let running = DIALOGUE_ROUTER.current(&data, msg.chat.id.0, user.id.0)?;
+    if running.is_none_or(|current| current.module != "collect") {
+        abort!(bot, data, msg, "{}", t!(lang, "collect.not_running"));
+    }
+
+    send_action!(@Typing; msg, bot);
+    DIALOGUE_ROUTER.cancel(&data, msg.chat.id.0, user.id.0)?;
+
+    let result = modules::collect::finish(data.clone(), &msg).await;
+    match result {
+        Ok(sendable) => {
+            sendable!(bot, data, msg, sendable, format = Html);
+        }
+        Err(err) => {
+            abort!(bot, data, msg, "{}: {}", t!(lang, "collect.failed"), err);
+        }
+    };
+    Ok(())
+}
@@ -0,0 +1,134 @@
+use anyhow::Result;
+use teloxide::prelude::*;
+
+use rusty_maid::{
+    app::AppData,
+    command::CommandInfo,
+    i18n,
+    module::{BotModule, Command},
+    modules, t,
+    topic::SendTo,
+};
+use rusty_maid::{modules::Sendable, sendable};
+use teloxide::{types::ParseMode, utils::html};
+
+use crate::handlers::{is_chat_admin, CALLBACK_ROUTER};
+
+/// Users shown in the leaderboard at most, ten on a page
+const LEADERBOARD_SIZE: isize = 100;
+
+pub struct Counter;
+
+#[async_trait::async_trait]
+impl BotModule for Counter {
+    fn name(&self) -> &'static str {
+        "counter"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Interaction counters")
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![Command::new(
+            CommandInfo::builder().name("counter").build(),
+            dptree::endpoint(counter_handler),
+        )]
+    }
+
+    async fn on_message(&self, bot: &Bot, data: &AppData, msg: &Message) -> anyhow::Result<()> {
+        hit_counter(msg, bot, data).await
+    }
+}
+
+async fn hit_counter(msg: &Message, bot: &Bot, data: &AppData) -> anyhow::Result<()> {
+    let Some(target) = msg.reply_to_message().and_then(|reply| reply.from.as_ref()) else {
+        return Ok(());
+    };
+    let Some(name) = modules::counter::trigger_name(msg.text().unwrap()) else {
+        return Ok(());
+    };
+    let Some(def) = modules::counter::get_definition(data, msg.chat.id.0, &name)? else {
+        return Ok(());
+    };
+
+    let text = modules::counter::hit(data, msg.chat.id.0, &def, (target.id.0, &target.first_name))?;
+    data.reply(msg.chat.id, bot.send_message_to(msg, text))
+        .await?;
+
+    Ok(())
+}
+
+async fn counter_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let text = msg.text().unwrap();
+    let mut args = text.splitn(3, ' ').skip(1);
+    let chat_id = msg.chat.id.0;
+
+    let result = match args.next() {
+        None | Some("list") => modules::counter::list(&data, chat_id, lang),
+        Some("top") => {
+            let Some(name) = args.next().and_then(modules::counter::trigger_name) else {
+                abort!(bot, data, msg, "{}", t!(lang, "counter.usage_top"));
+            };
+            match modules::counter::leaderboard(&data, chat_id, &name, LEADERBOARD_SIZE, lang) {
+                Ok(Some(board)) => {
+                    board.send(&bot, &data, &CALLBACK_ROUTER, &msg).await?;
+                    return Ok(());
+                }
+                Ok(None) => Ok(Sendable::text(t!(
+                    lang,
+                    "counter.nobody",
+                    name = html::escape(&name)
+                ))),
+                Err(err) => Err(err),
+            }
+        }
+        Some(op @ ("new" | "del")) => {
+            if !is_chat_admin(&bot, &data, &msg).await? {
+                abort!(bot, data, msg, "{}", t!(lang, "counter.admin_only"));
+            }
+            let args = args.next().unwrap_or_default();
+            if op == "new" {
+                match modules::counter::CounterDefinition::parse(args, lang) {
+                    Ok(def) => modules::counter::define(&data, chat_id, &def)
+                        .map(|_| Sendable::text(t!(lang, "counter.created", name = def.name))),
+                    Err(err) => {
+                        abort!(bot, data, msg, "{}", err);
+                    }
+                }
+            } else {
+                let Some(name) = modules::counter::trigger_name(args) else {
+                    abort!(bot, data, msg, "{}", t!(lang, "counter.usage_del"));
+                };
+                modules::counter::remove(&data, chat_id, &name).map(|removed| {
+                    if removed {
+                        Sendable::text(t!(lang, "counter.deleted"))
+                    } else {
+                        Sendable::text(t!(lang, "counter.not_found", name = html::escape(&name)))
+                    }
+                })
+            }
+        }
+        Some(op) => {
+            abort!(
+                bot,
+                data,
+                msg,
+                "{}",
+                t!(lang, "counter.unknown_operation", operation = op)
+            );
+        }
+    };
+
+    match result {
+        Ok(sendable) => {
+            sendable!(bot, data, msg, sendable, format = Html);
+        }
+        Err(err) => {
+            abort!(bot, data, msg, "{}: {}", t!(lang, "counter.failed"), err);
+        }
+    };
+
+    Ok(())
+}
@@ -0,0 +1,154 @@
+use anyhow::Result;
+use teloxide::{prelude::*, types::ParseMode, utils::html};
+
+use rusty_maid::{
+    app::AppData,
+    command::CommandInfo,
+    i18n,
+    module::{BotModule, Command},
+    modules::dns::{self, RecordType},
+    t,
+    topic::SendTo,
+};
+
+pub struct Dns;
+
+#[async_trait::async_trait]
+impl BotModule for Dns {
+    fn name(&self) -> &'static str {
+        "dns"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("DNS and WHOIS lookup")
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![
+            Command::new(
+                CommandInfo::builder().name("dns").build(),
+                dptree::endpoint(dns_handler),
+            ),
+            Command::new(
+                CommandInfo::builder().name("whois").build(),
+                dptree::endpoint(whois_handler),
+            ),
+        ]
+    }
+}
+
+/// Show the date part of the RFC 3339 time from RDAP.
+fn date_of(time: &str) -> String {
+    chrono::DateTime::parse_from_rfc3339(time)
+        .map(|time| time.format("%Y-%m-%d").to_string())
+        .unwrap_or_else(|_| time.to_string())
+}
+
+async fn dns_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let text = msg.text().unwrap();
+    let args = text.split_whitespace().skip(1).collect::<Vec<_>>();
+    let (domain, kind) = match args.as_slice() {
+        [domain] => (*domain, Ok(RecordType::A)),
+        [domain, kind] => (*domain, kind.parse::<RecordType>()),
+        _ => {
+            abort!(bot, data, msg, "{}", t!(lang, "dns.usage"));
+        }
+    };
+    let Ok(kind) = kind else {
+        abort!(bot, data, msg, "{}", t!(lang, "dns.usage"));
+    };
+
+    send_action!(@Typing; msg, bot);
+    let records = match dns::resolve(&data, domain, kind).await {
+        Ok(records) => records,
+        Err(err) => {
+            abort!(bot, data, msg, "{}: {err}", t!(lang, "dns.failed"));
+        }
+    };
+    let reply = if records.is_empty() {
+        t!(
+            lang,
+            "dns.no_record",
+            domain = html::escape(domain),
+            kind = kind
+        )
+    } else {
+        let lines = records
+            .iter()
+            .map(|record| {
+                format!(
+                    "<code>{}</code> (TTL {})",
+                    html::escape(&record.data),
+                    record.ttl
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        format!("<b>{}</b> {kind}\n{lines}", html::escape(domain))
+    };
+
+    data.reply(
+        msg.chat.id,
+        bot.send_message_to(&msg, reply).parse_mode(ParseMode::Html),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn whois_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let text = msg.text().unwrap();
+    let Some(domain) = text.split_whitespace().nth(1) else {
+        abort!(bot, data, msg, "{}", t!(lang, "dns.whois_usage"));
+    };
+
+    send_action!(@Typing; msg, bot);
+    let registration = match dns::whois(&data, domain).await {
+        Ok(registration) => registration,
+        Err(err) => {
+            abort!(bot, data, msg, "{}: {err}", t!(lang, "dns.whois_failed"));
+        }
+    };
+
+    let mut lines = vec![format!("<b>{}</b>", html::escape(&registration.domain))];
+    if let Some(registrar) = &registration.registrar {
+        lines.push(t!(
+            lang,
+            "dns.registrar",
+            registrar = html::escape(registrar)
+        ));
+    }
+    for (key, time) in [
+        ("dns.registered", &registration.registered),
+        ("dns.expires", &registration.expires),
+        ("dns.updated", &registration.updated),
+    ] {
+        if let Some(time) = time {
+            // `t!` only takes the literal keys
+            lines.push(i18n::translate(lang, key, &[("date", &date_of(time))]));
+        }
+    }
+    if !registration.status.is_empty() {
+        lines.push(t!(
+            lang,
+            "dns.status",
+            status = html::escape(&registration.status.join(", "))
+        ));
+    }
+    if !registration.nameservers.is_empty() {
+        lines.push(t!(
+            lang,
+            "dns.nameservers",
+            nameservers = html::escape(&registration.nameservers.join(", "))
+        ));
+    }
+
+    data.reply(
+        msg.chat.id,
+        bot.send_message_to(&msg, lines.join("\n"))
+            .parse_mode(ParseMode::Html),
+    )
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,66 @@
+use anyhow::Result;
+use teloxide::prelude::*;
+
+use rusty_maid::{
+    app::AppData,
+    command::CommandInfo,
+    i18n,
+    module::{BotModule, Command},
+    modules, t,
+};
+use rusty_maid::{modules::Sendable, sendable};
+use teloxide::types::ParseMode;
+
+use super::get_args;
+
+pub struct EHentai;
+
+#[async_trait::async_trait]
+impl BotModule for EHentai {
+    fn name(&self) -> &'static str {
+        "eh"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("E-hentai information")
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![Command::new(
+            CommandInfo::builder().name("eh").build(),
+            dptree::endpoint(eh_handler),
+        )]
+    }
+}
+
+async fn eh_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    send_action!(@UploadPhoto; msg, bot);
+    let lang = i18n::lang_of(&data, &msg);
+
+    let args = get_args(&msg, lang);
+    if let Err(err) = args {
+        abort!(bot, data, msg, "{}", err);
+    }
+
+    let args = args.unwrap();
+    let parse_result = modules::ehentai::parse_gid_list(&args);
+
+    if let Err(err) = parse_result {
+        abort!(bot, data, msg, "{}", err);
+    }
+
+    let result =
+        modules::ehentai::fetch_ehentai_comic_data(data.clone(), parse_result.unwrap()).await;
+    match result {
+        Ok(sendables) => {
+            for s in sendables {
+                sendable!(bot, data, msg, s, format = Html, spoiler = on);
+            }
+        }
+        Err(err) => {
+            abort!(bot, data, msg, "{}: {}", t!(lang, "eh.failed"), err);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use anyhow::Result;
+use teloxide::prelude::*;
+
+use rusty_maid::{
+    app::AppData,
+    command::CommandInfo,
+    i18n,
+    module::{BotModule, Command},
+    modules, t,
+};
+use rusty_maid::{modules::Sendable, sendable};
+use teloxide::types::ParseMode;
+
+pub struct Exchange;
+
+#[async_trait::async_trait]
+impl BotModule for Exchange {
+    fn name(&self) -> &'static str {
+        "exchange"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Exchange rate")
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![Command::new(
+            CommandInfo::builder().name("exchange").build(),
+            dptree::endpoint(exchange_handler),
+        )]
+    }
+}
+
+async fn exchange_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    send_action!(@Typing; msg, bot);
+    let lang = i18n::lang_of(&data, &msg);
+
+    let text = msg.text().unwrap();
+    let parts = text
+        .split(' ')
+        .filter(|s| !s.is_empty() && !s.contains(' '))
+        .map(|s| s.trim())
+        .collect::<Vec<&str>>();
+    if parts.len() < 4 {
+        abort!(bot, data, msg, "{}", t!(lang, "exchange.usage"));
+    }
+
+    let Ok(amount) = parts[1].parse::<f64>() else {
+        abort!(
+            bot,
+            data,
+            msg,
+            "{}",
+            t!(lang, "exchange.invalid_number", input = parts[1])
+        );
+    };
+
+    let result = modules::currency::exchange(
+        data.clone(),
+        amount,
+        &parts[2].to_lowercase(),
+        &parts[3].to_lowercase(),
+    )
+    .await;
+
+    match result {
+        Ok(sendable) => {
+            sendable!(bot, data, msg, sendable, format = Html);
+        }
+        Err(err) => {
+            abort!(bot, data, msg, "{}: {}", t!(lang, "exchange.failed"), err);
+        }
+    };
+
+    Ok(())
+}
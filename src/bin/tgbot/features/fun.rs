@@ -0,0 +1,77 @@
+use anyhow::Result;
+use teloxide::prelude::*;
+
+use rand::Rng;
+use rusty_maid::{
+    app::AppData,
+    command::CommandInfo,
+    i18n,
+    module::{BotModule, Command},
+    modules, t,
+    topic::SendTo,
+};
+
+pub struct Fun;
+
+#[async_trait::async_trait]
+impl BotModule for Fun {
+    fn name(&self) -> &'static str {
+        "fun"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Interact with ksyx and piggy")
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![
+            Command::new(
+                CommandInfo::builder().name("hitksyx").build(),
+                dptree::endpoint(hit_ksyx_handler),
+            ),
+            Command::new(
+                CommandInfo::builder().name("cookpiggy").build(),
+                dptree::endpoint(cook_piggy_handler),
+            ),
+        ]
+    }
+}
+
+async fn hit_ksyx_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let old = modules::ksyx::hit(data.clone());
+    if let Err(ref e) = old {
+        abort!(bot, data, msg, "{}: {}", t!(lang, "ksyx.failed"), e);
+    }
+
+    let action = &[
+        "爱抚", "中出", "暴打", "后入", "膜", "贴贴", "狂踹", "寸止", "绳缚",
+    ];
+
+    let choice = rand::thread_rng().gen_range(0..action.len());
+    data.reply(
+        msg.chat.id,
+        bot.send_message_to(
+            &msg,
+            format!(
+                "{} {}了 ksyx，ksyx 已经被动手动脚了 {} 次",
+                msg.from.as_ref().unwrap().first_name,
+                action[choice],
+                old.unwrap(),
+            ),
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn cook_piggy_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    send_action!(@Typing; msg, bot);
+    let lang = i18n::lang_of(&data, &msg);
+
+    let recipe = modules::piggy::get_pig_recipe(data.clone()).await;
+    handle_result!(bot, data, msg, recipe, t!(lang, "piggy.failed"));
+
+    Ok(())
+}
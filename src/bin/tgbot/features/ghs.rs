@@ -0,0 +1,49 @@
+use anyhow::Result;
+use teloxide::prelude::*;
+
+use rusty_maid::{
+    app::AppData,
+    command::CommandInfo,
+    i18n,
+    module::{BotModule, Command},
+    modules, t,
+};
+use rusty_maid::{modules::Sendable, sendable};
+use teloxide::types::ParseMode;
+
+pub struct Ghs;
+
+#[async_trait::async_trait]
+impl BotModule for Ghs {
+    fn name(&self) -> &'static str {
+        "ghs"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Random anime image")
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![Command::new(
+            CommandInfo::builder().name("ghs").build(),
+            dptree::endpoint(ghs_handler),
+        )]
+    }
+}
+
+async fn ghs_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    send_action!(@UploadPhoto; msg, bot);
+    let lang = i18n::lang_of(&data, &msg);
+
+    let result = modules::nsfw::fetch_nsfw_anime_img(data.clone()).await;
+    match result {
+        Ok(sendable) => {
+            sendable!(bot, data, msg, sendable, format = Html, spoiler = on);
+        }
+        Err(err) => {
+            abort!(bot, data, msg, "{}: {}", t!(lang, "ghs.failed"), err);
+        }
+    };
+
+    Ok(())
+}
@@ -0,0 +1,66 @@
+use anyhow::Result;
+use teloxide::prelude::*;
+
+use rusty_maid::config::Config;
+use rusty_maid::{
+    app::AppData,
+    command::CommandInfo,
+    i18n,
+    module::{BotModule, Command},
+    modules, t,
+};
+
+pub struct Holiday;
+
+#[async_trait::async_trait]
+impl BotModule for Holiday {
+    fn name(&self) -> &'static str {
+        "holiday"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Holiday calendar")
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![Command::new(
+            CommandInfo::builder().name("holiday").build(),
+            dptree::endpoint(holiday_handler),
+        )]
+    }
+
+    fn spawn_watchers(&self, bot: &Bot, data: &AppData, config: &Config) {
+        modules::holiday::spawn_holiday_reminder(bot.clone(), data.clone(), config);
+    }
+
+    fn check_config(&self, config: &Config) -> Vec<String> {
+        config
+            .holiday_event
+            .iter()
+            .flat_map(|(chat, regions)| regions.iter().map(move |region| (chat, region)))
+            .filter(|(_, region)| modules::holiday::normalize_region(region).is_none())
+            .map(|(chat, region)| {
+                format!("holiday_event.{chat}: {region} is not an ISO 3166-1 alpha-2 country code")
+            })
+            .collect()
+    }
+}
+
+async fn holiday_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    send_action!(@Typing; msg, bot);
+    let lang = i18n::lang_of(&data, &msg);
+
+    let text = msg.text().unwrap();
+    let mut args = text.split(' ').skip(1).filter(|s| !s.is_empty());
+    // `next` is the only operation for now, so it can be omitted
+    let region = match args.next() {
+        Some("next") | None => args.next(),
+        region => region,
+    }
+    .unwrap_or(modules::holiday::DEFAULT_REGION);
+
+    let result = modules::holiday::next_holidays(data.clone(), region, lang).await;
+    handle_result!(bot, data, msg, result, t!(lang, "holiday.failed"));
+
+    Ok(())
+}
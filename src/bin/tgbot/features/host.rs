@@ -0,0 +1,79 @@
+use anyhow::Result;
+use teloxide::{prelude::*, types::ParseMode, utils::html};
+
+use rusty_maid::{
+    app::AppData,
+    command::{CommandInfo, Permission},
+    config::Config,
+    i18n,
+    module::{BotModule, Command},
+    modules::host::{self, format_bytes, format_uptime},
+    t,
+    topic::SendTo,
+};
+
+pub struct Host;
+
+#[async_trait::async_trait]
+impl BotModule for Host {
+    fn name(&self) -> &'static str {
+        "host"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Resource usage of the host running the bot")
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![Command::new(
+            CommandInfo::builder()
+                .name("sysinfo")
+                .permission(Permission::Owner)
+                .build(),
+            dptree::endpoint(sysinfo_handler),
+        )]
+    }
+
+    fn spawn_watchers(&self, bot: &Bot, data: &AppData, config: &Config) {
+        host::spawn_resource_watcher(bot.clone(), data.clone(), config);
+    }
+}
+
+async fn sysinfo_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let text = msg.text().unwrap();
+    let disk_path = match text.split_whitespace().nth(1) {
+        Some(path) => path.to_string(),
+        None => Config::get_global_config()
+            .resource_alert
+            .as_ref()
+            .map_or_else(|| "/".to_string(), |alert| alert.disk_path.clone()),
+    };
+
+    send_action!(@Typing; msg, bot);
+    let report = host::report(&disk_path).await?;
+    let [one, five, fifteen] = report.load;
+    let reply = t!(
+        lang,
+        "host.report",
+        cpu = format!("{:.1}", report.cpu_percent),
+        cpus = report.cpus,
+        load = format!("{one:.2} {five:.2} {fifteen:.2}"),
+        memory_used = format_bytes(report.memory_used),
+        memory_total = format_bytes(report.memory_total),
+        memory_percent = format!("{:.1}", report.memory_percent()),
+        disk = html::escape(&report.disk_mount),
+        disk_used = format_bytes(report.disk_used),
+        disk_total = format_bytes(report.disk_total),
+        disk_percent = format!("{:.1}", report.disk_percent()),
+        host_uptime = format_uptime(report.host_uptime),
+        bot_uptime = format_uptime(report.bot_uptime)
+    );
+
+    data.reply(
+        msg.chat.id,
+        bot.send_message_to(&msg, reply).parse_mode(ParseMode::Html),
+    )
+    .await?;
+    Ok(())
+}
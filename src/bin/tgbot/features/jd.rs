@@ -0,0 +1,90 @@
+use anyhow::Result;
+use teloxide::prelude::*;
+
+use rusty_maid::modules::price::PriceInfo;
+use rusty_maid::{
+    app::AppData,
+    command::CommandInfo,
+    i18n,
+    module::{BotModule, Command},
+    modules, t,
+    topic::SendTo,
+};
+use teloxide::types::{InputFile, ParseMode};
+
+use super::get_args;
+
+pub struct Jd;
+
+#[async_trait::async_trait]
+impl BotModule for Jd {
+    fn name(&self) -> &'static str {
+        "jd"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("JD price")
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![Command::new(
+            CommandInfo::builder().name("jd").build(),
+            dptree::endpoint(jd_handler),
+        )]
+    }
+}
+
+async fn jd_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    send_action!(@UploadPhoto; msg, bot);
+    let lang = i18n::lang_of(&data, &msg);
+
+    let args = get_args(&msg, lang);
+    if let Err(err) = args {
+        abort!(bot, data, msg, "{}", err);
+    }
+
+    let args = args.unwrap();
+    let rules = regex::Regex::new(r"https://item\.jd\.com/(\d+)\.html").unwrap();
+    let capture: Vec<_> = rules
+        .captures_iter(&args)
+        .filter_map(|cap| Some(cap.get(1)?.as_str()))
+        .collect();
+    if capture.is_empty() {
+        abort!(bot, data, msg, "{}", t!(lang, "jd.no_url"));
+    }
+
+    let result = modules::price::JDPriceAnalyzer::get(capture[0]).await;
+    match result {
+        Ok(item) => {
+            let info = item.price();
+            let text = format!(
+                "<b>{}</b>\n\n<b>标价</b>: {}\n<b>现价</b>: {}\n<b>史低</b>: {}\n\n{}",
+                item.name(),
+                info.listed,
+                info.current,
+                info.lowest,
+                item.sales_info(),
+            );
+            if let Some(photo) = item.thumbnail() {
+                data.reply(
+                    msg.chat.id,
+                    bot.send_photo_to(&msg, InputFile::url(reqwest::Url::parse(&photo).unwrap()))
+                        .caption(text)
+                        .parse_mode(ParseMode::Html),
+                )
+                .await?;
+            } else {
+                data.reply(
+                    msg.chat.id,
+                    bot.send_message_to(&msg, text).parse_mode(ParseMode::Html),
+                )
+                .await?;
+            }
+        }
+        Err(err) => {
+            abort!(bot, data, msg, "{}: {}", t!(lang, "jd.failed"), err);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,104 @@
+use anyhow::Result;
+use teloxide::prelude::*;
+
+use rusty_maid::{
+    app::AppData,
+    command::CommandInfo,
+    i18n,
+    module::{BotModule, Command},
+    modules, t,
+    topic::SendTo,
+};
+use rusty_maid::{config::Config, modules::Sendable, sendable};
+use teloxide::types::ParseMode;
+
+pub struct Karma;
+
+#[async_trait::async_trait]
+impl BotModule for Karma {
+    fn name(&self) -> &'static str {
+        "karma"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Karma")
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![Command::new(
+            CommandInfo::builder().name("karma").build(),
+            dptree::endpoint(karma_handler),
+        )]
+    }
+
+    async fn on_message(&self, bot: &Bot, data: &AppData, msg: &Message) -> anyhow::Result<()> {
+        give_karma(msg, bot, data).await
+    }
+}
+
+async fn give_karma(msg: &Message, bot: &Bot, data: &AppData) -> anyhow::Result<()> {
+    let config = Config::get_global_config();
+    let config = &config.karma;
+    let (Some(reply_to), Some(giver)) = (msg.reply_to_message(), msg.from.as_ref()) else {
+        return Ok(());
+    };
+    let Some(receiver) = reply_to.from.as_ref() else {
+        return Ok(());
+    };
+    if receiver.is_bot
+        || receiver.id == giver.id
+        || !modules::karma::is_trigger(msg.text().unwrap(), &config.triggers)
+    {
+        return Ok(());
+    }
+
+    let karma = modules::karma::give(
+        data,
+        msg.chat.id.0,
+        giver.id.0,
+        (receiver.id.0, &receiver.first_name),
+        config.cooldown,
+    )
+    .await?;
+    if let Some(karma) = karma {
+        let lang = i18n::lang_of(data, msg);
+        let text = t!(
+            lang,
+            "karma.increased",
+            name = receiver.first_name,
+            karma = karma
+        );
+        data.reply(msg.chat.id, bot.send_message_to(msg, text))
+            .await?;
+    }
+
+    Ok(())
+}
+
+async fn karma_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let text = msg.text().unwrap();
+    let result = if text.split(' ').nth(1) == Some("top") {
+        modules::karma::leaderboard(&data, msg.chat.id.0, 10, lang).await
+    } else {
+        let target = msg
+            .reply_to_message()
+            .and_then(|reply| reply.from.as_ref())
+            .or(msg.from.as_ref());
+        let Some(target) = target else {
+            abort!(bot, data, msg, "{}", t!(lang, "karma.user_not_found"));
+        };
+        modules::karma::query(&data, msg.chat.id.0, target.id.0, lang).await
+    };
+
+    match result {
+        Ok(sendable) => {
+            sendable!(bot, data, msg, sendable, format = Html);
+        }
+        Err(err) => {
+            abort!(bot, data, msg, "{}: {}", t!(lang, "karma.failed"), err);
+        }
+    };
+
+    Ok(())
+}
@@ -0,0 +1,41 @@
+use anyhow::Result;
+use teloxide::prelude::*;
+
+use rusty_maid::{
+    app::AppData,
+    command::CommandInfo,
+    i18n,
+    module::{BotModule, Command},
+    modules, t,
+};
+
+pub struct Lunar;
+
+#[async_trait::async_trait]
+impl BotModule for Lunar {
+    fn name(&self) -> &'static str {
+        "lunar"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Lunar calendar")
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![Command::new(
+            CommandInfo::builder().name("lunar").build(),
+            dptree::endpoint(lunar_handler),
+        )]
+    }
+}
+
+async fn lunar_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let text = msg.text().unwrap();
+    let date = text.split_once(' ').map(|(_, date)| date);
+
+    let result = modules::lunar::lookup(date, lang);
+    handle_result!(bot, data, msg, result, t!(lang, "lunar.failed"));
+
+    Ok(())
+}
@@ -0,0 +1,107 @@
+//! Every feature of the bot is a [`BotModule`] in its own file, and registered in [`registry`].
+
+use anyhow::Result;
+use teloxide::prelude::Message;
+
+use rusty_maid::{module::ModuleRegistry, t};
+
+use crate::handlers::Core;
+
+mod archive;
+mod bilibili;
+mod broadcast;
+mod calc;
+mod cert;
+mod collect;
+mod counter;
+mod dns;
+mod eh;
+mod exchange;
+mod fun;
+mod ghs;
+mod holiday;
+mod host;
+mod jd;
+mod karma;
+mod lunar;
+mod monitor;
+mod package;
+mod pacman;
+mod qr;
+mod quiz;
+mod quote;
+mod reaction;
+mod roll;
+mod secret;
+mod spam;
+mod sticker_pack;
+mod summary;
+mod tr;
+mod url_cleaner;
+mod weather;
+mod wiki;
+mod ytdlp;
+
+lazy_static::lazy_static!(
+    static ref MATCH_URL: regex::Regex =
+        regex::Regex::new(
+            r"(http[s]?://(?:[a-zA-Z]|[0-9]|[$-_@.&+]|[!*\(\),]|(?:%[0-9a-fA-F][0-9a-fA-F]))+)"
+        ).unwrap();
+);
+
+/// Collect all the modules. The order decides the order in the help message and the settings
+/// menu.
+pub fn registry() -> ModuleRegistry {
+    ModuleRegistry::new()
+        .register(Core)
+        .register(weather::Weather)
+        .register(wiki::Wiki)
+        .register(summary::Summary)
+        .register(calc::Calc)
+        .register(exchange::Exchange)
+        .register(ghs::Ghs)
+        .register(eh::EHentai)
+        .register(collect::Collect)
+        .register(pacman::Pacman)
+        .register(package::Package)
+        .register(dns::Dns)
+        .register(monitor::Monitor)
+        .register(cert::Cert)
+        .register(fun::Fun)
+        .register(quiz::Quiz)
+        .register(jd::Jd)
+        .register(tr::Translate)
+        .register(qr::Qr)
+        .register(roll::Roll)
+        .register(secret::Secret)
+        .register(quote::Quote)
+        .register(sticker_pack::StickerPacks)
+        .register(ytdlp::Ytdlp)
+        .register(karma::Karma)
+        .register(counter::Counter)
+        .register(holiday::Holiday)
+        .register(lunar::Lunar)
+        .register(url_cleaner::UrlCleaner)
+        .register(spam::Spam)
+        .register(reaction::Reaction)
+        .register(archive::Archive)
+        .register(bilibili::Bilibili)
+        .register(broadcast::Broadcast)
+        .register(host::Host)
+}
+
+fn get_args(msg: &Message, lang: &str) -> Result<String> {
+    let text = msg.text().unwrap();
+    if let Some(args) = text.split_once(' ') {
+        Ok(args.1.to_string())
+    } else if let Some(reply_to) = msg.reply_to_message() {
+        let text = reply_to.text();
+        if text.is_none() {
+            anyhow::bail!(t!(lang, "common.need_reply_text"));
+        }
+
+        Ok(text.unwrap().to_string())
+    } else {
+        anyhow::bail!(t!(lang, "common.need_args_or_reply"))
+    }
+}
@@ -0,0 +1,141 @@
+use anyhow::Result;
+use chrono::{Local, TimeZone};
+use teloxide::{prelude::*, types::ParseMode, utils::html};
+
+use rusty_maid::{
+    app::AppData,
+    command::CommandInfo,
+    config::Config,
+    delayed_task, i18n,
+    module::{BotModule, Command},
+    modules::monitor,
+    t,
+    topic::{ChatTarget, SendTo},
+};
+
+use crate::handlers::is_chat_admin;
+
+pub struct Monitor;
+
+#[async_trait::async_trait]
+impl BotModule for Monitor {
+    fn name(&self) -> &'static str {
+        "monitor"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Uptime monitor for the URLs")
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![Command::new(
+            CommandInfo::builder().name("monitor").build(),
+            dptree::endpoint(monitor_handler),
+        )]
+    }
+
+    async fn on_chat_unreachable(&self, data: &AppData, chat_id: ChatId) -> Result<()> {
+        monitor::forget_chat(data, chat_id)
+    }
+
+    fn spawn_watchers(&self, bot: &Bot, data: &AppData, _config: &Config) {
+        monitor::spawn_uptime_monitor(bot.clone(), data.clone());
+    }
+}
+
+async fn monitor_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let text = msg.text().unwrap();
+    let args = text.split_whitespace().skip(1).collect::<Vec<_>>();
+    let target = ChatTarget::of(&msg);
+
+    let reply = match args.as_slice() {
+        [] | ["status"] => status_of(&data, target, lang)?,
+        [op @ ("add" | "remove"), url] => {
+            if !is_chat_admin(&bot, &data, &msg).await? {
+                delayed_task::send_ephemeral(
+                    &bot,
+                    &data,
+                    &msg,
+                    t!(lang, "common.permission_denied"),
+                    delayed_task::NOTICE_TTL,
+                )
+                .await?;
+                return Ok(());
+            }
+            let result = if *op == "add" {
+                monitor::add(&data, target, url).map(|added| {
+                    if added {
+                        t!(lang, "monitor.added", url = html::escape(url))
+                    } else {
+                        t!(lang, "monitor.already_added", url = html::escape(url))
+                    }
+                })
+            } else {
+                monitor::remove(&data, target, url).map(|removed| {
+                    if removed {
+                        t!(lang, "monitor.removed", url = html::escape(url))
+                    } else {
+                        t!(lang, "monitor.not_monitored", url = html::escape(url))
+                    }
+                })
+            };
+            match result {
+                Ok(reply) => reply,
+                Err(err) => {
+                    abort!(bot, data, msg, "{}: {err}", t!(lang, "monitor.failed"));
+                }
+            }
+        }
+        _ => {
+            abort!(bot, data, msg, "{}", t!(lang, "monitor.usage"));
+        }
+    };
+
+    data.reply(
+        msg.chat.id,
+        bot.send_message_to(&msg, reply).parse_mode(ParseMode::Html),
+    )
+    .await?;
+    Ok(())
+}
+
+fn status_of(data: &AppData, target: ChatTarget, lang: &str) -> Result<String> {
+    let status = monitor::status(data, target)?;
+    if status.is_empty() {
+        return Ok(t!(lang, "monitor.empty"));
+    }
+    let time_of = |timestamp: i64| {
+        Local
+            .timestamp_opt(timestamp, 0)
+            .single()
+            .map_or_else(String::new, |time| time.format("%m-%d %H:%M").to_string())
+    };
+
+    let lines = status
+        .into_iter()
+        .map(|(url, state)| {
+            let url = html::escape(&url);
+            match state {
+                None => t!(lang, "monitor.pending", url = url),
+                Some(state) if state.up => t!(
+                    lang,
+                    "monitor.up",
+                    url = url,
+                    latency = state
+                        .latency_ms
+                        .map_or_else(|| "-".to_string(), |latency| latency.to_string()),
+                    since = time_of(state.since)
+                ),
+                Some(state) => t!(
+                    lang,
+                    "monitor.down_since",
+                    url = url,
+                    since = time_of(state.since),
+                    error = html::escape(state.error.as_deref().unwrap_or_default())
+                ),
+            }
+        })
+        .collect::<Vec<_>>();
+    Ok(lines.join("\n"))
+}
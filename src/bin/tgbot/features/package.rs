@@ -0,0 +1,145 @@
+use anyhow::Result;
+use teloxide::{prelude::*, types::ParseMode, utils::html};
+
+use rusty_maid::{
+    app::AppData,
+    command::CommandInfo,
+    config::Config,
+    delayed_task, i18n,
+    module::{BotModule, Command},
+    modules::package::{self, Downloads, Registry},
+    t,
+    topic::{ChatTarget, SendTo},
+};
+
+use crate::handlers::is_chat_admin;
+
+pub struct Package;
+
+fn command_name(registry: Registry) -> &'static str {
+    match registry {
+        Registry::Crates => "crate",
+        Registry::PyPI => "pypi",
+        Registry::Npm => "npm",
+    }
+}
+
+fn command(registry: Registry) -> Command {
+    Command::new(
+        CommandInfo::builder().name(command_name(registry)).build(),
+        dptree::endpoint(move |msg: Message, bot: Bot, data: AppData| {
+            package_handler(msg, bot, data, registry)
+        }),
+    )
+}
+
+#[async_trait::async_trait]
+impl BotModule for Package {
+    fn name(&self) -> &'static str {
+        "package"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Latest versions on crates.io, PyPI and npm")
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![
+            command(Registry::Crates),
+            command(Registry::PyPI),
+            command(Registry::Npm),
+        ]
+    }
+
+    fn spawn_watchers(&self, bot: &Bot, data: &AppData, _config: &Config) {
+        package::spawn_release_watcher(bot.clone(), data.clone());
+    }
+}
+
+async fn package_handler(msg: Message, bot: Bot, data: AppData, registry: Registry) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let text = msg.text().unwrap();
+    let args = text.split_whitespace().skip(1).collect::<Vec<_>>();
+
+    let (op, name) = match args.as_slice() {
+        [name] => (None, *name),
+        [op @ ("watch" | "unwatch"), name] => (Some(*op), *name),
+        _ => {
+            abort!(
+                bot,
+                data,
+                msg,
+                "{}",
+                t!(lang, "package.usage", command = command_name(registry))
+            );
+        }
+    };
+
+    if op.is_some() && !is_chat_admin(&bot, &data, &msg).await? {
+        delayed_task::send_ephemeral(
+            &bot,
+            &data,
+            &msg,
+            t!(lang, "common.permission_denied"),
+            delayed_task::NOTICE_TTL,
+        )
+        .await?;
+        return Ok(());
+    }
+
+    send_action!(@Typing; msg, bot);
+    let target = ChatTarget::of(&msg);
+    let reply = match op {
+        Some("watch") => match package::watch(&data, target, registry, name).await {
+            Ok(true) => t!(lang, "package.watched", name = html::escape(name)),
+            Ok(false) => t!(lang, "package.already_watched", name = html::escape(name)),
+            Err(err) => {
+                abort!(bot, data, msg, "{}: {err}", t!(lang, "package.failed"));
+            }
+        },
+        Some(_) => {
+            if package::unwatch(&data, target, registry, name)? {
+                t!(lang, "package.unwatched", name = html::escape(name))
+            } else {
+                t!(lang, "package.not_watched", name = html::escape(name))
+            }
+        }
+        None => match package::lookup(&data, registry, name).await {
+            Ok(info) => {
+                let mut lines = vec![format!(
+                    "<b>{}</b> <code>{}</code>",
+                    html::escape(&info.name),
+                    html::escape(&info.version)
+                )];
+                if let Some(description) = &info.description {
+                    lines.push(html::escape(description.trim()));
+                }
+                match info.downloads {
+                    Some(Downloads::Total(count)) => {
+                        lines.push(t!(lang, "package.downloads", count = count))
+                    }
+                    Some(Downloads::LastWeek(count)) => {
+                        lines.push(t!(lang, "package.weekly_downloads", count = count))
+                    }
+                    None => {}
+                }
+                lines.push(format!(
+                    "<a href=\"{}\">{}</a>",
+                    html::escape(&info.docs),
+                    t!(lang, "package.docs")
+                ));
+                lines.join("\n")
+            }
+            Err(err) => {
+                abort!(bot, data, msg, "{}: {err}", t!(lang, "package.failed"));
+            }
+        },
+    };
+
+    data.reply(
+        msg.chat.id,
+        bot.send_message_to(&msg, reply).parse_mode(ParseMode::Html),
+    )
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use anyhow::Result;
+use teloxide::prelude::*;
+
+use rusty_maid::{
+    app::AppData,
+    command::CommandInfo,
+    i18n,
+    module::{BotModule, Command},
+    modules, t,
+    topic::SendTo,
+};
+use rusty_maid::{modules::Sendable, sendable};
+use teloxide::types::ParseMode;
+
+pub struct Pacman;
+
+#[async_trait::async_trait]
+impl BotModule for Pacman {
+    fn name(&self) -> &'static str {
+        "pacman"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Arch Linux package search")
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![Command::new(
+            CommandInfo::builder().name("pacman").build(),
+            dptree::endpoint(pacman_handler),
+        )]
+    }
+}
+
+async fn pacman_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let mut text = msg.text().unwrap().split(' ');
+    // shift one
+    text.next();
+
+    let operation = text.next();
+    if operation.is_none() {
+        abort!(bot, data, msg, "{}", t!(lang, "pacman.no_operation"));
+    }
+    let operation = operation.unwrap();
+
+    send_action!(@Typing; msg, bot);
+
+    match operation {
+        "-Si" => {
+            let pkg = text.next();
+            if pkg.is_none() {
+                abort!(bot, data, msg, "{}", t!(lang, "pacman.no_package"));
+            }
+            let resp = modules::archlinux::fetch_pkg_info(data.clone(), pkg.unwrap()).await;
+            match resp {
+                Ok(sendable) => {
+                    sendable!(bot, data, msg, sendable, format = Html);
+                }
+                Err(err) => {
+                    abort!(
+                        bot,
+                        data,
+                        msg,
+                        "{}: {:?}",
+                        t!(lang, "pacman.info_failed"),
+                        err
+                    );
+                }
+            };
+        }
+        "-Ss" => {
+            let pkg = text.next();
+            if pkg.is_none() {
+                abort!(bot, data, msg, "{}", t!(lang, "pacman.no_package"));
+            }
+            let resp = modules::archlinux::fetch_pkg_list(data.clone(), pkg.unwrap(), 8).await;
+            match resp {
+                Ok(sendable) => {
+                    sendable!(bot, data, msg, sendable, format = Html);
+                }
+                Err(err) => {
+                    abort!(
+                        bot,
+                        data,
+                        msg,
+                        "{}: {:?}",
+                        t!(lang, "pacman.search_failed"),
+                        err
+                    );
+                }
+            };
+        }
+        "-Syu" => {
+            if rand::random() {
+                data.reply(
+                    msg.chat.id,
+                    bot.send_message_to(&msg, t!(lang, "pacman.upgrade_success")),
+                )
+                .await?;
+            } else {
+                data.reply(
+                    msg.chat.id,
+                    bot.send_message_to(&msg, t!(lang, "pacman.upgrade_broken")),
+                )
+                .await?;
+            }
+        }
+        _ => {
+            data.reply(
+                msg.chat.id,
+                bot.send_message_to(&msg, t!(lang, "pacman.unimplemented")),
+            )
+            .await?;
+        }
+    };
+
+    Ok(())
+}
@@ -0,0 +1,163 @@
+use anyhow::Result;
+use teloxide::{
+    prelude::*,
+    types::{InputFile, ParseMode},
+    utils::html,
+};
+
+use rusty_maid::{
+    album::{self, MediaKind},
+    app::AppData,
+    bot_api,
+    command::CommandInfo,
+    i18n,
+    module::{BotModule, Command},
+    modules::qr,
+    t,
+    topic::SendTo,
+};
+
+pub struct Qr;
+
+#[async_trait::async_trait]
+impl BotModule for Qr {
+    fn name(&self) -> &'static str {
+        "qr"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("QR code generation and decoding")
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![Command::new(
+            CommandInfo::builder().name("qr").build(),
+            dptree::endpoint(qr_handler),
+        )]
+    }
+}
+
+/// The photos of the whole album when the message is a part of one, or else its own image.
+fn images_of(data: &AppData, msg: &Message) -> Result<Vec<String>> {
+    let photos = album::of_message(data, msg)?
+        .map(|album| {
+            album
+                .parts
+                .into_iter()
+                .filter(|part| part.kind == MediaKind::Photo)
+                .map(|part| part.file_id)
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    if !photos.is_empty() {
+        return Ok(photos);
+    }
+    Ok(image_of(msg).into_iter().collect())
+}
+
+/// The photo or the image file of the message.
+fn image_of(msg: &Message) -> Option<String> {
+    if let Some(sizes) = msg.photo() {
+        return sizes
+            .iter()
+            .max_by_key(|size| size.width)
+            .map(|size| size.file.id.clone());
+    }
+    let document = msg.document()?;
+    let is_image = document
+        .mime_type
+        .as_ref()
+        .is_some_and(|mime| mime.essence_str().starts_with("image/"));
+    is_image.then(|| document.file.id.clone())
+}
+
+async fn qr_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let text = msg.text().unwrap();
+    let args = text
+        .split_once([' ', '\n'])
+        .map_or("", |(_, args)| args.trim());
+    let reply = msg.reply_to_message();
+
+    if let Some(reply) = reply.filter(|_| args.is_empty()) {
+        let images = images_of(&data, reply)?;
+        if !images.is_empty() {
+            return decode(&bot, &data, lang, &msg, &images).await;
+        }
+    }
+    let content = if args.is_empty() {
+        reply.and_then(|reply| reply.text()).unwrap_or_default()
+    } else {
+        args
+    };
+    if content.is_empty() {
+        abort!(bot, data, msg, "{}", t!(lang, "qr.usage"));
+    }
+
+    let png = match qr::encode(content) {
+        Ok(png) => png,
+        Err(err) => {
+            abort!(bot, data, msg, "{}: {err}", t!(lang, "qr.encode_failed"));
+        }
+    };
+    send_action!(@UploadPhoto; msg, bot);
+    data.reply(
+        msg.chat.id,
+        bot.send_photo_to(&msg, InputFile::memory(png).file_name("qr.png")),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Read the QR codes in the images, the image that can't be read is skipped unless all fail.
+async fn decode(
+    bot: &Bot,
+    data: &AppData,
+    lang: &str,
+    msg: &Message,
+    file_ids: &[String],
+) -> Result<()> {
+    send_action!(@Typing; msg, bot);
+    let mut contents: Vec<String> = Vec::new();
+    let mut failures = Vec::new();
+    for file_id in file_ids {
+        let file = bot.get_file(file_id).await?;
+        let mut image = std::io::Cursor::new(Vec::with_capacity(file.size as usize));
+        bot_api::download_file(bot, &file.path, &mut image).await?;
+        let image = image.into_inner();
+        match tokio::task::spawn_blocking(move || qr::decode(&image)).await? {
+            Ok(found) => {
+                // The same code printed on several photos is listed once
+                for content in found {
+                    if !contents.contains(&content) {
+                        contents.push(content);
+                    }
+                }
+            }
+            Err(err) => failures.push(err),
+        }
+    }
+    if failures.len() == file_ids.len() {
+        let err = &failures[0];
+        abort!(bot, data, msg, "{}: {err}", t!(lang, "qr.decode_failed"));
+    }
+    if contents.is_empty() {
+        abort!(bot, data, msg, "{}", t!(lang, "qr.not_found"));
+    }
+
+    let mut lines = vec![t!(lang, "qr.found", count = contents.len())];
+    for content in &contents {
+        // Shown as code instead of a link, the warnings are read before opening it
+        lines.push(format!("<code>{}</code>", html::escape(content)));
+        for warning in qr::check_link(content).unwrap_or_default() {
+            lines.push(format!("⚠️ {}", i18n::translate(lang, warning.key(), &[])));
+        }
+    }
+    data.reply(
+        msg.chat.id,
+        bot.send_message_to(msg, lines.join("\n"))
+            .parse_mode(ParseMode::Html),
+    )
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,85 @@
+use anyhow::Result;
+use teloxide::{prelude::*, types::PollAnswer};
+
+use rusty_maid::{
+    app::AppData,
+    command::CommandInfo,
+    i18n,
+    module::{BotModule, Command},
+    modules::quiz,
+    t,
+    topic::{ChatTarget, SendTo},
+};
+
+pub struct Quiz;
+
+#[async_trait::async_trait]
+impl BotModule for Quiz {
+    fn name(&self) -> &'static str {
+        "quiz"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Trivia quiz game")
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![Command::new(
+            CommandInfo::builder().name("quiz").build(),
+            dptree::endpoint(quiz_handler),
+        )]
+    }
+
+    async fn on_poll_answer(
+        &self,
+        _bot: &Bot,
+        data: &AppData,
+        answer: &PollAnswer,
+    ) -> anyhow::Result<()> {
+        quiz::answer(data, answer)
+    }
+}
+
+async fn quiz_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let text = msg.text().unwrap();
+    let mut args = text.split_whitespace().skip(1);
+    let action = args.next().unwrap_or("start");
+    let rest = args.collect::<Vec<_>>().join(" ");
+
+    let reply = match action {
+        "start" => {
+            let category = Some(rest.as_str()).filter(|rest| !rest.is_empty());
+            match quiz::start(&bot, &data, ChatTarget::of(&msg), lang, category).await {
+                // The questions speak for themselves
+                Ok(true) => return Ok(()),
+                Ok(false) => t!(lang, "quiz.running"),
+                Err(err) => t!(lang, "quiz.failed", error = err),
+            }
+        }
+        "stop" => {
+            if quiz::stop(&bot, &data, msg.chat.id).await? {
+                return Ok(());
+            }
+            t!(lang, "quiz.not_running")
+        }
+        "categories" => {
+            let categories = match quiz::categories(&data).await {
+                Ok(categories) => categories,
+                Err(err) => {
+                    abort!(bot, data, msg, "{}", t!(lang, "quiz.failed", error = err));
+                }
+            };
+            let lines = categories
+                .iter()
+                .map(|category| format!("{} - {}", category.id, category.name))
+                .collect::<Vec<_>>();
+            format!("{}\n{}", t!(lang, "quiz.categories"), lines.join("\n"))
+        }
+        _ => t!(lang, "quiz.usage"),
+    };
+
+    data.reply(msg.chat.id, bot.send_message_to(&msg, reply))
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,413 @@
+use anyhow::Result;
+use image::ImageFormat;
+use redis::Commands;
+use teloxide::{
+    payloads::SendPhotoSetters,
+    prelude::*,
+    types::{ChatKind, InlineKeyboardMarkup, InputFile, InputSticker, ParseMode, User},
+};
+
+use rusty_maid::{
+    app::AppData,
+    bot_api,
+    callback::CallbackRouter,
+    command::CommandInfo,
+    i18n,
+    module::{BotModule, Command},
+    t,
+    topic::SendTo,
+};
+
+use crate::handlers::CALLBACK_ROUTER;
+
+pub struct Quote;
+
+#[async_trait::async_trait]
+impl BotModule for Quote {
+    fn name(&self) -> &'static str {
+        "quote"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Quote image and sticker")
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![
+            Command::new(
+                CommandInfo::builder().name("makequote").build(),
+                dptree::endpoint(make_quote_handler),
+            ),
+            Command::new(
+                CommandInfo::builder().name("delsticker").build(),
+                dptree::endpoint(del_sticker_handler),
+            ),
+        ]
+    }
+
+    fn callbacks(&self, router: CallbackRouter) -> CallbackRouter {
+        router.route("make_quote", |ctx| async move {
+            let (user_id, username): (u64, String) = ctx.payload()?;
+            add_photo_from_msg_to_sticker_set(ctx.query, ctx.bot, ctx.data, user_id, username)
+                .await?;
+            Ok(None)
+        })
+    }
+}
+
+fn create_quote_from_username(
+    target: &User,
+    username: &str,
+    quote: &str,
+    data: &AppData,
+) -> anyhow::Result<Vec<u8>> {
+    let avatar = make_quote::SpooledData::TgRandom {
+        id: target.id.0,
+        name: target.first_name.to_string(),
+    };
+    let quote_config = make_quote::ImgConfig::builder()
+        .username(username)
+        .quote(format!("「{}」", quote))
+        .avatar(&avatar)
+        .build();
+    let result = data.quote_maker.make_image(&quote_config)?;
+    Ok(result)
+}
+
+async fn create_quote(
+    bot: &Bot,
+    target: &User,
+    quote: &str,
+    data: &AppData,
+) -> anyhow::Result<Vec<u8>> {
+    let photos = bot
+        .get_user_profile_photos(target.id)
+        .limit(1)
+        .await?
+        .photos;
+
+    let username = if let Some(username) = &target.username {
+        format!("@{username}")
+    } else {
+        format!("- {}", target.first_name)
+    };
+
+    if photos.is_empty() || photos[0].is_empty() {
+        let img = create_quote_from_username(target, &username, quote, data)?;
+        return Ok(img);
+    }
+
+    let avatar_id = &photos
+        .last()
+        .unwrap()
+        .iter()
+        .max_by(|x, y| x.width.cmp(&y.width))
+        .unwrap()
+        .file
+        .id;
+    let file = bot.get_file(avatar_id).await?;
+    let avatar_cacher_key = format!("TG_AVATAR:USER:{}", avatar_id);
+    let cache: Option<Vec<u8>> = data.cacher.get_conn().get(&avatar_cacher_key)?;
+
+    let avatar = if let Some(cache) = cache {
+        cache
+    } else {
+        let mut avatar = std::io::Cursor::new(Vec::with_capacity(file.size as usize));
+        bot_api::download_file(bot, &file.path, &mut avatar).await?;
+        avatar.into_inner()
+    };
+
+    let () = data
+        .cacher
+        .get_conn()
+        .set_ex(avatar_cacher_key, avatar.as_slice(), 60 * 60 * 24)?;
+
+    let quote_config = make_quote::ImgConfig::builder()
+        .username(username)
+        .quote(format!("「{}」", quote))
+        .avatar(avatar.as_slice())
+        .build();
+    let result = data.quote_maker.make_image(&quote_config)?;
+    Ok(result)
+}
+
+async fn make_quote_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    send_action!(@Typing; msg, bot);
+    let lang = i18n::lang_of(&data, &msg);
+    let Some(reply_to_msg) = msg.reply_to_message() else {
+        abort!(bot, data, msg, "{}", t!(lang, "quote.need_reply"));
+    };
+
+    let quote = if let Some(quote) = reply_to_msg.text() {
+        quote
+    } else {
+        let Some(quote) = reply_to_msg.caption() else {
+            abort!(bot, data, msg, "{}", t!(lang, "quote.need_reply"));
+        };
+        quote
+    };
+
+    use chrono::prelude::*;
+    let today = Local::now();
+    let today_is_april_fool = today.month() == 4 && today.day() == 1;
+    let target = if today_is_april_fool {
+        let Some(target) = msg.from.as_ref() else {
+            abort!(bot, data, msg, "{}", t!(lang, "quote.joke_broken"));
+        };
+        target
+    } else if let Some(target) = reply_to_msg.forward_from_user() {
+        target
+    } else if let Some(target) = reply_to_msg.from.as_ref() {
+        target
+    } else {
+        abort!(bot, data, msg, "{}", t!(lang, "quote.need_normal_user"));
+    };
+
+    let photo = create_quote(&bot, target, quote, &data).await?;
+
+    send_action!(@UploadPhoto; msg, bot);
+
+    if today_is_april_fool {
+        data.reply(
+            msg.chat.id,
+            bot.send_photo_to(&msg, InputFile::memory(photo))
+                .caption(t!(lang, "quote.april_fool")),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    // The first name is only used as sticker set title, so it is fine to be truncated when it
+    // doesn't fit in the callback data.
+    let userid = target.id.0;
+    let mut user_first_name = target.first_name.clone();
+    let button = loop {
+        match CALLBACK_ROUTER.button(
+            t!(lang, "quote.add_to_sticker"),
+            "make_quote",
+            "add",
+            &(userid, &user_first_name),
+        ) {
+            Ok(button) => break button,
+            Err(err) if user_first_name.is_empty() => return Err(err),
+            Err(_) => {
+                user_first_name.pop();
+            }
+        }
+    };
+    let keyboard = InlineKeyboardMarkup::new(vec![vec![button]]);
+    data.reply(
+        msg.chat.id,
+        bot.send_photo_to(&msg, InputFile::memory(photo))
+            .reply_markup(keyboard),
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn get_chat_owner_from_cb(cb: &CallbackQuery, bot: Bot) -> Option<User> {
+    let msg = cb.message.as_ref()?;
+    match msg.chat().kind {
+        ChatKind::Public(_) => {
+            let member = bot
+                .get_chat_administrators(msg.chat().id)
+                .await
+                .ok()?
+                .into_iter()
+                .find(|member| member.is_owner())?;
+            Some(member.user)
+        }
+        ChatKind::Private(_) => Some(cb.from.clone()),
+    }
+}
+
+async fn download_photo(msg: &Message, bot: Bot) -> anyhow::Result<String> {
+    let photos = msg
+        .photo()
+        .ok_or_else(|| anyhow::anyhow!("This message doesn't contain any photo"))?;
+    let file_id = photos
+        .iter()
+        .max_by(|x, y| x.width.cmp(&y.width))
+        .unwrap_or_else(|| panic!("Fail to find any of the photo to compare? This is weird"))
+        .file
+        .id
+        .to_string();
+    let file = bot.get_file(&file_id).await?;
+    // Get the file extension. It should be ".jpg", but unwrapping it from the download filename is
+    // more reliable.
+    let path = std::path::Path::new(&file.path).extension().unwrap();
+
+    let dl_path = format!("/tmp/telegram-tmpfile-{file_id}.{}", path.to_string_lossy());
+    let mut tmpfile = tokio::fs::File::create(&dl_path).await?;
+    bot_api::download_file(&bot, &file.path, &mut tmpfile).await?;
+    Ok(dl_path)
+}
+
+fn legalize_sticker_img(path: &str) -> anyhow::Result<()> {
+    // Get the fd
+    let image = image::open(path)?;
+    // Tokio::fs::File doesn't implement std::io::Seek, so we need to use the std::fs::File.
+    // Truncate again into same path
+    let mut tmpfile = std::fs::File::create(path).unwrap();
+    // Telegram doesn't accept JPG format, so we need to convert it into PNG format here.
+    image
+        .thumbnail(512, 512)
+        .write_to(&mut tmpfile, ImageFormat::Png)?;
+    Ok(())
+}
+
+async fn add_or_create_sticker_set(
+    bot: Bot,
+    sticker: InputSticker,
+    sticker_owner: UserId,
+    sticker_name: &str,
+    sticker_title: &str,
+) -> anyhow::Result<()> {
+    let sticker_set = bot.get_sticker_set(sticker_name).await;
+    if let Ok(sticker_set) = sticker_set {
+        bot.add_sticker_to_set(sticker_owner, sticker_set.name, sticker)
+            .await?;
+    } else {
+        bot.create_new_sticker_set(sticker_owner, sticker_name, sticker_title, [sticker])
+            .await?;
+    }
+    Ok(())
+}
+
+async fn add_photo_from_msg_to_sticker_set(
+    cb: CallbackQuery,
+    bot: Bot,
+    data: AppData,
+    user_id: u64,
+    username: String,
+) -> anyhow::Result<()> {
+    // Bound check is done by callback_dispatcher
+    let msg = cb.regular_message().unwrap();
+    let lang = i18n::chat_language(&data, msg.chat.id.0, cb.from.language_code.as_deref());
+    let Some(keyboard) = msg.reply_markup() else {
+        // Actually this should be unreachable
+        abort!(bot, data, msg, "{}", t!(lang, "sticker.already_added"));
+    };
+
+    let lock_key = format!("quote_sticker_set_locker:{}", msg.id);
+    let mut redis_cli = data.cacher.get_conn();
+    let unhandle: bool = redis::cmd("SET")
+        .arg(&lock_key) // key
+        .arg(1) // val
+        .arg("NX") // NX
+        .arg("EX") // EX
+        .arg(60) // SECONDS
+        .query(&mut redis_cli)?;
+    if !unhandle {
+        return Ok(());
+    }
+
+    bot.edit_message_caption(msg.chat.id, msg.id)
+        .caption(t!(lang, "sticker.processing"))
+        .await?;
+
+    let result: anyhow::Result<()> = (async {
+        use teloxide::types::StickerFormat;
+        // STEP1: Get photo file from telegram
+        let dl_path = download_photo(msg, bot.clone()).await?;
+
+        // STEP2: Resize the image to 512px
+        //
+        // Using operation from std::fs will probably block the whole tokio task scheduler.
+        // SO I wrapped them into the `block_in_place` function to avoid that case.
+        tokio::task::block_in_place(|| legalize_sticker_img(&dl_path))?;
+
+        // STEP3: Read the resized image and send it to telegram
+        bot.edit_message_caption(msg.chat.id, msg.id)
+            .caption(t!(lang, "sticker.sending"))
+            .await?;
+        let sticker = InputSticker {
+            sticker: InputFile::file(&dl_path),
+            format: StickerFormat::Static,
+            emoji_list: vec!["💬".to_string()],
+            mask_position: None,
+            keywords: vec!["quote".to_string()],
+        };
+
+        // STEP4: Set the sticker
+        let bot_info = bot.get_me().await?;
+        let Some(sticker_owner) = get_chat_owner_from_cb(&cb, bot.clone()).await else {
+            abort!(bot, data, msg, "{}", t!(lang, "sticker.no_owner"));
+        };
+
+        let sticker_name = format!("quoting_{user_id}_by_{}", bot_info.username());
+        let sticker_title = format!("Quotes From {username}");
+
+        add_or_create_sticker_set(
+            bot.clone(),
+            sticker,
+            sticker_owner.id,
+            &sticker_name,
+            &sticker_title,
+        )
+        .await?;
+
+        // Step5: Clean up
+        let sticker_set_link = rusty_maid::helper::Html::a(
+            &format!("https://t.me/addstickers/{}", sticker_name),
+            "sticker set",
+        );
+        bot.edit_message_caption(msg.chat.id, msg.id)
+            .caption(t!(lang, "sticker.converted", link = sticker_set_link))
+            .parse_mode(ParseMode::Html)
+            .await?;
+
+        if let Err(err) = tokio::fs::remove_file(dl_path).await {
+            abort!(
+                bot,
+                data,
+                msg,
+                "fail to remove temp file after sticker converted: {err}"
+            );
+        }
+
+        Ok(())
+    })
+    .await;
+
+    if let Err(err) = result {
+        bot.edit_message_caption(msg.chat.id, msg.id)
+            .caption(t!(lang, "sticker.failed", error = err))
+            .reply_markup(keyboard.clone())
+            .await?;
+        let () = redis_cli.del(lock_key)?;
+    }
+
+    Ok(())
+}
+
+async fn del_sticker_handler(msg: Message, bot: Bot, data: AppData) -> anyhow::Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let Some(target_sticker_msg) = msg.reply_to_message() else {
+        abort!(bot, data, msg, "{}", t!(lang, "sticker.need_reply"));
+    };
+
+    let Some(sticker) = target_sticker_msg.sticker() else {
+        abort!(bot, data, msg, "{}", t!(lang, "sticker.need_reply"));
+    };
+
+    let result = bot.delete_sticker_from_set(&sticker.file.id).await;
+    if let Err(err) = result {
+        abort!(
+            bot,
+            data,
+            msg,
+            "{}",
+            t!(lang, "sticker.delete_failed", error = err)
+        );
+    }
+
+    data.reply(
+        msg.chat.id,
+        bot.send_message_to(&msg, t!(lang, "sticker.deleted")),
+    )
+    .await?;
+
+    Ok(())
+}
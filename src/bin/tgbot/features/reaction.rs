@@ -0,0 +1,173 @@
+use anyhow::Result;
+use std::num::NonZeroU64;
+use teloxide::{
+    prelude::*,
+    types::{MessageId, MessageReactionUpdated},
+};
+
+use rusty_maid::{
+    api,
+    app::AppData,
+    args::{ArgError, Args},
+    command::{CommandInfo, Permission},
+    i18n,
+    module::{BotModule, Command},
+    modules::reaction::{self, ReactionAction, ReactionRule},
+    role, t,
+    topic::SendTo,
+};
+
+pub struct Reaction;
+
+#[async_trait::async_trait]
+impl BotModule for Reaction {
+    fn name(&self) -> &'static str {
+        "reaction"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Reaction triggered actions")
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![Command::new(
+            CommandInfo::builder()
+                .name("reaction")
+                .permission(Permission::ChatAdmin)
+                .build(),
+            dptree::endpoint(reaction_handler),
+        )]
+    }
+
+    async fn on_reaction(
+        &self,
+        bot: &Bot,
+        data: &AppData,
+        update: &MessageReactionUpdated,
+    ) -> anyhow::Result<()> {
+        apply_rules(update, bot, data).await
+    }
+}
+
+async fn apply_rules(update: &MessageReactionUpdated, bot: &Bot, data: &AppData) -> Result<()> {
+    // Anonymous reactions can't be counted per user
+    let Some(user) = update.user() else {
+        return Ok(());
+    };
+    let chat_id = update.chat.id;
+    let rules = reaction::rules(&data.storage, chat_id.0).await?;
+    if rules.is_empty() {
+        return Ok(());
+    }
+
+    let (added, removed) = reaction::changed_emojis(update);
+    let message_id = update.message_id.0;
+    for rule in rules.iter().filter(|rule| removed.contains(&rule.emoji)) {
+        reaction::count_reaction(data, chat_id.0, message_id, &rule.emoji, user.id.0, false)?;
+    }
+
+    for rule in rules.iter().filter(|rule| added.contains(&rule.emoji)) {
+        // Only the messages of the bot are deleted, and only by the admins
+        if rule.action == ReactionAction::Delete
+            && (!api::sent_by_bot(data, chat_id, update.message_id)?
+                || !role::has_permission(bot, data, &update.chat, user.id, Permission::ChatAdmin)
+                    .await?)
+        {
+            continue;
+        }
+        let count =
+            reaction::count_reaction(data, chat_id.0, message_id, &rule.emoji, user.id.0, true)?;
+        if count < rule.threshold || !reaction::fire_once(data, chat_id.0, message_id, rule)? {
+            continue;
+        }
+
+        tracing::info!(
+            "[Reaction] {} message {message_id} in {chat_id} by {count} {}",
+            rule.action,
+            rule.emoji
+        );
+        match rule.action {
+            ReactionAction::Pin => {
+                bot.pin_chat_message(chat_id, MessageId(message_id))
+                    .disable_notification(true)
+                    .await?;
+            }
+            ReactionAction::Delete => {
+                bot.delete_message(chat_id, MessageId(message_id)).await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn reaction_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let mut args = Args::parse(&msg)?;
+    let chat_id = msg.chat.id.0;
+
+    let reply = match args.optional::<String>("operation")?.as_deref() {
+        None => {
+            let rules = reaction::rules(&data.storage, chat_id).await?;
+            if rules.is_empty() {
+                t!(lang, "reaction.no_rule")
+            } else {
+                rules
+                    .iter()
+                    .map(|rule| {
+                        t!(
+                            lang,
+                            "reaction.rule",
+                            emoji = rule.emoji,
+                            count = rule.threshold,
+                            action = rule.action
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+        }
+        Some("add") => {
+            let emoji = args.required::<String>("emoji")?;
+            let action = args.required::<ReactionAction>("action")?;
+            let threshold = args
+                .optional::<NonZeroU64>("count")?
+                .map_or(1, NonZeroU64::get);
+            args.finish()?;
+            let rule = ReactionRule {
+                emoji,
+                action,
+                threshold,
+            };
+            reaction::set_rule(&data.storage, chat_id, &rule).await?;
+            t!(
+                lang,
+                "reaction.added",
+                emoji = rule.emoji,
+                count = rule.threshold,
+                action = rule.action
+            )
+        }
+        Some("remove") => {
+            let emoji = args.required::<String>("emoji")?;
+            args.finish()?;
+            if reaction::remove_rule(&data.storage, chat_id, &emoji).await? {
+                t!(lang, "reaction.removed", emoji = emoji)
+            } else {
+                t!(lang, "reaction.not_found", emoji = emoji)
+            }
+        }
+        Some(operation) => {
+            return Err(ArgError::Invalid {
+                name: "operation",
+                value: operation.to_string(),
+                reason: "it should be add or remove".to_string(),
+            }
+            .into());
+        }
+    };
+
+    data.reply(msg.chat.id, bot.send_message_to(&msg, reply))
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,60 @@
+use anyhow::Result;
+use teloxide::prelude::*;
+
+use rusty_maid::{
+    app::AppData,
+    command::CommandInfo,
+    i18n,
+    module::{BotModule, Command},
+    modules, t,
+};
+use rusty_maid::{
+    inline::{self, InlineRouter},
+    modules::Sendable,
+};
+
+pub struct Roll;
+
+#[async_trait::async_trait]
+impl BotModule for Roll {
+    fn name(&self) -> &'static str {
+        "roll"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Roll number and dice")
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![Command::new(
+            CommandInfo::builder().name("roll").build(),
+            dptree::endpoint(roll_handler),
+        )]
+    }
+
+    fn inline_queries(&self, router: InlineRouter) -> InlineRouter {
+        router.route("roll", "roll.inline_usage", |_, _, lang, args| async move {
+            let Sendable::Text(result) = modules::roll::roll(Some(&args))? else {
+                anyhow::bail!("roll should always return text");
+            };
+            Ok(vec![inline::article(
+                "roll",
+                t!(lang, "roll.inline_title", args = args),
+                format!("🎲 {args}: {result}"),
+            )])
+        })
+    }
+}
+
+async fn roll_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    send_action!(@Typing; msg, bot);
+    let lang = i18n::lang_of(&data, &msg);
+
+    let text = msg.text().unwrap();
+    let args = text.split_once(' ').map(|(_, args)| args);
+
+    let result = modules::roll::roll(args);
+    handle_result!(bot, data, msg, result, t!(lang, "roll.failed"));
+
+    Ok(())
+}
@@ -0,0 +1,132 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use teloxide::{prelude::*, types::ParseMode, utils::html};
+
+use rusty_maid::{
+    app::AppData,
+    command::CommandInfo,
+    delayed_task, i18n,
+    module::{BotModule, Command},
+    modules::secret::{self, Charset, TokenFormat},
+    t,
+    topic::SendTo,
+};
+
+/// The secrets delete themselves after this, copy them in time
+const SECRET_TTL: Duration = Duration::from_secs(60);
+
+pub struct Secret;
+
+#[async_trait::async_trait]
+impl BotModule for Secret {
+    fn name(&self) -> &'static str {
+        "secret"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Passwords, UUIDs and tokens from a secure random source")
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![
+            Command::new(
+                CommandInfo::builder().name("pw").build(),
+                dptree::endpoint(pw_handler),
+            ),
+            Command::new(
+                CommandInfo::builder().name("uuid").build(),
+                dptree::endpoint(uuid_handler),
+            ),
+            Command::new(
+                CommandInfo::builder().name("token").build(),
+                dptree::endpoint(token_handler),
+            ),
+        ]
+    }
+}
+
+fn args_of(msg: &Message) -> Vec<&str> {
+    msg.text().unwrap().split_whitespace().skip(1).collect()
+}
+
+/// Send the secret for tap-to-copy, and delete it after [`SECRET_TTL`]. Everyone in the group
+/// sees it, so it comes with a warning there.
+async fn send_secret(bot: &Bot, data: &AppData, msg: &Message, secret: &str) -> Result<()> {
+    let lang = i18n::lang_of(data, msg);
+    let mut text = format!(
+        "<code>{}</code>\n\n{}",
+        html::escape(secret),
+        t!(lang, "secret.self_destruct", seconds = SECRET_TTL.as_secs())
+    );
+    if !msg.chat.is_private() {
+        text.push('\n');
+        text.push_str(&t!(lang, "secret.group_warning"));
+    }
+    let sent = data
+        .reply(
+            msg.chat.id,
+            bot.send_message_to(msg, text).parse_mode(ParseMode::Html),
+        )
+        .await?;
+    delayed_task::delete_later(data, &sent, SECRET_TTL)?;
+    Ok(())
+}
+
+async fn pw_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let (mut len, mut charset) = (secret::DEFAULT_PASSWORD_LEN, Charset::Full);
+    for arg in args_of(&msg) {
+        if let Ok(value) = arg.parse() {
+            len = value;
+            continue;
+        }
+        match arg.parse() {
+            Ok(value) => charset = value,
+            Err(err) => {
+                abort!(bot, data, msg, "{}\n{err}", t!(lang, "secret.pw_usage"));
+            }
+        }
+    }
+
+    match secret::password(len, charset) {
+        Ok(password) => send_secret(&bot, &data, &msg, &password).await,
+        Err(err) => {
+            abort!(bot, data, msg, "{}\n{err}", t!(lang, "secret.pw_usage"));
+        }
+    }
+}
+
+async fn uuid_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    data.reply(
+        msg.chat.id,
+        bot.send_message_to(&msg, format!("<code>{}</code>", secret::uuid()))
+            .parse_mode(ParseMode::Html),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn token_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let (mut format, mut bytes) = (TokenFormat::Hex, secret::DEFAULT_TOKEN_BYTES);
+    for arg in args_of(&msg) {
+        if let Ok(value) = arg.parse() {
+            bytes = value;
+            continue;
+        }
+        match arg.parse() {
+            Ok(value) => format = value,
+            Err(err) => {
+                abort!(bot, data, msg, "{}\n{err}", t!(lang, "secret.token_usage"));
+            }
+        }
+    }
+
+    match secret::token(format, bytes) {
+        Ok(token) => send_secret(&bot, &data, &msg, &token).await,
+        Err(err) => {
+            abort!(bot, data, msg, "{}\n{err}", t!(lang, "secret.token_usage"));
+        }
+    }
+}
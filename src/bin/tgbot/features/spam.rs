@@ -0,0 +1,223 @@
+use anyhow::Result;
+use teloxide::{
+    prelude::*,
+    types::{ChatPermissions, ParseMode, ReplyParameters},
+    utils::html,
+};
+
+use rusty_maid::{
+    app::AppData,
+    args::Args,
+    command::{CommandInfo, Permission},
+    i18n,
+    module::{BotModule, Command, UpdateFilter},
+    modules::spam::{self, SpamAction},
+    role,
+    settings::SettingSchema,
+    t,
+    topic::SendTo,
+};
+
+/// Seconds that the sender of the spam is muted
+const MUTE_SECONDS: i64 = 60 * 60 * 24;
+
+pub struct Spam;
+
+#[async_trait::async_trait]
+impl BotModule for Spam {
+    fn name(&self) -> &'static str {
+        "spam"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Spam filter")
+    }
+
+    fn opt_in(&self) -> bool {
+        true
+    }
+
+    fn settings(&self) -> Vec<SettingSchema> {
+        vec![SettingSchema {
+            key: "policy",
+            description: "Spam policy",
+            choices: &["report", "delete", "mute"],
+            get: |data, chat_id| Ok(spam::get_action(data, chat_id)?.to_string()),
+            set: |data, chat_id, value| spam::set_action(data, chat_id, value.parse()?),
+        }]
+    }
+
+    fn update_filter(&self) -> UpdateFilter {
+        UpdateFilter::GROUP_MEMBERS
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![
+            Command::new(
+                CommandInfo::builder()
+                    .name("spam")
+                    .permission(Permission::ChatAdmin)
+                    .build(),
+                dptree::endpoint(spam_handler),
+            ),
+            Command::new(
+                CommandInfo::builder()
+                    .name("ham")
+                    .permission(Permission::ChatAdmin)
+                    .build(),
+                dptree::endpoint(ham_handler),
+            ),
+            Command::new(
+                CommandInfo::builder()
+                    .name("spampolicy")
+                    .permission(Permission::ChatAdmin)
+                    .build(),
+                dptree::endpoint(policy_handler),
+            ),
+        ]
+    }
+
+    async fn on_message(&self, bot: &Bot, data: &AppData, msg: &Message) -> anyhow::Result<()> {
+        filter_spam(msg, bot, data, false).await
+    }
+
+    /// Catch the messages edited into spam after passing the filter
+    async fn on_edited_message(
+        &self,
+        bot: &Bot,
+        data: &AppData,
+        msg: &Message,
+    ) -> anyhow::Result<()> {
+        filter_spam(msg, bot, data, true).await
+    }
+}
+
+async fn filter_spam(msg: &Message, bot: &Bot, data: &AppData, edited: bool) -> anyhow::Result<()> {
+    let (Some(sender), Some(text)) = (msg.from.as_ref(), msg.text()) else {
+        return Ok(());
+    };
+
+    let chat_id = msg.chat.id;
+    let new_member = if edited {
+        spam::is_new_member(data, chat_id.0, sender.id.0)?
+    } else {
+        spam::record_member_message(data, chat_id.0, sender.id.0)?
+    };
+    let verdict = spam::check(data, chat_id.0, text, new_member)?;
+    if !verdict.is_spam() {
+        return Ok(());
+    }
+    if role::has_permission(bot, data, &msg.chat, sender.id, Permission::ChatAdmin).await? {
+        return Ok(());
+    }
+
+    let lang = i18n::chat_language(data, chat_id.0, None);
+    let reasons = verdict
+        .reasons
+        .iter()
+        .map(|reason| i18n::translate(lang, reason, &[]))
+        .collect::<Vec<_>>()
+        .join(", ");
+    tracing::info!(
+        "[Spam] message {} in {chat_id} looks like spam: {reasons}",
+        msg.id
+    );
+    match spam::get_action(data, chat_id.0)? {
+        SpamAction::Delete => {
+            bot.delete_message(chat_id, msg.id).await?;
+        }
+        SpamAction::Mute => {
+            bot.delete_message(chat_id, msg.id).await?;
+            let until = chrono::Utc::now() + chrono::Duration::seconds(MUTE_SECONDS);
+            bot.restrict_chat_member(chat_id, sender.id, ChatPermissions::empty())
+                .until_date(until)
+                .await?;
+        }
+        SpamAction::Report => {
+            let admins = bot
+                .get_chat_administrators(chat_id)
+                .await?
+                .into_iter()
+                .filter(|member| !member.user.is_bot)
+                .map(|member| {
+                    html::user_mention(member.user.id, &html::escape(&member.user.first_name))
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            data.reply(
+                msg.chat.id,
+                bot.send_message_to(
+                    msg,
+                    t!(lang, "spam.reported", reasons = reasons, admins = admins),
+                )
+                .reply_parameters(ReplyParameters::new(msg.id))
+                .parse_mode(ParseMode::Html),
+            )
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn spam_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let Some(text) = msg.reply_to_message().and_then(|reply| reply.text()) else {
+        abort!(bot, data, msg, "{}", t!(lang, "spam.need_reply"));
+    };
+    spam::train(&data, msg.chat.id.0, text, true)?;
+
+    let reply = msg.reply_to_message().unwrap();
+    if let Err(err) = bot.delete_message(msg.chat.id, reply.id).await {
+        abort!(
+            bot,
+            data,
+            msg,
+            "{}",
+            t!(lang, "spam.delete_failed", error = err)
+        );
+    }
+    data.reply(
+        msg.chat.id,
+        bot.send_message_to(&msg, t!(lang, "spam.learned_spam")),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn ham_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let Some(text) = msg.reply_to_message().and_then(|reply| reply.text()) else {
+        abort!(bot, data, msg, "{}", t!(lang, "spam.need_reply"));
+    };
+    spam::train(&data, msg.chat.id.0, text, false)?;
+    data.reply(
+        msg.chat.id,
+        bot.send_message_to(&msg, t!(lang, "spam.learned_ham")),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn policy_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let mut args = Args::parse(&msg)?;
+    let Some(action) = args.optional::<SpamAction>("action")? else {
+        let action = spam::get_action(&data, msg.chat.id.0)?;
+        abort!(
+            bot,
+            data,
+            msg,
+            "{}",
+            t!(lang, "spam.policy", action = action)
+        );
+    };
+    args.finish()?;
+    spam::set_action(&data, msg.chat.id.0, action)?;
+    data.reply(
+        msg.chat.id,
+        bot.send_message_to(&msg, t!(lang, "spam.policy_updated", action = action)),
+    )
+    .await?;
+    Ok(())
+}
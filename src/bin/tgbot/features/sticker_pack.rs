@@ -0,0 +1,232 @@
+use anyhow::Result;
+use teloxide::{
+    prelude::*,
+    types::{InputFile, InputSticker, ParseMode, StickerFormat, User},
+    utils::html,
+    RequestError,
+};
+
+use rusty_maid::{
+    app::AppData,
+    bot_api,
+    command::CommandInfo,
+    helper::Html,
+    i18n, media_cache,
+    module::{BotModule, Command},
+    modules::sticker_pack::{self, StickerPack},
+    t,
+    topic::SendTo,
+};
+
+/// Emoji of the sticker when neither the user nor the replied sticker gives one
+const DEFAULT_EMOJI: &str = "🙂";
+
+pub struct StickerPacks;
+
+#[async_trait::async_trait]
+impl BotModule for StickerPacks {
+    fn name(&self) -> &'static str {
+        "sticker_pack"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Sticker sets made from the chat images")
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![Command::new(
+            CommandInfo::builder().name("pack").build(),
+            dptree::endpoint(pack_handler),
+        )]
+    }
+}
+
+async fn pack_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let Some(user) = msg.from.as_ref() else {
+        abort!(bot, data, msg, "{}", t!(lang, "sticker_pack.need_user"));
+    };
+    let text = msg.text().unwrap();
+    let args = text.split_whitespace().skip(1).collect::<Vec<_>>();
+
+    let reply = match args.as_slice() {
+        ["new", name, title @ ..] => {
+            new_pack(&bot, &data, lang, user, name, &title.join(" ")).await
+        }
+        ["add", emoji @ ..] => {
+            add_sticker(&bot, &data, lang, &msg, user, emoji.first().copied()).await
+        }
+        ["del"] => del_sticker(&bot, lang, &msg, user).await,
+        _ => Ok(t!(lang, "sticker_pack.usage")),
+    };
+    let reply = match reply {
+        Ok(reply) => reply,
+        Err(err) => t!(
+            lang,
+            "sticker_pack.failed",
+            error = html::escape(&err.to_string())
+        ),
+    };
+
+    data.reply(
+        msg.chat.id,
+        bot.send_message_to(&msg, reply).parse_mode(ParseMode::Html),
+    )
+    .await?;
+    Ok(())
+}
+
+fn pack_link(pack: &StickerPack) -> String {
+    Html::a(&pack.link(), &html::escape(&pack.title))
+}
+
+async fn new_pack(
+    bot: &Bot,
+    data: &AppData,
+    lang: &str,
+    user: &User,
+    name: &str,
+    title: &str,
+) -> Result<String> {
+    let me = bot.get_me().await?;
+    let pack = match StickerPack::new(name, title, user.id.0, me.username()) {
+        Ok(pack) => pack,
+        Err(err) => {
+            return Ok(t!(
+                lang,
+                "sticker_pack.invalid_name",
+                error = html::escape(&err.to_string())
+            ))
+        }
+    };
+    sticker_pack::set_current(data, user.id.0, &pack)?;
+    // The set is created with the first sticker, Telegram doesn't allow an empty one
+    if bot.get_sticker_set(&pack.name).await.is_ok() {
+        Ok(t!(lang, "sticker_pack.switched", link = pack_link(&pack)))
+    } else {
+        Ok(t!(
+            lang,
+            "sticker_pack.created",
+            title = html::escape(&pack.title)
+        ))
+    }
+}
+
+/// The image of the message as the file and the emoji of the sticker made from it. Animated and
+/// video stickers are not images.
+fn image_of(msg: &Message) -> Option<(String, Option<String>)> {
+    if let Some(sizes) = msg.photo() {
+        let largest = sizes.iter().max_by_key(|size| size.width)?;
+        return Some((largest.file.id.clone(), None));
+    }
+    if let Some(sticker) = msg.sticker() {
+        if !sticker.is_static() {
+            return None;
+        }
+        return Some((sticker.file.id.clone(), sticker.emoji.clone()));
+    }
+    let document = msg.document()?;
+    let is_image = document
+        .mime_type
+        .as_ref()
+        .is_some_and(|mime| mime.essence_str().starts_with("image/"));
+    is_image.then(|| (document.file.id.clone(), None))
+}
+
+async fn add_sticker(
+    bot: &Bot,
+    data: &AppData,
+    lang: &str,
+    msg: &Message,
+    user: &User,
+    emoji: Option<&str>,
+) -> Result<String> {
+    let Some((file_id, sticker_emoji)) = msg.reply_to_message().and_then(image_of) else {
+        return Ok(t!(lang, "sticker_pack.need_image"));
+    };
+    let Some(pack) = sticker_pack::current(data, user.id.0)? else {
+        return Ok(t!(lang, "sticker_pack.no_pack"));
+    };
+
+    send_action!(@UploadPhoto; msg, bot);
+    let file = bot.get_file(file_id).await?;
+    let mut image = std::io::Cursor::new(Vec::with_capacity(file.size as usize));
+    bot_api::download_file(bot, &file.path, &mut image).await?;
+    let image = image.into_inner();
+    let png = tokio::task::spawn_blocking(move || sticker_pack::legalize(&image)).await??;
+
+    let emoji = emoji
+        .map(|emoji| emoji.to_string())
+        .or(sticker_emoji)
+        .unwrap_or_else(|| DEFAULT_EMOJI.to_string());
+    // The same image makes the same sticker, its upload is reused for the other packs
+    let cached = media_cache::lookup(data, bot, &png)?;
+    let file_id = match cached.clone() {
+        Some(file_id) => file_id,
+        None => upload_sticker(bot, data, user, &png).await?,
+    };
+    match put_sticker(bot, user, &pack, file_id, &emoji).await {
+        // The file might be gone, upload it again
+        Err(RequestError::Api(err)) if cached.is_some() => {
+            tracing::warn!("cached sticker file is rejected, uploading again: {err}");
+            media_cache::forget(data, bot, &png)?;
+            let file_id = upload_sticker(bot, data, user, &png).await?;
+            put_sticker(bot, user, &pack, file_id, &emoji).await?;
+        }
+        result => result?,
+    }
+    Ok(t!(lang, "sticker_pack.added", link = pack_link(&pack)))
+}
+
+/// Upload the sticker image, and return the file id to add it to a set.
+async fn upload_sticker(bot: &Bot, data: &AppData, user: &User, png: &[u8]) -> Result<String> {
+    let file = bot
+        .upload_sticker_file(
+            user.id,
+            InputFile::memory(png.to_vec()).file_name("sticker.png"),
+            StickerFormat::Static,
+        )
+        .await?;
+    media_cache::remember(data, bot, png, &file.id)?;
+    Ok(file.id)
+}
+
+/// Add the uploaded sticker to the pack, or create the pack with it.
+async fn put_sticker(
+    bot: &Bot,
+    user: &User,
+    pack: &StickerPack,
+    file_id: String,
+    emoji: &str,
+) -> Result<(), RequestError> {
+    let sticker = InputSticker {
+        sticker: InputFile::file_id(file_id),
+        format: StickerFormat::Static,
+        emoji_list: vec![emoji.to_string()],
+        mask_position: None,
+        keywords: Vec::new(),
+    };
+    if bot.get_sticker_set(&pack.name).await.is_ok() {
+        bot.add_sticker_to_set(user.id, &pack.name, sticker).await?;
+    } else {
+        bot.create_new_sticker_set(user.id, &pack.name, &pack.title, [sticker])
+            .await?;
+    }
+    Ok(())
+}
+
+async fn del_sticker(bot: &Bot, lang: &str, msg: &Message, user: &User) -> Result<String> {
+    let Some(sticker) = msg.reply_to_message().and_then(|reply| reply.sticker()) else {
+        return Ok(t!(lang, "sticker_pack.need_sticker"));
+    };
+    let me = bot.get_me().await?;
+    let owner = sticker
+        .set_name
+        .as_deref()
+        .and_then(|set_name| sticker_pack::owner_of(set_name, me.username()));
+    if owner != Some(user.id.0) {
+        return Ok(t!(lang, "sticker_pack.not_owner"));
+    }
+    bot.delete_sticker_from_set(&sticker.file.id).await?;
+    Ok(t!(lang, "sticker_pack.deleted"))
+}
@@ -0,0 +1,91 @@
+use anyhow::Result;
+use teloxide::{prelude::*, types::ParseMode, utils::html};
+
+use rusty_maid::{
+    app::AppData,
+    command::CommandInfo,
+    i18n,
+    module::{BotModule, Command},
+    modules::summary::{self, SummaryLength},
+    t,
+    topic::SendTo,
+};
+
+use super::MATCH_URL;
+
+pub struct Summary;
+
+#[async_trait::async_trait]
+impl BotModule for Summary {
+    fn name(&self) -> &'static str {
+        "summary"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Summary of the linked article")
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![Command::new(
+            CommandInfo::builder().name("sum").build(),
+            dptree::endpoint(sum_handler),
+        )]
+    }
+}
+
+/// The first link in the text, or in the replied message.
+fn find_url(args: &str, msg: &Message) -> Option<String> {
+    let replied = msg
+        .reply_to_message()
+        .and_then(|reply| reply.text().or(reply.caption()));
+    [Some(args), replied]
+        .into_iter()
+        .flatten()
+        .find_map(|text| MATCH_URL.captures(text)?.get(1))
+        .map(|url| url.as_str().to_string())
+}
+
+async fn sum_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let text = msg.text().unwrap();
+    let args = text
+        .split_once([' ', '\n'])
+        .map_or("", |(_, args)| args.trim());
+
+    let (first, rest) = args.split_once(' ').unwrap_or((args, ""));
+    let (length, args) = match first.parse() {
+        Ok(length) => (length, rest),
+        Err(_) => (SummaryLength::Medium, args),
+    };
+    let Some(url) = find_url(args, &msg) else {
+        abort!(bot, data, msg, "{}", t!(lang, "summary.usage"));
+    };
+
+    send_action!(@Typing; msg, bot);
+    let summary = match summary::summarize_url(&data, &url, length).await {
+        Ok(summary) => summary,
+        Err(err) => {
+            abort!(bot, data, msg, "{}: {err}", t!(lang, "summary.failed"));
+        }
+    };
+
+    let mut reply = String::new();
+    if let Some(title) = &summary.title {
+        reply.push_str(&format!("<b>{}</b>\n\n", html::escape(title)));
+    }
+    for sentence in &summary.sentences {
+        reply.push_str(&format!("• {}\n", html::escape(sentence)));
+    }
+    reply.push_str(&format!(
+        "\n<a href=\"{}\">{}</a>",
+        html::escape(&summary.url),
+        t!(lang, "summary.source")
+    ));
+
+    data.reply(
+        msg.chat.id,
+        bot.send_message_to(&msg, reply).parse_mode(ParseMode::Html),
+    )
+    .await?;
+    Ok(())
+}
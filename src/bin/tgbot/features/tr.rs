@@ -0,0 +1,123 @@
+use anyhow::Result;
+use teloxide::prelude::*;
+
+use rusty_maid::inline::{self, InlineRouter};
+use rusty_maid::{
+    app::AppData,
+    command::CommandInfo,
+    config::Config,
+    i18n,
+    module::{BotModule, Command},
+    modules, t,
+    topic::SendTo,
+    usage::QuotaExceeded,
+};
+
+/// The inline query only translates the text ending with one of them
+const SENTENCE_END: &[char] = &['.', '!', '?', '。', '！', '？', '…'];
+
+pub struct Translate;
+
+#[async_trait::async_trait]
+impl BotModule for Translate {
+    fn name(&self) -> &'static str {
+        "tr"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("DeepL translation")
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![Command::new(
+            CommandInfo::builder().name("tr").build(),
+            dptree::endpoint(tr_handler),
+        )]
+    }
+
+    fn check_config(&self, config: &Config) -> Vec<String> {
+        if config.deepl.api_key.is_empty() {
+            return vec![
+                "deepl.api_key is empty, fill it in or disable the `tr` module".to_string(),
+            ];
+        }
+        Vec::new()
+    }
+
+    fn inline_queries(&self, router: InlineRouter) -> InlineRouter {
+        router.route(
+            "tr",
+            "tr.inline_usage",
+            |data, user, lang, args| async move {
+                let (target, text) = modules::translate::split_target_lang(&args);
+                let title = t!(lang, "tr.inline_title", target = target);
+                // The query is sent on every keystroke, wait for the end of the sentence
+                if !text.ends_with(SENTENCE_END) {
+                    let hint = t!(lang, "tr.inline_hint");
+                    return Ok(vec![inline::article("tr", title, hint)]);
+                }
+                let translated =
+                    modules::translate::translate(&data, user, text, None, target).await?;
+                Ok(vec![inline::article("tr", title, translated)])
+            },
+        )
+    }
+}
+
+async fn tr_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let help = t!(lang, "tr.help");
+    let replyto = msg.reply_to_message();
+    if replyto.is_none() {
+        abort!(bot, data, msg, "{}\n{}", t!(lang, "tr.need_reply"), help);
+    }
+
+    let text = replyto.unwrap().text();
+    if text.is_none() {
+        abort!(bot, data, msg, "{}\n{}", t!(lang, "tr.need_reply"), help);
+    }
+    let text = text.unwrap();
+
+    let args = msg.text().unwrap().split(' ').skip(1).collect::<Vec<_>>();
+    if args.is_empty() {
+        abort!(bot, data, msg, "{}\n{}", t!(lang, "tr.need_target"), help);
+    }
+
+    let mut source_lang = None;
+    let target_lang;
+
+    macro_rules! parse_lang {
+        ($str:expr) => {{
+            let lang = modules::translate::parse_lang($str);
+            if let Err(err) = lang {
+                abort!(bot, data, msg, "{}", err);
+            }
+            lang.unwrap()
+        }};
+    }
+
+    if args.len() == 1 {
+        target_lang = parse_lang!(args[0]);
+    } else {
+        source_lang = Some(parse_lang!(args[0]));
+        target_lang = parse_lang!(args[1]);
+    }
+
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(());
+    };
+    let result =
+        modules::translate::translate(&data, user.id, text, source_lang, target_lang).await;
+    let reply = match result {
+        Ok(full_text) => full_text,
+        Err(err) => match err.downcast_ref::<QuotaExceeded>() {
+            Some(exceeded) if exceeded.per_user => t!(lang, "usage.user_quota_met"),
+            Some(_) => t!(lang, "usage.daily_quota_met"),
+            None => err.to_string(),
+        },
+    };
+    data.reply(msg.chat.id, bot.send_message_to(&msg, reply))
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,100 @@
+use std::fmt::Write;
+
+use redis::Commands;
+use teloxide::prelude::*;
+
+use rusty_maid::{app::AppData, module::BotModule, topic::SendTo};
+
+use super::MATCH_URL;
+
+/// Seconds to remember the links cleaned in the message
+const CLEANED_TTL: i64 = 60 * 60 * 24;
+
+pub struct UrlCleaner;
+
+#[async_trait::async_trait]
+impl BotModule for UrlCleaner {
+    fn name(&self) -> &'static str {
+        "url_cleaner"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Remove tracking parameters from links")
+    }
+
+    async fn on_message(&self, bot: &Bot, app_data: &AppData, msg: &Message) -> anyhow::Result<()> {
+        clean_urls(msg, bot, app_data).await
+    }
+
+    async fn on_edited_message(
+        &self,
+        bot: &Bot,
+        app_data: &AppData,
+        msg: &Message,
+    ) -> anyhow::Result<()> {
+        clean_urls(msg, bot, app_data).await
+    }
+}
+
+/// Reply the links without the tracking parameters. Links cleaned before are skipped, so
+/// editing the message only replies the new links.
+async fn clean_urls(msg: &Message, bot: &Bot, app_data: &AppData) -> anyhow::Result<()> {
+    let captures = MATCH_URL.captures_iter(msg.text().unwrap());
+    let urls: Vec<_> = captures
+        .filter_map(|cap| cap.get(1))
+        .map(|m| m.as_str())
+        .collect();
+
+    if urls.is_empty() {
+        return Ok(());
+    }
+
+    let mut data = Vec::new();
+
+    for url in urls {
+        if let Ok(result) = app_data.url_cleaner.clear(url).await {
+            if result.as_str() == url {
+                continue;
+            }
+
+            data.push(result);
+        }
+    }
+
+    if data.is_empty() {
+        return Ok(());
+    }
+    let data = {
+        let key = format!("URL_CLEANED:{}:{}", msg.chat.id, msg.id);
+        let mut conn = app_data.cacher.get_conn();
+        let mut cleaned = Vec::new();
+        for url in data {
+            let added: bool = conn.sadd(&key, url.as_str())?;
+            if added {
+                cleaned.push(url);
+            }
+        }
+        let () = conn.expire(&key, CLEANED_TTL)?;
+        cleaned
+    };
+
+    if !data.is_empty() {
+        app_data
+            .reply(
+                msg.chat.id,
+                bot.send_message_to(
+                    msg,
+                    format!(
+                        "Clean URLs\n{}",
+                        data.iter().fold(String::new(), |mut acc, item| {
+                            write!(&mut acc, "* {item}").unwrap();
+                            acc
+                        })
+                    ),
+                ),
+            )
+            .await?;
+    }
+
+    Ok(())
+}
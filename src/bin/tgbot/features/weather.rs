@@ -0,0 +1,82 @@
+use anyhow::Result;
+use teloxide::prelude::*;
+
+use rusty_maid::inline::{self, InlineRouter};
+use rusty_maid::{
+    app::AppData,
+    command::CommandInfo,
+    delayed_task, i18n, media_cache,
+    module::{BotModule, Command},
+    modules, t,
+    topic::{ChatTarget, SendTo},
+};
+
+pub struct Weather;
+
+#[async_trait::async_trait]
+impl BotModule for Weather {
+    fn name(&self) -> &'static str {
+        "weather"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Weather search")
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![Command::new(
+            CommandInfo::builder().name("weather").build(),
+            dptree::endpoint(weather_handler),
+        )]
+    }
+
+    fn inline_queries(&self, router: InlineRouter) -> InlineRouter {
+        router.route(
+            "weather",
+            "weather.inline_usage",
+            |data, _, _, city| async move {
+                let weather = modules::weather::fetch_weather_text(&data, &city).await?;
+                Ok(vec![inline::article("weather", city, weather)])
+            },
+        )
+    }
+}
+
+async fn weather_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    send_action!(@Typing; msg, bot);
+    let lang = i18n::lang_of(&data, &msg);
+
+    let text = msg.text().unwrap();
+    let parts = text.split(' ').collect::<Vec<&str>>();
+    if parts.len() < 2 {
+        abort!(bot, data, msg, "{}", t!(lang, "weather.usage"));
+    }
+
+    let city = parts[1..].join(" ");
+    let weather = tokio::try_join!(
+        modules::weather::fetch_weather_text(&data, &city),
+        modules::weather::fetch_weather_image(&data, &city),
+    );
+    let (caption, image) = match weather {
+        Ok(weather) => weather,
+        Err(err) => {
+            // The error notices are noisy in the group, they delete themselves
+            let notice = format!("{}: {err:?}", t!(lang, "weather.failed"));
+            delayed_task::send_ephemeral(
+                &bot,
+                &data,
+                ChatTarget::of(&msg),
+                notice,
+                delayed_task::NOTICE_TTL,
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    media_cache::send_cached(&data, &bot, msg.chat.id, image, |file| {
+        bot.send_photo_to(&msg, file).caption(caption.clone())
+    })
+    .await?;
+
+    Ok(())
+}
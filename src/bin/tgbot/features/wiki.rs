@@ -0,0 +1,91 @@
+use anyhow::Result;
+use teloxide::{prelude::*, types::ParseMode, utils::html};
+
+use rusty_maid::{
+    app::AppData,
+    command::CommandInfo,
+    i18n,
+    module::{BotModule, Command},
+    modules::wiki::{self, WikiResult, WikiSite},
+    t,
+    topic::SendTo,
+};
+
+pub struct Wiki;
+
+#[async_trait::async_trait]
+impl BotModule for Wiki {
+    fn name(&self) -> &'static str {
+        "wiki"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Wikipedia and Moegirlpedia summary")
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![
+            Command::new(
+                CommandInfo::builder().name("wiki").build(),
+                dptree::endpoint(|msg: Message, bot: Bot, data: AppData| {
+                    wiki_handler(msg, bot, data, WikiSite::Wikipedia)
+                }),
+            ),
+            Command::new(
+                CommandInfo::builder().name("moegirl").build(),
+                dptree::endpoint(|msg: Message, bot: Bot, data: AppData| {
+                    wiki_handler(msg, bot, data, WikiSite::Moegirl)
+                }),
+            ),
+        ]
+    }
+}
+
+async fn wiki_handler(msg: Message, bot: Bot, data: AppData, site: WikiSite) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let text = msg.text().unwrap();
+    let term = text
+        .split_once([' ', '\n'])
+        .map_or("", |(_, term)| term.trim());
+    if term.is_empty() {
+        abort!(bot, data, msg, "{}", t!(lang, "wiki.usage"));
+    }
+
+    send_action!(@Typing; msg, bot);
+    let reply = match wiki::lookup(&data, site, wiki::site_language(lang), term).await {
+        Ok(WikiResult::Page {
+            title,
+            summary,
+            url,
+        }) => format!(
+            "<b>{}</b>\n\n{}\n\n<a href=\"{}\">{}</a>",
+            html::escape(&title),
+            html::escape(&summary),
+            html::escape(&url),
+            t!(lang, "wiki.read_more")
+        ),
+        Ok(WikiResult::Suggestions(titles)) if titles.is_empty() => {
+            t!(lang, "wiki.not_found", term = html::escape(term))
+        }
+        Ok(WikiResult::Suggestions(titles)) => t!(
+            lang,
+            "wiki.suggestions",
+            term = html::escape(term),
+            titles = titles
+                .iter()
+                .map(|title| format!("• <code>{}</code>", html::escape(title)))
+                .collect::<Vec<_>>()
+                .join("\n")
+        ),
+        Err(err) => {
+            abort!(bot, data, msg, "{}: {err}", t!(lang, "wiki.failed"));
+        }
+    };
+
+    data.reply(
+        msg.chat.id,
+        bot.send_message_to(&msg, reply).parse_mode(ParseMode::Html),
+    )
+    .await?;
+    Ok(())
+}
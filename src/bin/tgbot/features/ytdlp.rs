@@ -0,0 +1,69 @@
+use teloxide::prelude::*;
+
+use rusty_maid::{
+    app::AppData,
+    command::CommandInfo,
+    i18n,
+    job_queue::{self, Job},
+    module::{BotModule, Command},
+    t,
+};
+
+use super::MATCH_URL;
+
+pub struct Ytdlp;
+
+#[async_trait::async_trait]
+impl BotModule for Ytdlp {
+    fn name(&self) -> &'static str {
+        "ytdlp"
+    }
+
+    fn description(&self) -> Option<&'static str> {
+        Some("Video download")
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![Command::new(
+            CommandInfo::builder().name("ytdlp").build(),
+            dptree::endpoint(ytdlp_handler),
+        )]
+    }
+}
+
+async fn ytdlp_handler(msg: Message, bot: Bot, data: AppData) -> anyhow::Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+
+    let text = msg.text().expect("Unreachable");
+    let payload = text.split(' ').skip(1).collect::<String>();
+    if payload.len() < 2 {
+        abort!(bot, data, msg, "{}", t!(lang, "ytdlp.no_url"));
+    }
+
+    let Some(capture) = MATCH_URL.captures(&payload) else {
+        abort!(bot, data, msg, "{}", t!(lang, "ytdlp.url_not_found"));
+    };
+    let Some(url) = capture.get(1) else {
+        abort!(
+            bot,
+            data,
+            msg,
+            "{} (This might be an internal regexp error)",
+            t!(lang, "ytdlp.url_not_found")
+        );
+    };
+    let final_url = if let Ok(clean_url) = data.url_cleaner.clear(url.as_str()).await {
+        clean_url
+    } else {
+        reqwest::Url::parse(url.as_str())
+            .expect("internal error: fail to parse url, check REGEXP valid or not")
+    };
+
+    // Downloading takes minutes, leave it to the job workers
+    let job = Job::DownloadVideo {
+        url: final_url.to_string(),
+    };
+    job_queue::submit(&bot, &data, &msg, lang, job).await?;
+
+    Ok(())
+}
@@ -1,1139 +1,987 @@
 use anyhow::Result;
-use image::ImageFormat;
-use rand::Rng;
-use redis::Commands;
-use std::fmt::Write;
+use std::{ops::ControlFlow, sync::Arc};
 use teloxide::{
-    dispatching::{dialogue, UpdateHandler},
-    net::Download,
-    payloads::SendPhotoSetters,
-    prelude::*,
-    types::{
-        ChatKind, InlineKeyboardButton, InlineKeyboardMarkup, InputFile, InputSticker, ParseMode,
-        User,
+    dispatching::UpdateHandler,
+    dptree::{
+        di::{DependencyMap, DependencySupplier},
+        Cont,
     },
-    utils::command::BotCommands,
+    prelude::*,
+    types::{AllowedUpdate, Me, MessageReactionUpdated, PollAnswer, UpdateKind},
 };
+use tracing::Instrument;
 
 use rusty_maid::{
+    album,
     app::AppData,
-    modules::{self, price::PriceInfo, Sendable},
-    sendable,
+    args::{ArgError, Args, UserRef},
+    callback::CallbackRouter,
+    chat_migration,
+    command::{ChatScope, CommandInfo, CommandRegistry, Permission},
+    config::Config,
+    delayed_task,
+    dialogue::{DialogueRouter, DialogueState},
+    digest::{self, DigestSchedule},
+    doctor, error_sink, event, i18n, idempotency,
+    inline::InlineRouter,
+    metrics,
+    module::{called_command, BotModule, Command, ModuleRegistry},
+    modules, paginator,
+    quiet_hours::{self, QuietHours},
+    role, settings, t, telemetry,
+    topic::SendTo,
+    usage,
 };
 
+use crate::settings_menu;
+
 lazy_static::lazy_static!(
-    static ref MATCH_URL: regex::Regex =
-        regex::Regex::new(
-            r"(http[s]?://(?:[a-zA-Z]|[0-9]|[$-_@.&+]|[!*\(\),]|(?:%[0-9a-fA-F][0-9a-fA-F]))+)"
-        ).unwrap();
+    pub(crate) static ref CALLBACK_ROUTER: CallbackRouter = ModuleRegistry::global()
+        .callback_router(CallbackRouter::new(&Config::get_global_config().bot_token));
+    static ref INLINE_ROUTER: InlineRouter =
+        ModuleRegistry::global().inline_router(InlineRouter::new());
+    pub(crate) static ref DIALOGUE_ROUTER: DialogueRouter =
+        ModuleRegistry::global().dialogue_router(DialogueRouter::new());
 );
 
-/// Represent the bot status for the current requesting user.
-#[derive(Clone)]
-pub enum DialogueStatus {
-    /// Normal status
-    None,
-    /// All the message from current user should be collected
-    CmdCollectRunning,
-}
+/// Updates to receive. Telegram doesn't send the reactions unless they are asked for.
+pub const ALLOWED_UPDATES: [AllowedUpdate; 6] = [
+    AllowedUpdate::Message,
+    AllowedUpdate::EditedMessage,
+    AllowedUpdate::CallbackQuery,
+    AllowedUpdate::InlineQuery,
+    AllowedUpdate::MessageReaction,
+    AllowedUpdate::PollAnswer,
+];
 
-impl std::default::Default for DialogueStatus {
-    fn default() -> Self {
-        Self::None
-    }
-}
+/// Users listed under each module by `/usage`
+const USAGE_TOP_USERS: isize = 5;
 
-type Dialogue = dialogue::Dialogue<DialogueStatus, dialogue::InMemStorage<DialogueStatus>>;
-macro_rules! generate_commands {
-    (
-        stateless: {
-            $(
-                #[desc = $desc:literal]
-                $cmd:ident,
-            )+
-        }
-        stateful: {
-            $(
-                #[desc = $sdesc:literal]
-                $scmd:ident,
-            )+
-        }
-    ) => {
-        #[derive(BotCommands, Clone, Debug)]
-        #[command(
-            rename_rule = "lowercase",
-            description = "These commands are supported:"
-        )]
-        pub enum Command {
-            $(
-                #[command(description=$desc)]
-                $cmd,
-            )+
-            $(
-                #[command(description=$sdesc)]
-                $scmd,
-            )+
-        }
-
-        paste::paste! {
-            fn generate_stateless_cmd_handler() -> UpdateHandler<anyhow::Error>  {
-                teloxide::filter_command::<Command, _>()
-                    $(
-                        .branch(
-                            dptree::case![Command::$cmd]
-                                .endpoint( [< $cmd:snake _handler>] )
-                        )
-                    )+
-            }
-        }
-    };
+pub fn command_registry() -> &'static CommandRegistry {
+    ModuleRegistry::global().command_registry()
 }
 
-generate_commands! {
-    stateless: {
-        #[desc = "Display this help message"]
-        Help,
-        #[desc = "Search weather. Usage example: /weather 上海"]
-        Weather,
-        #[desc =  "Search exchange rate. Usage example: /exchange 1 usd cny"]
-        Exchange,
-        #[desc = "随机二次元色图"]
-        Ghs,
-        #[desc = "查询 e-hentai 链接内的本子信息"]
-        Eh,
-        #[desc = "收集所有信息并合并"]
-        Collect,
-        #[desc = "Search package information in Arch Linux Repo and AUR"]
-        Pacman,
-        #[desc = "Interact with ksyx"]
-        HitKsyx,
-        #[desc = "Interact with piggy"]
-        CookPiggy,
-        #[desc = "Get some useful id"]
-        Id,
-        #[desc = "Get JD price info"]
-        Jd,
-        #[desc = "Translate text by DeepL"]
-        Tr,
-        #[desc = "Roll a number"]
-        Roll,
-        #[desc = "Make a image to record somebody's quote"]
-        MakeQuote,
-        #[desc = "Delete a sticker create by this bot"]
-        DelSticker,
-        #[desc = "Download video through yt-dlp"]
-        Ytdlp,
-    }
-    stateful: {
-        #[desc = "Finish Collect"]
-        CollectDone,
-    }
-}
+/// Commands to use and manage the bot itself
+pub struct Core;
 
-macro_rules! send_action {
-    (@$action:ident; $msg:ident, $bot:ident) => {
-        $bot.send_chat_action($msg.chat.id, teloxide::types::ChatAction::$action)
-            .await?
-    };
-}
+#[async_trait::async_trait]
+impl BotModule for Core {
+    fn name(&self) -> &'static str {
+        "core"
+    }
 
-macro_rules! abort {
-    ($bot:expr, $msg:expr, $($arg:tt)*) => {
-        $bot.send_message($msg.chat.id, format!($($arg)*)).await?;
-        return Ok(());
-    };
-}
+    fn commands(&self) -> Vec<Command> {
+        vec![
+            Command::new(
+                CommandInfo::builder().name("help").build(),
+                dptree::endpoint(help_handler),
+            ),
+            Command::new(
+                CommandInfo::builder().name("id").build(),
+                dptree::endpoint(id_handler),
+            ),
+            Command::new(
+                CommandInfo::builder()
+                    .name("settings")
+                    .permission(Permission::ChatAdmin)
+                    .build(),
+                dptree::endpoint(settings_menu::settings_handler),
+            ),
+            Command::new(
+                CommandInfo::builder().name("lang").build(),
+                dptree::endpoint(lang_handler),
+            ),
+            Command::new(
+                CommandInfo::builder().name("quiet").build(),
+                dptree::endpoint(quiet_handler),
+            ),
+            Command::new(
+                CommandInfo::builder().name("digest").build(),
+                dptree::endpoint(digest_handler),
+            ),
+            Command::new(
+                CommandInfo::builder()
+                    .name("admin")
+                    .permission(Permission::Owner)
+                    .build(),
+                dptree::endpoint(admin_handler),
+            ),
+            Command::new(
+                CommandInfo::builder()
+                    .name("reload")
+                    .permission(Permission::Owner)
+                    .build(),
+                dptree::endpoint(reload_handler),
+            ),
+            Command::new(
+                CommandInfo::builder()
+                    .name("watcher")
+                    .permission(Permission::Owner)
+                    .build(),
+                dptree::endpoint(watcher_handler),
+            ),
+            Command::new(
+                CommandInfo::builder()
+                    .name("usage")
+                    .permission(Permission::Owner)
+                    .build(),
+                dptree::endpoint(usage_handler),
+            ),
+            Command::new(
+                CommandInfo::builder()
+                    .name("doctor")
+                    .permission(Permission::Owner)
+                    .build(),
+                dptree::endpoint(doctor_handler),
+            ),
+            Command::new(
+                CommandInfo::builder().name("cancel").build(),
+                dptree::endpoint(cancel_handler),
+            )
+            .stateful(),
+        ]
+    }
 
-macro_rules! handle_result {
-    ($bot:expr, $msg:expr, $result:expr, $on_failure:literal) => {
-        match $result {
-            Ok(sendable) => {
-                sendable.send(&$bot, &$msg).await?;
-            }
-            Err(err) => {
-                abort!($bot, $msg, "{}: {:?}", $on_failure, err);
-            }
-        }
-    };
+    fn callbacks(&self, router: CallbackRouter) -> CallbackRouter {
+        paginator::route(settings_menu::route(router))
+    }
 }
 
 pub fn handler_schema() -> UpdateHandler<anyhow::Error> {
-    let stateless_cmd_handler = generate_stateless_cmd_handler();
+    let registry = ModuleRegistry::global();
+    let stateless_cmd_handler = registry.command_handler(false);
+
+    // Commands that work on the running dialogue, they should be matched first
+    let stateful_cmd_handler = registry.command_handler(true);
 
-    let stateful_cmd_handler = teloxide::filter_command::<Command, _>()
-        .branch(dptree::case![Command::CollectDone].endpoint(collect_done_handler));
+    let dialogue_handler = dptree::filter_map(running_dialogue).endpoint(dialogue_message_handler);
 
     let msg_handler = Update::filter_message()
-        .branch(dptree::case![DialogueStatus::None].branch(stateless_cmd_handler))
-        .branch(dptree::case![DialogueStatus::None].endpoint(plain_message_handler))
-        .branch(dptree::case![DialogueStatus::CmdCollectRunning].branch(stateful_cmd_handler))
-        .branch(dptree::case![DialogueStatus::CmdCollectRunning].endpoint(collect_message_handler));
+        .inspect(remember_chat)
+        .branch(
+            dptree::filter_map(|msg: Message| msg.migrate_to_chat_id().copied())
+                .endpoint(chat_migrated_handler),
+        )
+        .branch(stateful_cmd_handler)
+        .branch(dialogue_handler)
+        .branch(dptree::filter(is_module_disabled).endpoint(ignore_message))
+        .branch(dptree::filter_map(wrong_scope).endpoint(wrong_scope_handler))
+        .branch(dptree::filter_map_async(missing_permission).endpoint(permission_denied_handler))
+        .branch(dptree::filter_map(rate_limited).endpoint(slow_down_handler))
+        .branch(stateless_cmd_handler)
+        .endpoint(plain_message_handler);
+
+    let edited_handler = Update::filter_edited_message()
+        .filter(|msg: Message, me: Me| !is_own_message(&msg, &me))
+        .endpoint(edited_message_handler);
 
     let callback_handler = Update::filter_callback_query().endpoint(callback_dispatcher);
 
-    let root = dptree::entry().branch(msg_handler).branch(callback_handler);
-
-    dialogue::enter::<Update, dialogue::InMemStorage<DialogueStatus>, DialogueStatus, _>()
-        .branch(root)
-}
-
-async fn plain_message_handler(msg: Message, bot: Bot, app_data: AppData) -> anyhow::Result<()> {
-    if msg.text().is_none() {
-        return Ok(());
-    }
-
-    let captures = MATCH_URL.captures_iter(msg.text().unwrap());
-    let urls: Vec<_> = captures
-        .filter_map(|cap| cap.get(1))
-        .map(|m| m.as_str())
-        .collect();
-
-    if urls.is_empty() {
-        return Ok(());
-    }
-
-    let mut data = Vec::new();
+    let inline_handler = Update::filter_inline_query().endpoint(inline_query_handler);
+
+    let reaction_handler = Update::filter_message_reaction_updated().endpoint(reaction_handler);
+
+    let poll_answer_handler = Update::filter_poll_answer().endpoint(poll_answer_handler);
+
+    dptree::from_fn(observe_update)
+        .branch(msg_handler)
+        .branch(edited_handler)
+        .branch(callback_handler)
+        .branch(inline_handler)
+        .branch(reaction_handler)
+        .branch(poll_answer_handler)
+}
+
+/// Record the count and latency of the update, and report the error it returns. The update
+/// already handled or being handled is skipped, and the failed one is left to be handled again.
+async fn observe_update(
+    deps: DependencyMap,
+    cont: Cont<'static, DependencyMap, Result<()>>,
+) -> ControlFlow<Result<()>, DependencyMap> {
+    let update: Arc<Update> = deps.get();
+    let me: Arc<Me> = deps.get();
+    let bot: Arc<Bot> = deps.get();
+    let data: Arc<AppData> = deps.get();
+    let claim = match idempotency::first_delivery(&data, me.id.0, update.id.0) {
+        Ok(Some(claim)) => Some(claim),
+        Ok(None) => {
+            tracing::info!("skip the duplicated update {}", update.id.0);
+            return ControlFlow::Break(Ok(()));
+        }
+        // Handling it twice is better than dropping it
+        Err(err) => {
+            tracing::error!("fail to deduplicate update {}: {err}", update.id.0);
+            None
+        }
+    };
+    let label = match &update.kind {
+        UpdateKind::Message(msg) => {
+            parse_command(msg, &me).map_or("message", |cmd| cmd.name.as_str())
+        }
+        UpdateKind::EditedMessage(_) => "edited_message",
+        UpdateKind::CallbackQuery(_) => "callback_query",
+        UpdateKind::InlineQuery(_) => "inline_query",
+        UpdateKind::MessageReaction(_) => "message_reaction",
+        UpdateKind::PollAnswer(_) => "poll_answer",
+        _ => "other",
+    };
 
-    for url in urls {
-        if let Ok(result) = app_data.url_cleaner.clear(url).await {
-            if result.as_str() == url {
-                continue;
+    let span = tracing::info_span!("update", command = label, update_id = update.id.0);
+    let start = std::time::Instant::now();
+    let result = cont(deps).instrument(span).await;
+    metrics::observe_update(label, start.elapsed());
+    if let (ControlFlow::Break(Err(err)), UpdateKind::Message(msg)) = (&result, &update.kind) {
+        if let Some(err) = err.downcast_ref::<ArgError>() {
+            if let Some(claim) = claim {
+                claim.handled();
             }
-
-            data.push(result);
+            return ControlFlow::Break(reply_arg_error(&bot, &me, &data, msg, err).await);
         }
     }
-
-    if !data.is_empty() {
-        bot.send_message(
-            msg.chat.id,
-            format!(
-                "Clean URLs\n{}",
-                data.iter().fold(String::new(), |mut acc, item| {
-                    write!(&mut acc, "* {item}").unwrap();
-                    acc
-                })
-            ),
-        )
-        .await?;
+    if let ControlFlow::Break(Err(err)) = &result {
+        metrics::observe_error(err);
+        error_sink::report_error(label, Some(update.id.0), err);
+        telemetry::capture_handler_error(label, &update, err);
+        // The claim is dropped and released, so that the update is handled again if delivered
+        return result;
     }
-
-    Ok(())
+    if let Some(claim) = claim {
+        claim.handled();
+    }
+    result
 }
 
-async fn callback_dispatcher(cb: CallbackQuery, bot: Bot, app_data: AppData) -> anyhow::Result<()> {
-    bot.answer_callback_query(&cb.id).await?;
-
-    if cb.data.is_none() || cb.message.is_none() {
-        return Ok(());
+/// Reply the invalid arguments of the command with its usage, they are not failures of the bot
+async fn reply_arg_error(
+    bot: &Bot,
+    me: &Me,
+    data: &AppData,
+    msg: &Message,
+    err: &ArgError,
+) -> Result<()> {
+    let lang = i18n::lang_of(data, msg);
+    let mut reply = err.localize(lang);
+    if let Some(usage) = parse_command(msg, me).and_then(|cmd| cmd.usage_in(lang)) {
+        reply.push('\n');
+        reply.push_str(&t!(lang, "args.usage", usage = usage));
     }
+    data.reply(msg.chat.id, bot.send_message_to(msg, reply))
+        .await?;
+    Ok(())
+}
 
-    let payload = cb.data.as_deref().unwrap().split('.').collect::<Vec<_>>();
-    match payload[0] {
-        "make_quote" => add_photo_from_msg_to_sticker_set(cb, bot, app_data).await?,
-        _ => return Ok(()),
+/// Remember the chat as a target of /broadcast
+fn remember_chat(msg: Message, data: AppData) {
+    if let Err(err) = modules::broadcast::remember_chat(&data, msg.chat.id.0) {
+        tracing::error!("fail to remember chat {}: {err}", msg.chat.id);
     }
-
-    Ok(())
 }
 
-async fn help_handler(msg: Message, bot: Bot) -> Result<()> {
-    bot.send_message(msg.chat.id, Command::descriptions().to_string())
-        .await?;
+/// The group is upgraded to a supergroup, move what is kept for it to the new chat id
+async fn chat_migrated_handler(msg: Message, data: AppData, to: ChatId) -> Result<()> {
+    chat_migration::migrate_chat(&data, msg.chat.id, to).await?;
     Ok(())
 }
 
-async fn weather_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
-    send_action!(@Typing; msg, bot);
-
-    let text = msg.text().unwrap();
-    let parts = text.split(' ').collect::<Vec<&str>>();
-    if parts.len() < 2 {
-        abort!(bot, msg, "No enough argument. Usage: /weather 上海");
-    }
+fn module_enabled(data: &AppData, chat_id: ChatId, module: &str) -> bool {
+    settings::is_enabled(data, chat_id.0, module).unwrap_or_else(|err| {
+        tracing::error!("fail to get settings of module {module}: {err}");
+        true
+    })
+}
 
-    let result = modules::weather::fetch_weather(data, text).await;
+/// Find the registered command that the message calls, the commands of other bots are ignored
+/// like the dispatcher does.
+fn parse_command(msg: &Message, me: &Me) -> Option<&'static CommandInfo> {
+    let name = called_command(msg.text()?, me.username())?;
+    command_registry().get(name)
+}
 
-    handle_result!(bot, msg, result, "fail to get weather");
+/// Return true when the command belongs to a module that is disabled in current chat
+fn is_module_disabled(msg: Message, me: Me, data: AppData) -> bool {
+    let Some(module) = parse_command(&msg, &me).and_then(|cmd| cmd.module.as_deref()) else {
+        return false;
+    };
+    !module_enabled(&data, msg.chat.id, module)
+}
 
+async fn ignore_message() -> Result<()> {
     Ok(())
 }
 
-async fn exchange_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
-    send_action!(@Typing; msg, bot);
-
-    let text = msg.text().unwrap();
-    let parts = text
-        .split(' ')
-        .filter(|s| !s.is_empty() && !s.contains(' '))
-        .map(|s| s.trim())
-        .collect::<Vec<&str>>();
-    if parts.len() < 4 {
-        abort!(bot, msg, "No enough argument. Usage: /exchange 123 JPY CNY");
-    }
+/// Return the scope of the command when it can't be used in this kind of chat
+fn wrong_scope(msg: Message, me: Me) -> Option<ChatScope> {
+    let cmd = parse_command(&msg, &me)?;
+    (!cmd.available_in(msg.chat.is_private())).then_some(cmd.scope)
+}
 
-    let Ok(amount) = parts[1].parse::<f64>() else {
-        abort!(bot, msg, "Not a valid number: {}", parts[1]);
+async fn wrong_scope_handler(
+    msg: Message,
+    bot: Bot,
+    data: AppData,
+    scope: ChatScope,
+) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let notice = match scope {
+        ChatScope::Private => t!(lang, "common.private_only"),
+        _ => t!(lang, "common.group_only"),
     };
+    delayed_task::send_ephemeral(&bot, &data, &msg, notice, delayed_task::NOTICE_TTL).await?;
+    Ok(())
+}
 
-    let result = modules::currency::exchange(
-        data,
-        amount,
-        &parts[2].to_lowercase(),
-        &parts[3].to_lowercase(),
-    )
-    .await;
-
-    match result {
-        Ok(sendable) => {
-            sendable!(bot, msg, sendable, format = Html);
-        }
+/// Return the required permission when the sender can't use the command
+async fn missing_permission(msg: Message, bot: Bot, me: Me, data: AppData) -> Option<Permission> {
+    let required = parse_command(&msg, &me)?.permission;
+    let user = msg.from.as_ref()?;
+    match role::has_permission(&bot, &data, &msg.chat, user.id, required).await {
+        Ok(true) => None,
+        Ok(false) => Some(required),
         Err(err) => {
-            abort!(bot, msg, "{}: {}", "fail to make currency exchange", err);
+            tracing::error!("fail to get the role of user {}: {err}", user.id);
+            Some(required)
         }
-    };
+    }
+}
 
-    Ok(())
+/// Return the time to wait when the sender calls the command too frequently
+fn rate_limited(msg: Message, me: Me, data: AppData) -> Option<std::time::Duration> {
+    let cmd = parse_command(&msg, &me)?;
+    let rule = *Config::get_global_config().rate_limit.get(&cmd.name)?;
+    let user = msg.from.as_ref()?;
+    let key = format!("RATE_LIMIT:{}:{}", cmd.name, user.id);
+    data.cacher
+        .hit_sliding_window(&key, rule.max, std::time::Duration::from_secs(rule.window))
+        .unwrap_or_else(|err| {
+            tracing::error!("fail to check rate limit of /{}: {err}", cmd.name);
+            None
+        })
 }
 
-async fn ghs_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
-    send_action!(@UploadPhoto; msg, bot);
+async fn slow_down_handler(
+    msg: Message,
+    bot: Bot,
+    data: AppData,
+    wait: std::time::Duration,
+) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let seconds = wait.as_secs().max(1);
+    let notice = data
+        .reply(
+            msg.chat.id,
+            bot.send_message_to(&msg, t!(lang, "common.slow_down", seconds = seconds))
+                .reply_parameters(teloxide::types::ReplyParameters::new(msg.id)),
+        )
+        .await?;
 
-    let result = modules::nsfw::fetch_nsfw_anime_img(data).await;
-    match result {
-        Ok(sendable) => {
-            sendable!(bot, msg, sendable, format = Html, spoiler = on);
-        }
-        Err(err) => {
-            abort!(bot, msg, "{}: {}", "fail to get image", err);
-        }
-    };
+    // Delete the notice later so that it doesn't flood the chat
+    delayed_task::delete_later(&data, &notice, std::time::Duration::from_secs(10))?;
 
     Ok(())
 }
 
-fn get_args(msg: &Message) -> anyhow::Result<String> {
-    let text = msg.text().unwrap();
-    if let Some(args) = text.split_once(' ') {
-        Ok(args.1.to_string())
-    } else if let Some(reply_to) = msg.reply_to_message() {
-        let text = reply_to.text();
-        if text.is_none() {
-            anyhow::bail!("You need to reply to a text message");
-        }
-
-        Ok(text.unwrap().to_string())
-    } else {
-        anyhow::bail!("You need to attach text after the command, or reply to a text message")
-    }
+async fn permission_denied_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    delayed_task::send_ephemeral(
+        &bot,
+        &data,
+        &msg,
+        t!(lang, "common.permission_denied"),
+        delayed_task::NOTICE_TTL,
+    )
+    .await?;
+    Ok(())
 }
 
-async fn eh_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
-    send_action!(@UploadPhoto; msg, bot);
-
-    let args = get_args(&msg);
-    if let Err(err) = args {
-        abort!(bot, msg, "{}", err);
+async fn plain_message_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    if msg.media_group_id().is_some() {
+        let registry = ModuleRegistry::global();
+        if registry.modules().any(|module| module.wants_albums()) {
+            // Waiting for the rest of the album shouldn't hold the next updates of the chat
+            tokio::spawn(album_handler(msg, bot, data));
+        } else {
+            // Kept for the commands replying to the album, like `/qr`
+            album::push_part(&data, &msg)?;
+        }
+        return Ok(());
     }
-
-    let args = args.unwrap();
-    let parse_result = modules::ehentai::parse_gid_list(&args);
-
-    if let Err(err) = parse_result {
-        abort!(bot, msg, "{}", err);
+    if msg.text().is_none() {
+        return Ok(());
     }
 
-    let result = modules::ehentai::fetch_ehentai_comic_data(data, parse_result.unwrap()).await;
-    match result {
-        Ok(sendables) => {
-            for s in sendables {
-                sendable!(bot, msg, s, format = Html, spoiler = on);
-            }
+    let mut failures = Vec::new();
+    for module in ModuleRegistry::global().modules() {
+        if !module_sees(&data, module, msg.chat.id) || !module.update_filter().matches(&msg) {
+            continue;
         }
-        Err(err) => {
-            abort!(bot, msg, "fail to get ehentai data: {}", err);
+        if let Err(err) = module.on_message(&bot, &data, &msg).await {
+            failures.push(err.context(format!("module {} fails to handle message", module.name())));
         }
     }
 
-    Ok(())
+    first_failure(failures)
 }
 
-async fn cook_piggy_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
-    send_action!(@Typing; msg, bot);
-
-    let recipe = modules::piggy::get_pig_recipe(data).await;
-    handle_result!(bot, msg, recipe, "fail to cook piggy");
-
-    Ok(())
-}
+async fn album_handler(msg: Message, bot: Bot, data: AppData) {
+    let album = match album::collect(&data, &msg).await {
+        Ok(Some(album)) => album,
+        Ok(None) => return,
+        Err(err) => {
+            tracing::error!("fail to collect album in chat {}: {err}", msg.chat.id);
+            return;
+        }
+    };
 
-/// handler for the collect command
-async fn collect_handler(msg: Message, bot: Bot, dialogue: Dialogue) -> Result<()> {
-    if let teloxide::types::ChatKind::Public(_) = msg.chat.kind {
-        abort!(bot, msg, "This command can only be used in private chat");
+    let mut failures = Vec::new();
+    for module in ModuleRegistry::global().modules() {
+        if !module.wants_albums() || !module_sees(&data, module, album.chat_id) {
+            continue;
+        }
+        if let Err(err) = module.on_album(&bot, &data, &album).await {
+            failures.push(err.context(format!("module {} fails to handle album", module.name())));
+        }
+    }
+    // The album is handled out of the dispatcher, so its failure is reported here
+    if let Err(err) = first_failure(failures) {
+        metrics::observe_error(&err);
+        error_sink::report_error("album", None, &err);
+        telemetry::capture_album_error(album.chat_id, &err);
     }
-    bot.send_message(
-        msg.chat.id,
-        "你可以开始转发信息了，使用命令 /collectdone 来结束命令收集",
-    )
-    .await?;
-    dialogue.update(DialogueStatus::CmdCollectRunning).await?;
-    Ok(())
 }
 
-async fn collect_message_handler(
-    bot: Bot,
-    msg: Message,
-    data: AppData,
-    dialogue: Dialogue,
-) -> Result<()> {
-    let chat_id = msg.chat.id;
-    if let Err(err) = modules::collect::push_msg(data, msg).await {
-        bot.send_message(chat_id, format!("fail to collect message: {err}"))
-            .await?;
-        dialogue.exit().await?;
-    };
-    Ok(())
+/// The edits made by the bot itself, handling them may edit the message again and loop forever
+fn is_own_message(msg: &Message, me: &Me) -> bool {
+    msg.from.as_ref().is_some_and(|user| user.id == me.id)
 }
 
-async fn collect_done_handler(
-    msg: Message,
-    bot: Bot,
-    dialogue: Dialogue,
-    data: AppData,
-) -> Result<()> {
-    send_action!(@Typing; msg, bot);
-    dialogue.exit().await?;
+async fn edited_message_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    if msg.text().is_none() {
+        return Ok(());
+    }
 
-    let result = modules::collect::finish(data, &msg).await;
-    match result {
-        Ok(sendable) => {
-            sendable!(bot, msg, sendable, format = Html);
+    let mut failures = Vec::new();
+    for module in ModuleRegistry::global().modules() {
+        if !module_sees(&data, module, msg.chat.id) || !module.update_filter().matches(&msg) {
+            continue;
         }
-        Err(err) => {
-            abort!(bot, msg, "{}: {}", "fail to collect message", err);
+        if let Err(err) = module.on_edited_message(&bot, &data, &msg).await {
+            failures.push(err.context(format!(
+                "module {} fails to handle edited message",
+                module.name()
+            )));
         }
-    };
-    Ok(())
-}
-
-async fn tr_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
-    const HELP: &str = "\
-            Usage: Reply to a text message and input: \n\
-                \t/tr [source language(optional)] target-language.\n\
-            Example:\n\
-                \t/tr zh en\
-            ";
-    let replyto = msg.reply_to_message();
-    if replyto.is_none() {
-        abort!(bot, msg, "You should reply to a text message. \n{}", HELP);
     }
 
-    let text = replyto.unwrap().text();
-    if text.is_none() {
-        abort!(bot, msg, "You should reply to a text message. \n{}", HELP);
-    }
-    let text = text.unwrap();
+    first_failure(failures)
+}
 
-    let args = msg.text().unwrap().split(' ').skip(1).collect::<Vec<_>>();
-    if args.is_empty() {
-        abort!(
-            bot,
-            msg,
-            "You should at least give me a target language. \n{}",
-            HELP
-        );
+async fn reaction_handler(reaction: MessageReactionUpdated, bot: Bot, data: AppData) -> Result<()> {
+    let mut failures = Vec::new();
+    for module in ModuleRegistry::global().modules() {
+        if !module_sees(&data, module, reaction.chat.id) {
+            continue;
+        }
+        if let Err(err) = module.on_reaction(&bot, &data, &reaction).await {
+            failures
+                .push(err.context(format!("module {} fails to handle reaction", module.name())));
+        }
     }
 
-    let mut source_lang = None;
-    let target_lang;
+    first_failure(failures)
+}
 
-    macro_rules! parse_lang {
-        ($str:expr) => {{
-            let lang = deepl::Lang::try_from(&$str.to_uppercase());
-            if lang.is_err() {
-                abort!(bot, msg, "invalid language code {}", args[0]);
-            }
-            lang.unwrap()
-        }};
+async fn poll_answer_handler(answer: PollAnswer, bot: Bot, data: AppData) -> Result<()> {
+    let mut failures = Vec::new();
+    for module in ModuleRegistry::global().modules() {
+        if let Err(err) = module.on_poll_answer(&bot, &data, &answer).await {
+            failures.push(err.context(format!(
+                "module {} fails to handle poll answer",
+                module.name()
+            )));
+        }
     }
 
-    if args.len() == 1 {
-        target_lang = parse_lang!(args[0]);
-    } else {
-        source_lang = Some(parse_lang!(args[0]));
-        target_lang = parse_lang!(args[1]);
-    }
+    first_failure(failures)
+}
 
-    let current_usage = data.deepl.get_usage().await;
-    if let Err(e) = current_usage {
-        abort!(bot, msg, "fail to get current api usage: {}", e);
-    }
-    let current_usage = current_usage.unwrap();
-    if current_usage.character_count > current_usage.character_limit / 3 {
-        abort!(
-            bot,
-            msg,
-            "API usage limit are met, this command is temporary unusable."
-        );
+/// Whether the module is enabled in the chat, and its filter takes the updates of the chat
+fn module_sees(data: &AppData, module: &dyn BotModule, chat_id: ChatId) -> bool {
+    if module.description().is_some() && !module_enabled(data, chat_id, module.name()) {
+        return false;
     }
+    module.update_filter().matches_chat(chat_id)
+}
 
-    let result = if let Some(src) = source_lang {
-        data.deepl
-            .translate_text(text, target_lang)
-            .source_lang(src)
-            .await
-    } else {
-        data.deepl.translate_text(text, target_lang).await
+/// One module failing doesn't stop the others. The first failure is returned to be reported like
+/// the failed commands, and the rest are logged.
+fn first_failure(failures: Vec<anyhow::Error>) -> Result<()> {
+    let mut failures = failures.into_iter();
+    let Some(first) = failures.next() else {
+        return Ok(());
     };
+    for err in failures {
+        tracing::error!("{err:#}");
+    }
+    Err(first)
+}
 
-    if let Err(err) = result {
-        abort!(bot, msg, "fail to translate: {:?}", err);
+async fn callback_dispatcher(cb: CallbackQuery, bot: Bot, app_data: AppData) -> anyhow::Result<()> {
+    if cb.message.is_none() {
+        bot.answer_callback_query(&cb.id).await?;
+        return Ok(());
     }
 
-    let resp = result.unwrap();
-    let full_text = resp
-        .translations
-        .iter()
-        .map(|rp| rp.text.as_str())
-        .collect::<String>();
-    bot.send_message(msg.chat.id, full_text).await?;
+    CALLBACK_ROUTER.dispatch(bot, app_data, cb).await
+}
 
+async fn inline_query_handler(query: InlineQuery, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::user_language(query.from.language_code.as_deref());
+    let results = INLINE_ROUTER
+        .dispatch(data, query.from.id, lang, &query.query)
+        .await;
+    bot.answer_inline_query(&query.id, results)
+        .cache_time(10)
+        .await?;
     Ok(())
 }
 
-async fn pacman_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
-    let mut text = msg.text().unwrap().split(' ');
-    // shift one
-    text.next();
-
-    let operation = text.next();
-    if operation.is_none() {
-        abort!(bot, msg, "No operation was given, abort!");
-    }
-    let operation = operation.unwrap();
-
-    send_action!(@Typing; msg, bot);
-
-    match operation {
-        "-Si" => {
-            let pkg = text.next();
-            if pkg.is_none() {
-                abort!(bot, msg, "No package name! Abort");
-            }
-            let resp = modules::archlinux::fetch_pkg_info(data, pkg.unwrap()).await;
-            match resp {
-                Ok(sendable) => {
-                    sendable!(bot, msg, sendable, format = Html);
-                }
-                Err(err) => {
-                    abort!(bot, msg, "{}: {:?}", "fail to get pkg info", err);
-                }
-            };
-        }
-        "-Ss" => {
-            let pkg = text.next();
-            if pkg.is_none() {
-                abort!(bot, msg, "No package name! Abort");
-            }
-            let resp = modules::archlinux::fetch_pkg_list(data, pkg.unwrap(), 8).await;
-            match resp {
-                Ok(sendable) => {
-                    sendable!(bot, msg, sendable, format = Html);
-                }
-                Err(err) => {
-                    abort!(bot, msg, "{}: {:?}", "fail to get pkg", err);
-                }
-            };
-        }
-        "-Syu" => {
-            if rand::random() {
-                bot.send_message(
-                    msg.chat.id,
-                    "Wow, you are lucky! The full system was upgraded successfully!",
-                )
-                .await?;
-            } else {
-                bot.send_message(
-                    msg.chat.id,
-                    "Oops, your system is broken during the upgrade!",
-                )
-                .await?;
+async fn help_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let text = msg.text().unwrap();
+    let help = match text.split_whitespace().nth(1) {
+        Some(cmd) => match command_registry().help_for(cmd, lang) {
+            Some(help) => help,
+            None => {
+                abort!(
+                    bot,
+                    data,
+                    msg,
+                    "{}",
+                    t!(lang, "common.unknown_command", command = cmd)
+                );
             }
-        }
-        _ => {
-            bot.send_message(msg.chat.id, "Unimplemented").await?;
-        }
+        },
+        None => command_registry().help(msg.chat.is_private(), lang),
     };
-
+    data.reply(msg.chat.id, bot.send_message_to(&msg, help))
+        .await?;
     Ok(())
 }
 
-async fn hit_ksyx_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
-    let old = modules::ksyx::hit(data);
-    if let Err(ref e) = old {
-        abort!(bot, msg, "fail to interact with ksyx: {}", e);
-    }
-
-    let action = &[
-        "爱抚", "中出", "暴打", "后入", "膜", "贴贴", "狂踹", "寸止", "绳缚",
-    ];
+fn running_dialogue(msg: Message, data: AppData) -> Option<DialogueState> {
+    let user = msg.from.as_ref()?;
+    DIALOGUE_ROUTER
+        .current(&data, msg.chat.id.0, user.id.0)
+        .unwrap_or_else(|err| {
+            tracing::error!("fail to get dialogue state: {err}");
+            None
+        })
+}
 
-    let choice = rand::thread_rng().gen_range(0..action.len());
-    bot.send_message(
-        msg.chat.id,
-        format!(
-            "{} {}了 ksyx，ksyx 已经被动手动脚了 {} 次",
-            msg.from.unwrap().first_name,
-            action[choice],
-            old.unwrap(),
-        ),
-    )
-    .await?;
+async fn dialogue_message_handler(
+    msg: Message,
+    bot: Bot,
+    data: AppData,
+    current: DialogueState,
+) -> Result<()> {
+    DIALOGUE_ROUTER.dispatch(bot, data, msg, current).await
+}
 
+async fn cancel_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(());
+    };
+    let lang = i18n::lang_of(&data, &msg);
+    if DIALOGUE_ROUTER.cancel(&data, msg.chat.id.0, user.id.0)? {
+        data.reply(
+            msg.chat.id,
+            bot.send_message_to(&msg, t!(lang, "dialogue.cancelled")),
+        )
+        .await?;
+    } else {
+        data.reply(
+            msg.chat.id,
+            bot.send_message_to(&msg, t!(lang, "dialogue.nothing_to_cancel")),
+        )
+        .await?;
+    }
     Ok(())
 }
 
-async fn id_handler(msg: Message, bot: Bot) -> Result<()> {
+async fn id_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
     let user_id = if let Some(reply) = msg.reply_to_message() {
         reply.from.as_ref().map_or(0, |user| user.id.0)
     } else {
-        msg.from.map_or(0, |user| user.id.0)
+        msg.from.as_ref().map_or(0, |user| user.id.0)
     };
     let chat_id = msg.chat.id;
 
-    bot.send_message(
+    data.reply(
         msg.chat.id,
-        format!("user id: {user_id}\nchat id: {chat_id}"),
+        bot.send_message_to(
+            &msg,
+            t!(lang, "id.result", user_id = user_id, chat_id = chat_id),
+        ),
     )
     .await?;
 
     Ok(())
 }
 
-async fn roll_handler(msg: Message, bot: Bot) -> Result<()> {
-    send_action!(@Typing; msg, bot);
-
-    let text = msg.text().unwrap();
-    let parts = text.split_once(' ');
-
-    let choosen: u64;
-    if let Some((_, max)) = parts {
-        let Ok(max) = max.parse::<u64>() else {
-            abort!(bot, msg, "expect number");
-        };
-
-        choosen = rand::thread_rng().gen_range(0..max);
-    } else {
-        choosen = rand::random();
-    }
-
-    bot.send_message(msg.chat.id, choosen.to_string()).await?;
-
-    Ok(())
-}
-
-fn create_quote_from_username(
-    target: &User,
-    username: &str,
-    quote: &str,
-    data: &AppData,
-) -> anyhow::Result<InputFile> {
-    let avatar = make_quote::SpooledData::TgRandom {
-        id: target.id.0,
-        name: target.first_name.to_string(),
-    };
-    let quote_config = make_quote::ImgConfig::builder()
-        .username(username)
-        .quote(format!("「{}」", quote))
-        .avatar(&avatar)
-        .build();
-    let result = data.quote_maker.make_image(&quote_config)?;
-    Ok(InputFile::memory(result))
-}
-
-async fn create_quote(
+pub(crate) async fn is_chat_admin(
     bot: &Bot,
-    target: &User,
-    quote: &str,
     data: &AppData,
-) -> anyhow::Result<InputFile> {
-    let photos = bot
-        .get_user_profile_photos(target.id)
-        .limit(1)
-        .await?
-        .photos;
-
-    let username = if let Some(username) = &target.username {
-        format!("@{username}")
-    } else {
-        format!("- {}", target.first_name)
+    msg: &Message,
+) -> anyhow::Result<bool> {
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(false);
     };
-
-    if photos.is_empty() || photos[0].is_empty() {
-        let img = create_quote_from_username(target, &username, quote, data)?;
-        return Ok(img);
-    }
-
-    let avatar_id = &photos
-        .last()
-        .unwrap()
-        .iter()
-        .max_by(|x, y| x.width.cmp(&y.width))
-        .unwrap()
-        .file
-        .id;
-    let file = bot.get_file(avatar_id).await?;
-    let avatar_cacher_key = format!("TG_AVATAR:USER:{}", avatar_id);
-    let cache: Option<Vec<u8>> = data.cacher.get_conn().get(&avatar_cacher_key)?;
-
-    let avatar = if let Some(cache) = cache {
-        cache
-    } else {
-        let mut avatar = std::io::Cursor::new(Vec::with_capacity(file.size as usize));
-        bot.download_file(&file.path, &mut avatar).await?;
-        avatar.into_inner()
+    role::has_permission(bot, data, &msg.chat, user.id, Permission::ChatAdmin).await
+}
+
+async fn admin_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let mut args = Args::parse(&msg)?;
+    let operation = args
+        .optional::<String>("operation")?
+        .unwrap_or_else(|| "list".to_string());
+
+    if operation == "list" {
+        args.finish()?;
+        let admins = role::global_admins(&data).await?;
+        let config = Config::get_global_config();
+        let config = &config.permission;
+        let list = config
+            .admins
+            .iter()
+            .chain(admins.iter())
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        abort!(bot, data, msg, "{}", t!(lang, "admin.list", admins = list));
+    }
+
+    if !matches!(operation.as_str(), "add" | "remove") {
+        return Err(ArgError::Invalid {
+            name: "operation",
+            value: operation,
+            reason: "it should be list, add or remove".to_string(),
+        }
+        .into());
+    }
+    let target = match args.optional::<UserRef>("user")? {
+        Some(UserRef::Id(id)) => id.0,
+        Some(UserRef::Username(name)) => {
+            return Err(ArgError::Invalid {
+                name: "user",
+                value: format!("@{name}"),
+                reason: "the bot can't look up a user by the username".to_string(),
+            }
+            .into());
+        }
+        None => match msg.reply_to_message().and_then(|reply| reply.from.as_ref()) {
+            Some(user) => user.id.0,
+            None => return Err(ArgError::Missing { name: "user" }.into()),
+        },
     };
+    args.finish()?;
 
-    let () = data
-        .cacher
-        .get_conn()
-        .set_ex(avatar_cacher_key, avatar.as_slice(), 60 * 60 * 24)?;
+    let reply = match operation.as_str() {
+        "add" if role::add_global_admin(&data, target).await? => {
+            t!(lang, "admin.added", user = target)
+        }
+        "add" => t!(lang, "admin.already_admin", user = target),
+        "remove" if role::remove_global_admin(&data, target).await? => {
+            t!(lang, "admin.removed", user = target)
+        }
+        _ => t!(lang, "admin.not_admin", user = target),
+    };
+    data.reply(msg.chat.id, bot.send_message_to(&msg, reply))
+        .await?;
 
-    let quote_config = make_quote::ImgConfig::builder()
-        .username(username)
-        .quote(format!("「{}」", quote))
-        .avatar(avatar.as_slice())
-        .build();
-    let result = data.quote_maker.make_image(&quote_config)?;
-    Ok(InputFile::memory(result))
+    Ok(())
 }
 
-async fn make_quote_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
-    send_action!(@Typing; msg, bot);
-    let Some(reply_to_msg) = msg.reply_to_message() else {
-        abort!(
-            bot,
-            msg,
-            "You should reply to somebody's text message to generate the quote image"
-        );
-    };
-
-    let quote = if let Some(quote) = reply_to_msg.text() {
-        quote
-    } else {
-        let Some(quote) = reply_to_msg.caption() else {
+async fn reload_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let changes = match Config::reload() {
+        Ok(changes) => changes,
+        Err(err) => {
             abort!(
                 bot,
+                data,
                 msg,
-                "You should reply to somebody's text message to generate the quote image"
+                "{}",
+                t!(lang, "reload.failed", error = format!("{err:#}"))
             );
-        };
-        quote
-    };
-
-    use chrono::prelude::*;
-    let today = Local::now();
-    let today_is_april_fool = today.month() == 4 && today.day() == 1;
-    let target = if today_is_april_fool {
-        let Some(target) = msg.from.as_ref() else {
-            abort!(bot, msg, "My joke broken...");
-        };
-        target
-    } else if let Some(target) = reply_to_msg.forward_from_user() {
-        target
-    } else if let Some(target) = reply_to_msg.from.as_ref() {
-        target
-    } else {
-        abort!(bot, msg, "You should reply to normal user");
+        }
     };
 
-    let photo = create_quote(&bot, target, quote, &data).await?;
-
-    send_action!(@UploadPhoto; msg, bot);
-
-    if today_is_april_fool {
-        bot.send_photo(msg.chat.id, photo)
-            .caption("Happy April Fools' Day!")
-            .await?;
-        return Ok(());
+    if changes.is_empty() {
+        abort!(bot, data, msg, "{}", t!(lang, "reload.unchanged"));
     }
 
-    let userid = target.id.0;
-    let user_first_name = target.first_name.as_str();
-    let callback_data = format!("make_quote.{userid}.{user_first_name}");
-    let button = InlineKeyboardButton::callback("加入表情包", callback_data);
-    let keyboard = InlineKeyboardMarkup::new(vec![vec![button]]);
-    bot.send_photo(msg.chat.id, photo)
-        .reply_markup(keyboard)
+    let mut lines = Vec::new();
+    if !changes.applied.is_empty() {
+        let sections = changes.applied.join(", ");
+        lines.push(t!(lang, "reload.applied", sections = sections));
+    }
+    if !changes.need_restart.is_empty() {
+        let sections = changes.need_restart.join(", ");
+        lines.push(t!(lang, "reload.need_restart", sections = sections));
+    }
+    data.reply(msg.chat.id, bot.send_message_to(&msg, lines.join("\n")))
         .await?;
 
     Ok(())
 }
 
-async fn get_chat_owner_from_cb(cb: &CallbackQuery, bot: Bot) -> Option<User> {
-    let msg = cb.message.as_ref()?;
-    match msg.chat().kind {
-        ChatKind::Public(_) => {
-            let member = bot
-                .get_chat_administrators(msg.chat().id)
-                .await
-                .ok()?
-                .into_iter()
-                .find(|member| member.is_owner())?;
-            Some(member.user)
-        }
-        ChatKind::Private(_) => Some(cb.from.clone()),
-    }
-}
-
-async fn download_photo(msg: &Message, bot: Bot) -> anyhow::Result<String> {
-    let photos = msg
-        .photo()
-        .ok_or_else(|| anyhow::anyhow!("This message doesn't contain any photo"))?;
-    let file_id = photos
-        .iter()
-        .max_by(|x, y| x.width.cmp(&y.width))
-        .unwrap_or_else(|| panic!("Fail to find any of the photo to compare? This is weird"))
-        .file
-        .id
-        .to_string();
-    let file = bot.get_file(&file_id).await?;
-    // Get the file extension. It should be ".jpg", but unwrapping it from the download filename is
-    // more reliable.
-    let path = std::path::Path::new(&file.path).extension().unwrap();
-
-    let dl_path = format!("/tmp/telegram-tmpfile-{file_id}.{}", path.to_string_lossy());
-    let mut tmpfile = tokio::fs::File::create(&dl_path).await?;
-    bot.download_file(&file.path, &mut tmpfile).await?;
-    Ok(dl_path)
-}
-
-fn legalize_sticker_img(path: &str) -> anyhow::Result<()> {
-    // Get the fd
-    let image = image::open(path)?;
-    // Tokio::fs::File doesn't implement std::io::Seek, so we need to use the std::fs::File.
-    // Truncate again into same path
-    let mut tmpfile = std::fs::File::create(path).unwrap();
-    // Telegram doesn't accept JPG format, so we need to convert it into PNG format here.
-    image
-        .thumbnail(512, 512)
-        .write_to(&mut tmpfile, ImageFormat::Png)?;
-    Ok(())
-}
+async fn watcher_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let mut args = Args::parse(&msg)?;
+    let operation = args
+        .optional::<String>("operation")?
+        .unwrap_or_else(|| "list".to_string());
 
-async fn add_or_create_sticker_set(
-    bot: Bot,
-    sticker: InputSticker,
-    sticker_owner: UserId,
-    sticker_name: &str,
-    sticker_title: &str,
-) -> anyhow::Result<()> {
-    let sticker_set = bot.get_sticker_set(sticker_name).await;
-    if let Ok(sticker_set) = sticker_set {
-        bot.add_sticker_to_set(sticker_owner, sticker_set.name, sticker)
-            .await?;
-    } else {
-        bot.create_new_sticker_set(
-            sticker_owner,
-            sticker_name,
-            sticker_title,
-            [sticker]
-        )
-        .await?;
-    }
-    Ok(())
-}
-
-async fn add_photo_from_msg_to_sticker_set(
-    cb: CallbackQuery,
-    bot: Bot,
-    data: AppData,
-) -> anyhow::Result<()> {
-    // Bound check is done by callback_dispatcher
-    let msg = cb.regular_message().unwrap();
-    let Some(keyboard) = msg.reply_markup() else {
-        // Actually this should be unreachable
-        abort!(bot, msg, "This photo is already added.");
+    let reply = match operation.as_str() {
+        "list" => {
+            args.finish()?;
+            let paused = event::paused_watchers(&data)?;
+            if paused.is_empty() {
+                t!(lang, "watcher.none_paused")
+            } else {
+                let lines = paused
+                    .iter()
+                    .map(|(name, error)| format!("{name}: {error}"))
+                    .collect::<Vec<_>>();
+                format!("{}\n{}", t!(lang, "watcher.list"), lines.join("\n"))
+            }
+        }
+        "resume" => {
+            let name = args.required::<String>("name")?;
+            args.finish()?;
+            if event::resume(&data, &name)? {
+                t!(lang, "watcher.resumed", name = name)
+            } else {
+                t!(lang, "watcher.not_paused", name = name)
+            }
+        }
+        _ => {
+            return Err(ArgError::Invalid {
+                name: "operation",
+                value: operation,
+                reason: "it should be list or resume".to_string(),
+            }
+            .into());
+        }
     };
-
-    let lock_key = format!("quote_sticker_set_locker:{}", msg.id);
-    let mut redis_cli = data.cacher.get_conn();
-    let unhandle: bool = redis::cmd("SET")
-        .arg(&lock_key) // key
-        .arg(1) // val
-        .arg("NX") // NX
-        .arg("EX") // EX
-        .arg(60) // SECONDS
-        .query(&mut redis_cli)?;
-    if !unhandle {
-        return Ok(());
-    }
-
-    bot.edit_message_caption(msg.chat.id, msg.id)
-        .caption("Processing image...")
+    data.reply(msg.chat.id, bot.send_message_to(&msg, reply))
         .await?;
 
-    let result: anyhow::Result<()> = (async {
-        use teloxide::types::StickerFormat;
-        // STEP1: Get photo file from telegram
-        let dl_path = download_photo(msg, bot.clone()).await?;
-
-        // STEP2: Resize the image to 512px
-        //
-        // Using operation from std::fs will probably block the whole tokio task scheduler.
-        // SO I wrapped them into the `block_in_place` function to avoid that case.
-        tokio::task::block_in_place(|| legalize_sticker_img(&dl_path))?;
-
-        // STEP3: Read the resized image and send it to telegram
-        bot.edit_message_caption(msg.chat.id, msg.id)
-            .caption("Image converted, sending...")
-            .await?;
-        let sticker = InputSticker {
-            sticker: InputFile::file(&dl_path),
-            format: StickerFormat::Static,
-            emoji_list: vec!["💬".to_string()],
-            mask_position: None,
-            keywords: vec!["quote".to_string()],
-        };
+    Ok(())
+}
 
-        // STEP4: Set the sticker
-        let bot_info = bot.get_me().await?;
-        let Some(sticker_owner) = get_chat_owner_from_cb(&cb, bot.clone()).await else {
-            abort!(
-                bot,
-                msg,
-                "Fail to find chat owner, sticker set need at least one owner"
-            );
-        };
+async fn usage_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let mut args = Args::parse(&msg)?;
+    let date = args
+        .optional::<chrono::NaiveDate>("date")?
+        .unwrap_or_else(usage::today);
+    args.finish()?;
 
-        let payload = cb
-            .data
-            .as_ref()
-            .unwrap()
-            .split('.')
-            .skip(1)
-            .collect::<Vec<_>>();
-        let user_id = payload[0].parse::<i64>();
-        if user_id.is_err() {
-            abort!(
-                bot,
-                msg,
-                "Internal error: fail to get correct user id from payload data."
+    let report = usage::report(&data, date, USAGE_TOP_USERS)?;
+    let reply = if report.is_empty() {
+        t!(lang, "usage.none", date = date)
+    } else {
+        let limit = |limit: Option<u64>| limit.map_or("∞".to_string(), |limit| limit.to_string());
+        let mut lines = vec![t!(lang, "usage.title", date = date)];
+        for module in report {
+            let rule = module.rule.unwrap_or_default();
+            lines.push(t!(
+                lang,
+                "usage.module",
+                module = module.module,
+                units = module.units,
+                daily = limit(rule.daily),
+                per_user = limit(rule.per_user),
+                requests = module.requests
+            ));
+            lines.extend(
+                module
+                    .top_users
+                    .iter()
+                    .map(|(user, units)| format!("  {user}: {units}")),
             );
         }
-        let user_id = user_id.unwrap();
-        let username = payload.into_iter().skip(1).collect::<String>();
-
-        let sticker_name = format!("quoting_{user_id}_by_{}", bot_info.username());
-        let sticker_title = format!("Quotes From {username}");
-
-        add_or_create_sticker_set(
-            bot.clone(),
-            sticker,
-            sticker_owner.id,
-            &sticker_name,
-            &sticker_title,
-        )
+        lines.join("\n")
+    };
+    data.reply(msg.chat.id, bot.send_message_to(&msg, reply))
         .await?;
 
-        // Step5: Clean up
-        let sticker_set_link = rusty_maid::helper::Html::a(
-            &format!("https://t.me/addstickers/{}", sticker_name),
-            "sticker set",
-        );
-        bot.edit_message_caption(msg.chat.id, msg.id)
-            .caption(format!("Image converted, see {}.", sticker_set_link))
-            .parse_mode(ParseMode::Html)
-            .await?;
-
-        if let Err(err) = tokio::fs::remove_file(dl_path).await {
-            abort!(
-                bot,
-                msg,
-                "fail to remove temp file after sticker converted: {err}"
-            );
-        }
-
-        Ok(())
-    })
-    .await;
-
-    if let Err(err) = result {
-        bot.edit_message_caption(msg.chat.id, msg.id)
-            .caption(format!("Fail to convert this image into sticker: {err}"))
-            .reply_markup(keyboard.clone())
-            .await?;
-        let () = redis_cli.del(lock_key)?;
-    }
-
     Ok(())
 }
 
-async fn del_sticker_handler(msg: Message, bot: Bot) -> anyhow::Result<()> {
-    let Some(target_sticker_msg) = msg.reply_to_message() else {
-        abort!(bot, msg, "please reply to a sticker message");
-    };
+async fn doctor_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    Args::parse(&msg)?.finish()?;
 
-    let Some(sticker) = target_sticker_msg.sticker() else {
-        abort!(bot, msg, "Please reply to a sticker message");
+    let checks = doctor::diagnose(&bot, &data, &Config::get_global_config()).await;
+    let summary = match doctor::overall(&checks) {
+        doctor::Status::Ok => t!(lang, "doctor.ok", count = checks.len()),
+        doctor::Status::Degraded => t!(lang, "doctor.degraded"),
+        doctor::Status::Failed => t!(lang, "doctor.failed"),
     };
-
-    let result = bot.delete_sticker_from_set(&sticker.file.id).await;
-    if let Err(err) = result {
-        abort!(bot, msg, "Fail to delete this sticker: {err}");
-    }
-
-    bot.send_message(msg.chat.id, "Deleted").await?;
+    data.reply(
+        msg.chat.id,
+        bot.send_message_to(&msg, format!("{summary}\n\n{}", doctor::render(&checks))),
+    )
+    .await?;
 
     Ok(())
 }
 
-async fn ytdlp_handler(msg: Message, bot: Bot, data: AppData) -> anyhow::Result<()> {
-    let user_id = msg.from.as_ref().unwrap().id;
-    let rate_limit_key = format!("YTDLP_DOWNLOAD:USER:{}", user_id);
-    let mut redis_cli = data.cacher.get_conn();
-    let unhandle: bool = redis::cmd("SET")
-        .arg(&rate_limit_key) // key
-        .arg(1) // val
-        .arg("NX") // NX
-        .arg("EX") // EX
-        .arg(60) // SECONDS
-        .query(&mut redis_cli)?;
-    if !unhandle {
+async fn lang_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let mut args = Args::parse(&msg)?;
+    let Some(code) = args.optional::<String>("language")? else {
+        let languages = i18n::LANGUAGES
+            .iter()
+            .map(|(code, name)| format!("{code} ({name})"))
+            .collect::<Vec<_>>()
+            .join(", ");
         abort!(
             bot,
+            data,
             msg,
-            "You have requested a download task in last 1 min, please wait."
+            "{}",
+            t!(lang, "lang.current", lang = lang, languages = languages)
         );
-    }
+    };
+    args.finish()?;
 
-    let text = msg.text().expect("Unreachable");
-    let payload = text.split(' ').skip(1).collect::<String>();
-    if payload.len() < 2 {
-        abort!(bot, msg, "No URL given");
+    if !is_chat_admin(&bot, &data, &msg).await? {
+        abort!(bot, data, msg, "{}", t!(lang, "lang.admin_only"));
     }
-
-    let Some(capture) = MATCH_URL.captures(&payload) else {
-        abort!(bot, msg, "Can't find URL from your input");
-    };
-    let Some(url) = capture.get(1) else {
+    if i18n::normalize(&code).is_none() {
         abort!(
             bot,
+            data,
             msg,
-            "Can't find URL from your input (This might be an internal regexp error)"
+            "{}",
+            t!(lang, "lang.unsupported", code = code)
         );
-    };
-    let resp = bot
-        .send_message(msg.chat.id, "Try downloading video...")
-        .await?;
-
-    let final_url = if let Ok(clean_url) = data.url_cleaner.clear(url.as_str()).await {
-        clean_url
-    } else {
-        reqwest::Url::parse(url.as_str())
-            .expect("internal error: fail to parse url, check REGEXP valid or not")
-    };
+    }
+    let lang = i18n::set_chat_language(&data, msg.chat.id.0, &code)?;
+    data.reply(
+        msg.chat.id,
+        bot.send_message_to(&msg, t!(lang, "lang.updated", lang = lang)),
+    )
+    .await?;
 
-    let result = modules::ytd::YtdlpVideo::dl_from_url(final_url.as_str()).await;
+    Ok(())
+}
 
-    if let Err(err) = result {
-        bot.edit_message_text(
-            msg.chat.id,
-            resp.id,
-            format!("fail to download video: {err}"),
-        )
-        .await?;
-        return Ok(());
+async fn quiet_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let mut args = Args::parse(&msg)?;
+    if args.is_empty() {
+        let reply = match quiet_hours::get(&data, msg.chat.id.0)? {
+            Some(hours) => t!(lang, "quiet.current", hours = hours),
+            None => t!(lang, "quiet.off"),
+        };
+        abort!(bot, data, msg, "{}", reply);
     }
 
-    let video = result.unwrap();
-    let resp_text = if video.maybe_playlist {
-        "Uploading video...\n\
-            (This video appears to be in a playlist, but bot will only download p1. \
-             You will need to add another argument, such as '?p=3', to specify which video in the playlist \
-             you want to download.)"
+    let hours = if args.peek() == Some("off") {
+        args.required::<String>("hours")?;
+        None
     } else {
-        "Uploding video..."
+        Some(args.required::<QuietHours>("hours")?)
     };
-    let resp = bot
-        .edit_message_text(msg.chat.id, resp.id, resp_text)
+    args.finish()?;
+    if !is_chat_admin(&bot, &data, &msg).await? {
+        abort!(bot, data, msg, "{}", t!(lang, "quiet.admin_only"));
+    }
+    quiet_hours::set(&data, msg.chat.id.0, hours)?;
+    let reply = match hours {
+        Some(hours) => t!(lang, "quiet.updated", hours = hours),
+        None => t!(lang, "quiet.turned_off"),
+    };
+    data.reply(msg.chat.id, bot.send_message_to(&msg, reply))
         .await?;
-    let result = bot
-        .send_video(msg.chat.id, InputFile::file(&video.filename))
-        .caption(video.as_tg_video_caption())
-        .parse_mode(ParseMode::Html)
-        .width(video.width)
-        .height(video.height)
-        .thumbnail(InputFile::file(&video.thumbnail_filepath))
-        .await;
 
-    let clean_result = video.clean().await;
-    if let Err(err) = clean_result {
-        bot.edit_message_text(msg.chat.id, resp.id, format!("Fail to do clean up: {err}"))
-            .await?;
-        return Ok(());
+    Ok(())
+}
+
+async fn digest_handler(msg: Message, bot: Bot, data: AppData) -> Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let mut args = Args::parse(&msg)?;
+    let describe = |schedule: DigestSchedule| match schedule {
+        DigestSchedule::Hourly => t!(lang, "digest.hourly"),
+        DigestSchedule::Daily(_) => t!(lang, "digest.daily", time = schedule),
+    };
+    if args.is_empty() {
+        let reply = match digest::get(&data, msg.chat.id.0)? {
+            Some(schedule) => describe(schedule),
+            None => t!(lang, "digest.off"),
+        };
+        abort!(bot, data, msg, "{}", reply);
     }
 
-    // handle send result later to make sure video is indeed clear
-    if let Err(err) = result {
-        bot.edit_message_text(msg.chat.id, resp.id, format!("Fail to upload video: {err}"))
-            .await?;
+    let schedule = if args.peek() == Some("off") {
+        args.required::<String>("schedule")?;
+        None
     } else {
-        bot.delete_message(msg.chat.id, resp.id).await?;
-    }
+        Some(args.required::<DigestSchedule>("schedule")?)
+    };
+    args.finish()?;
+    if !is_chat_admin(&bot, &data, &msg).await? {
+        abort!(bot, data, msg, "{}", t!(lang, "digest.admin_only"));
+    }
+    digest::set(&data, msg.chat.id.0, schedule)?;
+    let reply = match schedule {
+        Some(schedule) => describe(schedule),
+        None => t!(lang, "digest.turned_off"),
+    };
+    data.reply(msg.chat.id, bot.send_message_to(&msg, reply))
+        .await?;
 
     Ok(())
 }
 
-async fn jd_handler(msg: Message, bot: Bot) -> Result<()> {
-    send_action!(@UploadPhoto; msg, bot);
-
-    let args = get_args(&msg);
-    if let Err(err) = args {
-        abort!(bot, msg, "{}", err);
-    }
-
-    let args = args.unwrap();
-    let rules = regex::Regex::new(r"https://item\.jd\.com/(\d+)\.html").unwrap();
-    let capture: Vec<_> = rules
-        .captures_iter(&args)
-        .filter_map(|cap| Some(cap.get(1)?.as_str()))
-        .collect();
-    if capture.is_empty() {
-        abort!(bot, msg, "No item.jd.com url found");
-    }
+// The dispatcher blocks the worker running it, another one drives the test
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_dispatch_commands() {
+    use rusty_maid::testkit::{self, FakeTelegram};
+    use std::time::Duration;
 
-    let result = crate::modules::price::JDPriceAnalyzer::get(capture[0]).await;
-    match result {
-        Ok(item) => {
-            let info = item.price();
-            let text = format!(
-                "<b>{}</b>\n\n<b>标价</b>: {}\n<b>现价</b>: {}\n<b>史低</b>: {}\n\n{}",
-                item.name(),
-                info.listed,
-                info.current,
-                info.lowest,
-                item.sales_info(),
-            );
-            if let Some(photo) = item.thumbnail() {
-                bot.send_photo(
-                    msg.chat.id,
-                    InputFile::url(reqwest::Url::parse(&photo).unwrap()),
-                )
-                .caption(text)
-                .parse_mode(ParseMode::Html)
-                .await?;
-            } else {
-                bot.send_message(msg.chat.id, text)
-                    .parse_mode(ParseMode::Html)
-                    .await?;
-            }
-        }
-        Err(err) => {
-            abort!(bot, msg, "fail to get JD data: {}", err);
-        }
+    if rusty_maid::module::ModuleRegistry::try_global().is_none() {
+        crate::features::registry().install();
     }
+    let telegram = FakeTelegram::start();
+    let data = testkit::app_data(telegram.bot()).await;
+    let mut dispatcher = Dispatcher::builder(telegram.bot(), handler_schema())
+        .dependencies(dptree::deps![data])
+        .default_handler(|_| async move {})
+        .build();
+    let shutdown = dispatcher.shutdown_token();
+    let running = tokio::spawn(async move { dispatcher.dispatch().await });
 
-    Ok(())
+    telegram.push_update(testkit::text_update(-100, 42, "/roll 6"));
+    telegram.push_update(testkit::text_update(42, 42, "/help roll"));
+    let sent = telegram
+        .wait_for("sendMessage", 2, Duration::from_secs(10))
+        .await;
+    assert!(sent.iter().all(|call| call.chat_id().is_some()));
+    let roll = sent
+        .iter()
+        .find(|call| call.chat_id() == Some(-100))
+        .unwrap();
+    assert!(roll.text().unwrap().chars().any(|c| c.is_ascii_digit()));
+    let help = sent.iter().find(|call| call.chat_id() == Some(42)).unwrap();
+    assert!(help.text().unwrap().starts_with("/roll — "));
+
+    shutdown.shutdown().unwrap().await;
+    running.await.unwrap();
 }
@@ -0,0 +1,45 @@
+macro_rules! send_action {
+    (@$action:ident; $msg:ident, $bot:ident) => {{
+        use rusty_maid::topic::SendTo as _;
+        $bot.send_chat_action_to(
+            rusty_maid::topic::ChatTarget::of(&$msg),
+            teloxide::types::ChatAction::$action,
+        )
+        .await?
+    }};
+}
+
+macro_rules! abort {
+    ($bot:expr, $data:expr, $msg:expr, $($arg:tt)*) => {
+        {
+            use rusty_maid::topic::SendTo as _;
+            let target = rusty_maid::topic::ChatTarget::of(&$msg);
+            $data
+                .reply(target.chat_id, $bot.send_message_to(target, format!($($arg)*)))
+                .await?;
+        }
+        return Ok(());
+    };
+}
+
+macro_rules! handle_result {
+    ($bot:expr, $data:expr, $msg:expr, $result:expr, $on_failure:expr) => {
+        match $result {
+            Ok(sendable) => {
+                sendable.send(&$data, &$bot, &$msg).await?;
+            }
+            Err(err) => {
+                // The error notices are noisy in the group, they delete themselves
+                rusty_maid::delayed_task::send_ephemeral(
+                    &$bot,
+                    &$data,
+                    rusty_maid::topic::ChatTarget::of(&$msg),
+                    format!("{}: {:?}", $on_failure, err),
+                    rusty_maid::delayed_task::NOTICE_TTL,
+                )
+                .await?;
+                return Ok(());
+            }
+        }
+    };
+}
@@ -2,58 +2,216 @@ use anyhow::Context;
 use clearurl::UrlCleaner;
 use deepl::DeepLApi;
 use rusty_maid::{
-    app::{AppData, RuntimeData},
+    app::{AppData, Bots, RuntimeData},
+    bot_api,
     cache::Cacher,
     config::Config,
+    delayed_task,
+    dry_run::DryRunProxy,
+    error_sink,
     http::HttpClient,
-    modules,
+    job_queue, logging,
+    module::ModuleRegistry,
+    modules::{self, health::HealthCheck},
+    send_queue::SendQueue,
+    startup,
+    storage::Storage,
+    telemetry,
+};
+use std::sync::{atomic::Ordering, Arc};
+use teloxide::{
+    dptree, error_handlers::LoggingErrorHandler, prelude::Dispatcher, update_listeners::Polling,
 };
-use teloxide::{dispatching::dialogue, dptree, prelude::Dispatcher};
 
+#[macro_use]
+mod macros;
+mod cli;
+mod features;
 mod handlers;
+mod settings_menu;
+mod webhook;
+
+use cli::Command;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    let command = cli::parse(std::env::args().skip(1))?;
+    if command == Command::Help {
+        println!("{}", cli::USAGE);
+        return Ok(());
+    }
+
     dotenvy::dotenv().ok();
-    tracing_subscriber::fmt::init();
+    // The config validation needs to know the modules
+    features::registry().install();
+    let config = Config::init_global_config()?;
+    if command == Command::CheckConfig {
+        println!("config is valid");
+        return Ok(());
+    }
+
+    let _sentry = config.sentry.as_ref().map(telemetry::init);
+    let _log_guard = logging::init(&config)?;
 
-    run().await
+    match command {
+        Command::Run { dry_run } => {
+            let dry_run = dry_run || config.dry_run;
+            run(config, dry_run).await
+        }
+        Command::ExportState => export_state(&config).await,
+        Command::Send { chat, text, bot } => send(&config, chat, &text, bot.as_deref()).await,
+        Command::RedisMigrate => redis_migrate(&config).await,
+        Command::CheckConfig | Command::Help => Ok(()),
+    }
 }
 
-async fn run() -> anyhow::Result<()> {
-    use std::time::Duration;
-    let config = Config::get_global_config();
-    let bot = if let Some(proxy_url) = config.proxy.telegram() {
-        // use teloxide default config
-        let client = reqwest::Client::builder()
-            .proxy(reqwest::Proxy::all(proxy_url)?)
-            .connect_timeout(Duration::from_secs(5))
-            .timeout(Duration::from_secs(17))
-            .tcp_nodelay(true)
-            .build()?;
-        teloxide::Bot::with_client(&config.bot_token, client)
+async fn export_state(config: &Config) -> anyhow::Result<()> {
+    let cacher = prepare_cache(config);
+    startup::redis(&cacher).await?;
+    let storage = Storage::connect(&config.database).await?;
+    // The redis client is blocking
+    let redis = tokio::task::spawn_blocking(move || cacher.export("*")).await??;
+    let state = serde_json::json!({
+        "database": storage.export().await?,
+        "redis": redis,
+    });
+    println!("{}", serde_json::to_string_pretty(&state)?);
+    Ok(())
+}
+
+async fn send(config: &Config, chat: i64, text: &str, bot: Option<&str>) -> anyhow::Result<()> {
+    use teloxide::{prelude::Requester, types::ChatId};
+
+    let bots = prepare_bots(config, None)?;
+    let bot = match bot {
+        Some(name) => bots
+            .get(name)
+            .with_context(|| format!("bot `{name}` is not in the config"))?,
+        None => bots.main(),
+    };
+    let sent = bot
+        .send_message(ChatId(chat), text)
+        .await
+        .with_context(|| format!("fail to send message to {chat}"))?;
+    println!("sent message {} to {chat}", sent.id.0);
+    Ok(())
+}
+
+async fn redis_migrate(config: &Config) -> anyhow::Result<()> {
+    let cacher = prepare_cache(config);
+    startup::redis(&cacher).await?;
+    let storage = Storage::connect(&config.database).await?;
+    let imported = storage
+        .import_from_redis(&cacher, true)
+        .await
+        .with_context(|| "fail to import the durable data from Redis")?;
+    println!("imported {imported} rows from Redis, the existing rows are kept");
+    Ok(())
+}
+
+async fn run(config: Arc<Config>, dry_run: bool) -> anyhow::Result<()> {
+    Config::spawn_watcher();
+    let dry_run = if dry_run {
+        Some(DryRunProxy::start(telegram_client(&config)?, &config.bot_api.url).await?)
     } else {
-        teloxide::Bot::new(&config.bot_token)
+        None
     };
+    let bots = prepare_bots(&config, dry_run.as_ref())?;
+    let bot = bots.main().clone();
+    let me = startup::telegram(&bot).await?;
+
+    if let Err(err) = handlers::command_registry().sync_bot_commands(&bot).await {
+        tracing::error!("fail to push command list to telegram: {err}");
+    }
 
     let handler = handlers::handler_schema();
-    let dialogue_state = dialogue::InMemStorage::<handlers::DialogueStatus>::new();
-    let app_data = prepare_app_data(&config).await;
+    let app_data = prepare_app_data(&config, bots).await?;
+    if let Some(report) = &config.error_report {
+        error_sink::init(app_data.clone(), report);
+    }
 
-    modules::health::spawn_healthcheck_listner(config.health_check_port);
-    modules::bilibili::spawn_bilibili_live_room_listener(bot.clone(), app_data.clone(), &config);
+    let health = HealthCheck::new()
+        .redis(app_data.clone())
+        .database(app_data.clone())
+        .telegram(bot.clone());
+    let ready = health.ready_flag();
+    modules::health::spawn_healthcheck_listner(
+        &config.health_check_bind,
+        config.health_check_port,
+        health,
+    );
+    ModuleRegistry::global().spawn_watchers(&bot, &app_data, &config);
+    delayed_task::spawn_worker(bot.clone(), app_data.clone());
+    job_queue::spawn_workers(bot.clone(), app_data.clone(), &config.job_queue);
+    tracing::info!("{}", startup::summary(&me, &config, dry_run.is_some()));
 
-    Dispatcher::builder(bot, handler)
-        .dependencies(dptree::deps![app_data, dialogue_state])
+    let mut dispatcher = Dispatcher::builder(bot.clone(), handler)
+        .dependencies(dptree::deps![app_data])
         .enable_ctrlc_handler()
         .default_handler(|_| async move {})
-        .build()
-        .dispatch()
-        .await;
+        .build();
+
+    if let Some(webhook) = &config.webhook {
+        let listener = webhook::listener(bot, webhook, handlers::ALLOWED_UPDATES.to_vec()).await?;
+        ready.store(true, Ordering::Relaxed);
+        dispatcher
+            .dispatch_with_listener(
+                listener,
+                LoggingErrorHandler::with_custom_text("webhook listener error"),
+            )
+            .await;
+    } else {
+        let listener = Polling::builder(bot)
+            .allowed_updates(handlers::ALLOWED_UPDATES.to_vec())
+            .delete_webhook()
+            .await
+            .build();
+        ready.store(true, Ordering::Relaxed);
+        dispatcher
+            .dispatch_with_listener(
+                listener,
+                LoggingErrorHandler::with_custom_text("polling error"),
+            )
+            .await;
+    }
 
     Ok(())
 }
 
+fn telegram_client(cfg: &Config) -> anyhow::Result<reqwest::Client> {
+    use std::time::Duration;
+    // use teloxide default config
+    let mut builder = reqwest::Client::builder()
+        .connect_timeout(Duration::from_secs(5))
+        .timeout(Duration::from_secs(cfg.bot_api.timeout))
+        .tcp_nodelay(true);
+    if let Some(proxy_url) = cfg.proxy.telegram() {
+        builder = builder.proxy(reqwest::Proxy::all(proxy_url)?);
+    }
+    Ok(builder.build()?)
+}
+
+fn prepare_bot(
+    cfg: &Config,
+    token: &str,
+    dry_run: Option<&DryRunProxy>,
+) -> anyhow::Result<teloxide::Bot> {
+    let bot = if let Some(proxy) = dry_run {
+        proxy.bot(&bot_api::token(&cfg.bot_api, token))
+    } else {
+        bot_api::bot(&cfg.bot_api, token, telegram_client(cfg)?)?
+    };
+    Ok(bot)
+}
+
+fn prepare_bots(cfg: &Config, dry_run: Option<&DryRunProxy>) -> anyhow::Result<Bots> {
+    let mut bots = Bots::new(prepare_bot(cfg, &cfg.bot_token, dry_run)?);
+    for (name, token) in &cfg.bots {
+        bots = bots.with(name, prepare_bot(cfg, token, dry_run)?);
+    }
+    Ok(bots)
+}
+
 fn prepare_cache(cfg: &Config) -> Cacher {
     let client = redis::Client::open(cfg.redis_addr.as_str()).expect("fail to open client");
     Cacher::new(client)
@@ -73,6 +231,31 @@ pub fn prepare_deepl(cfg: &Config) -> DeepLApi {
     api_builder.new()
 }
 
+/// Rebuild the DeepL client when the config reload changes its API key or proxy
+fn watch_deepl(cfg: &Config) -> tokio::sync::watch::Receiver<DeepLApi> {
+    let (tx, rx) = tokio::sync::watch::channel(prepare_deepl(cfg));
+    let mut current = (
+        cfg.deepl.api_key.clone(),
+        cfg.proxy.deepl().map(String::from),
+    );
+    let mut config = Config::subscribe();
+    tokio::spawn(async move {
+        while config.changed().await.is_ok() {
+            let cfg = config.borrow_and_update().clone();
+            let latest = (
+                cfg.deepl.api_key.clone(),
+                cfg.proxy.deepl().map(String::from),
+            );
+            if latest != current {
+                tracing::info!("rebuilding DeepL client for the new config");
+                tx.send_replace(prepare_deepl(&cfg));
+                current = latest;
+            }
+        }
+    });
+    rx
+}
+
 fn prepare_quote_maker() -> make_quote::QuoteProducer<'static> {
     let bold = include_bytes!(env!("QUOTE_TEXT_FONT_PATH"));
     let light = include_bytes!(env!("QUOTE_USERNAME_FONT_PATH"));
@@ -82,21 +265,33 @@ fn prepare_quote_maker() -> make_quote::QuoteProducer<'static> {
         .build()
 }
 
-fn url_cleaner() -> UrlCleaner {
-    let path = std::env::var("URL_CLEANER_RULE_FILE")
-        .with_context(|| "url clearner rule file env not set")
+fn url_cleaner(cfg: &Config) -> UrlCleaner {
+    let path = cfg
+        .url_cleaner_rule_file
+        .as_deref()
+        .with_context(|| "url clearner rule file not set")
         .unwrap();
-    UrlCleaner::from_file(&path).unwrap()
+    UrlCleaner::from_file(path).unwrap()
 }
 
-async fn prepare_app_data(cfg: &Config) -> AppData {
+async fn prepare_app_data(cfg: &Config, bots: Bots) -> anyhow::Result<AppData> {
+    let cacher = prepare_cache(cfg);
+    startup::redis(&cacher).await?;
+    let storage = startup::retry("database", || Storage::connect(&cfg.database)).await?;
+    if let Err(err) = storage.import_from_redis(&cacher, false).await {
+        tracing::error!("fail to import the durable data from Redis: {err:#}");
+    }
+
     let data = RuntimeData::builder()
-        .cacher(prepare_cache(cfg))
+        .bots(bots)
+        .cacher(cacher)
+        .storage(storage)
         .requester(HttpClient::new())
-        .deepl(prepare_deepl(cfg))
+        .deepl(watch_deepl(cfg))
         .quote_maker(prepare_quote_maker())
-        .url_cleaner(url_cleaner())
+        .url_cleaner(url_cleaner(cfg))
+        .send_queue(SendQueue::spawn())
         .build();
 
-    data.into()
+    Ok(data.into())
 }
@@ -0,0 +1,289 @@
+//! The `/settings` control panel, an inline keyboard menu editing the same per-chat settings the
+//! dispatcher reads.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use teloxide::{
+    prelude::*,
+    types::{InlineKeyboardButton, InlineKeyboardMarkup},
+    ApiError, RequestError,
+};
+
+use rusty_maid::{
+    app::AppData,
+    cache::Subscription,
+    callback::{CallbackAnswer, CallbackContext, CallbackRouter},
+    command::Permission,
+    i18n, quiet_hours, role, settings, t,
+    topic::SendTo,
+};
+
+use crate::handlers::CALLBACK_ROUTER;
+
+/// Subscriptions listed in the menu at most, the rest are managed by the commands
+const MAX_SUBSCRIPTIONS: usize = 20;
+/// Long events like the URLs are cut in the buttons
+const MAX_EVENT_LEN: usize = 32;
+/// Quiet hours to pick from the menu, the others are set by `/quiet`
+const QUIET_HOURS_PRESETS: [&str; 3] = ["22:00-08:00", "23:00-07:00", "00:00-08:00"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Page {
+    Main,
+    Language,
+    Modules,
+    Options,
+    QuietHours,
+    Subscriptions,
+}
+
+pub fn route(router: CallbackRouter) -> CallbackRouter {
+    router.route("settings", settings_callback)
+}
+
+pub async fn settings_handler(msg: Message, bot: Bot, data: AppData) -> anyhow::Result<()> {
+    let lang = i18n::lang_of(&data, &msg);
+    let (text, keyboard) = menu(&data, msg.chat.id, lang, Page::Main)?;
+    data.reply(
+        msg.chat.id,
+        bot.send_message_to(&msg, text).reply_markup(keyboard),
+    )
+    .await?;
+    Ok(())
+}
+
+fn button(text: impl Into<String>, action: &str, payload: &impl Serialize) -> InlineKeyboardButton {
+    CALLBACK_ROUTER
+        .button(text, "settings", action, payload)
+        .expect("settings callback data should fit in 64 bytes")
+}
+
+fn back_button(lang: &str) -> InlineKeyboardButton {
+    button(t!(lang, "settings.back"), "page", &Page::Main)
+}
+
+/// Short id of the subscription, the event is too long for the callback data.
+fn subscription_id(sub: &Subscription) -> String {
+    let digest = Sha256::digest(format!(
+        "{}\n{}\n{}",
+        sub.watcher, sub.event, sub.subscriber
+    ));
+    digest[..6]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+fn quiet_hours_of(data: &AppData, chat_id: ChatId, lang: &str) -> anyhow::Result<String> {
+    Ok(match quiet_hours::get(data, chat_id.0)? {
+        Some(hours) => hours.to_string(),
+        None => t!(lang, "settings.quiet_off"),
+    })
+}
+
+fn menu(
+    data: &AppData,
+    chat_id: ChatId,
+    lang: &str,
+    page: Page,
+) -> anyhow::Result<(String, InlineKeyboardMarkup)> {
+    let (text, rows) = match page {
+        Page::Main => {
+            let mut rows = vec![
+                vec![button(
+                    t!(lang, "settings.language", lang = lang),
+                    "page",
+                    &Page::Language,
+                )],
+                vec![button(t!(lang, "settings.modules"), "page", &Page::Modules)],
+            ];
+            if !settings::module_settings().is_empty() {
+                rows.push(vec![button(
+                    t!(lang, "settings.options"),
+                    "page",
+                    &Page::Options,
+                )]);
+            }
+            rows.extend([
+                vec![button(
+                    t!(
+                        lang,
+                        "settings.quiet_hours",
+                        hours = quiet_hours_of(data, chat_id, lang)?
+                    ),
+                    "page",
+                    &Page::QuietHours,
+                )],
+                vec![button(
+                    t!(lang, "settings.subscriptions"),
+                    "page",
+                    &Page::Subscriptions,
+                )],
+            ]);
+            (t!(lang, "settings.menu"), rows)
+        }
+        Page::Language => {
+            let mut rows = i18n::LANGUAGES
+                .iter()
+                .map(|(code, name)| {
+                    let mark = if *code == lang { "✅ " } else { "" };
+                    vec![button(format!("{mark}{name}"), "lang", code)]
+                })
+                .collect::<Vec<_>>();
+            rows.push(vec![back_button(lang)]);
+            (t!(lang, "settings.choose_language"), rows)
+        }
+        Page::Modules => {
+            let buttons = settings::list(data, chat_id.0)?
+                .into_iter()
+                .map(|(module, enabled)| {
+                    let mark = if enabled { "✅" } else { "❌" };
+                    button(
+                        format!("{mark} {}", module.description),
+                        "toggle",
+                        &module.name,
+                    )
+                })
+                .collect::<Vec<_>>();
+            let mut rows = buttons
+                .chunks(2)
+                .map(|row| row.to_vec())
+                .collect::<Vec<_>>();
+            rows.push(vec![back_button(lang)]);
+            (t!(lang, "settings.toggle_modules"), rows)
+        }
+        Page::Options => {
+            let mut rows = settings::module_settings()
+                .into_iter()
+                .map(|(module, setting)| {
+                    let value = (setting.get)(data, chat_id.0)?;
+                    Ok(vec![button(
+                        format!("{}: {value}", setting.description),
+                        "option",
+                        &(module, setting.key),
+                    )])
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            rows.push(vec![back_button(lang)]);
+            (t!(lang, "settings.choose_options"), rows)
+        }
+        Page::QuietHours => {
+            let current = quiet_hours::get(data, chat_id.0)?.map(|hours| hours.to_string());
+            let mut rows = QUIET_HOURS_PRESETS
+                .iter()
+                .map(|preset| {
+                    let mark = if current.as_deref() == Some(*preset) {
+                        "✅ "
+                    } else {
+                        ""
+                    };
+                    vec![button(format!("{mark}{preset}"), "quiet", preset)]
+                })
+                .collect::<Vec<_>>();
+            let off = if current.is_none() { "✅ " } else { "" };
+            rows.push(vec![button(
+                format!("{off}{}", t!(lang, "settings.quiet_off")),
+                "quiet",
+                &"off",
+            )]);
+            rows.push(vec![back_button(lang)]);
+            (t!(lang, "settings.choose_quiet_hours"), rows)
+        }
+        Page::Subscriptions => {
+            let subscriptions = data.cacher.subscriptions_of(chat_id.0)?;
+            let text = if subscriptions.is_empty() {
+                t!(lang, "settings.no_subscriptions")
+            } else {
+                t!(lang, "settings.unsubscribe", count = subscriptions.len())
+            };
+            let mut rows = subscriptions
+                .iter()
+                .take(MAX_SUBSCRIPTIONS)
+                .map(|sub| {
+                    let event = match sub.event.char_indices().nth(MAX_EVENT_LEN) {
+                        Some((end, _)) => format!("{}…", &sub.event[..end]),
+                        None => sub.event.clone(),
+                    };
+                    let text = format!("❌ {}: {event}", sub.watcher);
+                    vec![button(text, "unsub", &subscription_id(sub))]
+                })
+                .collect::<Vec<_>>();
+            rows.push(vec![back_button(lang)]);
+            (text, rows)
+        }
+    };
+    Ok((text, InlineKeyboardMarkup::new(rows)))
+}
+
+async fn settings_callback(ctx: CallbackContext) -> anyhow::Result<CallbackAnswer> {
+    let CallbackContext {
+        bot, data, query, ..
+    } = &ctx;
+    let Some(msg) = query.regular_message() else {
+        anyhow::bail!(t!(i18n::DEFAULT_LANG, "settings.expired"));
+    };
+    let chat_id = msg.chat.id;
+    let user_lang = query.from.language_code.as_deref();
+    let lang = i18n::chat_language(data, chat_id.0, user_lang);
+    if !role::has_permission(bot, data, &msg.chat, query.from.id, Permission::ChatAdmin).await? {
+        anyhow::bail!(t!(lang, "settings.admin_only"));
+    }
+
+    let (page, answer) = match ctx.action.as_str() {
+        "page" => (ctx.payload()?, None),
+        "lang" => {
+            let code: String = ctx.payload()?;
+            let lang = i18n::set_chat_language(data, chat_id.0, &code)?;
+            (Page::Language, Some(t!(lang, "lang.updated", lang = lang)))
+        }
+        "toggle" => {
+            let module: String = ctx.payload()?;
+            let answer = if settings::toggle(data, chat_id.0, &module)? {
+                t!(lang, "settings.turned_on", module = module)
+            } else {
+                t!(lang, "settings.turned_off", module = module)
+            };
+            (Page::Modules, Some(answer))
+        }
+        "option" => {
+            let (module, key): (String, String) = ctx.payload()?;
+            let value = settings::cycle_setting(data, chat_id.0, &module, &key)?;
+            let answer = t!(lang, "settings.option_updated", option = key, value = value);
+            (Page::Options, Some(answer))
+        }
+        "quiet" => {
+            let preset: String = ctx.payload()?;
+            let hours = match preset.as_str() {
+                "off" => None,
+                preset => Some(preset.parse()?),
+            };
+            quiet_hours::set(data, chat_id.0, hours)?;
+            (Page::QuietHours, None)
+        }
+        "unsub" => {
+            let id: String = ctx.payload()?;
+            let subscriptions = data.cacher.subscriptions_of(chat_id.0)?;
+            let Some(sub) = subscriptions.iter().find(|sub| subscription_id(sub) == id) else {
+                anyhow::bail!(t!(lang, "settings.subscription_gone"));
+            };
+            data.cacher
+                .unsubscribe(&sub.watcher, &sub.subscriber, &sub.event)?;
+            let answer = t!(lang, "settings.unsubscribed", event = sub.event);
+            (Page::Subscriptions, Some(answer))
+        }
+        action => anyhow::bail!("unknown settings action {action}"),
+    };
+
+    // The language might be changed just now
+    let lang = i18n::chat_language(data, chat_id.0, user_lang);
+    let (text, keyboard) = menu(data, chat_id, lang, page)?;
+    let edited = bot
+        .edit_message_text(chat_id, msg.id, text)
+        .reply_markup(keyboard)
+        .await;
+    match edited {
+        Ok(_) | Err(RequestError::Api(ApiError::MessageNotModified)) => Ok(answer),
+        Err(err) => Err(err.into()),
+    }
+}
@@ -0,0 +1,95 @@
+use std::{convert::Infallible, net::SocketAddr, time::Duration};
+
+use anyhow::Context;
+use axum_server::{tls_rustls::RustlsConfig, Handle};
+use rusty_maid::config::WebhookConfig;
+use teloxide::{
+    payloads::SetWebhookSetters,
+    requests::Requester,
+    types::{AllowedUpdate, InputFile},
+    update_listeners::{webhooks, UpdateListener},
+    Bot,
+};
+
+/// Register the webhook to Telegram and spawn the server that receives updates. The webhook is
+/// deleted when the dispatcher stops.
+pub async fn listener(
+    bot: Bot,
+    config: &WebhookConfig,
+    allowed_updates: Vec<AllowedUpdate>,
+) -> anyhow::Result<impl UpdateListener<Err = Infallible>> {
+    let address: SocketAddr = config
+        .listen
+        .parse()
+        .with_context(|| format!("invalid webhook listen address {}", config.listen))?;
+    let url: reqwest::Url = config
+        .url
+        .parse()
+        .with_context(|| format!("invalid webhook url {}", config.url))?;
+
+    // `webhooks::Options` has no allowed updates, but Telegram keeps the previous setting when
+    // the webhook is set again without them
+    bot.set_webhook(url.clone())
+        .allowed_updates(allowed_updates)
+        .await
+        .with_context(|| "fail to set the allowed updates")?;
+
+    let mut options = webhooks::Options::new(address, url);
+    if let Some(path) = &config.path {
+        options = options.path(path.clone());
+    }
+    if let Some(token) = &config.secret_token {
+        options = options.secret_token(token.clone());
+    }
+    if let Some(max) = config.max_connections {
+        options = options.max_connections(max);
+    }
+    if config.drop_pending_updates {
+        options = options.drop_pending_updates();
+    }
+
+    let tls = match &config.tls {
+        Some(tls) => {
+            if tls.self_signed {
+                options = options.certificate(InputFile::file(&tls.cert));
+            }
+            let rustls = RustlsConfig::from_pem_file(&tls.cert, &tls.key)
+                .await
+                .with_context(|| "fail to load webhook certificate")?;
+            Some(rustls)
+        }
+        None => None,
+    };
+
+    let (mut listener, stop_flag, router) = webhooks::axum_to_router(bot, options)
+        .await
+        .with_context(|| "fail to set webhook")?;
+    let stop_token = listener.stop_token();
+
+    let handle = Handle::new();
+    let shutdown = handle.clone();
+    tokio::spawn(async move {
+        stop_flag.await;
+        shutdown.graceful_shutdown(Some(Duration::from_secs(5)));
+    });
+
+    tokio::spawn(async move {
+        let app = router.into_make_service();
+        let served = match tls {
+            Some(tls) => {
+                axum_server::bind_rustls(address, tls)
+                    .handle(handle)
+                    .serve(app)
+                    .await
+            }
+            None => axum_server::bind(address).handle(handle).serve(app).await,
+        };
+        if let Err(err) = served {
+            tracing::error!("webhook server stopped: {err}");
+            stop_token.stop();
+        }
+    });
+
+    tracing::info!("listening webhook on {address}");
+    Ok(listener)
+}
@@ -0,0 +1,58 @@
+//! Talk to a self-hosted Bot API server or the test environment instead of api.telegram.org, as
+//! set in the `bot_api` config.
+
+use std::path::Path;
+
+use teloxide::{net::Download, prelude::*};
+use tokio::io::AsyncWrite;
+
+use crate::config::{BotApiConfig, Config};
+
+/// The token for teloxide to put in the URL. The test environment serves the methods at
+/// `/bot<token>/test/<method>` and the files at `/file/bot<token>/test/<path>`, so the `/test`
+/// suffix on the token covers both.
+pub fn token(config: &BotApiConfig, token: &str) -> String {
+    if config.test_environment {
+        format!("{token}/test")
+    } else {
+        token.to_string()
+    }
+}
+
+/// Create the bot talking to the configured server by the client.
+pub fn bot(config: &BotApiConfig, bot_token: &str, client: reqwest::Client) -> anyhow::Result<Bot> {
+    let url = reqwest::Url::parse(&config.url)?;
+    Ok(Bot::with_client(token(config, bot_token), client).set_api_url(url))
+}
+
+/// Download the file by the path that `getFile` returns. The self-hosted server in the local mode
+/// returns the absolute path on its disk instead, and the file is read from there.
+pub async fn download_file(
+    bot: &Bot,
+    path: &str,
+    dst: &mut (dyn AsyncWrite + Unpin + Send),
+) -> anyhow::Result<()> {
+    if Config::get_global_config().bot_api.local_mode && Path::new(path).is_absolute() {
+        let mut file = tokio::fs::File::open(path).await?;
+        tokio::io::copy(&mut file, dst).await?;
+    } else {
+        bot.download_file(path, dst).await?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_bot_api() {
+    let mut config = BotApiConfig::default();
+    assert_eq!(token(&config, "123:abc"), "123:abc");
+    let client = reqwest::Client::new();
+    let official = bot(&config, "123:abc", client.clone()).unwrap();
+    assert_eq!(official.api_url().as_str(), "https://api.telegram.org/");
+
+    config.url = "http://localhost:8081".to_string();
+    config.test_environment = true;
+    assert_eq!(token(&config, "123:abc"), "123:abc/test");
+    let local = bot(&config, "123:abc", client).unwrap();
+    assert_eq!(local.token(), "123:abc/test");
+    assert_eq!(local.api_url().as_str(), "http://localhost:8081/");
+}
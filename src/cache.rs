@@ -1,49 +1,329 @@
-use redis::Commands;
-use std::{collections::HashSet, hash::Hash};
+use redis::{Commands, ConnectionLike, RedisWrite, ToRedisArgs};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display},
+    hash::Hash,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
+use crate::metrics;
+
+#[derive(Clone)]
 pub struct Cacher(r2d2::Pool<redis::Client>);
 
+/// Keys shared by several places, built here so that a typo or a refactor can't silently orphan
+/// the data stored under the old key. The keys are persisted, never change their format.
+#[derive(Clone, Copy)]
+pub enum RedisKey<'a> {
+    /// Subscribers of the event watched by the watcher
+    SubscribeRegistry {
+        watcher: &'a str,
+        event: &'a dyn Display,
+    },
+    /// Pattern matching the subscribe registries of the watcher, or of every watcher
+    SubscribeRegistryPattern { watcher: Option<&'a str> },
+    /// Options of each subscriber of the event, in JSON
+    SubscribeOptions {
+        watcher: &'a str,
+        event: &'a dyn Display,
+    },
+    /// Events that have subscribers, for the watcher to check
+    EventPool { watcher: &'a str },
+    /// Failed runs in a row of each watcher
+    WatcherFailures,
+    /// Paused watchers and their last errors
+    PausedWatchers,
+}
+
+/// The event subscribed by the subscriber, which is the chat target like `-100` or `-100:42`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Subscription {
+    pub watcher: String,
+    pub event: String,
+    pub subscriber: String,
+}
+
+impl RedisKey<'_> {
+    const SUBSCRIBE_REGISTRY: &'static str = "SUBSCRIBE_REGISTRY";
+
+    /// Split the subscribe registry key back into the watcher name and the event.
+    pub fn parse_subscribe_registry(key: &str) -> Option<(&str, &str)> {
+        // The watcher name has no colon, while the event might have one like `host:port`
+        key.strip_prefix(Self::SUBSCRIBE_REGISTRY)?
+            .strip_prefix(':')?
+            .split_once(':')
+    }
+}
+
+impl Display for RedisKey<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SubscribeRegistry { watcher, event } => {
+                write!(f, "{}:{watcher}:{event}", Self::SUBSCRIBE_REGISTRY)
+            }
+            Self::SubscribeRegistryPattern {
+                watcher: Some(watcher),
+            } => write!(f, "{}:{watcher}:*", Self::SUBSCRIBE_REGISTRY),
+            Self::SubscribeRegistryPattern { watcher: None } => {
+                write!(f, "{}:*", Self::SUBSCRIBE_REGISTRY)
+            }
+            Self::SubscribeOptions { watcher, event } => {
+                write!(f, "SUBSCRIBE_OPTIONS:{watcher}:{event}")
+            }
+            Self::EventPool { watcher } => write!(f, "REGISTRY_EVENT_POOL:{watcher}"),
+            Self::WatcherFailures => f.write_str("WATCHER_FAILURES"),
+            Self::PausedWatchers => f.write_str("PAUSED_WATCHERS"),
+        }
+    }
+}
+
+impl ToRedisArgs for RedisKey<'_> {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        out.write_arg(self.to_string().as_bytes())
+    }
+}
+
+/// Pooled connection that records the latency of every command
+pub struct MeteredConnection(r2d2::PooledConnection<redis::Client>);
+
+impl ConnectionLike for MeteredConnection {
+    fn req_packed_command(&mut self, cmd: &[u8]) -> redis::RedisResult<redis::Value> {
+        let start = Instant::now();
+        let result = self.0.req_packed_command(cmd);
+        metrics::observe_redis(&metrics::redis_command_name(cmd), start.elapsed());
+        result
+    }
+
+    fn req_packed_commands(
+        &mut self,
+        cmd: &[u8],
+        offset: usize,
+        count: usize,
+    ) -> redis::RedisResult<Vec<redis::Value>> {
+        let start = Instant::now();
+        let result = self.0.req_packed_commands(cmd, offset, count);
+        metrics::observe_redis("PIPELINE", start.elapsed());
+        result
+    }
+
+    fn get_db(&self) -> i64 {
+        self.0.get_db()
+    }
+
+    fn check_connection(&mut self) -> bool {
+        self.0.check_connection()
+    }
+
+    fn is_open(&self) -> bool {
+        self.0.is_open()
+    }
+}
+
 impl Cacher {
+    /// Create the connection pool without connecting, so that the bot doesn't panic when Redis is
+    /// still starting. Wait for Redis by [`Cacher::ping`] before using it.
     pub fn new(client: redis::Client) -> Self {
-        Self(
-            r2d2::Pool::builder()
-                .build(client)
-                .expect("fail to construct a R2D2 Redis connection"),
-        )
+        Self(r2d2::Pool::builder().build_unchecked(client))
     }
 
-    pub fn get_conn(&self) -> r2d2::PooledConnection<redis::Client> {
-        self.0.get().expect("fail to get redis connection")
+    pub fn get_conn(&self) -> MeteredConnection {
+        MeteredConnection(self.0.get().expect("fail to get redis connection"))
     }
 
+    /// Check the Redis server responds. Unlike [`Cacher::get_conn`], it doesn't panic when the
+    /// connection is unavailable.
+    pub fn ping(&self, timeout: Duration) -> anyhow::Result<()> {
+        let mut conn = self.0.get_timeout(timeout)?;
+        let _: String = redis::cmd("PING").query(&mut *conn)?;
+        Ok(())
+    }
+
+    /// Atomically increase the score of `member` in the counter stored at `key`, and return the
+    /// new score. The counter is a sorted set, so it can be ranked with [`Cacher::counter_top`].
+    pub fn incr_counter<Member>(&self, key: &str, member: Member, delta: i64) -> anyhow::Result<i64>
+    where
+        Member: redis::ToRedisArgs,
+    {
+        let score = self.get_conn().zincr(key, member, delta)?;
+        Ok(score)
+    }
+
+    /// Get the score and the zero-based rank (highest score first) of `member` in the counter.
+    pub fn counter_rank<Member>(
+        &self,
+        key: &str,
+        member: Member,
+    ) -> anyhow::Result<Option<(i64, u64)>>
+    where
+        Member: redis::ToRedisArgs + Copy,
+    {
+        let mut conn = self.get_conn();
+        let score: Option<i64> = conn.zscore(key, member)?;
+        let rank: Option<u64> = conn.zrevrank(key, member)?;
+        Ok(score.zip(rank))
+    }
+
+    /// Get the top `max` members with their score from the counter, highest score first.
+    pub fn counter_top<Member>(&self, key: &str, max: isize) -> anyhow::Result<Vec<(Member, i64)>>
+    where
+        Member: redis::FromRedisValue,
+    {
+        let top = self.get_conn().zrevrange_withscores(key, 0, max - 1)?;
+        Ok(top)
+    }
+
+    /// Record a hit in the sliding window stored at `key`. When there are already `max` hits in
+    /// the last `window`, the hit is dropped and the time to wait for the next hit is returned.
+    pub fn hit_sliding_window(
+        &self,
+        key: &str,
+        max: u32,
+        window: Duration,
+    ) -> anyhow::Result<Option<Duration>> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+        let window_ms = window.as_millis() as u64;
+        // Hits in the same millisecond should not overwrite each other
+        let member = format!("{now}:{}", rand::random::<u32>());
+
+        let mut conn = self.get_conn();
+        let (count,): (u32,) = redis::pipe()
+            .atomic()
+            .zrembyscore(key, 0, now.saturating_sub(window_ms))
+            .ignore()
+            .zadd(key, &member, now)
+            .ignore()
+            .zcard(key)
+            .pexpire(key, window_ms as i64)
+            .ignore()
+            .query(&mut conn)?;
+        if count <= max {
+            return Ok(None);
+        }
+
+        let () = conn.zrem(key, &member)?;
+        let oldest: Vec<(String, u64)> = conn.zrange_withscores(key, 0, 0)?;
+        let oldest = oldest.first().map_or(now, |(_, score)| *score);
+        Ok(Some(window_retry_after(oldest, now, window_ms)))
+    }
+
+    /// Dump the keys matching `pattern` with their type, TTL in seconds and value, for backing up
+    /// or inspecting the state without redis-cli. The values are read by their type, and the
+    /// binary ones are encoded as `{"base64": ...}`.
+    pub fn export(&self, pattern: &str) -> anyhow::Result<serde_json::Value> {
+        use serde_json::json;
+
+        let mut conn = self.get_conn();
+        let mut keys: Vec<String> = conn.keys(pattern)?;
+        keys.sort();
+
+        let mut exported = serde_json::Map::new();
+        for key in keys {
+            let kind: String = redis::cmd("TYPE").arg(&key).query(&mut conn)?;
+            let value = match kind.as_str() {
+                "string" => bytes_to_json(conn.get(&key)?),
+                "hash" => {
+                    let fields: Vec<(Vec<u8>, Vec<u8>)> = conn.hgetall(&key)?;
+                    let fields = fields
+                        .into_iter()
+                        .map(|(field, value)| {
+                            let field = String::from_utf8_lossy(&field).into_owned();
+                            (field, bytes_to_json(value))
+                        })
+                        .collect::<std::collections::BTreeMap<_, _>>();
+                    json!(fields)
+                }
+                "set" => {
+                    let mut members: Vec<Vec<u8>> = conn.smembers(&key)?;
+                    members.sort();
+                    json!(members.into_iter().map(bytes_to_json).collect::<Vec<_>>())
+                }
+                "zset" => {
+                    let members: Vec<(Vec<u8>, f64)> = conn.zrange_withscores(&key, 0, -1)?;
+                    let members = members
+                        .into_iter()
+                        .map(|(member, score)| json!([bytes_to_json(member), score]))
+                        .collect::<Vec<_>>();
+                    json!(members)
+                }
+                "list" => {
+                    let items: Vec<Vec<u8>> = conn.lrange(&key, 0, -1)?;
+                    json!(items.into_iter().map(bytes_to_json).collect::<Vec<_>>())
+                }
+                "stream" => {
+                    let reply: redis::streams::StreamRangeReply = conn.xrange_all(&key)?;
+                    let entries = reply
+                        .ids
+                        .into_iter()
+                        .map(|entry| {
+                            let fields = entry
+                                .map
+                                .into_iter()
+                                .map(|(field, value)| {
+                                    let value = redis::from_redis_value::<Vec<u8>>(&value)?;
+                                    Ok((field, bytes_to_json(value)))
+                                })
+                                .collect::<redis::RedisResult<std::collections::BTreeMap<_, _>>>(
+                                )?;
+                            Ok(json!({ "id": entry.id, "fields": fields }))
+                        })
+                        .collect::<redis::RedisResult<Vec<_>>>()?;
+                    json!(entries)
+                }
+                // Expired between KEYS and TYPE, or a type this bot never writes
+                _ => continue,
+            };
+            let ttl: i64 = conn.ttl(&key)?;
+            exported.insert(key, json!({ "type": kind, "ttl": ttl, "value": value }));
+        }
+        Ok(exported.into())
+    }
+
+    /// Subscribe the events from the config at startup, and drop the subscriptions of the
+    /// registrants removed from the config. Each registrant is set up in one transaction, and
+    /// the registrants that fail are reported together at the end.
     pub fn setup_subscribe_registry<'iter, Subscriber, Event, Relation>(
         &self,
         event_name: &str,
         iter: Relation,
-    ) where
+    ) -> anyhow::Result<()>
+    where
         Subscriber: Eq + Hash + std::fmt::Debug + redis::ToRedisArgs + 'iter,
         Event: Eq + Hash + std::fmt::Debug + std::fmt::Display + redis::ToRedisArgs + 'iter,
         Relation: Iterator<Item = (&'iter Subscriber, &'iter Vec<Event>)>,
     {
-        iter.for_each(|(k, v)| {
-            self.subscribe_event(event_name, k, v)
-                .unwrap_or_else(|err| {
-                    panic!(
-                        "fail to initialize the {} subscribe registry \
-                        when subscribe event {:?} for registrant {:?}: \
-                        {err}",
-                        event_name, v, k
-                    )
-                });
-        });
+        let search = RedisKey::SubscribeRegistryPattern {
+            watcher: Some(event_name),
+        };
+        let existing: HashSet<String> = self.get_conn().keys(search)?;
+
+        let errors = iter
+            .filter_map(|(registrant, events)| {
+                self.subscribe_event(event_name, &existing, registrant, events)
+                    .err()
+                    .map(|err| format!("{registrant:?} to {events:?}: {err}"))
+            })
+            .collect::<Vec<_>>();
+        if !errors.is_empty() {
+            anyhow::bail!(
+                "fail to set up the {event_name} subscribe registry for {} registrants:\n{}",
+                errors.len(),
+                errors.join("\n")
+            );
+        }
+        Ok(())
     }
 
     pub fn event_pool<Event>(&self, event_name: &str) -> anyhow::Result<Vec<Event>>
     where
         Event: redis::FromRedisValue,
     {
-        let event_pool_key = format!("REGISTRY_EVENT_POOL:{}", event_name);
-        let events = self.get_conn().smembers(event_pool_key)?;
+        let events = self.get_conn().smembers(RedisKey::EventPool {
+            watcher: event_name,
+        })?;
         Ok(events)
     }
 
@@ -56,15 +336,204 @@ impl Cacher {
         Subscriber: redis::FromRedisValue,
         Event: redis::ToRedisArgs + std::fmt::Display,
     {
-        let key = format!("SUBSCRIBE_REGISTRY:{}:{}", event_name, event);
+        let key = RedisKey::SubscribeRegistry {
+            watcher: event_name,
+            event,
+        };
         let subscriber = self.get_conn().smembers(key)?;
         Ok(subscriber)
     }
 
-    // Create `event = [registrant]` key-value pair
+    /// Subscribe the event at runtime, return false if it is already subscribed.
+    pub fn subscribe<Subscriber, Event>(
+        &self,
+        event_name: &str,
+        subscriber: &Subscriber,
+        event: &Event,
+    ) -> anyhow::Result<bool>
+    where
+        Subscriber: redis::ToRedisArgs,
+        Event: redis::ToRedisArgs + std::fmt::Display,
+    {
+        let added = self.subscribe_many(event_name, [(subscriber, event)])?;
+        Ok(added > 0)
+    }
+
+    /// Subscribe all the pairs of subscriber and event in one transaction, return how many of
+    /// them are not subscribed before.
+    pub fn subscribe_many<Subscriber, Event>(
+        &self,
+        event_name: &str,
+        pairs: impl IntoIterator<Item = (Subscriber, Event)>,
+    ) -> anyhow::Result<usize>
+    where
+        Subscriber: redis::ToRedisArgs,
+        Event: redis::ToRedisArgs + std::fmt::Display,
+    {
+        let event_pool_key = RedisKey::EventPool {
+            watcher: event_name,
+        };
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        for (subscriber, event) in pairs {
+            let key = RedisKey::SubscribeRegistry {
+                watcher: event_name,
+                event: &event,
+            };
+            pipe.sadd(key, &subscriber);
+            pipe.sadd(event_pool_key, &event).ignore();
+        }
+        let added: Vec<usize> = pipe.query(&mut self.get_conn())?;
+        Ok(added.into_iter().sum())
+    }
+
+    /// Store the options of the subscriber for the event, like the threshold to be notified at.
+    /// The options are removed along with the subscription.
+    pub fn set_subscribe_options<Subscriber, Event, T>(
+        &self,
+        event_name: &str,
+        subscriber: &Subscriber,
+        event: &Event,
+        options: &T,
+    ) -> anyhow::Result<()>
+    where
+        Subscriber: redis::ToRedisArgs,
+        Event: std::fmt::Display,
+        T: Serialize,
+    {
+        let key = RedisKey::SubscribeOptions {
+            watcher: event_name,
+            event,
+        };
+        let () = self
+            .get_conn()
+            .hset(key, subscriber, serde_json::to_string(options)?)?;
+        Ok(())
+    }
+
+    /// Get the subscribers of the event along with their options, for the watcher to filter and
+    /// customize the notifications. The subscribers without options, or with the options it
+    /// fails to read, get the default options.
+    pub fn get_subscribers_with_opts<T, Event>(
+        &self,
+        event_name: &str,
+        event: &Event,
+    ) -> anyhow::Result<Vec<(String, T)>>
+    where
+        T: DeserializeOwned + Default,
+        Event: std::fmt::Display,
+    {
+        let mut conn = self.get_conn();
+        let subscribers: Vec<String> = conn.smembers(RedisKey::SubscribeRegistry {
+            watcher: event_name,
+            event,
+        })?;
+        let mut options: HashMap<String, String> = conn.hgetall(RedisKey::SubscribeOptions {
+            watcher: event_name,
+            event,
+        })?;
+
+        let subscribers = subscribers
+            .into_iter()
+            .map(|subscriber| {
+                let opts = options
+                    .remove(&subscriber)
+                    .and_then(|json| match serde_json::from_str(&json) {
+                        Ok(opts) => Some(opts),
+                        Err(err) => {
+                            tracing::warn!(
+                                "bad options of {subscriber} for {event_name} {event}: {err}"
+                            );
+                            None
+                        }
+                    })
+                    .unwrap_or_default();
+                (subscriber, opts)
+            })
+            .collect();
+        Ok(subscribers)
+    }
+
+    /// Unsubscribe the event, it leaves the event pool when nobody subscribes it. Return false
+    /// if it is not subscribed.
+    pub fn unsubscribe<Subscriber, Event>(
+        &self,
+        event_name: &str,
+        subscriber: &Subscriber,
+        event: &Event,
+    ) -> anyhow::Result<bool>
+    where
+        Subscriber: redis::ToRedisArgs,
+        Event: redis::ToRedisArgs + std::fmt::Display,
+    {
+        let mut conn = self.get_conn();
+        let key = RedisKey::SubscribeRegistry {
+            watcher: event_name,
+            event,
+        };
+        let removed: bool = conn.srem(key, subscriber)?;
+        let options = RedisKey::SubscribeOptions {
+            watcher: event_name,
+            event,
+        };
+        let () = conn.hdel(options, subscriber)?;
+        let left: usize = conn.scard(key)?;
+        if left == 0 {
+            let () = conn.srem(
+                RedisKey::EventPool {
+                    watcher: event_name,
+                },
+                event,
+            )?;
+        }
+        Ok(removed)
+    }
+
+    /// Unsubscribe every event subscribed by the chat or its topics, for the chat which blocks or
+    /// removes the bot. Return the subscriptions removed.
+    pub fn unsubscribe_chat(&self, chat_id: i64) -> anyhow::Result<usize> {
+        let mut removed = 0;
+        for sub in self.subscriptions_of(chat_id)? {
+            if self.unsubscribe(&sub.watcher, &sub.subscriber, &sub.event)? {
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Every event subscribed by the chat or its topics, sorted by the watcher and the event.
+    pub fn subscriptions_of(&self, chat_id: i64) -> anyhow::Result<Vec<Subscription>> {
+        let keys: Vec<String> = self
+            .get_conn()
+            .keys(RedisKey::SubscribeRegistryPattern { watcher: None })?;
+        let chat = chat_id.to_string();
+        let mut subscriptions = Vec::new();
+        for key in keys {
+            let Some((watcher, event)) = RedisKey::parse_subscribe_registry(&key) else {
+                continue;
+            };
+            let subscribers: Vec<String> = self.get_conn().smembers(&key)?;
+            subscriptions.extend(
+                subscribers
+                    .into_iter()
+                    .filter(|subscriber| subscriber.split(':').next() == Some(chat.as_str()))
+                    .map(|subscriber| Subscription {
+                        watcher: watcher.to_string(),
+                        event: event.to_string(),
+                        subscriber,
+                    }),
+            );
+        }
+        subscriptions.sort();
+        Ok(subscriptions)
+    }
+
+    // Create `event = [registrant]` key-value pair, and remove the registrant from the
+    // `existing` events it no longer subscribes
     fn subscribe_event<Subscriber, Event>(
         &self,
         event_name: &str,
+        existing: &HashSet<String>,
         registrant: &Subscriber,
         events: &Vec<Event>,
     ) -> anyhow::Result<()>
@@ -72,30 +541,133 @@ impl Cacher {
         Subscriber: redis::ToRedisArgs,
         Event: redis::ToRedisArgs + std::fmt::Display,
     {
-        let mut conn = self.get_conn();
-        let event_pool_key = format!("REGISTRY_EVENT_POOL:{}", event_name);
-
-        let search = format!("SUBSCRIBE_REGISTRY:{event_name}:*");
-        let existing: HashSet<String> = conn.keys(&search)?;
-        let mut popingin: HashSet<String> = HashSet::with_capacity(existing.len());
+        let event_pool_key = RedisKey::EventPool {
+            watcher: event_name,
+        };
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        let mut popingin: HashSet<String> = HashSet::with_capacity(events.len());
 
         for event in events {
-            let key = format!("SUBSCRIBE_REGISTRY:{}:{}", event_name, event);
-            let () = conn.sadd(key.as_str(), registrant)?;
-            let () = conn.sadd(event_pool_key.as_str(), event)?;
+            let key = RedisKey::SubscribeRegistry {
+                watcher: event_name,
+                event,
+            };
+            pipe.sadd(key, registrant).ignore();
+            pipe.sadd(event_pool_key, event).ignore();
 
-            popingin.insert(key);
+            popingin.insert(key.to_string());
         }
 
-        let garbage: Vec<String> = (&existing - &popingin).iter().cloned().collect();
-        for event in garbage {
-            let () = conn.srem(event, registrant)?;
+        for key in existing - &popingin {
+            pipe.srem(&key, registrant).ignore();
+            if let Some((_, event)) = RedisKey::parse_subscribe_registry(&key) {
+                let options = RedisKey::SubscribeOptions {
+                    watcher: event_name,
+                    event: &event,
+                };
+                pipe.hdel(options, registrant).ignore();
+            }
         }
 
+        let () = pipe.query(&mut self.get_conn())?;
         Ok(())
     }
 }
 
+/// The text as it is, and the binary value as `{"base64": ...}`.
+fn bytes_to_json(bytes: Vec<u8>) -> serde_json::Value {
+    use base64::Engine;
+
+    match String::from_utf8(bytes) {
+        Ok(text) => serde_json::Value::String(text),
+        Err(err) => serde_json::json!({
+            "base64": base64::engine::general_purpose::STANDARD.encode(err.into_bytes())
+        }),
+    }
+}
+
+fn window_retry_after(oldest_ms: u64, now_ms: u64, window_ms: u64) -> Duration {
+    Duration::from_millis((oldest_ms + window_ms).saturating_sub(now_ms))
+}
+
+#[test]
+fn test_window_retry_after() {
+    assert_eq!(
+        window_retry_after(1_000, 31_000, 60_000),
+        Duration::from_secs(30)
+    );
+    assert_eq!(window_retry_after(1_000, 90_000, 60_000), Duration::ZERO);
+}
+
+#[test]
+fn test_redis_key() {
+    // Changing any of these orphans the data stored by the running bots
+    let key = RedisKey::SubscribeRegistry {
+        watcher: "CertExpiryWatcher",
+        event: &"example.com:443",
+    };
+    assert_eq!(
+        key.to_string(),
+        "SUBSCRIBE_REGISTRY:CertExpiryWatcher:example.com:443"
+    );
+    assert_eq!(
+        RedisKey::SubscribeRegistry {
+            watcher: "BilibiliLiveRoomWatcher",
+            event: &1000_u64,
+        }
+        .to_string(),
+        "SUBSCRIBE_REGISTRY:BilibiliLiveRoomWatcher:1000"
+    );
+    assert_eq!(
+        RedisKey::SubscribeRegistryPattern {
+            watcher: Some("UptimeMonitor")
+        }
+        .to_string(),
+        "SUBSCRIBE_REGISTRY:UptimeMonitor:*"
+    );
+    assert_eq!(
+        RedisKey::SubscribeRegistryPattern { watcher: None }.to_string(),
+        "SUBSCRIBE_REGISTRY:*"
+    );
+    assert_eq!(
+        RedisKey::EventPool {
+            watcher: "HolidayReminder"
+        }
+        .to_string(),
+        "REGISTRY_EVENT_POOL:HolidayReminder"
+    );
+    assert_eq!(
+        RedisKey::SubscribeOptions {
+            watcher: "UptimeMonitor",
+            event: &"https://example.com/",
+        }
+        .to_string(),
+        "SUBSCRIBE_OPTIONS:UptimeMonitor:https://example.com/"
+    );
+    assert_eq!(RedisKey::WatcherFailures.to_string(), "WATCHER_FAILURES");
+    assert_eq!(RedisKey::PausedWatchers.to_string(), "PAUSED_WATCHERS");
+    assert_eq!(
+        redis::cmd("GET").arg(key).get_packed_command(),
+        redis::cmd("GET")
+            .arg("SUBSCRIBE_REGISTRY:CertExpiryWatcher:example.com:443")
+            .get_packed_command()
+    );
+
+    assert_eq!(
+        RedisKey::parse_subscribe_registry(&key.to_string()),
+        Some(("CertExpiryWatcher", "example.com:443"))
+    );
+    assert_eq!(
+        RedisKey::parse_subscribe_registry("SUBSCRIBE_REGISTRY_X:a:b"),
+        None
+    );
+    assert_eq!(
+        RedisKey::parse_subscribe_registry("REGISTRY_EVENT_POOL:a"),
+        None
+    );
+}
+
 #[test]
 fn test_event_registry() {
     dotenvy::dotenv().ok();
@@ -110,7 +682,9 @@ fn test_event_registry() {
     ]);
 
     let name = "TestRegistry";
-    cacher.setup_subscribe_registry(name, relation.iter());
+    cacher
+        .setup_subscribe_registry(name, relation.iter())
+        .unwrap();
 
     let mut events: Vec<i32> = cacher.event_pool(name).unwrap();
     events.sort();
@@ -132,8 +706,155 @@ fn test_event_registry() {
         ("bar", vec![1, 2]),
         ("baz", vec![3, 4, 5]),
     ]);
-    cacher.setup_subscribe_registry(name, relation.iter());
+    cacher
+        .setup_subscribe_registry(name, relation.iter())
+        .unwrap();
     let subscribers: Vec<String> = cacher.get_subscribers(name, &3_i32).unwrap();
     assert_eq!(subscribers.len(), 1);
     assert!(subscribers.iter().any(|x| x == "baz"));
 }
+
+#[test]
+fn test_export() {
+    let redis = crate::testkit::MemoryRedis::start();
+    let cacher = redis.cacher();
+    let mut conn = cacher.get_conn();
+    let () = conn.set_ex("LANG:1", "en", 60).unwrap();
+    let () = conn.sadd("GLOBAL_ADMINS", 42).unwrap();
+    cacher.incr_counter("COUNTER:hug", 7, 2).unwrap();
+
+    let exported = cacher.export("*").unwrap();
+    assert_eq!(exported["LANG:1"]["type"], "string");
+    assert_eq!(exported["LANG:1"]["value"], "en");
+    assert!(exported["LANG:1"]["ttl"].as_i64().unwrap() > 0);
+    assert_eq!(
+        exported["GLOBAL_ADMINS"]["value"],
+        serde_json::json!(["42"])
+    );
+    assert_eq!(exported["GLOBAL_ADMINS"]["ttl"], -1);
+    assert_eq!(
+        exported["COUNTER:hug"]["value"],
+        serde_json::json!([["7", 2.0]])
+    );
+
+    let () = conn
+        .set("QUOTE_IMAGE:1", &[0xff_u8, 0xd8, 0xff][..])
+        .unwrap();
+    let exported = cacher.export("QUOTE_IMAGE:*").unwrap();
+    assert_eq!(
+        exported["QUOTE_IMAGE:1"]["value"],
+        serde_json::json!({ "base64": "/9j/" })
+    );
+}
+
+#[test]
+fn test_unsubscribe_chat() {
+    let redis = crate::testkit::MemoryRedis::start();
+    let cacher = redis.cacher();
+    for (name, subscriber, event) in [
+        ("CertExpiryWatcher", "-100", "example.com:443"),
+        ("CertExpiryWatcher", "-100:42", "example.org:443"),
+        ("CertExpiryWatcher", "-1001", "example.org:443"),
+        ("UptimeMonitor", "-100", "https://example.com/"),
+    ] {
+        cacher.subscribe(name, &subscriber, &event).unwrap();
+    }
+
+    let subscriptions = cacher.subscriptions_of(-100).unwrap();
+    assert_eq!(
+        subscriptions
+            .iter()
+            .map(|sub| (
+                sub.watcher.as_str(),
+                sub.event.as_str(),
+                sub.subscriber.as_str()
+            ))
+            .collect::<Vec<_>>(),
+        [
+            ("CertExpiryWatcher", "example.com:443", "-100"),
+            ("CertExpiryWatcher", "example.org:443", "-100:42"),
+            ("UptimeMonitor", "https://example.com/", "-100"),
+        ]
+    );
+
+    assert_eq!(cacher.unsubscribe_chat(-100).unwrap(), 3);
+    let events: Vec<String> = cacher.event_pool("CertExpiryWatcher").unwrap();
+    assert_eq!(events, ["example.org:443"]);
+    let subscribers: Vec<String> = cacher
+        .get_subscribers("CertExpiryWatcher", &"example.org:443")
+        .unwrap();
+    assert_eq!(subscribers, ["-1001"]);
+    let events: Vec<String> = cacher.event_pool("UptimeMonitor").unwrap();
+    assert!(events.is_empty());
+}
+
+#[test]
+fn test_subscribers_with_opts() {
+    #[derive(Debug, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+    #[serde(default)]
+    struct Options {
+        min_magnitude: f32,
+        silent: bool,
+    }
+
+    let redis = crate::testkit::MemoryRedis::start();
+    let cacher = redis.cacher();
+    let name = "EarthquakeWatcher";
+    for subscriber in ["-100", "-1001"] {
+        cacher.subscribe(name, &subscriber, &"JP").unwrap();
+    }
+    let options = Options {
+        min_magnitude: 6.0,
+        silent: true,
+    };
+    cacher
+        .set_subscribe_options(name, &"-100", &"JP", &options)
+        .unwrap();
+
+    let mut subscribers: Vec<(String, Options)> =
+        cacher.get_subscribers_with_opts(name, &"JP").unwrap();
+    subscribers.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(
+        subscribers,
+        [
+            ("-100".to_string(), options),
+            ("-1001".to_string(), Options::default())
+        ]
+    );
+
+    // Subscribing again starts with the default options
+    cacher.unsubscribe(name, &"-100", &"JP").unwrap();
+    cacher.subscribe(name, &"-100", &"JP").unwrap();
+    let subscribers: Vec<(String, Options)> =
+        cacher.get_subscribers_with_opts(name, &"JP").unwrap();
+    assert!(subscribers
+        .iter()
+        .all(|(_, opts)| *opts == Options::default()));
+}
+
+#[test]
+fn test_subscribe_many() {
+    let redis = crate::testkit::MemoryRedis::start();
+    let cacher = redis.cacher();
+    let name = "UptimeMonitor";
+
+    assert!(cacher.subscribe(name, &"-100", &"https://a.com/").unwrap());
+    let added = cacher
+        .subscribe_many(
+            name,
+            [
+                ("-100", "https://a.com/"),
+                ("-100", "https://b.com/"),
+                ("-1001", "https://b.com/"),
+            ],
+        )
+        .unwrap();
+    assert_eq!(added, 2);
+
+    let mut events: Vec<String> = cacher.event_pool(name).unwrap();
+    events.sort();
+    assert_eq!(events, ["https://a.com/", "https://b.com/"]);
+    let mut subscribers: Vec<String> = cacher.get_subscribers(name, &"https://b.com/").unwrap();
+    subscribers.sort();
+    assert_eq!(subscribers, ["-100", "-1001"]);
+}
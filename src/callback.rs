@@ -0,0 +1,252 @@
+use std::{future::Future, pin::Pin};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::Sha256;
+use teloxide::{
+    payloads::AnswerCallbackQuerySetters,
+    prelude::{Bot, CallbackQuery, Requester},
+    types::InlineKeyboardButton,
+};
+
+use crate::app::AppData;
+
+/// Telegram limits the `callback_data` to 64 bytes
+const MAX_CALLBACK_DATA_LEN: usize = 64;
+/// Length of the base64 encoded signature
+const SIGNATURE_LEN: usize = 8;
+
+/// The decoded and verified callback data.
+#[derive(Debug, PartialEq)]
+pub struct CallbackData {
+    pub module: String,
+    pub action: String,
+    pub payload: String,
+}
+
+/// Everything a callback handler needs to know about the button press.
+pub struct CallbackContext {
+    pub bot: Bot,
+    pub data: AppData,
+    pub query: CallbackQuery,
+    pub action: String,
+    payload: String,
+}
+
+impl CallbackContext {
+    /// Deserialize the typed payload encoded by [`CallbackRouter::button`].
+    pub fn payload<T: DeserializeOwned>(&self) -> anyhow::Result<T> {
+        serde_json::from_str(&self.payload)
+            .map_err(|err| anyhow::anyhow!("invalid callback payload: {err}"))
+    }
+}
+
+/// Text replied to the user by the popup after the button is pressed
+pub type CallbackAnswer = Option<String>;
+
+type CallbackFuture = Pin<Box<dyn Future<Output = anyhow::Result<CallbackAnswer>> + Send>>;
+type CallbackHandler = Box<dyn Fn(CallbackContext) -> CallbackFuture + Send + Sync>;
+
+struct CallbackRoute {
+    module: &'static str,
+    /// The [`crate::module::BotModule`] adding the route
+    owner: Option<&'static str>,
+    handler: CallbackHandler,
+}
+
+/// Route the inline keyboard button press to the module which create the button. The
+/// `callback_data` is encoded as `<signature><module>.<action>.<payload>`, and the signature is
+/// a truncated HMAC so that user can't forge button.
+pub struct CallbackRouter {
+    secret: Vec<u8>,
+    routes: Vec<CallbackRoute>,
+    owner: Option<&'static str>,
+}
+
+impl CallbackRouter {
+    pub fn new(secret: impl AsRef<[u8]>) -> Self {
+        Self {
+            secret: secret.as_ref().to_vec(),
+            routes: Vec::new(),
+            owner: None,
+        }
+    }
+
+    /// A router with the same secret but no route, for a handler creating the buttons.
+    pub fn signer(&self) -> Self {
+        Self::new(&self.secret)
+    }
+
+    /// Set the module adding the routes next, its routes are skipped when it is disabled in the
+    /// config.
+    pub fn owned_by(mut self, module: &'static str) -> Self {
+        self.owner = Some(module);
+        self
+    }
+
+    pub fn route<F, Fut>(mut self, module: &'static str, handler: F) -> Self
+    where
+        F: Fn(CallbackContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = anyhow::Result<CallbackAnswer>> + Send + 'static,
+    {
+        assert!(!module.contains('.'), "module name should not contains dot");
+        self.routes.push(CallbackRoute {
+            module,
+            owner: self.owner,
+            handler: Box::new(move |ctx| Box::pin(handler(ctx))),
+        });
+        self
+    }
+
+    fn mac(&self, content: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accept any key");
+        mac.update(content.as_bytes());
+        mac
+    }
+
+    fn sign(&self, content: &str) -> String {
+        let digest = self.mac(content).finalize().into_bytes();
+        // 6 bytes turns into 8 characters in base64
+        URL_SAFE_NO_PAD.encode(&digest[..6])
+    }
+
+    /// Encode the callback data, returns error if it doesn't fit in 64 bytes.
+    pub fn encode<T: Serialize>(
+        &self,
+        module: &str,
+        action: &str,
+        payload: &T,
+    ) -> anyhow::Result<String> {
+        let payload = serde_json::to_string(payload)?;
+        let content = format!("{module}.{action}.{payload}");
+        let data = format!("{}{content}", self.sign(&content));
+        if data.len() > MAX_CALLBACK_DATA_LEN {
+            anyhow::bail!(
+                "callback data for {module}.{action} is {} bytes, exceed the {MAX_CALLBACK_DATA_LEN} bytes limit",
+                data.len()
+            );
+        }
+        Ok(data)
+    }
+
+    /// Decode and verify the callback data.
+    pub fn decode(&self, data: &str) -> anyhow::Result<CallbackData> {
+        if data.len() < SIGNATURE_LEN || !data.is_char_boundary(SIGNATURE_LEN) {
+            anyhow::bail!("callback data too short");
+        }
+        let (signature, content) = data.split_at(SIGNATURE_LEN);
+        // Compare in constant time, so the signature can't be guessed byte by byte
+        let valid = URL_SAFE_NO_PAD
+            .decode(signature)
+            .is_ok_and(|signature| self.mac(content).verify_truncated_left(&signature).is_ok());
+        if !valid {
+            anyhow::bail!("invalid callback signature");
+        }
+
+        let mut parts = content.splitn(3, '.');
+        let (Some(module), Some(action), Some(payload)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            anyhow::bail!("malformed callback data");
+        };
+
+        Ok(CallbackData {
+            module: module.to_string(),
+            action: action.to_string(),
+            payload: payload.to_string(),
+        })
+    }
+
+    /// Create a inline keyboard button which will be routed back to the `module`.
+    pub fn button<T: Serialize>(
+        &self,
+        text: impl Into<String>,
+        module: &str,
+        action: &str,
+        payload: &T,
+    ) -> anyhow::Result<InlineKeyboardButton> {
+        Ok(InlineKeyboardButton::callback(
+            text,
+            self.encode(module, action, payload)?,
+        ))
+    }
+
+    /// Dispatch the callback query to the registered handler, and answer the query with the
+    /// handler returned text. Error is displayed as an alert to the user.
+    pub async fn dispatch(
+        &self,
+        bot: Bot,
+        data: AppData,
+        query: CallbackQuery,
+    ) -> anyhow::Result<()> {
+        let query_id = query.id.clone();
+        let result = self.handle(bot.clone(), data, query).await;
+
+        let answer = bot.answer_callback_query(&query_id);
+        let answer = match result {
+            Ok(Some(text)) => answer.text(text),
+            Ok(None) => answer,
+            Err(err) => answer.text(format!("{err}")).show_alert(true),
+        };
+        // Slow handler might miss the answer deadline, which is fine
+        if let Err(err) = answer.await {
+            tracing::debug!("fail to answer callback query: {err}");
+        }
+
+        Ok(())
+    }
+
+    async fn handle(
+        &self,
+        bot: Bot,
+        data: AppData,
+        query: CallbackQuery,
+    ) -> anyhow::Result<CallbackAnswer> {
+        let Some(raw) = query.data.as_deref() else {
+            return Ok(None);
+        };
+        let decoded = self.decode(raw)?;
+        let Some(route) = self.routes.iter().find(|route| {
+            route.module == decoded.module
+                && !route.owner.is_some_and(crate::settings::globally_disabled)
+        }) else {
+            anyhow::bail!("This button is no longer supported");
+        };
+
+        let ctx = CallbackContext {
+            bot,
+            data,
+            query,
+            action: decoded.action,
+            payload: decoded.payload,
+        };
+        (route.handler)(ctx).await
+    }
+}
+
+#[test]
+fn test_callback_data_codec() {
+    let router = CallbackRouter::new("secret");
+
+    let data = router
+        .encode("make_quote", "add", &(1145141919_u64, "先辈"))
+        .unwrap();
+    assert!(data.len() <= MAX_CALLBACK_DATA_LEN);
+
+    let decoded = router.decode(&data).unwrap();
+    assert_eq!(decoded.module, "make_quote");
+    assert_eq!(decoded.action, "add");
+    let payload: (u64, String) = serde_json::from_str(&decoded.payload).unwrap();
+    assert_eq!(payload, (1145141919, "先辈".to_string()));
+
+    // Forged data should not pass the verification
+    let forged = data.replace("add", "del");
+    assert!(router.decode(&forged).is_err());
+    assert!(CallbackRouter::new("other").decode(&data).is_err());
+    let not_base64 = format!("{}{}", "!".repeat(SIGNATURE_LEN), &data[SIGNATURE_LEN..]);
+    assert!(router.decode(&not_base64).is_err());
+    assert!(router.decode("short").is_err());
+
+    assert!(router.encode("m", "a", &"x".repeat(64)).is_err());
+}
@@ -0,0 +1,278 @@
+//! Move what the bot keeps for a group to its new id when the group is upgraded to a supergroup.
+//! Telegram tells by the `migrate_to_chat_id` service message in the old group, or by the
+//! `MigrateToChatId` error when the bot still sends to the old id.
+
+use std::time::Duration;
+
+use chrono::{Local, Timelike};
+use redis::Commands;
+use teloxide::types::ChatId;
+
+use crate::{
+    app::AppData,
+    cache::RedisKey,
+    config::Config,
+    delayed_task::{self, DelayedTask},
+    digest, quiet_hours,
+};
+
+/// Keys named after the chat like `CHAT_SETTINGS:{chat_id}`. The keys about a message like the
+/// reaction counts are left behind, as the supergroup numbers its messages again.
+const CHAT_KEYS: &[&str] = &[
+    "CHAT_SETTINGS",
+    "CHAT_LANGUAGE",
+    "ARCHIVE_RETENTION",
+    "ARCHIVE_OPT_OUT",
+    "COUNTER_DEFINITION",
+    "COUNTER_USERNAME",
+    "MONITOR_URLS",
+    "QUIZ_SCORES",
+    "QUIZ_PLAYERS",
+    "SPAM_POLICY",
+    "SPAM_WORDS",
+    "SPAM_SEEN",
+];
+/// Keys named after the chat and something of it, like `COUNTER:{chat_id}:{name}`
+const CHAT_PREFIXED_KEYS: &[&str] = &["COUNTER"];
+/// Hashes with the chat id as the field
+const CHAT_FIELDS: &[&str] = &["QUIET_HOURS", "DIGEST_SCHEDULE"];
+/// Sets with the chat id as the member
+const CHAT_MEMBERS: &[&str] = &["KNOWN_CHATS"];
+
+/// Key of the notifications held for the chat target by the bot, and the task sending them
+type HeldKey = (fn(&str, &str) -> String, fn(String, String) -> DelayedTask);
+
+const HELD_KEYS: &[HeldKey] = &[
+    (quiet_hours::held_key, |bot, target| {
+        DelayedTask::QuietDigest { bot, target }
+    }),
+    (digest::held_key, |bot, target| DelayedTask::Digest {
+        bot,
+        target,
+    }),
+];
+
+/// What is moved to the new chat id.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Migrated {
+    pub keys: usize,
+    pub subscriptions: usize,
+    pub rows: u64,
+}
+
+/// Move the settings, counters and subscriptions of the chat to its new id. The Redis data is
+/// moved in one transaction and the database rows in another, running it again moves nothing.
+pub async fn migrate_chat(data: &AppData, from: ChatId, to: ChatId) -> anyhow::Result<Migrated> {
+    let (keys, subscriptions, held) = migrate_redis(data, from, to)?;
+    let rows = data.storage.migrate_chat(from.0, to.0).await?;
+
+    // The digests scheduled for the old id find nothing, schedule them again for the new one
+    let now = Local::now().time().with_nanosecond(0).unwrap_or_default();
+    let quiet_delay = match quiet_hours::get(data, to.0)? {
+        Some(hours) if hours.contains(now) => hours.remaining(now),
+        _ => Duration::ZERO,
+    };
+    let digest_delay =
+        digest::get(data, to.0)?.map_or(Duration::ZERO, |schedule| schedule.remaining(now));
+    for task in held {
+        let delay = match task {
+            DelayedTask::Digest { .. } => digest_delay,
+            _ => quiet_delay,
+        };
+        delayed_task::schedule(data, &task, delay)?;
+    }
+
+    let config = Config::get_global_config();
+    let chat = from.to_string();
+    for (section, configured) in [
+        (
+            "bili_live_room_event",
+            config.bili_live_room_event.contains_key(&chat),
+        ),
+        ("holiday_event", config.holiday_event.contains_key(&chat)),
+    ] {
+        if configured {
+            tracing::warn!(
+                "chat {from} in config section {section} is migrated to {to}, update the config"
+            );
+        }
+    }
+
+    let migrated = Migrated {
+        keys,
+        subscriptions,
+        rows,
+    };
+    tracing::info!("migrated chat {from} to {to}: {migrated:?}");
+    Ok(migrated)
+}
+
+/// Return the keys and subscriptions moved, and the tasks to send the notifications held for the
+/// chat.
+fn migrate_redis(
+    data: &AppData,
+    from: ChatId,
+    to: ChatId,
+) -> anyhow::Result<(usize, usize, Vec<DelayedTask>)> {
+    let mut conn = data.cacher.get_conn();
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+
+    let mut renames = Vec::new();
+    for prefix in CHAT_KEYS {
+        let key = format!("{prefix}:{from}");
+        if conn.exists(&key)? {
+            renames.push((key, format!("{prefix}:{to}")));
+        }
+    }
+    for prefix in CHAT_PREFIXED_KEYS {
+        let keys: Vec<String> = conn.keys(format!("{prefix}:{from}:*"))?;
+        for key in keys {
+            let rest = &key[format!("{prefix}:{from}:").len()..];
+            renames.push((key.clone(), format!("{prefix}:{to}:{rest}")));
+        }
+    }
+    let mut held = Vec::new();
+    for (held_key, task) in HELD_KEYS {
+        let keys: Vec<String> = conn.keys(held_key("*", &from.to_string()))?;
+        for key in keys {
+            let Some(bot) = key.split(':').nth(1) else {
+                continue;
+            };
+            renames.push((key.clone(), held_key(bot, &to.to_string())));
+            held.push(task(bot.to_string(), to.to_string()));
+        }
+    }
+    for (old, new) in &renames {
+        pipe.rename(old, new).ignore();
+    }
+
+    for key in CHAT_FIELDS {
+        let value: Option<String> = conn.hget(key, from.0)?;
+        if let Some(value) = value {
+            pipe.hdel(key, from.0)
+                .ignore()
+                .hset(key, to.0, value)
+                .ignore();
+        }
+    }
+    for key in CHAT_MEMBERS {
+        if conn.sismember(key, from.0)? {
+            pipe.srem(key, from.0).ignore().sadd(key, to.0).ignore();
+        }
+    }
+
+    let subscriptions = data.cacher.subscriptions_of(from.0)?;
+    for sub in &subscriptions {
+        // The subscriber is the chat, or a topic of it like `-100123:7`
+        let subscriber = match sub.subscriber.split_once(':') {
+            Some((_, topic)) => format!("{to}:{topic}"),
+            None => to.to_string(),
+        };
+        let registry = RedisKey::SubscribeRegistry {
+            watcher: &sub.watcher,
+            event: &sub.event,
+        };
+        pipe.srem(registry, &sub.subscriber)
+            .ignore()
+            .sadd(registry, &subscriber)
+            .ignore();
+        let options = RedisKey::SubscribeOptions {
+            watcher: &sub.watcher,
+            event: &sub.event,
+        };
+        let value: Option<String> = conn.hget(options, &sub.subscriber)?;
+        if let Some(value) = value {
+            pipe.hdel(options, &sub.subscriber)
+                .ignore()
+                .hset(options, &subscriber, value)
+                .ignore();
+        }
+    }
+
+    let () = pipe.query(&mut conn)?;
+    Ok((renames.len(), subscriptions.len(), held))
+}
+
+#[tokio::test]
+async fn test_migrate_chat() {
+    use crate::modules::archive;
+
+    let data = crate::testkit::app_data(teloxide::Bot::new("1000:fake-token")).await;
+    let (from, to) = (ChatId(-123), ChatId(-100123));
+    let mut conn = data.cacher.get_conn();
+    let () = conn.hset("CHAT_SETTINGS:-123", "tr", "0").unwrap();
+    let () = conn.zadd("COUNTER:-123:coffee", 42, 3).unwrap();
+    let () = conn.hset("REACTION_USERS:-123:7:👍", 42, 1).unwrap();
+    let () = conn.sadd("KNOWN_CHATS", -123).unwrap();
+    quiet_hours::set(&data, from.0, Some("23:00-08:00".parse().unwrap())).unwrap();
+    data.cacher
+        .subscribe("CertWatcher", &from.0, &"example.com")
+        .unwrap();
+    data.cacher
+        .set_subscribe_options("CertWatcher", &from.0, &"example.com", &7)
+        .unwrap();
+    sqlx::query("INSERT INTO karma (chat_id, user_id, username, karma) VALUES ($1, $2, $3, $4)")
+        .bind(from.0)
+        .bind(42_i64)
+        .bind("alice")
+        .bind(5_i64)
+        .execute(data.storage.pool())
+        .await
+        .unwrap();
+    // The message 1 of the group is dropped, as the supergroup has its own message 1
+    for (chat_id, message_id, text) in [(from, 1, "old"), (from, 2, "hello"), (to, 1, "new")] {
+        sqlx::query(
+            "INSERT INTO archived_messages (chat_id, message_id, user_id, username, text, sent_at) \
+             VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(chat_id.0)
+        .bind(message_id as i64)
+        .bind(42_i64)
+        .bind("alice")
+        .bind(text)
+        .bind(0_i64)
+        .execute(data.storage.pool())
+        .await
+        .unwrap();
+    }
+
+    let migrated = migrate_chat(&data, from, to).await.unwrap();
+    assert_eq!(
+        migrated,
+        Migrated {
+            keys: 2,
+            subscriptions: 1,
+            rows: 2,
+        }
+    );
+    let settings: Option<String> = conn.hget("CHAT_SETTINGS:-100123", "tr").unwrap();
+    assert_eq!(settings.as_deref(), Some("0"));
+    let count: Option<i64> = conn.zscore("COUNTER:-100123:coffee", 42).unwrap();
+    assert_eq!(count, Some(3));
+    assert!(conn.exists::<_, bool>("REACTION_USERS:-123:7:👍").unwrap());
+    assert!(conn
+        .sismember::<_, _, bool>("KNOWN_CHATS", -100123)
+        .unwrap());
+    assert!(quiet_hours::get(&data, from.0).unwrap().is_none());
+    assert!(quiet_hours::get(&data, to.0).unwrap().is_some());
+    let subscriptions = data.cacher.subscriptions_of(to.0).unwrap();
+    assert_eq!(subscriptions[0].subscriber, "-100123");
+    let options: Vec<(String, i32)> = data
+        .cacher
+        .get_subscribers_with_opts("CertWatcher", &"example.com")
+        .unwrap();
+    assert_eq!(options, [("-100123".to_string(), 7)]);
+    let (karma,): (i64,) = sqlx::query_as("SELECT karma FROM karma WHERE chat_id = $1")
+        .bind(to.0)
+        .fetch_one(data.storage.pool())
+        .await
+        .unwrap();
+    assert_eq!(karma, 5);
+    assert_eq!(archive::count(&data.storage, from.0).await.unwrap(), 0);
+    assert_eq!(archive::purge_chat(&data.storage, to.0).await.unwrap(), 2);
+
+    // Nothing is left to move
+    let again = migrate_chat(&data, from, to).await.unwrap();
+    assert_eq!(again, Migrated::default());
+}
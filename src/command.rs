@@ -0,0 +1,257 @@
+use std::fmt::Write;
+
+use teloxide::types::{BotCommand, BotCommandScope};
+
+use crate::i18n;
+
+/// Who can use the command
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Permission {
+    #[default]
+    User,
+    ChatAdmin,
+    /// Admin of the bot, who is treated as chat admin in every chat
+    GlobalAdmin,
+    Owner,
+}
+
+impl Permission {
+    fn describe(self, lang: &str) -> String {
+        match self {
+            Self::User => crate::t!(lang, "help.everyone"),
+            Self::ChatAdmin => crate::t!(lang, "help.chat_admin"),
+            Self::GlobalAdmin => crate::t!(lang, "help.bot_admin"),
+            Self::Owner => crate::t!(lang, "help.bot_owner"),
+        }
+    }
+}
+
+/// Which kind of chat the command is available in
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ChatScope {
+    #[default]
+    All,
+    Private,
+    Group,
+}
+
+/// The description and usage are read from `command.<name>.description` and
+/// `command.<name>.usage` of the locale files, the ones given here are only used when the locale
+/// files don't have the command.
+#[derive(Debug, Clone, typed_builder::TypedBuilder)]
+pub struct CommandInfo {
+    #[builder(setter(into))]
+    pub name: String,
+    #[builder(default, setter(into))]
+    pub description: String,
+    #[builder(default, setter(strip_option, into))]
+    pub usage: Option<String>,
+    #[builder(default)]
+    pub permission: Permission,
+    #[builder(default)]
+    pub scope: ChatScope,
+    /// The module that the command belongs to, filled in by the [`crate::module::ModuleRegistry`].
+    /// The command is ignored when the chat disables the module.
+    #[builder(default, setter(strip_option, into))]
+    pub module: Option<String>,
+}
+
+impl CommandInfo {
+    /// Whether the command can be used in the private chat or the group.
+    pub fn available_in(&self, private: bool) -> bool {
+        match self.scope {
+            ChatScope::All => true,
+            ChatScope::Private => private,
+            ChatScope::Group => !private,
+        }
+    }
+
+    pub fn description_in(&self, lang: &str) -> String {
+        i18n::lookup(lang, &format!("command.{}.description", self.name))
+            .map_or_else(|| self.description.clone(), str::to_string)
+    }
+
+    pub fn usage_in(&self, lang: &str) -> Option<String> {
+        i18n::lookup(lang, &format!("command.{}.usage", self.name))
+            .map(str::to_string)
+            .or_else(|| self.usage.clone())
+    }
+}
+
+/// The central place for every command to register its name, description, usage and
+/// permission. The help message and the Telegram command list are generated from here.
+#[derive(Debug, Default)]
+pub struct CommandRegistry {
+    commands: Vec<CommandInfo>,
+}
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, info: CommandInfo) -> &mut Self {
+        if self.get(&info.name).is_some() {
+            panic!("command /{} is registered twice", info.name);
+        }
+        self.commands.push(info);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CommandInfo> {
+        let name = name.trim_start_matches('/').to_lowercase();
+        self.commands.iter().find(|cmd| cmd.name == name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &CommandInfo> {
+        self.commands.iter()
+    }
+
+    /// Generate the help message for the commands that regular user can use in current chat.
+    pub fn help(&self, private: bool, lang: &str) -> String {
+        let mut help = self
+            .commands
+            .iter()
+            .filter(|cmd| cmd.permission <= Permission::ChatAdmin && cmd.available_in(private))
+            .fold(crate::t!(lang, "help.header"), |mut acc, cmd| {
+                write!(acc, "\n/{} — {}", cmd.name, cmd.description_in(lang)).unwrap();
+                acc
+            });
+        write!(help, "\n\n{}", crate::t!(lang, "help.footer")).unwrap();
+        help
+    }
+
+    /// Generate the detail help message for the given command.
+    pub fn help_for(&self, name: &str, lang: &str) -> Option<String> {
+        let cmd = self.get(name)?;
+        let mut help = format!("/{} — {}", cmd.name, cmd.description_in(lang));
+        if let Some(usage) = cmd.usage_in(lang) {
+            write!(help, "\n{}", crate::t!(lang, "help.usage", usage = usage)).unwrap();
+        }
+        let permission = cmd.permission.describe(lang);
+        write!(
+            help,
+            "\n{}",
+            crate::t!(lang, "help.permission", permission = permission)
+        )
+        .unwrap();
+        match cmd.scope {
+            ChatScope::All => (),
+            ChatScope::Private => {
+                write!(help, "\n{}", crate::t!(lang, "help.private_only")).unwrap()
+            }
+            ChatScope::Group => write!(help, "\n{}", crate::t!(lang, "help.group_only")).unwrap(),
+        }
+        Some(help)
+    }
+
+    /// Get the command list in `lang` that should be pushed to Telegram for the given scope.
+    pub fn bot_commands(&self, scope: &BotCommandScope, lang: &str) -> Vec<BotCommand> {
+        self.commands
+            .iter()
+            .filter(|cmd| match scope {
+                BotCommandScope::AllPrivateChats => {
+                    cmd.permission == Permission::User && cmd.available_in(true)
+                }
+                BotCommandScope::AllGroupChats => {
+                    cmd.permission == Permission::User && cmd.available_in(false)
+                }
+                BotCommandScope::AllChatAdministrators => {
+                    cmd.permission <= Permission::ChatAdmin && cmd.available_in(false)
+                }
+                _ => cmd.permission == Permission::User && cmd.scope == ChatScope::All,
+            })
+            .map(|cmd| BotCommand::new(&cmd.name, cmd.description_in(lang)))
+            .collect()
+    }
+
+    /// Push the command list to Telegram, so that user can see them in the command menu. The
+    /// default language is pushed for every user, and the others for the users whose Telegram
+    /// language matches.
+    pub async fn sync_bot_commands(&self, bot: &teloxide::Bot) -> anyhow::Result<()> {
+        use teloxide::{payloads::SetMyCommandsSetters, requests::Requester};
+
+        for (lang, _) in i18n::LANGUAGES {
+            for scope in [
+                BotCommandScope::Default,
+                BotCommandScope::AllPrivateChats,
+                BotCommandScope::AllGroupChats,
+                BotCommandScope::AllChatAdministrators,
+            ] {
+                let commands = self.bot_commands(&scope, lang);
+                let mut request = bot.set_my_commands(commands).scope(scope);
+                if *lang != i18n::DEFAULT_LANG {
+                    // Telegram only accepts the two-letter ISO 639-1 code
+                    let code = lang.split('-').next().unwrap_or(lang);
+                    request = request.language_code(code);
+                }
+                request.await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn test_command_registry() {
+    let mut registry = CommandRegistry::new();
+    registry
+        .register(
+            CommandInfo::builder()
+                .name("weather")
+                .description("Search weather")
+                .usage("/weather 上海")
+                .build(),
+        )
+        .register(
+            CommandInfo::builder()
+                .name("collect")
+                .description("Collect messages")
+                .scope(ChatScope::Private)
+                .build(),
+        )
+        .register(
+            CommandInfo::builder()
+                .name("ban")
+                .description("Ban user")
+                .permission(Permission::ChatAdmin)
+                .build(),
+        );
+
+    assert!(registry
+        .help(false, "en")
+        .contains("/weather — Search weather"));
+    assert!(!registry.help(false, "en").contains("/collect"));
+    assert!(registry.help(true, "en").contains("/collect"));
+    assert_eq!(
+        registry.help_for("/Weather", "en").unwrap(),
+        "/weather — Search weather\nUsage: /weather 上海\nPermission: everyone"
+    );
+    assert_eq!(
+        registry.help_for("/ban", "zh-hans").unwrap(),
+        "/ban — Ban user\n权限：聊天管理员"
+    );
+    assert!(registry
+        .help_for("/weather", "zh-hans")
+        .unwrap()
+        .starts_with("/weather — 查询天气"));
+    assert!(registry.help_for("unknown", "en").is_none());
+
+    let names = |scope| {
+        registry
+            .bot_commands(&scope, "en")
+            .into_iter()
+            .map(|cmd| cmd.command)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(names(BotCommandScope::AllGroupChats), ["weather"]);
+    assert_eq!(
+        names(BotCommandScope::AllChatAdministrators),
+        ["weather", "ban"]
+    );
+    assert_eq!(
+        names(BotCommandScope::AllPrivateChats),
+        ["weather", "collect"]
+    );
+}
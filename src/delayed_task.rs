@@ -0,0 +1,179 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use redis::Commands;
+use serde::{Deserialize, Serialize};
+use teloxide::{
+    prelude::*,
+    types::{Message, MessageId},
+};
+
+use crate::{
+    app::AppData,
+    topic::{ChatTarget, SendTo},
+};
+
+/// Sorted set of the JSON encoded tasks, scored by the unix timestamp to run them
+const DELAYED_TASKS: &str = "DELAYED_TASKS";
+/// How often the worker looks for the due tasks
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Keep the notices like the rate limit and command errors for this long
+pub const NOTICE_TTL: Duration = Duration::from_secs(30);
+
+/// Something to do later. Tasks are kept in Redis, so they still run after the bot restarts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "task", rename_all = "snake_case")]
+pub enum DelayedTask {
+    DeleteMessage {
+        chat_id: i64,
+        message_id: i32,
+    },
+    /// Time is up for the question of the quiz game
+    QuizTimeout {
+        chat_id: i64,
+        question: usize,
+    },
+    /// The quiet hours of the chat are over, send the notifications held by the bot
+    QuietDigest {
+        bot: String,
+        target: String,
+    },
+    /// Time for the scheduled digest of the chat, send the notifications held by the bot
+    Digest {
+        bot: String,
+        target: String,
+    },
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs())
+}
+
+/// Run the task after `delay`.
+pub fn schedule(data: &AppData, task: &DelayedTask, delay: Duration) -> anyhow::Result<()> {
+    let due = now() + delay.as_secs();
+    let () = data
+        .cacher
+        .get_conn()
+        .zadd(DELAYED_TASKS, serde_json::to_string(task)?, due)?;
+    Ok(())
+}
+
+/// Tasks scheduled, and those overdue by more than `late` that the worker hasn't taken yet.
+pub fn depth(data: &AppData, late: Duration) -> anyhow::Result<(usize, usize)> {
+    let mut conn = data.cacher.get_conn();
+    let scheduled: usize = conn.zcard(DELAYED_TASKS)?;
+    let overdue: Vec<String> =
+        conn.zrangebyscore(DELAYED_TASKS, 0, now().saturating_sub(late.as_secs()))?;
+    Ok((scheduled, overdue.len()))
+}
+
+/// Take the tasks due at `now` out of the queue. A task is only taken by one worker.
+pub fn take_due(data: &AppData, now: u64) -> anyhow::Result<Vec<DelayedTask>> {
+    let mut conn = data.cacher.get_conn();
+    let due: Vec<String> = conn.zrangebyscore(DELAYED_TASKS, 0, now)?;
+    let mut tasks = Vec::new();
+    for encoded in due {
+        let taken: bool = conn.zrem(DELAYED_TASKS, &encoded)?;
+        if !taken {
+            continue;
+        }
+        match serde_json::from_str(&encoded) {
+            Ok(task) => tasks.push(task),
+            Err(err) => tracing::error!("[DelayedTask] drop invalid task {encoded}: {err}"),
+        }
+    }
+    Ok(tasks)
+}
+
+async fn run(bot: &Bot, data: &AppData, task: DelayedTask) -> anyhow::Result<()> {
+    match task {
+        DelayedTask::DeleteMessage {
+            chat_id,
+            message_id,
+        } => {
+            bot.delete_message(ChatId(chat_id), MessageId(message_id))
+                .await?;
+        }
+        DelayedTask::QuizTimeout { chat_id, question } => {
+            crate::modules::quiz::timeout(bot, data, ChatId(chat_id), question).await?;
+        }
+        DelayedTask::QuietDigest {
+            bot: notifier,
+            target,
+        } => {
+            crate::quiet_hours::send_digest(data, &notifier, &target).await?;
+        }
+        DelayedTask::Digest {
+            bot: notifier,
+            target,
+        } => {
+            crate::digest::send(data, &notifier, &target).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Start the worker running the due tasks with the bot.
+pub fn spawn_worker(bot: Bot, data: AppData) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            let tasks = match take_due(&data, now()) {
+                Ok(tasks) => tasks,
+                Err(err) => {
+                    tracing::error!("[DelayedTask] fail to take the due tasks: {err}");
+                    continue;
+                }
+            };
+            for task in tasks {
+                let description = format!("{task:?}");
+                if let Err(err) = run(&bot, &data, task).await {
+                    tracing::warn!("[DelayedTask] fail to run {description}: {err}");
+                }
+            }
+        }
+    });
+}
+
+/// Delete the message after `ttl`.
+pub fn delete_later(data: &AppData, msg: &Message, ttl: Duration) -> anyhow::Result<()> {
+    let task = DelayedTask::DeleteMessage {
+        chat_id: msg.chat.id.0,
+        message_id: msg.id.0,
+    };
+    schedule(data, &task, ttl)
+}
+
+/// Send the text which deletes itself after `ttl`, for the notices that would flood the group.
+pub async fn send_ephemeral(
+    bot: &Bot,
+    data: &AppData,
+    to: impl Into<ChatTarget>,
+    text: impl Into<String>,
+    ttl: Duration,
+) -> anyhow::Result<Message> {
+    let to = to.into();
+    let sent = data
+        .reply(to.chat_id, bot.send_message_to(to, text.into()))
+        .await?;
+    delete_later(data, &sent, ttl)?;
+    Ok(sent)
+}
+
+#[tokio::test]
+async fn test_delayed_task() {
+    let data = crate::testkit::app_data(Bot::new("1000:fake-token")).await;
+    let task = DelayedTask::DeleteMessage {
+        chat_id: -100,
+        message_id: 7,
+    };
+    schedule(&data, &task, Duration::from_secs(30)).unwrap();
+
+    assert!(take_due(&data, now()).unwrap().is_empty());
+    assert_eq!(take_due(&data, now() + 30).unwrap(), [task]);
+    assert!(take_due(&data, now() + 30).unwrap().is_empty());
+}
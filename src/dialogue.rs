@@ -0,0 +1,244 @@
+use std::{future::Future, pin::Pin, time::Duration};
+
+use redis::Commands;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use teloxide::prelude::{Bot, Message};
+
+use crate::{app::AppData, topic::SendTo};
+
+/// The running step of a dialogue, stored as JSON in Redis for each chat and user.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DialogueState {
+    pub module: String,
+    pub step: String,
+    state: serde_json::Value,
+}
+
+impl DialogueState {
+    pub fn new<T: Serialize>(module: &str, step: &str, state: &T) -> anyhow::Result<Self> {
+        Ok(Self {
+            module: module.to_string(),
+            step: step.to_string(),
+            state: serde_json::to_value(state)?,
+        })
+    }
+
+    /// Deserialize the typed state saved by the previous step.
+    pub fn state<T: DeserializeOwned>(&self) -> anyhow::Result<T> {
+        serde_json::from_value(self.state.clone())
+            .map_err(|err| anyhow::anyhow!("invalid dialogue state: {err}"))
+    }
+}
+
+/// Everything a dialogue handler needs to know about the incoming message.
+pub struct DialogueContext {
+    pub bot: Bot,
+    pub data: AppData,
+    pub msg: Message,
+    pub current: DialogueState,
+}
+
+/// What the dialogue should do after the message is handled.
+pub enum Transition {
+    /// Move to the given step with the new state
+    Next(String, serde_json::Value),
+    /// Keep the current step and state, but refresh the timeout
+    Stay,
+    /// The dialogue is finished
+    Exit,
+}
+
+impl Transition {
+    pub fn next<T: Serialize>(step: &str, state: &T) -> anyhow::Result<Self> {
+        Ok(Self::Next(step.to_string(), serde_json::to_value(state)?))
+    }
+}
+
+type DialogueFuture = Pin<Box<dyn Future<Output = anyhow::Result<Transition>> + Send>>;
+type DialogueHandler = Box<dyn Fn(DialogueContext) -> DialogueFuture + Send + Sync>;
+
+struct DialogueRoute {
+    module: &'static str,
+    /// The [`crate::module::BotModule`] adding the route
+    owner: Option<&'static str>,
+    timeout: Duration,
+    handler: DialogueHandler,
+}
+
+/// Route the message to the module when the user is in the middle of a multi-step flow. The state
+/// expires after the module's timeout without any new message, and can be dropped by `/cancel`.
+#[derive(Default)]
+pub struct DialogueRouter {
+    routes: Vec<DialogueRoute>,
+    owner: Option<&'static str>,
+}
+
+fn dialogue_key(chat_id: i64, user_id: u64) -> String {
+    format!("DIALOGUE:{chat_id}:{user_id}")
+}
+
+impl DialogueRouter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the module adding the routes next, its dialogues are dropped when it is disabled in
+    /// the config.
+    pub fn owned_by(mut self, module: &'static str) -> Self {
+        self.owner = Some(module);
+        self
+    }
+
+    pub fn route<F, Fut>(mut self, module: &'static str, timeout: Duration, handler: F) -> Self
+    where
+        F: Fn(DialogueContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = anyhow::Result<Transition>> + Send + 'static,
+    {
+        self.routes.push(DialogueRoute {
+            module,
+            owner: self.owner,
+            timeout,
+            handler: Box::new(move |ctx| Box::pin(handler(ctx))),
+        });
+        self
+    }
+
+    fn get_route(&self, module: &str) -> anyhow::Result<&DialogueRoute> {
+        self.routes
+            .iter()
+            .find(|route| route.module == module)
+            .ok_or_else(|| anyhow::anyhow!("dialogue {module} is not registered"))
+    }
+
+    fn save(
+        &self,
+        data: &AppData,
+        chat_id: i64,
+        user_id: u64,
+        state: &DialogueState,
+    ) -> anyhow::Result<()> {
+        let route = self.get_route(&state.module)?;
+        let () = data.cacher.get_conn().set_ex(
+            dialogue_key(chat_id, user_id),
+            serde_json::to_string(state)?,
+            route.timeout.as_secs(),
+        )?;
+        Ok(())
+    }
+
+    /// Start the dialogue of `module` for the user in the chat, any running dialogue is replaced.
+    pub fn start<T: Serialize>(
+        &self,
+        data: &AppData,
+        chat_id: i64,
+        user_id: u64,
+        module: &str,
+        step: &str,
+        state: &T,
+    ) -> anyhow::Result<()> {
+        self.save(
+            data,
+            chat_id,
+            user_id,
+            &DialogueState::new(module, step, state)?,
+        )
+    }
+
+    /// Get the running dialogue of the user in the chat. The dialogue of a module disabled in the
+    /// config, or no longer registered, is dropped so that the message is handled as usual.
+    pub fn current(
+        &self,
+        data: &AppData,
+        chat_id: i64,
+        user_id: u64,
+    ) -> anyhow::Result<Option<DialogueState>> {
+        let raw: Option<String> = data.cacher.get_conn().get(dialogue_key(chat_id, user_id))?;
+        let Some(raw) = raw else {
+            return Ok(None);
+        };
+        let current: DialogueState = serde_json::from_str(&raw)?;
+        let enabled = self.routes.iter().any(|route| {
+            route.module == current.module
+                && !route.owner.is_some_and(crate::settings::globally_disabled)
+        });
+        if !enabled {
+            self.cancel(data, chat_id, user_id)?;
+            return Ok(None);
+        }
+        Ok(Some(current))
+    }
+
+    /// Drop the running dialogue. Return false if there is no dialogue running.
+    pub fn cancel(&self, data: &AppData, chat_id: i64, user_id: u64) -> anyhow::Result<bool> {
+        let deleted: u32 = data.cacher.get_conn().del(dialogue_key(chat_id, user_id))?;
+        Ok(deleted > 0)
+    }
+
+    /// Pass the message to the module of the running dialogue, and save the state it returns. The
+    /// dialogue is dropped when the handler fails, the error is logged and the user is told.
+    pub async fn dispatch(
+        &self,
+        bot: Bot,
+        data: AppData,
+        msg: Message,
+        current: DialogueState,
+    ) -> anyhow::Result<()> {
+        let chat_id = msg.chat.id;
+        let Some(user) = msg.from.as_ref() else {
+            return Ok(());
+        };
+        let (user_id, user_lang) = (user.id.0, user.language_code.clone());
+
+        let route = self.get_route(&current.module)?;
+        let ctx = DialogueContext {
+            bot: bot.clone(),
+            data: data.clone(),
+            msg: msg.clone(),
+            current: current.clone(),
+        };
+        match (route.handler)(ctx).await {
+            Ok(Transition::Next(step, state)) => {
+                let next = DialogueState {
+                    module: current.module,
+                    step,
+                    state,
+                };
+                self.save(&data, chat_id.0, user_id, &next)?;
+            }
+            Ok(Transition::Stay) => self.save(&data, chat_id.0, user_id, &current)?,
+            Ok(Transition::Exit) => {
+                self.cancel(&data, chat_id.0, user_id)?;
+            }
+            Err(err) => {
+                tracing::error!(
+                    "[{}] dialogue failed in chat {chat_id}: {err:#}",
+                    current.module
+                );
+                self.cancel(&data, chat_id.0, user_id)?;
+                let lang = crate::i18n::chat_language(&data, chat_id.0, user_lang.as_deref());
+                data.reply(
+                    chat_id,
+                    bot.send_message_to(&msg, crate::t!(lang, "dialogue.failed")),
+                )
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn test_dialogue_state() {
+    let current = DialogueState::new("rss", "wait_url", &(114514_i64, "feed")).unwrap();
+    let raw = serde_json::to_string(&current).unwrap();
+    let restored: DialogueState = serde_json::from_str(&raw).unwrap();
+    assert_eq!(restored, current);
+    assert_eq!(restored.step, "wait_url");
+
+    let (id, name): (i64, String) = restored.state().unwrap();
+    assert_eq!((id, name.as_str()), (114514, "feed"));
+    assert!(restored.state::<u64>().is_err());
+
+    assert_eq!(dialogue_key(-1001, 42), "DIALOGUE:-1001:42");
+}
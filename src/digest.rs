@@ -0,0 +1,199 @@
+//! Combine the notifications of a chat into one message at the scheduled time. The chats opting
+//! in by `/digest` get the watcher notifications marked as digestable, like the package releases,
+//! every hour or once a day in one message instead of one message each.
+
+use std::{fmt::Display, str::FromStr, time::Duration};
+
+use chrono::{Local, NaiveTime, Timelike};
+use redis::Commands;
+use teloxide::{prelude::*, types::ParseMode};
+
+use crate::{
+    app::AppData,
+    config::MAIN_BOT,
+    delayed_task::{self, DelayedTask},
+    quiet_hours::{self, Notification},
+    send_queue::Priority,
+    topic::{ChatTarget, SendTo},
+};
+
+/// Hash of the chat id and its digest schedule like `hourly` or `09:00`
+const DIGEST_SCHEDULE_KEY: &str = "DIGEST_SCHEDULE";
+const DAY_SECS: i64 = 24 * 60 * 60;
+
+/// When the digest of the chat is sent, in the local time of the bot host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DigestSchedule {
+    /// At the start of every hour
+    Hourly,
+    Daily(NaiveTime),
+}
+
+impl DigestSchedule {
+    /// Time left before the next digest.
+    pub fn remaining(&self, time: NaiveTime) -> Duration {
+        let left = match self {
+            Self::Hourly => 60 * 60 - i64::from(time.minute() * 60 + time.second()),
+            Self::Daily(at) => match (*at - time).num_seconds().rem_euclid(DAY_SECS) {
+                0 => DAY_SECS,
+                left => left,
+            },
+        };
+        Duration::from_secs(left as u64)
+    }
+}
+
+impl FromStr for DigestSchedule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("hourly") {
+            return Ok(Self::Hourly);
+        }
+        NaiveTime::parse_from_str(s.trim(), "%H:%M")
+            .map(Self::Daily)
+            .map_err(|_| anyhow::anyhow!("a digest should be hourly or at a time like 09:00"))
+    }
+}
+
+impl Display for DigestSchedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Hourly => write!(f, "hourly"),
+            Self::Daily(at) => write!(f, "{}", at.format("%H:%M")),
+        }
+    }
+}
+
+pub fn get(data: &AppData, chat_id: i64) -> anyhow::Result<Option<DigestSchedule>> {
+    let stored: Option<String> = data.cacher.get_conn().hget(DIGEST_SCHEDULE_KEY, chat_id)?;
+    Ok(stored.and_then(|stored| stored.parse().ok()))
+}
+
+/// Set the digest schedule of the chat, or turn it off with `None`. The notifications already
+/// held are still sent at the time scheduled before.
+pub fn set(data: &AppData, chat_id: i64, schedule: Option<DigestSchedule>) -> anyhow::Result<()> {
+    let mut conn = data.cacher.get_conn();
+    let () = match schedule {
+        Some(schedule) => conn.hset(DIGEST_SCHEDULE_KEY, chat_id, schedule.to_string())?,
+        None => conn.hdel(DIGEST_SCHEDULE_KEY, chat_id)?,
+    };
+    Ok(())
+}
+
+pub(crate) fn held_key(bot: &str, target: &str) -> String {
+    format!("DIGEST_HELD:{bot}:{target}")
+}
+
+/// Hold the digestable notification if the chat gets a digest, return false if it doesn't. The
+/// first held notification schedules the next digest.
+pub fn hold(
+    bot: &Bot,
+    data: &AppData,
+    target: ChatTarget,
+    notification: &Notification,
+) -> anyhow::Result<bool> {
+    if !notification.digestable {
+        return Ok(false);
+    }
+    let Some(schedule) = get(data, target.chat_id.0)? else {
+        return Ok(false);
+    };
+
+    let bot = data.bots.name_of(bot).unwrap_or(MAIN_BOT).to_string();
+    let target = target.to_string();
+    let held: usize = data.cacher.get_conn().rpush(
+        held_key(&bot, &target),
+        serde_json::to_string(notification)?,
+    )?;
+    if held == 1 {
+        let now = Local::now().time().with_nanosecond(0).unwrap_or_default();
+        let task = DelayedTask::Digest { bot, target };
+        delayed_task::schedule(data, &task, schedule.remaining(now))?;
+    }
+    Ok(true)
+}
+
+/// Send the notifications held for the chat target by the bot as the digest. If the chat is in
+/// its quiet hours by then, they are held again for the digest after the quiet hours.
+pub async fn send(data: &AppData, bot: &str, target: &str) -> anyhow::Result<()> {
+    let notifications = quiet_hours::take_held(data, &held_key(bot, target))?;
+    if notifications.is_empty() {
+        return Ok(());
+    }
+
+    let target = target.parse::<ChatTarget>()?;
+    let bot = data.bots.pick(Some(bot)).clone();
+    if quiet_hours::hold_all(&bot, data, target, &notifications)? {
+        return Ok(());
+    }
+    let lang = crate::i18n::chat_language(data, target.chat_id.0, None);
+    let header = crate::t!(lang, "digest.header", count = notifications.len());
+    for text in quiet_hours::digest(&header, &notifications) {
+        let bot = bot.clone();
+        data.submit(target.chat_id, Priority::Background, move || {
+            bot.send_message_to(target, &text)
+                .parse_mode(ParseMode::Html)
+        })
+        .await?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_digest_schedule() {
+    let time = |s| NaiveTime::parse_from_str(s, "%H:%M:%S").unwrap();
+    assert_eq!(
+        "hourly".parse::<DigestSchedule>().unwrap(),
+        DigestSchedule::Hourly
+    );
+    let morning: DigestSchedule = "09:00".parse().unwrap();
+    assert_eq!(morning.to_string(), "09:00");
+    assert!("9am".parse::<DigestSchedule>().is_err());
+
+    assert_eq!(
+        DigestSchedule::Hourly.remaining(time("10:59:30")),
+        Duration::from_secs(30)
+    );
+    assert_eq!(
+        DigestSchedule::Hourly.remaining(time("10:00:00")),
+        Duration::from_secs(3600)
+    );
+    assert_eq!(
+        morning.remaining(time("08:30:00")),
+        Duration::from_secs(1800)
+    );
+    assert_eq!(
+        morning.remaining(time("09:00:00")),
+        Duration::from_secs(24 * 3600)
+    );
+    assert_eq!(
+        morning.remaining(time("10:00:00")),
+        Duration::from_secs(23 * 3600)
+    );
+}
+
+#[tokio::test]
+async fn test_hold_for_digest() {
+    let bot = Bot::new("1000:fake-token");
+    let data = crate::testkit::app_data(bot.clone()).await;
+    let target = ChatTarget::from(ChatId(-100123));
+    let release = Notification::text("v1.0.0 is released").digestable();
+
+    assert!(!hold(&bot, &data, target, &release).unwrap());
+    set(&data, -100123, Some(DigestSchedule::Hourly)).unwrap();
+    assert!(!hold(&bot, &data, target, &Notification::text("live")).unwrap());
+    assert!(hold(&bot, &data, target, &release).unwrap());
+    assert!(hold(&bot, &data, target, &release).unwrap());
+
+    let key = held_key(MAIN_BOT, &target.to_string());
+    let held: usize = data.cacher.get_conn().llen(&key).unwrap();
+    assert_eq!(held, 2);
+    let scheduled: Vec<String> = data
+        .cacher
+        .get_conn()
+        .zrange("DELAYED_TASKS", 0, -1)
+        .unwrap();
+    assert_eq!(scheduled.len(), 1);
+    assert!(scheduled[0].contains("\"task\":\"digest\""));
+}
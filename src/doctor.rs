@@ -0,0 +1,359 @@
+//! Live diagnostics for `/doctor`, so that the operators can triage an incident from inside
+//! Telegram. Each dependency is checked once with a timeout, and the result is one report.
+
+use std::{
+    fmt::Display,
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use teloxide::prelude::*;
+
+use crate::{app::AppData, config::Config, delayed_task, event, job_queue};
+
+/// Give up the check if the dependency doesn't respond in time
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// A dependency responding slower than this is reported as degraded
+const SLOW_RESPONSE: Duration = Duration::from_secs(1);
+/// A queue longer than this is reported as backed up
+const QUEUE_BACKLOG: usize = 100;
+/// The delayed task worker polls every second, a task later than this means it is stuck
+const TASK_OVERDUE: Duration = Duration::from_secs(60);
+/// The upstream APIs and the module using them, skipped when the module is disabled
+const UPSTREAMS: &[(&str, &str)] = &[
+    ("weather", "https://wttr.in"),
+    ("bilibili", "https://api.live.bilibili.com"),
+    ("holiday", "https://date.nager.at"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Status {
+    Ok,
+    Degraded,
+    Failed,
+}
+
+impl Status {
+    fn icon(self) -> &'static str {
+        match self {
+            Self::Ok => "✅",
+            Self::Degraded => "⚠️",
+            Self::Failed => "❌",
+        }
+    }
+}
+
+/// Result of checking one dependency.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub name: String,
+    pub status: Status,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, status: Status, detail: impl Display) -> Self {
+        Self {
+            name: name.into(),
+            status,
+            detail: detail.to_string(),
+        }
+    }
+
+    /// Report the latency of the probe, or why it fails.
+    fn timed(
+        name: impl Into<String>,
+        (elapsed, result): (Duration, anyhow::Result<String>),
+    ) -> Self {
+        match result {
+            Ok(detail) => {
+                let status = if elapsed > SLOW_RESPONSE {
+                    Status::Degraded
+                } else {
+                    Status::Ok
+                };
+                let latency = format!("{} ms", elapsed.as_millis());
+                let detail = if detail.is_empty() {
+                    latency
+                } else {
+                    format!("{detail}, {latency}")
+                };
+                Self::new(name, status, detail)
+            }
+            Err(err) => Self::new(name, Status::Failed, err),
+        }
+    }
+}
+
+/// Run all the checks. The probes run at the same time, so the report takes no longer than the
+/// slowest one.
+pub async fn diagnose(bot: &Bot, data: &AppData, config: &Config) -> Vec<Check> {
+    let (redis, database, telegram, updates, upstreams) = tokio::join!(
+        redis(data),
+        database(data),
+        telegram(bot),
+        updates(bot, config),
+        upstreams(data, config),
+    );
+    let mut checks = vec![redis, database, telegram, updates];
+    checks.extend(upstreams);
+    checks.extend(queues(data));
+    checks.extend(watchers(data));
+    checks
+}
+
+/// The worst status of the checks.
+pub fn overall(checks: &[Check]) -> Status {
+    checks
+        .iter()
+        .map(|check| check.status)
+        .max()
+        .unwrap_or(Status::Ok)
+}
+
+/// One line for each check.
+pub fn render(checks: &[Check]) -> String {
+    checks
+        .iter()
+        .map(|check| format!("{} {}: {}", check.status.icon(), check.name, check.detail))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+async fn measure<T, F>(probe: F) -> (Duration, anyhow::Result<T>)
+where
+    F: Future<Output = anyhow::Result<T>>,
+{
+    let start = Instant::now();
+    let result = match tokio::time::timeout(PROBE_TIMEOUT, probe).await {
+        Ok(result) => result,
+        Err(_) => Err(anyhow::anyhow!("timeout")),
+    };
+    (start.elapsed(), result)
+}
+
+async fn redis(data: &AppData) -> Check {
+    let data = data.clone();
+    let probe = measure(async move {
+        // The redis client is blocking
+        tokio::task::spawn_blocking(move || data.cacher.ping(PROBE_TIMEOUT)).await??;
+        Ok(String::new())
+    });
+    Check::timed("redis", probe.await)
+}
+
+async fn database(data: &AppData) -> Check {
+    let probe = measure(async {
+        data.storage.ping().await?;
+        Ok(String::new())
+    });
+    Check::timed("database", probe.await)
+}
+
+async fn telegram(bot: &Bot) -> Check {
+    let probe = measure(async {
+        let me = bot.get_me().await?;
+        Ok(format!("@{}", me.username()))
+    });
+    Check::timed("telegram", probe.await)
+}
+
+/// Whether Telegram delivers the updates the way the bot expects them.
+async fn updates(bot: &Bot, config: &Config) -> Check {
+    let info = match tokio::time::timeout(PROBE_TIMEOUT, bot.get_webhook_info()).await {
+        Ok(Ok(info)) => info,
+        Ok(Err(err)) => return Check::new("updates", Status::Failed, err),
+        Err(_) => return Check::new("updates", Status::Failed, "timeout"),
+    };
+    let pending = info.pending_update_count as usize;
+    let webhook = config.webhook.is_some();
+    let mode = if webhook { "webhook" } else { "polling" };
+    let detail = format!("{mode}, {pending} pending");
+
+    let (status, detail) = match (webhook, &info.url, &info.last_error_message) {
+        (true, None, _) => (Status::Failed, "the webhook is not set".to_string()),
+        (false, Some(url), _) => (
+            Status::Failed,
+            format!("polling while the webhook {url} is set"),
+        ),
+        (true, _, Some(error)) => (Status::Degraded, format!("{detail}, last error: {error}")),
+        _ if pending > QUEUE_BACKLOG => (Status::Degraded, detail),
+        _ => (Status::Ok, detail),
+    };
+    Check::new("updates", status, detail)
+}
+
+/// Any response tells the upstream is reachable, only the server errors are degraded.
+async fn upstreams(data: &AppData, config: &Config) -> Vec<Check> {
+    let enabled = |module: &str| !config.disabled_modules.iter().any(|name| name == module);
+    let mut targets: Vec<(String, String)> = UPSTREAMS
+        .iter()
+        .filter(|(module, _)| enabled(module))
+        .map(|(module, url)| (module.to_string(), url.to_string()))
+        .collect();
+    if enabled("tr") && !config.deepl.api_key.is_empty() {
+        // The keys of the free plan end with `:fx`, and use another host
+        let url = if config.deepl.api_key.ends_with(":fx") {
+            "https://api-free.deepl.com"
+        } else {
+            "https://api.deepl.com"
+        };
+        targets.push(("deepl".to_string(), url.to_string()));
+    }
+
+    let mut tasks = tokio::task::JoinSet::new();
+    for (name, url) in targets {
+        let data = data.clone();
+        tasks.spawn(async move {
+            let probe = measure(async {
+                let resp = data
+                    .requester
+                    .send(data.requester.get(&url).timeout(PROBE_TIMEOUT))
+                    .await
+                    .map_err(|err| err.without_url())?;
+                Ok(resp.status())
+            });
+            let (elapsed, result) = probe.await;
+            match result {
+                Ok(status) if status.is_server_error() => {
+                    Check::new(name, Status::Degraded, format!("HTTP {status}"))
+                }
+                result => Check::timed(
+                    name,
+                    (elapsed, result.map(|status| format!("HTTP {status}"))),
+                ),
+            }
+        });
+    }
+    let mut checks = tasks.join_all().await;
+    checks.sort_by(|a, b| a.name.cmp(&b.name));
+    checks
+}
+
+fn queues(data: &AppData) -> Vec<Check> {
+    let backlog = |name: &str, queued: usize, detail: String| {
+        let status = if queued > QUEUE_BACKLOG {
+            Status::Degraded
+        } else {
+            Status::Ok
+        };
+        Check::new(name, status, detail)
+    };
+
+    let pending = data.send_queue.pending();
+    let mut checks = vec![backlog("send queue", pending, format!("{pending} pending"))];
+    checks.push(match job_queue::depth(data) {
+        Ok((queued, 0)) => backlog("job queue", queued, format!("{queued} queued")),
+        Ok((queued, dead)) => Check::new(
+            "job queue",
+            Status::Degraded,
+            format!("{queued} queued, {dead} dead letters"),
+        ),
+        Err(err) => Check::new("job queue", Status::Failed, err),
+    });
+    checks.push(match delayed_task::depth(data, TASK_OVERDUE) {
+        Ok((scheduled, 0)) => Check::new(
+            "delayed tasks",
+            Status::Ok,
+            format!("{scheduled} scheduled"),
+        ),
+        Ok((scheduled, overdue)) => Check::new(
+            "delayed tasks",
+            Status::Degraded,
+            format!("{scheduled} scheduled, {overdue} overdue"),
+        ),
+        Err(err) => Check::new("delayed tasks", Status::Failed, err),
+    });
+    checks
+}
+
+/// The watchers paused after failing too many times, and those failing but still running.
+fn watchers(data: &AppData) -> Vec<Check> {
+    let started = event::started_watchers();
+    let (paused, failing) = match (event::paused_watchers(data), event::failing_watchers(data)) {
+        (Ok(paused), Ok(failing)) => (paused, failing),
+        (Err(err), _) | (_, Err(err)) => {
+            return vec![Check::new("watchers", Status::Failed, err)];
+        }
+    };
+
+    let status = if !paused.is_empty() {
+        Status::Failed
+    } else if !failing.is_empty() {
+        Status::Degraded
+    } else {
+        Status::Ok
+    };
+    let mut checks = vec![Check::new(
+        "watchers",
+        status,
+        format!(
+            "{} started, {} paused, {} failing",
+            started.len(),
+            paused.len(),
+            failing.len()
+        ),
+    )];
+    checks.extend(
+        paused
+            .into_iter()
+            .map(|(name, error)| Check::new(name, Status::Failed, format!("paused: {error}"))),
+    );
+    checks.extend(failing.into_iter().map(|(name, failures)| {
+        Check::new(
+            name,
+            Status::Degraded,
+            format!("failed {failures} times in a row"),
+        )
+    }));
+    checks
+}
+
+#[test]
+fn test_render() {
+    let checks = vec![
+        Check::timed("redis", (Duration::from_millis(3), Ok(String::new()))),
+        Check::timed(
+            "telegram",
+            (Duration::from_millis(1500), Ok("@maid_bot".to_string())),
+        ),
+        Check::timed(
+            "weather",
+            (Duration::from_secs(5), Err(anyhow::anyhow!("timeout"))),
+        ),
+    ];
+    assert_eq!(
+        render(&checks),
+        "✅ redis: 3 ms\n⚠️ telegram: @maid_bot, 1500 ms\n❌ weather: timeout"
+    );
+    assert_eq!(overall(&checks), Status::Failed);
+    assert_eq!(overall(&checks[..2]), Status::Degraded);
+    assert_eq!(overall(&[]), Status::Ok);
+}
+
+#[tokio::test]
+async fn test_watchers() {
+    use redis::Commands;
+
+    let data = crate::testkit::app_data(Bot::new("1000:fake-token")).await;
+    assert_eq!(watchers(&data)[0].status, Status::Ok);
+
+    let mut conn = data.cacher.get_conn();
+    let () = conn
+        .hset(crate::cache::RedisKey::WatcherFailures, "CertWatcher", 2)
+        .unwrap();
+    let checks = watchers(&data);
+    assert_eq!(checks[0].status, Status::Degraded);
+    assert_eq!(checks[1].detail, "failed 2 times in a row");
+
+    let () = conn
+        .hset(
+            crate::cache::RedisKey::PausedWatchers,
+            "PackageWatcher",
+            "HTTP 503",
+        )
+        .unwrap();
+    let checks = watchers(&data);
+    assert_eq!(checks[0].status, Status::Failed);
+    assert_eq!(checks[1].detail, "paused: HTTP 503");
+}
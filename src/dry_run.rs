@@ -0,0 +1,234 @@
+use std::sync::{
+    atomic::{AtomicI64, Ordering},
+    Arc,
+};
+
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{header, HeaderMap, Method, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    Json, Router,
+};
+use serde_json::{json, Value};
+use teloxide::Bot;
+
+/// Method prefixes that change what the users see in the chat
+const MUTATING_PREFIXES: &[&str] = &[
+    "send",
+    "edit",
+    "delete",
+    "forward",
+    "copy",
+    "pin",
+    "unpin",
+    "ban",
+    "unban",
+    "restrict",
+    "promote",
+    "stopPoll",
+    "setMessageReaction",
+];
+/// Bot level setup that has to be live for receiving updates
+const PASSTHROUGH: &[&str] = &["deleteWebhook", "deleteMyCommands"];
+
+/// Whether the Bot API method is skipped in the dry-run mode.
+pub fn is_mutating(method: &str) -> bool {
+    !PASSTHROUGH.contains(&method)
+        && MUTATING_PREFIXES
+            .iter()
+            .any(|prefix| method.starts_with(prefix))
+}
+
+struct Proxy {
+    client: reqwest::Client,
+    upstream: reqwest::Url,
+    next_message_id: AtomicI64,
+}
+
+/// A local Bot API proxy for the dry-run mode. The calls that send, edit or delete messages are
+/// logged and answered with a fake result, and everything else like `getUpdates` and file
+/// downloads is forwarded to Telegram, so the watchers still run against the live data.
+pub struct DryRunProxy {
+    url: reqwest::Url,
+}
+
+impl DryRunProxy {
+    /// Spawn the proxy forwarding to the Bot API server at `upstream` by the given client, which
+    /// carries the proxy and timeout settings.
+    pub async fn start(client: reqwest::Client, upstream: &str) -> anyhow::Result<Self> {
+        Self::with_upstream(client, reqwest::Url::parse(upstream)?).await
+    }
+
+    pub async fn with_upstream(
+        client: reqwest::Client,
+        upstream: reqwest::Url,
+    ) -> anyhow::Result<Self> {
+        let proxy = Proxy {
+            client,
+            upstream,
+            next_message_id: AtomicI64::new(0),
+        };
+        let app = Router::new().fallback(handle).with_state(Arc::new(proxy));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let url = reqwest::Url::parse(&format!("http://{}", listener.local_addr()?))?;
+        tokio::spawn(async move {
+            if let Err(err) = axum::serve(listener, app).await {
+                tracing::error!("dry-run proxy stopped: {err}");
+            }
+        });
+
+        tracing::warn!("dry-run mode, messages are logged instead of sent");
+        Ok(Self { url })
+    }
+
+    /// A bot whose calls go through this proxy.
+    pub fn bot(&self, token: &str) -> Bot {
+        Bot::new(token).set_api_url(self.url.clone())
+    }
+}
+
+async fn handle(
+    State(proxy): State<Arc<Proxy>>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    // The path looks like `/bot<token>/sendMessage`, or `/file/bot<token>/<path>` for downloads,
+    // with `/test` after the token in the test environment. teloxide calls the methods like
+    // `SendMessage`, Telegram doesn't care about the case.
+    let mut segments = uri.path().trim_start_matches('/').splitn(2, '/');
+    let api_method = match (segments.next(), segments.next()) {
+        (Some(bot), Some(api_method)) if bot.starts_with("bot") => {
+            let api_method = api_method.strip_prefix("test/").unwrap_or(api_method);
+            let mut chars = api_method.chars();
+            let first = chars.next().map(|first| first.to_ascii_lowercase());
+            Some(first.into_iter().chain(chars).collect::<String>())
+        }
+        _ => None,
+    };
+    if let Some(api_method) = api_method.filter(|api_method| is_mutating(api_method)) {
+        let api_method = api_method.as_str();
+        let params = serde_json::from_slice(&body).unwrap_or(Value::Null);
+        if params.is_null() {
+            tracing::info!("dry run: skip {api_method} with uploaded files");
+        } else {
+            tracing::info!("dry run: skip {api_method} {params}");
+        }
+        let result = fake_result(&proxy, api_method, &params);
+        return Json(json!({ "ok": true, "result": result })).into_response();
+    }
+
+    match forward(&proxy, method, &uri, &headers, body).await {
+        Ok(response) => response,
+        Err(err) => {
+            tracing::error!("dry-run proxy fail to reach telegram: {err}");
+            let description = format!("dry-run proxy: {err}");
+            let body = json!({ "ok": false, "error_code": 502, "description": description });
+            (StatusCode::BAD_GATEWAY, Json(body)).into_response()
+        }
+    }
+}
+
+async fn forward(
+    proxy: &Proxy,
+    method: Method,
+    uri: &Uri,
+    headers: &HeaderMap,
+    body: Bytes,
+) -> anyhow::Result<Response> {
+    let path = uri.path_and_query().map_or("/", |path| path.as_str());
+    let mut request = proxy
+        .client
+        .request(method, proxy.upstream.join(path)?)
+        .body(body);
+    if let Some(content_type) = headers.get(header::CONTENT_TYPE) {
+        request = request.header(header::CONTENT_TYPE, content_type);
+    }
+    let response = request.send().await?;
+
+    let status = response.status();
+    let content_type = response.headers().get(header::CONTENT_TYPE).cloned();
+    let body = response.bytes().await?;
+    let mut response = (status, body).into_response();
+    if let Some(content_type) = content_type {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_TYPE, content_type);
+    }
+    Ok(response)
+}
+
+/// Something that the bot can parse as the result of the method.
+fn fake_result(proxy: &Proxy, method: &str, params: &Value) -> Value {
+    let message = || {
+        let message_id = proxy.next_message_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let chat_id = params.get("chat_id").and_then(Value::as_i64).unwrap_or(0);
+        let chat = if chat_id > 0 {
+            json!({ "id": chat_id, "type": "private", "first_name": "User" })
+        } else {
+            json!({ "id": chat_id, "type": "supergroup", "title": "Group" })
+        };
+        let text = params
+            .get("text")
+            .or_else(|| params.get("caption"))
+            .cloned()
+            .unwrap_or_else(|| json!(""));
+        json!({
+            "message_id": message_id,
+            "date": chrono::Utc::now().timestamp(),
+            "chat": chat,
+            "text": text,
+        })
+    };
+
+    match method {
+        "sendChatAction" => json!(true),
+        "sendMediaGroup" => json!([message()]),
+        "copyMessage" => json!({ "message_id": message()["message_id"] }),
+        "forwardMessages" | "copyMessages" => json!([]),
+        _ if method.starts_with("send")
+            || method.starts_with("editMessage")
+            || method == "forwardMessage"
+            || method == "stopPoll" =>
+        {
+            message()
+        }
+        _ => json!(true),
+    }
+}
+
+#[test]
+fn test_is_mutating() {
+    assert!(is_mutating("sendMessage"));
+    assert!(is_mutating("editMessageText"));
+    assert!(is_mutating("deleteMessage"));
+    assert!(is_mutating("pinChatMessage"));
+    assert!(!is_mutating("deleteWebhook"));
+    assert!(!is_mutating("getUpdates"));
+    assert!(!is_mutating("getChatAdministrators"));
+    assert!(!is_mutating("setMyCommands"));
+}
+
+#[tokio::test]
+async fn test_dry_run_proxy() {
+    use crate::testkit::FakeTelegram;
+    use teloxide::prelude::*;
+
+    let telegram = FakeTelegram::start();
+    let proxy = DryRunProxy::with_upstream(reqwest::Client::new(), telegram.url().clone())
+        .await
+        .unwrap();
+    let bot = proxy.bot("1000:fake-token");
+
+    bot.get_me().await.unwrap();
+    assert_eq!(telegram.calls_to("getMe").len(), 1);
+
+    let sent = bot.send_message(ChatId(-100), "hello").await.unwrap();
+    assert_eq!(sent.chat.id, ChatId(-100));
+    assert_eq!(sent.text(), Some("hello"));
+    bot.delete_message(ChatId(-100), sent.id).await.unwrap();
+    assert!(telegram.calls_to("sendMessage").is_empty());
+    assert!(telegram.calls_to("deleteMessage").is_empty());
+}
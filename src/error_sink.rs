@@ -0,0 +1,237 @@
+use std::{
+    backtrace::{Backtrace, BacktraceStatus},
+    collections::{HashMap, VecDeque},
+    fmt::Write,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use teloxide::{prelude::Requester, types::ChatId, Bot};
+use tokio::sync::mpsc;
+
+use crate::{app::AppData, config::ErrorReportConfig, send_queue::Priority};
+
+/// Telegram rejects message longer than 4096 characters
+const MAX_REPORT_LEN: usize = 3800;
+/// Frames of our own code to keep in the backtrace
+const BACKTRACE_FRAMES: usize = 8;
+
+static SINK: OnceLock<mpsc::UnboundedSender<Report>> = OnceLock::new();
+
+struct Report {
+    source: String,
+    update_id: Option<u32>,
+    message: String,
+    backtrace: Option<String>,
+}
+
+/// Start forwarding the panics and errors to the admin chat. Reports are dropped silently
+/// before this is called.
+pub fn init(data: AppData, config: &ErrorReportConfig) {
+    let bot = data.bots.pick(config.bot.as_deref()).clone();
+    let (tx, rx) = mpsc::unbounded_channel();
+    if SINK.set(tx).is_err() {
+        tracing::warn!("error sink is already initialized");
+        return;
+    }
+    tokio::spawn(
+        Worker {
+            bot,
+            data,
+            chat_id: ChatId(config.chat_id),
+            dedup_window: Duration::from_secs(config.dedup_window),
+            max_per_minute: config.max_per_minute,
+            last_seen: HashMap::new(),
+            recent: VecDeque::new(),
+            suppressed: 0,
+        }
+        .run(rx),
+    );
+
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let message = match info.payload().downcast_ref::<&str>() {
+            Some(message) => message.to_string(),
+            None => info
+                .payload()
+                .downcast_ref::<String>()
+                .cloned()
+                .unwrap_or_else(|| "unknown panic".to_string()),
+        };
+        let location = info
+            .location()
+            .map(|location| format!(" at {location}"))
+            .unwrap_or_default();
+        let backtrace = Backtrace::force_capture().to_string();
+        submit(Report {
+            source: "panic".to_string(),
+            update_id: None,
+            message: format!("{message}{location}"),
+            backtrace: Some(short_backtrace(&backtrace)),
+        });
+        default_hook(info);
+    }));
+}
+
+fn submit(report: Report) {
+    if let Some(sink) = SINK.get() {
+        // The worker only stops when the runtime shuts down
+        sink.send(report).ok();
+    }
+}
+
+/// Report the error returned by the handler or the watcher.
+pub fn report_error(source: &str, update_id: Option<u32>, err: &anyhow::Error) {
+    let backtrace = match err.backtrace().status() {
+        BacktraceStatus::Captured => Some(short_backtrace(&err.backtrace().to_string())),
+        _ => None,
+    };
+    submit(Report {
+        source: source.to_string(),
+        update_id,
+        message: format!("{err:#}"),
+        backtrace,
+    });
+}
+
+/// Keep the frames of this crate only, the rest are mostly tokio and teloxide internals.
+fn short_backtrace(backtrace: &str) -> String {
+    let mut frames = Vec::new();
+    let mut lines = backtrace.lines().peekable();
+    while let Some(line) = lines.next() {
+        let frame = line.trim();
+        if !(frame.contains("rusty_maid") || frame.contains("tgbot::")) {
+            continue;
+        }
+        let frame = frame.split_once(": ").map_or(frame, |(_, name)| name);
+        let location = lines
+            .next_if(|next| next.trim_start().starts_with("at "))
+            .map(|next| format!("\n    {}", next.trim()))
+            .unwrap_or_default();
+        frames.push(format!("{frame}{location}"));
+        if frames.len() == BACKTRACE_FRAMES {
+            break;
+        }
+    }
+    frames.join("\n")
+}
+
+/// Errors with the same source and first line are regarded as the same error
+fn fingerprint(report: &Report) -> String {
+    let first_line = report.message.lines().next().unwrap_or_default();
+    format!("{}:{first_line}", report.source)
+}
+
+fn render(report: &Report, suppressed: u32) -> String {
+    let mut text = format!("⚠️ {} failed", report.source);
+    if let Some(update_id) = report.update_id {
+        write!(text, " (update {update_id})").unwrap();
+    }
+    write!(text, "\n\n{}", report.message).unwrap();
+    if let Some(backtrace) = report.backtrace.as_deref().filter(|bt| !bt.is_empty()) {
+        write!(text, "\n\nBacktrace:\n{backtrace}").unwrap();
+    }
+    if text.chars().count() > MAX_REPORT_LEN {
+        text = text.chars().take(MAX_REPORT_LEN).collect::<String>() + "…";
+    }
+    if suppressed > 0 {
+        write!(text, "\n\n({suppressed} more errors were suppressed)").unwrap();
+    }
+    text
+}
+
+struct Worker {
+    bot: Bot,
+    data: AppData,
+    chat_id: ChatId,
+    dedup_window: Duration,
+    max_per_minute: usize,
+    last_seen: HashMap<String, Instant>,
+    /// Time of the reports sent in the last minute
+    recent: VecDeque<Instant>,
+    /// Reports dropped since the last one sent
+    suppressed: u32,
+}
+
+impl Worker {
+    /// Return false if the report is a duplicate or exceeds the rate limit.
+    fn accept(&mut self, report: &Report, now: Instant) -> bool {
+        let key = fingerprint(report);
+        if let Some(last) = self.last_seen.get(&key) {
+            if now.duration_since(*last) < self.dedup_window {
+                return false;
+            }
+        }
+
+        while self
+            .recent
+            .front()
+            .is_some_and(|sent| now.duration_since(*sent) >= Duration::from_secs(60))
+        {
+            self.recent.pop_front();
+        }
+        if self.recent.len() >= self.max_per_minute {
+            return false;
+        }
+
+        self.last_seen
+            .retain(|_, last| now.duration_since(*last) < self.dedup_window);
+        self.last_seen.insert(key, now);
+        self.recent.push_back(now);
+        true
+    }
+
+    async fn run(mut self, mut rx: mpsc::UnboundedReceiver<Report>) {
+        while let Some(report) = rx.recv().await {
+            if !self.accept(&report, Instant::now()) {
+                self.suppressed += 1;
+                continue;
+            }
+
+            let text = render(&report, std::mem::take(&mut self.suppressed));
+            let (bot, chat_id) = (self.bot.clone(), self.chat_id);
+            let sent = self
+                .data
+                .send_queue
+                .submit(chat_id, Priority::Background, move || {
+                    bot.send_message(chat_id, &text)
+                })
+                .await;
+            if let Err(err) = sent {
+                // Don't report it again, or it loops forever
+                tracing::error!("fail to send error report to {chat_id}: {err}");
+            }
+        }
+    }
+}
+
+#[test]
+fn test_short_backtrace() {
+    let backtrace = "   0: std::backtrace::Backtrace::force_capture
+             at /rustc/library/std/src/backtrace.rs:312:13
+   1: rusty_maid::error_sink::init::{{closure}}
+             at ./src/error_sink.rs:60:25
+   2: tokio::runtime::task::harness::poll
+   3: tgbot::handlers::weather_handler::{{closure}}
+             at ./src/bin/tgbot/handlers.rs:511:5";
+    assert_eq!(
+        short_backtrace(backtrace),
+        "rusty_maid::error_sink::init::{{closure}}\n    at ./src/error_sink.rs:60:25\n\
+         tgbot::handlers::weather_handler::{{closure}}\n    at ./src/bin/tgbot/handlers.rs:511:5"
+    );
+}
+
+#[test]
+fn test_render_report() {
+    let report = Report {
+        source: "weather".to_string(),
+        update_id: Some(42),
+        message: "fail to fetch: timeout".to_string(),
+        backtrace: None,
+    };
+    assert_eq!(
+        render(&report, 2),
+        "⚠️ weather failed (update 42)\n\nfail to fetch: timeout\n\n(2 more errors were suppressed)"
+    );
+    assert_eq!(fingerprint(&report), "weather:fail to fetch: timeout");
+}
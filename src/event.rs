@@ -1,17 +1,29 @@
 use std::{
+    collections::BTreeMap,
     fmt::Display,
     future::Future,
     hash::Hash,
     ops::{Deref, DerefMut},
-    sync::Arc,
+    sync::{Arc, Mutex},
     time::Duration,
 };
 
+use redis::Commands;
+use teloxide::{prelude::Requester, types::ChatId};
 use tokio::sync::watch;
 use typed_builder::TypedBuilder;
 
 use crate::app::AppData;
+use crate::cache::RedisKey;
+use crate::config::{Config, WatcherConfig};
 use crate::http::HttpClient;
+use crate::send_queue::Priority;
+
+/// Long errors are cut in the alert, the full one is in the log
+const MAX_ALERT_ERROR_LEN: usize = 1000;
+
+/// Names of the watchers started in this process, for the startup summary
+static STARTED: Mutex<Vec<String>> = Mutex::new(Vec::new());
 
 #[derive(Debug, Default, Clone, Copy)]
 pub struct State<S>(pub S);
@@ -36,7 +48,15 @@ pub struct EventWatcher<S> {
     name: Arc<Box<str>>,
     #[builder(default = 60)]
     heartbeat_interval: u64,
+    /// Read the interval from the reloaded config, the watcher keeps the old interval without it
+    #[builder(default, setter(strip_option))]
+    interval_of: Option<fn(&Config) -> u64>,
+    /// The default bot to send notifications
     pub bot: teloxide::Bot,
+    /// Read the bot name in [`crate::app::Bots`] from the latest config before each run, and
+    /// use it instead of the default bot
+    #[builder(default, setter(strip_option))]
+    bot_of: Option<fn(&Config) -> Option<&str>>,
     pub data: AppData,
     #[builder(default, setter( transform = |s: S| Some(Arc::new(State(s))) ))]
     pub state: Option<Arc<State<S>>>,
@@ -49,7 +69,9 @@ impl<S> Clone for EventWatcher<S> {
             client: None,
             name: Arc::clone(&self.name),
             heartbeat_interval: self.heartbeat_interval,
+            interval_of: self.interval_of,
             bot: self.bot.clone(),
+            bot_of: self.bot_of,
             data: self.data.clone(),
             state: self.state.clone(),
         }
@@ -70,21 +92,57 @@ where
     {
         let (tx, rx) = watch::channel(1_u8);
         let mut heartbeat = tokio::time::interval(Duration::from_secs(self.heartbeat_interval));
+        let mut config = Config::subscribe();
         let name = self.name.to_string();
+        STARTED.lock().unwrap().push(name.clone());
 
         tokio::spawn(async move {
+            let mut current_interval = self.heartbeat_interval;
             loop {
-                let watcher = self.clone();
+                let mut watcher = self.clone();
+                if let Some(bot_of) = self.bot_of {
+                    let config = Config::get_global_config();
+                    if let Some(bot) = bot_of(&config).and_then(|name| self.data.bots.get(name)) {
+                        watcher.bot = bot.clone();
+                    }
+                }
                 let mut rx = rx.clone();
 
                 tokio::select! {
                     _ = rx.changed() => {
                         break;
                     }
+                    Ok(()) = config.changed(), if self.interval_of.is_some() => {
+                        let interval_of = self.interval_of.unwrap();
+                        let interval = interval_of(&config.borrow_and_update());
+                        if interval != current_interval {
+                            tracing::info!("{} runs every {interval}s now", self.name);
+                            let period = Duration::from_secs(interval);
+                            heartbeat = tokio::time::interval_at(
+                                tokio::time::Instant::now() + period,
+                                period,
+                            );
+                            current_interval = interval;
+                        }
+                    }
                     _ = heartbeat.tick() => {
-                        if let Err(err) = task(watcher).await {
+                        // The paused watcher waits for `/watcher resume`
+                        if is_paused(&self.data, &self.name).unwrap_or(false) {
+                            continue;
+                        }
+                        let bot = watcher.bot.clone();
+                        let start = std::time::Instant::now();
+                        let result = task(watcher).await;
+                        crate::metrics::observe_watcher(&self.name, result.is_ok(), start.elapsed());
+                        let error = result.as_ref().err().map(|err| format!("{err:#}"));
+                        if let Err(err) = result {
+                            crate::metrics::observe_error(&err);
+                            crate::error_sink::report_error(&self.name, None, &err);
+                            crate::telemetry::capture_watcher_error(&self.name, &err);
                             tracing::error!("{}", err)
                         }
+
+                        spend_error_budget(bot, &self.data, &self.name, error).await;
                     }
                 }
             }
@@ -109,7 +167,9 @@ where
         Event: Eq + Hash + std::fmt::Debug + std::fmt::Display + redis::ToRedisArgs + 'iter,
         Relation: Iterator<Item = (&'iter Subscriber, &'iter Vec<Event>)>,
     {
-        self.data.cacher.setup_subscribe_registry(&self.name, iter);
+        if let Err(err) = self.data.cacher.setup_subscribe_registry(&self.name, iter) {
+            tracing::error!("{err}");
+        }
 
         self
     }
@@ -133,4 +193,143 @@ where
         let subscriber = self.data.cacher.get_subscribers(&self.name, event)?;
         Ok(subscriber)
     }
+
+    /// The subscribers with their options, see [`crate::cache::Cacher::get_subscribers_with_opts`].
+    pub fn get_subscribers_with_opts<T, Event>(
+        &self,
+        event: &Event,
+    ) -> anyhow::Result<Vec<(String, T)>>
+    where
+        T: serde::de::DeserializeOwned + Default,
+        Event: std::fmt::Display,
+    {
+        let subscribers = self
+            .data
+            .cacher
+            .get_subscribers_with_opts(&self.name, event)?;
+        Ok(subscribers)
+    }
+}
+
+/// Count the run of the watcher, and pause it once `budget` runs in a row have failed. A
+/// successful run resets the count, and a `budget` of `0` never pauses the watcher.
+///
+/// Returns true when this run paused the watcher.
+pub fn record_run(
+    data: &AppData,
+    name: &str,
+    error: Option<&str>,
+    budget: u32,
+) -> anyhow::Result<bool> {
+    let mut conn = data.cacher.get_conn();
+    let Some(error) = error else {
+        let () = conn.hdel(RedisKey::WatcherFailures, name)?;
+        return Ok(false);
+    };
+    let failures: u32 = conn.hincr(RedisKey::WatcherFailures, name, 1)?;
+    if budget == 0 || failures < budget {
+        return Ok(false);
+    }
+    let () = conn.hset(RedisKey::PausedWatchers, name, error)?;
+    let () = conn.hdel(RedisKey::WatcherFailures, name)?;
+    Ok(true)
+}
+
+/// Names of the watchers started in this process.
+pub fn started_watchers() -> Vec<String> {
+    STARTED.lock().unwrap().clone()
+}
+
+pub fn is_paused(data: &AppData, name: &str) -> anyhow::Result<bool> {
+    Ok(data
+        .cacher
+        .get_conn()
+        .hexists(RedisKey::PausedWatchers, name)?)
+}
+
+/// The paused watchers and the errors pausing them.
+pub fn paused_watchers(data: &AppData) -> anyhow::Result<BTreeMap<String, String>> {
+    Ok(data.cacher.get_conn().hgetall(RedisKey::PausedWatchers)?)
+}
+
+/// The watchers failing in a row but not paused yet, and the failures so far.
+pub fn failing_watchers(data: &AppData) -> anyhow::Result<BTreeMap<String, u32>> {
+    Ok(data.cacher.get_conn().hgetall(RedisKey::WatcherFailures)?)
+}
+
+/// Run the paused watcher again from its next heartbeat, returns false if it is not paused.
+pub fn resume(data: &AppData, name: &str) -> anyhow::Result<bool> {
+    let removed: u32 = data
+        .cacher
+        .get_conn()
+        .hdel(RedisKey::PausedWatchers, name)?;
+    Ok(removed > 0)
+}
+
+/// Record the run, and alert the admin chat when it pauses the watcher.
+async fn spend_error_budget(bot: teloxide::Bot, data: &AppData, name: &str, error: Option<String>) {
+    let budget = Config::get_global_config().watcher.error_budget;
+    match record_run(data, name, error.as_deref(), budget) {
+        Ok(false) => {}
+        Ok(true) => {
+            tracing::warn!("{name} is paused after {budget} failed runs");
+            let error = error.unwrap_or_default();
+            if let Err(err) = alert_paused(bot, data, name, budget, &error).await {
+                tracing::error!("fail to alert the pause of {name}: {err}");
+            }
+        }
+        Err(err) => tracing::warn!("fail to record the run of {name}: {err}"),
+    }
+}
+
+async fn alert_paused(
+    bot: teloxide::Bot,
+    data: &AppData,
+    name: &str,
+    failures: u32,
+    error: &str,
+) -> anyhow::Result<()> {
+    let Some(chat) = WatcherConfig::alert_chat(&Config::get_global_config()) else {
+        return Ok(());
+    };
+    let lang = crate::i18n::chat_language(data, chat, None);
+    let error = match error.char_indices().nth(MAX_ALERT_ERROR_LEN) {
+        Some((end, _)) => format!("{}...", &error[..end]),
+        None => error.to_string(),
+    };
+    let text = crate::t!(
+        lang,
+        "watcher.paused",
+        name = name,
+        failures = failures,
+        error = error
+    );
+    let chat_id = ChatId(chat);
+    data.submit(chat_id, Priority::Background, move || {
+        bot.send_message(chat_id, text.clone())
+    })
+    .await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_error_budget() {
+    let data = crate::testkit::app_data(teloxide::Bot::new("1000:fake-token")).await;
+    let name = "TestWatcher";
+
+    assert!(!record_run(&data, name, Some("timeout"), 3).unwrap());
+    assert!(!record_run(&data, name, Some("timeout"), 3).unwrap());
+    // A success in between starts the count over
+    assert!(!record_run(&data, name, None, 3).unwrap());
+    assert!(!record_run(&data, name, Some("timeout"), 3).unwrap());
+    assert!(!record_run(&data, name, Some("timeout"), 3).unwrap());
+    assert!(!is_paused(&data, name).unwrap());
+    assert!(record_run(&data, name, Some("refused"), 3).unwrap());
+    assert!(is_paused(&data, name).unwrap());
+    assert_eq!(paused_watchers(&data).unwrap()[name], "refused");
+
+    assert!(resume(&data, name).unwrap());
+    assert!(!resume(&data, name).unwrap());
+    assert!(!is_paused(&data, name).unwrap());
+    assert!(!record_run(&data, name, Some("timeout"), 0).unwrap());
 }
@@ -46,8 +46,7 @@ impl HttpClient {
         // for debugging usage
         let url_str = url.to_string();
 
-        self.get(url)
-            .send()
+        self.send(self.get(url))
             .await
             .with_context(|| format!("fail to send GET request to url: {}", url_str))?
             .json::<T>()
@@ -65,9 +64,7 @@ impl HttpClient {
     {
         let url_str = url.to_string();
 
-        self.post(url)
-            .json(payload)
-            .send()
+        self.send(self.post(url).json(payload))
             .await
             .with_context(|| format!("fail to send GET request to url: `{}`", url_str))?
             .json::<T>()
@@ -83,6 +80,27 @@ impl HttpClient {
 
     #[inline]
     pub async fn get_text(&self, url: impl IntoUrl + Display) -> anyhow::Result<String> {
-        Ok(self.get(url).send().await?.text().await?)
+        Ok(self.send(self.get(url)).await?.text().await?)
+    }
+
+    /// Send the request in a tracing span, and count it by host and status code in the metrics.
+    #[cfg(feature = "reqwest")]
+    pub async fn send(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> reqwest::Result<reqwest::Response> {
+        use tracing::Instrument;
+
+        let request = request.build()?;
+        let host = request.url().host_str().unwrap_or_default().to_string();
+        let span = tracing::info_span!("http.client", host = %host, method = %request.method());
+        let result = self.execute(request).instrument(span).await;
+        let status = match &result {
+            Ok(resp) => resp.status().as_u16().to_string(),
+            Err(err) if err.is_timeout() => "timeout".to_string(),
+            Err(_) => "error".to_string(),
+        };
+        crate::metrics::observe_http(&host, &status);
+        result
     }
 }
@@ -0,0 +1,175 @@
+use std::{collections::HashMap, fmt::Display};
+
+use redis::Commands;
+
+use crate::app::AppData;
+
+/// The language used when neither the chat nor the user has a supported language.
+pub const DEFAULT_LANG: &str = "en";
+
+/// Supported language code and its display name.
+pub const LANGUAGES: &[(&str, &str)] = &[("en", "English"), ("zh-hans", "简体中文")];
+
+lazy_static::lazy_static!(
+    static ref LOCALES: HashMap<&'static str, HashMap<String, String>> = HashMap::from([
+        ("en", parse_locale(include_str!("../locales/en.toml"))),
+        ("zh-hans", parse_locale(include_str!("../locales/zh-hans.toml"))),
+    ]);
+);
+
+/// Flatten the TOML locale file into `section.key = template` pairs.
+fn parse_locale(content: &str) -> HashMap<String, String> {
+    fn flatten(prefix: &str, table: toml::Table, result: &mut HashMap<String, String>) {
+        for (key, value) in table {
+            let key = if prefix.is_empty() {
+                key
+            } else {
+                format!("{prefix}.{key}")
+            };
+            match value {
+                toml::Value::String(template) => {
+                    result.insert(key, template);
+                }
+                toml::Value::Table(table) => flatten(&key, table, result),
+                _ => panic!("locale key {key} should be a string or a table"),
+            }
+        }
+    }
+
+    let table: toml::Table = toml::from_str(content).expect("invalid locale file");
+    let mut result = HashMap::new();
+    flatten("", table, &mut result);
+    result
+}
+
+/// Map the IETF language tag from Telegram, like `en-US` or `zh-CN`, to a supported language.
+pub fn normalize(code: &str) -> Option<&'static str> {
+    let code = code.trim().to_lowercase().replace('_', "-");
+    if code == "en" || code.starts_with("en-") {
+        Some("en")
+    } else if code == "zh" || code == "zh-hans" || code == "zh-cn" || code == "zh-sg" {
+        Some("zh-hans")
+    } else {
+        None
+    }
+}
+
+/// Get the raw template of `key` in `lang`, falling back to the default language.
+pub fn lookup(lang: &str, key: &str) -> Option<&'static str> {
+    LOCALES
+        .get(lang)
+        .and_then(|locale| locale.get(key))
+        .or_else(|| LOCALES[DEFAULT_LANG].get(key))
+        .map(String::as_str)
+}
+
+/// Render the template of `key` in `lang`, falling back to the default language and then the
+/// key itself. Placeholder like `{name}` is replaced by the argument with the same name.
+pub fn translate(lang: &str, key: &str, args: &[(&str, &(dyn Display + Sync))]) -> String {
+    let Some(template) = lookup(lang, key) else {
+        tracing::warn!("missing locale key {key}");
+        return key.to_string();
+    };
+
+    args.iter()
+        .fold(template.to_string(), |text, (name, value)| {
+            text.replace(&format!("{{{name}}}"), &value.to_string())
+        })
+}
+
+/// Shorthand of [`translate`]: `t!(lang, "weather.usage")` or `t!(lang, "exchange.nan", input = x)`.
+#[macro_export]
+macro_rules! t {
+    ($lang:expr, $key:literal $(, $name:ident = $value:expr)* $(,)?) => {
+        $crate::i18n::translate(
+            $lang,
+            $key,
+            &[$((stringify!($name), &$value as &(dyn std::fmt::Display + Sync))),*],
+        )
+    };
+}
+
+fn language_key(chat_id: i64) -> String {
+    format!("CHAT_LANGUAGE:{chat_id}")
+}
+
+/// Get the language for the user without a chat, like the sender of an inline query.
+pub fn user_language(user_lang: Option<&str>) -> &'static str {
+    user_lang.and_then(normalize).unwrap_or(DEFAULT_LANG)
+}
+
+/// Get the language of the chat. If the chat doesn't set one, the user's Telegram language is
+/// used.
+pub fn chat_language(data: &AppData, chat_id: i64, user_lang: Option<&str>) -> &'static str {
+    let stored: Option<String> = data
+        .cacher
+        .get_conn()
+        .get(language_key(chat_id))
+        .unwrap_or_else(|err| {
+            tracing::error!("fail to get language of chat {chat_id}: {err}");
+            None
+        });
+
+    stored
+        .as_deref()
+        .and_then(normalize)
+        .unwrap_or_else(|| user_language(user_lang))
+}
+
+/// Get the language for replying the message.
+pub fn lang_of(data: &AppData, msg: &teloxide::types::Message) -> &'static str {
+    let user_lang = msg
+        .from
+        .as_ref()
+        .and_then(|user| user.language_code.as_deref());
+    chat_language(data, msg.chat.id.0, user_lang)
+}
+
+pub fn set_chat_language(data: &AppData, chat_id: i64, code: &str) -> anyhow::Result<&'static str> {
+    let Some(lang) = normalize(code) else {
+        anyhow::bail!("unsupported language {code}");
+    };
+    let () = data.cacher.get_conn().set(language_key(chat_id), lang)?;
+    Ok(lang)
+}
+
+#[test]
+fn test_locales() {
+    assert_eq!(normalize("en-US"), Some("en"));
+    assert_eq!(normalize("zh_CN"), Some("zh-hans"));
+    assert_eq!(normalize("zh-hant"), None);
+
+    // Every translation should cover the keys and placeholders of the default language
+    let default = &LOCALES[DEFAULT_LANG];
+    let placeholders = |template: &str| {
+        let mut found = template
+            .split('{')
+            .skip(1)
+            .filter_map(|s| s.split_once('}').map(|(name, _)| name.to_string()))
+            .collect::<Vec<_>>();
+        found.sort();
+        found
+    };
+    for (lang, _) in LANGUAGES {
+        let locale = &LOCALES[lang];
+        for (key, template) in default {
+            let Some(translated) = locale.get(key) else {
+                panic!("{lang} miss the locale key {key}");
+            };
+            assert_eq!(
+                placeholders(template),
+                placeholders(translated),
+                "{lang}: {key}"
+            );
+        }
+    }
+
+    assert_eq!(
+        crate::t!("zh-hans", "common.unknown_command", command = "foo"),
+        "未知命令 foo，使用 /help 查看所有命令"
+    );
+    assert_eq!(translate("en", "missing.key", &[]), "missing.key");
+    assert_eq!(lookup("zh-hans", "missing.key"), None);
+    assert_eq!(user_language(Some("zh-CN")), "zh-hans");
+    assert_eq!(user_language(Some("fr")), DEFAULT_LANG);
+}
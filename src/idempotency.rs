@@ -0,0 +1,125 @@
+//! Run the side effects once. The same update can be handled twice when the bot restarts before
+//! Telegram learns it is handled, or when Telegram retries a slow webhook, so the handled updates
+//! are remembered in Redis, and the modules guard their own side effects by [`idempotent`].
+
+use std::{future::Future, time::Duration};
+
+use redis::Commands;
+
+use crate::app::AppData;
+
+/// Telegram keeps the pending updates for 24 hours, so a duplicate comes within a day
+const UPDATE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Claim the key for `ttl`, return false if it is already claimed.
+fn claim(data: &AppData, key: &str, ttl: Duration) -> anyhow::Result<bool> {
+    let claimed: bool = redis::cmd("SET")
+        .arg(key)
+        .arg(1)
+        .arg("NX")
+        .arg("PX")
+        .arg(ttl.as_millis() as u64)
+        .query(&mut data.cacher.get_conn())?;
+    Ok(claimed)
+}
+
+/// The update claimed by [`first_delivery`]. Unless it is marked [`UpdateClaim::handled`], the
+/// claim is released when dropped, so that the update failed or aborted is handled again when
+/// Telegram delivers it again.
+pub struct UpdateClaim {
+    data: AppData,
+    key: String,
+    handled: bool,
+}
+
+impl UpdateClaim {
+    /// Keep the claim, so that the duplicates of the update are skipped.
+    pub fn handled(mut self) {
+        self.handled = true;
+    }
+}
+
+impl Drop for UpdateClaim {
+    fn drop(&mut self) {
+        if self.handled {
+            return;
+        }
+        let released: redis::RedisResult<()> = self.data.cacher.get_conn().del(&self.key);
+        if let Err(err) = released {
+            tracing::error!("fail to release the claim {}: {err}", self.key);
+        }
+    }
+}
+
+/// Claim the update of the bot the first time it is seen, and return `None` for the duplicates
+/// while it is being handled or after it is handled.
+pub fn first_delivery(
+    data: &AppData,
+    bot_id: u64,
+    update_id: u32,
+) -> anyhow::Result<Option<UpdateClaim>> {
+    let key = format!("UPDATE_SEEN:{bot_id}:{update_id}");
+    if !claim(data, &key, UPDATE_TTL)? {
+        return Ok(None);
+    }
+    Ok(Some(UpdateClaim {
+        data: data.clone(),
+        key,
+        handled: false,
+    }))
+}
+
+/// Run the future unless another run of the same key succeeded or is running in the last `ttl`,
+/// and return `None` when it is skipped. The key is released when the future fails, so that the
+/// side effect can be retried.
+pub async fn idempotent<T, Fut>(
+    data: &AppData,
+    key: &str,
+    ttl: Duration,
+    fut: Fut,
+) -> anyhow::Result<Option<T>>
+where
+    Fut: Future<Output = anyhow::Result<T>>,
+{
+    let key = format!("IDEMPOTENT:{key}");
+    if !claim(data, &key, ttl)? {
+        tracing::debug!("skip the side effect {key} which already ran");
+        return Ok(None);
+    }
+    match fut.await {
+        Ok(value) => Ok(Some(value)),
+        Err(err) => {
+            let () = data.cacher.get_conn().del(&key)?;
+            Err(err)
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_idempotent() {
+    let data = crate::testkit::app_data(teloxide::Bot::new("1000:fake-token")).await;
+    let ttl = Duration::from_secs(60);
+
+    let ran = idempotent(&data, "notify:1", ttl, async { Ok(1) }).await;
+    assert_eq!(ran.unwrap(), Some(1));
+    let ran = idempotent(&data, "notify:1", ttl, async { Ok(2) }).await;
+    assert_eq!(ran.unwrap(), None);
+
+    let failed = idempotent(&data, "notify:2", ttl, async {
+        Err::<(), _>(anyhow::anyhow!("network error"))
+    })
+    .await;
+    assert!(failed.is_err());
+    let retried = idempotent(&data, "notify:2", ttl, async { Ok(3) }).await;
+    assert_eq!(retried.unwrap(), Some(3));
+
+    let claimed = first_delivery(&data, 1000, 42).unwrap().unwrap();
+    assert!(first_delivery(&data, 1000, 42).unwrap().is_none());
+    assert!(first_delivery(&data, 1001, 42).unwrap().is_some());
+    claimed.handled();
+    assert!(first_delivery(&data, 1000, 42).unwrap().is_none());
+
+    // The update failed is released and handled again
+    drop(first_delivery(&data, 1000, 43).unwrap());
+    assert!(first_delivery(&data, 1000, 43).unwrap().is_some());
+}
@@ -0,0 +1,155 @@
+use std::{future::Future, pin::Pin};
+
+use teloxide::types::{
+    InlineQueryResult, InlineQueryResultArticle, InputMessageContent, InputMessageContentText,
+    ParseMode, UserId,
+};
+
+use crate::app::AppData;
+
+type InlineFuture = Pin<Box<dyn Future<Output = anyhow::Result<Vec<InlineQueryResult>>> + Send>>;
+type InlineHandler =
+    Box<dyn Fn(AppData, UserId, &'static str, String) -> InlineFuture + Send + Sync>;
+
+struct InlineRoute {
+    keyword: &'static str,
+    /// The locale key of the usage
+    usage: &'static str,
+    /// The [`crate::module::BotModule`] adding the route
+    owner: Option<&'static str>,
+    handler: InlineHandler,
+}
+
+impl InlineRoute {
+    fn enabled(&self) -> bool {
+        !self.owner.is_some_and(crate::settings::globally_disabled)
+    }
+
+    fn usage_article(&self, lang: &str) -> InlineQueryResult {
+        let usage = crate::i18n::translate(lang, self.usage, &[]);
+        article(self.keyword, self.keyword, usage)
+    }
+}
+
+/// Dispatch the inline query like `@bot weather Tokyo` to the module opt-in for keyword
+/// `weather`, with the sender, the sender's language and the rest of the query `Tokyo` as
+/// arguments.
+#[derive(Default)]
+pub struct InlineRouter {
+    routes: Vec<InlineRoute>,
+    owner: Option<&'static str>,
+}
+
+impl InlineRouter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the module adding the routes next, its routes are skipped when it is disabled in the
+    /// config.
+    pub fn owned_by(mut self, module: &'static str) -> Self {
+        self.owner = Some(module);
+        self
+    }
+
+    /// Add the route for `keyword`, `usage` is the locale key of the usage shown when the query
+    /// has no argument.
+    pub fn route<F, Fut>(mut self, keyword: &'static str, usage: &'static str, handler: F) -> Self
+    where
+        F: Fn(AppData, UserId, &'static str, String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = anyhow::Result<Vec<InlineQueryResult>>> + Send + 'static,
+    {
+        self.routes.push(InlineRoute {
+            keyword,
+            usage,
+            owner: self.owner,
+            handler: Box::new(move |data, user, lang, args| {
+                Box::pin(handler(data, user, lang, args))
+            }),
+        });
+        self
+    }
+
+    /// Dispatch the query to the matching module. The usage of all the modules will be returned
+    /// when no module matches, and the error will be rendered as an article.
+    pub async fn dispatch(
+        &self,
+        data: AppData,
+        user: UserId,
+        lang: &'static str,
+        query: &str,
+    ) -> Vec<InlineQueryResult> {
+        let query = query.trim();
+        let (keyword, args) = query.split_once(' ').unwrap_or((query, ""));
+        let args = args.trim();
+
+        let Some(route) = self
+            .routes
+            .iter()
+            .find(|route| route.keyword.eq_ignore_ascii_case(keyword) && route.enabled())
+        else {
+            return self.usage(lang);
+        };
+        if args.is_empty() {
+            return vec![route.usage_article(lang)];
+        }
+
+        match (route.handler)(data, user, lang, args.to_string()).await {
+            Ok(results) if !results.is_empty() => results,
+            Ok(_) => vec![article(
+                "empty",
+                crate::t!(lang, "inline.no_result"),
+                crate::t!(lang, "inline.no_result_for", query = query),
+            )],
+            Err(err) => vec![article(
+                "error",
+                crate::t!(lang, "inline.error"),
+                format!("{err}"),
+            )],
+        }
+    }
+
+    fn usage(&self, lang: &str) -> Vec<InlineQueryResult> {
+        self.routes
+            .iter()
+            .filter(|route| route.enabled())
+            .map(|route| route.usage_article(lang))
+            .collect()
+    }
+}
+
+/// Create a plain text article. The `description` will be both the preview and sent text.
+pub fn article(
+    id: impl Into<String>,
+    title: impl Into<String>,
+    description: impl Into<String>,
+) -> InlineQueryResult {
+    let description = description.into();
+    InlineQueryResult::Article(
+        InlineQueryResultArticle::new(
+            id,
+            title,
+            InputMessageContent::Text(InputMessageContentText::new(&description)),
+        )
+        .description(description),
+    )
+}
+
+/// Create an article with HTML text, the `preview` is displayed in the result list.
+pub fn html_article(
+    id: impl Into<String>,
+    title: impl Into<String>,
+    preview: impl Into<String>,
+    html: impl Into<String>,
+) -> InlineQueryResult {
+    InlineQueryResult::Article(
+        InlineQueryResultArticle::new(
+            id,
+            title,
+            InputMessageContent::Text(
+                InputMessageContentText::new(html).parse_mode(ParseMode::Html),
+            ),
+        )
+        .description(preview),
+    )
+}
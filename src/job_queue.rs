@@ -0,0 +1,357 @@
+use std::time::Duration;
+
+use redis::{
+    streams::{StreamMaxlen, StreamReadOptions, StreamReadReply},
+    Commands,
+};
+use serde::{Deserialize, Serialize};
+use teloxide::{
+    payloads::SendVideoSetters,
+    prelude::*,
+    types::{InputFile, MessageId, ParseMode},
+};
+
+use crate::{
+    app::AppData,
+    config::{Config, JobQueueConfig},
+    i18n, metrics,
+    modules::ytd::YtdlpVideo,
+    topic::{ChatTarget, SendTo},
+};
+
+/// Stream of the jobs waiting or running, a job is deleted when it is finished
+const JOB_QUEUE: &str = "JOB_QUEUE";
+/// Consumer group of the workers reading [`JOB_QUEUE`]
+const WORKER_GROUP: &str = "workers";
+/// Stream of the jobs failed too many times, kept for the owner to look into
+const JOB_DEAD_LETTER: &str = "JOB_DEAD_LETTER";
+/// Dead letters kept at most, the older ones are trimmed
+const DEAD_LETTER_LEN: usize = 1000;
+/// How often the idle worker looks for the new jobs
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Something too slow to run in the handler. Jobs are kept in Redis, so they still run after the
+/// bot restarts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "job", rename_all = "snake_case")]
+pub enum Job {
+    DownloadVideo { url: String },
+}
+
+impl Job {
+    /// Name of the job in the logs and metrics.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Job::DownloadVideo { .. } => "download_video",
+        }
+    }
+}
+
+/// The job with the chat asking for it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JobEntry {
+    pub job: Job,
+    /// Chat receiving the result, in the `chat:topic` format of [`ChatTarget`]
+    pub target: String,
+    /// Message in the chat showing the progress of the job
+    pub status_message: i32,
+    pub lang: String,
+    /// Failed runs so far
+    #[serde(default)]
+    pub attempts: u32,
+}
+
+/// What to do with the failed job.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Retry {
+    Again,
+    DeadLetter,
+}
+
+impl Retry {
+    /// `attempts` is the failed runs including the last one.
+    pub fn after(attempts: u32, max_attempts: u32) -> Self {
+        if attempts < max_attempts {
+            Retry::Again
+        } else {
+            Retry::DeadLetter
+        }
+    }
+}
+
+/// Everything a running job needs to report to the chat.
+pub struct JobContext {
+    pub bot: Bot,
+    pub data: AppData,
+    pub target: ChatTarget,
+    pub lang: &'static str,
+    status_message: MessageId,
+}
+
+impl JobContext {
+    /// Show the progress in the status message. The job goes on when the message can't be edited,
+    /// like it is deleted by the user.
+    pub async fn progress(&self, text: impl Into<String>) {
+        let edited = self
+            .bot
+            .edit_message_text(self.target.chat_id, self.status_message, text)
+            .await;
+        if let Err(err) = edited {
+            tracing::debug!("[JobQueue] fail to show progress in {}: {err}", self.target);
+        }
+    }
+}
+
+/// Add the job to the end of the queue, return the jobs before it.
+pub fn enqueue(data: &AppData, entry: &JobEntry) -> anyhow::Result<usize> {
+    let mut conn = data.cacher.get_conn();
+    let ahead: usize = conn.xlen(JOB_QUEUE)?;
+    let _: String = conn.xadd(JOB_QUEUE, "*", &[("entry", serde_json::to_string(entry)?)])?;
+    Ok(ahead)
+}
+
+/// Jobs waiting in the queue, and the failed jobs kept in the dead letter queue.
+pub fn depth(data: &AppData) -> anyhow::Result<(usize, usize)> {
+    let mut conn = data.cacher.get_conn();
+    Ok((conn.xlen(JOB_QUEUE)?, conn.xlen(JOB_DEAD_LETTER)?))
+}
+
+/// Queue the job for the message, and reply a status message which the worker keeps updating.
+pub async fn submit(
+    bot: &Bot,
+    data: &AppData,
+    msg: &Message,
+    lang: &'static str,
+    job: Job,
+) -> anyhow::Result<()> {
+    let status = data
+        .reply(
+            msg.chat.id,
+            bot.send_message_to(msg, crate::t!(lang, "job_queue.queued")),
+        )
+        .await?;
+    let entry = JobEntry {
+        job,
+        target: ChatTarget::of(msg).to_string(),
+        status_message: status.id.0,
+        lang: lang.to_string(),
+        attempts: 0,
+    };
+    let ahead = enqueue(data, &entry)?;
+    if ahead > 0 {
+        let text = crate::t!(lang, "job_queue.queued_behind", ahead = ahead);
+        bot.edit_message_text(msg.chat.id, status.id, text).await?;
+    }
+    Ok(())
+}
+
+fn create_group(data: &AppData) -> anyhow::Result<()> {
+    let created: redis::RedisResult<()> =
+        data.cacher
+            .get_conn()
+            .xgroup_create_mkstream(JOB_QUEUE, WORKER_GROUP, "0");
+    match created {
+        Err(err) if err.code() != Some("BUSYGROUP") => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Read one job for the worker. `id` is `>` for a new job, or `0` for the job the worker took
+/// before the bot restarts.
+fn read(data: &AppData, consumer: &str, id: &str) -> anyhow::Result<Option<(String, JobEntry)>> {
+    let options = StreamReadOptions::default()
+        .group(WORKER_GROUP, consumer)
+        .count(1);
+    let reply: StreamReadReply =
+        data.cacher
+            .get_conn()
+            .xread_options(&[JOB_QUEUE], &[id], &options)?;
+    let Some(stream_id) = reply.keys.into_iter().flat_map(|key| key.ids).next() else {
+        return Ok(None);
+    };
+    let encoded: Option<String> = stream_id.get("entry");
+    match encoded.as_deref().map(serde_json::from_str) {
+        Some(Ok(entry)) => Ok(Some((stream_id.id, entry))),
+        _ => {
+            tracing::error!("[JobQueue] drop invalid job {}: {encoded:?}", stream_id.id);
+            finish(data, &stream_id.id)?;
+            // Read again so that the backlog is not mistaken as drained
+            read(data, consumer, id)
+        }
+    }
+}
+
+fn finish(data: &AppData, id: &str) -> anyhow::Result<()> {
+    let mut conn = data.cacher.get_conn();
+    let _: usize = conn.xack(JOB_QUEUE, WORKER_GROUP, &[id])?;
+    let _: usize = conn.xdel(JOB_QUEUE, &[id])?;
+    Ok(())
+}
+
+fn dead_letter(data: &AppData, entry: &JobEntry, error: &str) -> anyhow::Result<()> {
+    let _: String = data.cacher.get_conn().xadd_maxlen(
+        JOB_DEAD_LETTER,
+        StreamMaxlen::Approx(DEAD_LETTER_LEN),
+        "*",
+        &[
+            ("entry", serde_json::to_string(entry)?),
+            ("error", error.to_string()),
+        ],
+    )?;
+    Ok(())
+}
+
+/// Start the workers running the queued jobs with the bot.
+pub fn spawn_workers(bot: Bot, data: AppData, config: &JobQueueConfig) {
+    if let Err(err) = create_group(&data) {
+        tracing::error!("[JobQueue] fail to create the worker group: {err}");
+        return;
+    }
+    for index in 0..config.workers {
+        let (bot, data) = (bot.clone(), data.clone());
+        tokio::spawn(async move {
+            let consumer = format!("worker-{index}");
+            // Jobs taken before the restart are run first
+            let mut backlog = true;
+            let mut interval = tokio::time::interval(POLL_INTERVAL);
+            loop {
+                let next = read(&data, &consumer, if backlog { "0" } else { ">" });
+                match next {
+                    Ok(Some((id, entry))) => work(&bot, &data, &id, entry).await,
+                    Ok(None) if backlog => backlog = false,
+                    Ok(None) => {
+                        interval.tick().await;
+                    }
+                    Err(err) => {
+                        tracing::error!("[JobQueue] {consumer} fail to read the queue: {err}");
+                        interval.tick().await;
+                    }
+                }
+            }
+        });
+    }
+}
+
+async fn work(bot: &Bot, data: &AppData, id: &str, mut entry: JobEntry) {
+    let Ok(target) = entry.target.parse::<ChatTarget>() else {
+        tracing::error!(
+            "[JobQueue] drop job {id} of invalid target {}",
+            entry.target
+        );
+        if let Err(err) = finish(data, id) {
+            tracing::error!("[JobQueue] fail to finish job {id}: {err}");
+        }
+        return;
+    };
+    let ctx = JobContext {
+        bot: bot.clone(),
+        data: data.clone(),
+        target,
+        lang: i18n::normalize(&entry.lang).unwrap_or(i18n::DEFAULT_LANG),
+        status_message: MessageId(entry.status_message),
+    };
+    let name = entry.job.name();
+    let lang = ctx.lang;
+
+    let result = run(&ctx, &entry.job).await;
+    let handled = match result {
+        Ok(()) => {
+            metrics::observe_job(name, "done");
+            if let Err(err) = bot.delete_message(target.chat_id, ctx.status_message).await {
+                tracing::debug!("[JobQueue] fail to delete the status of job {id}: {err}");
+            }
+            finish(data, id)
+        }
+        Err(err) => {
+            entry.attempts += 1;
+            let max_attempts = Config::get_global_config().job_queue.max_attempts;
+            tracing::warn!(
+                "[JobQueue] job {id} {name} failed {}/{max_attempts}: {err:#}",
+                entry.attempts
+            );
+            match Retry::after(entry.attempts, max_attempts) {
+                Retry::Again => {
+                    metrics::observe_job(name, "retried");
+                    ctx.progress(crate::t!(
+                        lang,
+                        "job_queue.retrying",
+                        attempts = entry.attempts,
+                        max = max_attempts,
+                        error = format!("{err:#}")
+                    ))
+                    .await;
+                    enqueue(data, &entry).and_then(|_| finish(data, id))
+                }
+                Retry::DeadLetter => {
+                    metrics::observe_job(name, "dead");
+                    ctx.progress(crate::t!(
+                        lang,
+                        "job_queue.failed",
+                        error = format!("{err:#}")
+                    ))
+                    .await;
+                    dead_letter(data, &entry, &format!("{err:#}")).and_then(|_| finish(data, id))
+                }
+            }
+        }
+    };
+    if let Err(err) = handled {
+        tracing::error!("[JobQueue] fail to finish job {id}: {err}");
+    }
+}
+
+async fn run(ctx: &JobContext, job: &Job) -> anyhow::Result<()> {
+    match job {
+        Job::DownloadVideo { url } => download_video(ctx, url).await,
+    }
+}
+
+async fn download_video(ctx: &JobContext, url: &str) -> anyhow::Result<()> {
+    let lang = ctx.lang;
+    ctx.progress(crate::t!(lang, "ytdlp.downloading")).await;
+    let video = YtdlpVideo::dl_from_url(url).await?;
+
+    ctx.progress(if video.maybe_playlist {
+        crate::t!(lang, "ytdlp.uploading_playlist")
+    } else {
+        crate::t!(lang, "ytdlp.uploading")
+    })
+    .await;
+    let sent = ctx
+        .data
+        .call(ctx.target.chat_id, || {
+            ctx.bot
+                .send_video_to(ctx.target, InputFile::file(&video.filename))
+                .caption(video.as_tg_video_caption())
+                .parse_mode(ParseMode::Html)
+                .width(video.width)
+                .height(video.height)
+                .thumbnail(InputFile::file(&video.thumbnail_filepath))
+        })
+        .await;
+
+    // Clean up before handling the send result, the retry downloads the video again
+    video.clean().await?;
+    sent?;
+    Ok(())
+}
+
+#[test]
+fn test_job_entry() {
+    let entry = JobEntry {
+        job: Job::DownloadVideo {
+            url: "https://example.com/video".to_string(),
+        },
+        target: "-100:42".to_string(),
+        status_message: 7,
+        lang: "en".to_string(),
+        attempts: 0,
+    };
+    let encoded = serde_json::to_string(&entry).unwrap();
+    assert!(encoded.contains(r#""job":"download_video""#));
+    assert_eq!(serde_json::from_str::<JobEntry>(&encoded).unwrap(), entry);
+
+    assert_eq!(Retry::after(1, 3), Retry::Again);
+    assert_eq!(Retry::after(3, 3), Retry::DeadLetter);
+    assert_eq!(Retry::after(1, 1), Retry::DeadLetter);
+}
@@ -1,7 +1,41 @@
+pub mod album;
+pub mod api;
 pub mod app;
+pub mod args;
+pub mod bot_api;
 pub mod cache;
+pub mod callback;
+pub mod chat_migration;
+pub mod command;
 pub mod config;
+pub mod delayed_task;
+pub mod dialogue;
+pub mod digest;
+pub mod doctor;
+pub mod dry_run;
+pub mod error_sink;
 pub mod event;
 pub mod helper;
 pub mod http;
+pub mod i18n;
+pub mod idempotency;
+pub mod inline;
+pub mod job_queue;
+pub mod logging;
+pub mod media_cache;
+pub mod metrics;
+pub mod module;
 pub mod modules;
+pub mod paginator;
+pub mod quiet_hours;
+pub mod role;
+pub mod send_queue;
+pub mod settings;
+pub mod startup;
+pub mod storage;
+pub mod telemetry;
+pub mod template;
+#[cfg(feature = "testkit")]
+pub mod testkit;
+pub mod topic;
+pub mod usage;
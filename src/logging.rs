@@ -0,0 +1,182 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use tracing_appender::{non_blocking::WorkerGuard, rolling};
+use tracing_subscriber::{
+    layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer, Registry,
+};
+
+use crate::config::{Config, LogFileConfig, LogFormat, LogRotation};
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// Install the global logger. Logs are flushed to the file only while the returned guard is
+/// alive.
+pub fn init(config: &Config) -> anyhow::Result<Option<WorkerGuard>> {
+    let filter = filter_directives(config);
+    let filter = EnvFilter::try_new(&filter)
+        .with_context(|| format!("invalid log level directives `{filter}`"))?;
+
+    let mut layers: Vec<BoxedLayer> = vec![format_layer(config.log.format, io::stdout, true)];
+    let mut guard = None;
+    if let Some(file) = &config.log.file {
+        let (writer, file_guard) = tracing_appender::non_blocking(file_writer(file)?);
+        layers.push(format_layer(config.log.format, writer, false));
+        guard = Some(file_guard);
+    }
+    if config.sentry.is_some() {
+        layers.push(Box::new(crate::telemetry::layer()));
+    }
+
+    tracing_subscriber::registry()
+        .with(layers)
+        .with(filter)
+        .try_init()?;
+    Ok(guard)
+}
+
+fn format_layer<W>(format: LogFormat, writer: W, ansi: bool) -> BoxedLayer
+where
+    W: for<'writer> tracing_subscriber::fmt::MakeWriter<'writer> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(ansi);
+    match format {
+        LogFormat::Pretty => Box::new(layer),
+        LogFormat::Json => Box::new(layer.json()),
+    }
+}
+
+/// Build the filter like `info,teloxide=warn`. `RUST_LOG` replaces the config when set.
+fn filter_directives(config: &Config) -> String {
+    if let Ok(directives) = std::env::var("RUST_LOG") {
+        return directives;
+    }
+
+    let level = config.log.level.as_deref().unwrap_or(&config.log_level);
+    let mut modules = config.log.modules.iter().collect::<Vec<_>>();
+    modules.sort();
+    std::iter::once(level.to_lowercase())
+        .chain(
+            modules
+                .into_iter()
+                .map(|(module, level)| format!("{module}={}", level.to_lowercase())),
+        )
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn file_writer(config: &LogFileConfig) -> anyhow::Result<Box<dyn Write + Send>> {
+    fs::create_dir_all(&config.dir)
+        .with_context(|| format!("fail to create log directory {}", config.dir))?;
+
+    let rotation = match config.rotation {
+        LogRotation::Size => {
+            let path = Path::new(&config.dir).join(&config.prefix);
+            let file =
+                SizeRotatingFile::open(path, config.max_size * 1024 * 1024, config.max_files)?;
+            return Ok(Box::new(file));
+        }
+        LogRotation::Hourly => rolling::Rotation::HOURLY,
+        LogRotation::Daily => rolling::Rotation::DAILY,
+        LogRotation::Never => rolling::Rotation::NEVER,
+    };
+    let appender = rolling::Builder::new()
+        .rotation(rotation)
+        .filename_prefix(&config.prefix)
+        .max_log_files(config.max_files)
+        .build(&config.dir)
+        .with_context(|| "fail to create log file")?;
+    Ok(Box::new(appender))
+}
+
+/// Log file that is renamed to `name.1` when it grows over the size limit, and the older files
+/// are shifted to `name.2`, `name.3`... until `max_files` are kept.
+struct SizeRotatingFile {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    max_size: u64,
+    max_files: usize,
+}
+
+impl SizeRotatingFile {
+    fn open(path: PathBuf, max_size: u64, max_files: usize) -> io::Result<Self> {
+        let file = File::options().create(true).append(true).open(&path)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            path,
+            file,
+            written,
+            max_size,
+            max_files,
+        })
+    }
+
+    fn backup(&self, index: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{index}"));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let keep = self.max_files.saturating_sub(1);
+        if keep == 0 {
+            self.file.set_len(0)?;
+        } else {
+            for index in (1..keep).rev() {
+                let from = self.backup(index);
+                if from.exists() {
+                    fs::rename(from, self.backup(index + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.backup(1))?;
+            self.file = File::options().create(true).append(true).open(&self.path)?;
+        }
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl Write for SizeRotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written > 0 && self.written + buf.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[test]
+fn test_size_rotating_file() {
+    let dir = std::env::temp_dir().join("tg-maid-log-test");
+    fs::remove_dir_all(&dir).ok();
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("maid.log");
+
+    let mut file = SizeRotatingFile::open(path.clone(), 10, 3).unwrap();
+    for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+        file.write_all(line.as_bytes()).unwrap();
+    }
+    file.flush().unwrap();
+
+    let read = |name: &str| fs::read_to_string(dir.join(name)).unwrap();
+    assert_eq!(read("maid.log"), "fourth\n");
+    assert_eq!(read("maid.log.1"), "third\n");
+    assert_eq!(read("maid.log.2"), "second\n");
+    assert!(!dir.join("maid.log.3").exists());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
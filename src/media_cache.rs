@@ -0,0 +1,151 @@
+use std::future::IntoFuture;
+
+use base64::Engine;
+use redis::Commands;
+use sha2::{Digest, Sha256};
+use teloxide::{
+    types::{ChatId, InputFile, Message},
+    Bot, RequestError,
+};
+
+use crate::app::AppData;
+
+/// Forget the file id after 30 days without use, Telegram keeps the files much longer
+const FILE_ID_TTL: u64 = 60 * 60 * 24 * 30;
+
+/// The file ids only work for the bot that uploaded the file, so the key contains the bot id.
+fn media_key(bot: &Bot, hash: &str) -> String {
+    let bot_id = bot.token().split(':').next().unwrap_or_default();
+    format!("MEDIA_FILE_ID:{bot_id}:{hash}")
+}
+
+/// Hash of the file content, used to recognize the same file.
+pub fn content_hash(content: &[u8]) -> String {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(Sha256::digest(content))
+}
+
+/// The file id of the photo, sticker, video, animation or document in the message.
+pub fn file_id_of(msg: &Message) -> Option<String> {
+    let photo = msg
+        .photo()
+        .and_then(|sizes| sizes.iter().max_by_key(|size| size.width))
+        .map(|size| &size.file);
+    photo
+        .or_else(|| msg.sticker().map(|sticker| &sticker.file))
+        .or_else(|| msg.video().map(|video| &video.file))
+        .or_else(|| msg.animation().map(|animation| &animation.file))
+        .or_else(|| msg.document().map(|document| &document.file))
+        .map(|file| file.id.to_string())
+}
+
+/// The file id of the content uploaded by the bot before.
+pub fn lookup(data: &AppData, bot: &Bot, content: &[u8]) -> anyhow::Result<Option<String>> {
+    let key = media_key(bot, &content_hash(content));
+    let cached: Option<String> = data.cacher.get_conn().get(&key)?;
+    if cached.is_some() {
+        let () = data.cacher.get_conn().expire(&key, FILE_ID_TTL as i64)?;
+    }
+    Ok(cached)
+}
+
+/// Remember the file id of the content just uploaded by the bot.
+pub fn remember(data: &AppData, bot: &Bot, content: &[u8], file_id: &str) -> anyhow::Result<()> {
+    let key = media_key(bot, &content_hash(content));
+    let () = data.cacher.get_conn().set_ex(&key, file_id, FILE_ID_TTL)?;
+    Ok(())
+}
+
+/// Forget the file id Telegram rejects, the content is uploaded again the next time.
+pub fn forget(data: &AppData, bot: &Bot, content: &[u8]) -> anyhow::Result<()> {
+    let () = data
+        .cacher
+        .get_conn()
+        .del(media_key(bot, &content_hash(content)))?;
+    Ok(())
+}
+
+/// Send the file content by `send` through [`AppData::reply`], reusing the Telegram file id when
+/// the same content was uploaded before. The file id of a new upload is remembered for the next
+/// time.
+///
+/// ```ignore
+/// let image = make_image()?;
+/// media_cache::send_cached(&data, &bot, chat_id, image, |file| bot.send_photo(chat_id, file))
+///     .await?;
+/// ```
+pub async fn send_cached<F, Req>(
+    data: &AppData,
+    bot: &Bot,
+    chat_id: ChatId,
+    content: Vec<u8>,
+    send: F,
+) -> anyhow::Result<Message>
+where
+    F: Fn(InputFile) -> Req,
+    Req: IntoFuture<Output = Result<Message, RequestError>> + Clone + Send + 'static,
+    Req::IntoFuture: Send + 'static,
+{
+    if let Some(file_id) = lookup(data, bot, &content)? {
+        let err = match data.reply(chat_id, send(InputFile::file_id(file_id))).await {
+            Ok(msg) => return Ok(msg),
+            Err(err) => err,
+        };
+        // The file might be gone, upload it again
+        let Some(RequestError::Api(api_err)) = err.downcast_ref::<RequestError>() else {
+            return Err(err);
+        };
+        tracing::warn!("cached file id is rejected, uploading again: {api_err}");
+        forget(data, bot, &content)?;
+    }
+
+    let msg = data
+        .reply(chat_id, send(InputFile::memory(content.clone())))
+        .await?;
+    if let Some(file_id) = file_id_of(&msg) {
+        remember(data, bot, &content, &file_id)?;
+    }
+    Ok(msg)
+}
+
+#[tokio::test]
+async fn test_send_cached() {
+    use crate::testkit::{self, FakeTelegram};
+    use serde_json::json;
+    use teloxide::prelude::*;
+
+    let telegram = FakeTelegram::start();
+    let bot = telegram.bot();
+    let data = testkit::app_data(bot.clone()).await;
+    telegram.respond(
+        "sendPhoto",
+        json!({
+            "message_id": 1,
+            "date": 0,
+            "chat": { "id": -100, "type": "supergroup", "title": "Group" },
+            "photo": [
+                { "file_id": "small", "file_unique_id": "s", "width": 90, "height": 90 },
+                { "file_id": "large", "file_unique_id": "l", "width": 512, "height": 512 },
+            ],
+        }),
+    );
+
+    let image = b"not really a png".to_vec();
+    for _ in 0..2 {
+        send_cached(&data, &bot, ChatId(-100), image.clone(), |file| {
+            bot.send_photo(ChatId(-100), file)
+        })
+        .await
+        .unwrap();
+    }
+
+    // The first send uploads the image, the second one sends the cached file id
+    let calls = telegram.calls_to("sendPhoto");
+    assert_eq!(calls.len(), 2);
+    assert_eq!(calls[0].chat_id(), Some(-100));
+    let uploaded = calls[0].params["photo"].as_str().unwrap();
+    assert!(uploaded.starts_with("attach://"));
+    assert_eq!(calls[1].params["photo"], "large");
+    let key = media_key(&bot, &content_hash(&image));
+    let cached: String = data.cacher.get_conn().get(key).unwrap();
+    assert_eq!(cached, "large");
+}
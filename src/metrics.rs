@@ -0,0 +1,163 @@
+use std::time::Duration;
+
+use prometheus::{
+    register_histogram_vec, register_int_counter_vec, Encoder, HistogramVec, IntCounterVec,
+    TextEncoder,
+};
+use teloxide::RequestError;
+
+lazy_static::lazy_static!(
+    static ref UPDATES: IntCounterVec = register_int_counter_vec!(
+        "tg_maid_updates_total",
+        "Updates handled by the bot, labeled by the command or the update kind",
+        &["command"]
+    )
+    .unwrap();
+    static ref HANDLER_DURATION: HistogramVec = register_histogram_vec!(
+        "tg_maid_handler_duration_seconds",
+        "Time to handle an update, labeled by the command or the update kind",
+        &["command"]
+    )
+    .unwrap();
+    static ref TELEGRAM_ERRORS: IntCounterVec = register_int_counter_vec!(
+        "tg_maid_telegram_errors_total",
+        "Failed Telegram API calls, labeled by the error kind",
+        &["error"]
+    )
+    .unwrap();
+    static ref REDIS_DURATION: HistogramVec = register_histogram_vec!(
+        "tg_maid_redis_duration_seconds",
+        "Latency of the Redis commands",
+        &["command"],
+        vec![0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0]
+    )
+    .unwrap();
+    static ref HTTP_REQUESTS: IntCounterVec = register_int_counter_vec!(
+        "tg_maid_http_requests_total",
+        "Requests sent by the HTTP client, labeled by host and status code",
+        &["host", "status"]
+    )
+    .unwrap();
+    static ref WATCHER_TICKS: IntCounterVec = register_int_counter_vec!(
+        "tg_maid_watcher_ticks_total",
+        "Runs of the event watchers, labeled by the result",
+        &["watcher", "result"]
+    )
+    .unwrap();
+    static ref WATCHER_DURATION: HistogramVec = register_histogram_vec!(
+        "tg_maid_watcher_duration_seconds",
+        "Time of each event watcher run",
+        &["watcher"]
+    )
+    .unwrap();
+    static ref API_FAILURES: IntCounterVec = register_int_counter_vec!(
+        "tg_maid_api_failures_total",
+        "Failed Telegram API calls made through the app data, labeled by the error kind and \
+        whether it is retried, cleaned up or returned",
+        &["error", "action"]
+    )
+    .unwrap();
+    static ref JOBS: IntCounterVec = register_int_counter_vec!(
+        "tg_maid_jobs_total",
+        "Background jobs run by the workers, labeled by the job and the result",
+        &["job", "result"]
+    )
+    .unwrap();
+);
+
+pub fn observe_update(command: &str, elapsed: Duration) {
+    UPDATES.with_label_values(&[command]).inc();
+    HANDLER_DURATION
+        .with_label_values(&[command])
+        .observe(elapsed.as_secs_f64());
+}
+
+/// Count the error if it comes from the Telegram API.
+pub fn observe_error(err: &anyhow::Error) {
+    if let Some(err) = err.downcast_ref::<RequestError>() {
+        TELEGRAM_ERRORS
+            .with_label_values(&[&request_error_label(err)])
+            .inc();
+    }
+}
+
+/// Count the failed API call made by [`crate::app::AppData::call`] or
+/// [`crate::app::AppData::submit`].
+pub fn observe_api_failure(err: &RequestError, action: &str) {
+    API_FAILURES
+        .with_label_values(&[&request_error_label(err), action])
+        .inc();
+}
+
+pub fn observe_redis(command: &str, elapsed: Duration) {
+    REDIS_DURATION
+        .with_label_values(&[command])
+        .observe(elapsed.as_secs_f64());
+}
+
+pub fn observe_http(host: &str, status: &str) {
+    HTTP_REQUESTS.with_label_values(&[host, status]).inc();
+}
+
+pub fn observe_watcher(watcher: &str, succeed: bool, elapsed: Duration) {
+    let result = if succeed { "ok" } else { "error" };
+    WATCHER_TICKS.with_label_values(&[watcher, result]).inc();
+    WATCHER_DURATION
+        .with_label_values(&[watcher])
+        .observe(elapsed.as_secs_f64());
+}
+
+/// Count the job run, `result` is `done`, `retried` or `dead`.
+pub fn observe_job(job: &str, result: &str) {
+    JOBS.with_label_values(&[job, result]).inc();
+}
+
+/// Render all the metrics in the Prometheus text format.
+pub fn render() -> String {
+    let mut buffer = Vec::new();
+    if let Err(err) = TextEncoder::new().encode(&prometheus::gather(), &mut buffer) {
+        tracing::error!("fail to encode metrics: {err}");
+    }
+    String::from_utf8(buffer).unwrap_or_default()
+}
+
+/// Use the variant name as the label, so that the label doesn't contain user data.
+fn request_error_label(err: &RequestError) -> String {
+    let label = match err {
+        RequestError::Api(api) => return format!("{api:?}").split('(').next().unwrap().to_string(),
+        RequestError::MigrateToChatId(_) => "MigrateToChatId",
+        RequestError::RetryAfter(_) => "RetryAfter",
+        RequestError::Network(_) => "Network",
+        RequestError::InvalidJson { .. } => "InvalidJson",
+        RequestError::Io(_) => "Io",
+    };
+    label.to_string()
+}
+
+/// Get the command name from the RESP encoded command, like `*2\r\n$3\r\nGET\r\n$3\r\nfoo\r\n`.
+pub fn redis_command_name(packed: &[u8]) -> String {
+    let name = packed
+        .split(|c| *c == b'\n')
+        .nth(2)
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .filter(|name| !name.is_empty() && name.iter().all(u8::is_ascii_alphabetic))
+        .map(|name| String::from_utf8_lossy(name).to_uppercase());
+    name.unwrap_or_else(|| "UNKNOWN".to_string())
+}
+
+#[test]
+fn test_labels() {
+    assert_eq!(
+        redis_command_name(b"*2\r\n$3\r\nget\r\n$3\r\nfoo\r\n"),
+        "GET"
+    );
+    assert_eq!(redis_command_name(b"*1\r\n$4\r\nPING\r\n"), "PING");
+    assert_eq!(redis_command_name(b"garbage"), "UNKNOWN");
+
+    let api = RequestError::Api(teloxide::ApiError::BotBlocked);
+    assert_eq!(request_error_label(&api), "BotBlocked");
+    let unknown = RequestError::Api(teloxide::ApiError::Unknown("secret".to_string()));
+    assert_eq!(request_error_label(&unknown), "Unknown");
+    let retry = RequestError::RetryAfter(teloxide::types::Seconds::from_seconds(3));
+    assert_eq!(request_error_label(&retry), "RetryAfter");
+}
@@ -0,0 +1,381 @@
+use std::sync::OnceLock;
+
+use teloxide::{
+    dispatching::UpdateHandler,
+    dptree,
+    prelude::{Bot, Message},
+    types::{ChatId, Me, MessageReactionUpdated, PollAnswer},
+};
+
+use crate::{
+    album::Album,
+    app::AppData,
+    callback::CallbackRouter,
+    command::{CommandInfo, CommandRegistry},
+    config::Config,
+    dialogue::DialogueRouter,
+    inline::InlineRouter,
+    settings::{ModuleInfo, SettingSchema},
+};
+
+static REGISTRY: OnceLock<ModuleRegistry> = OnceLock::new();
+
+/// A command and the endpoint handling it.
+pub struct Command {
+    pub info: CommandInfo,
+    handler: UpdateHandler<anyhow::Error>,
+    /// Matched before the running dialogue, for the commands that work on the dialogue
+    stateful: bool,
+}
+
+impl Command {
+    pub fn new(info: CommandInfo, handler: UpdateHandler<anyhow::Error>) -> Self {
+        Self {
+            info,
+            handler,
+            stateful: false,
+        }
+    }
+
+    /// Handle the command even when the user is in a dialogue, like `/cancel`.
+    pub fn stateful(mut self) -> Self {
+        self.stateful = true;
+        self
+    }
+}
+
+/// The updates that a module sees in its `on_*` hooks, checked by the dispatcher before calling
+/// them. The poll answers don't tell the chat, so they are not filtered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpdateFilter {
+    /// The updates in the private chats with the bot
+    pub private_chats: bool,
+    /// The updates in the groups and the channels
+    pub group_chats: bool,
+    /// The messages sent by the other bots
+    pub bot_messages: bool,
+}
+
+impl UpdateFilter {
+    /// Everything, the default of the modules.
+    pub const ALL: Self = Self {
+        private_chats: true,
+        group_chats: true,
+        bot_messages: true,
+    };
+    /// The messages of the humans in the groups, like what the moderation modules look at.
+    pub const GROUP_MEMBERS: Self = Self {
+        private_chats: false,
+        group_chats: true,
+        bot_messages: false,
+    };
+
+    /// Private chats have the positive id as the user.
+    pub fn matches_chat(&self, chat_id: ChatId) -> bool {
+        if chat_id.is_user() {
+            self.private_chats
+        } else {
+            self.group_chats
+        }
+    }
+
+    pub fn matches(&self, msg: &Message) -> bool {
+        let from_bot = msg.from.as_ref().is_some_and(|user| user.is_bot);
+        self.matches_chat(msg.chat.id) && (self.bot_messages || !from_bot)
+    }
+}
+
+/// A feature of the bot. Everything the feature needs is declared here, and the dispatcher,
+/// help message, settings menu and watchers are generated from the [`ModuleRegistry`].
+#[async_trait::async_trait]
+pub trait BotModule: Send + Sync + 'static {
+    /// Unique name of the module, also used as the key in the chat settings.
+    fn name(&self) -> &'static str;
+
+    /// Modules with a description can be turned on or off by the chat admin in `/settings`, and
+    /// by `disabled_modules` in the config.
+    fn description(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Opt-in modules are off in every chat until the chat admin turns them on.
+    fn opt_in(&self) -> bool {
+        false
+    }
+
+    /// The settings of the module besides on and off, edited in the `/settings` menu by the
+    /// chat admin.
+    fn settings(&self) -> Vec<SettingSchema> {
+        Vec::new()
+    }
+
+    /// The updates the module sees in the `on_*` hooks.
+    fn update_filter(&self) -> UpdateFilter {
+        UpdateFilter::ALL
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        Vec::new()
+    }
+
+    /// Every enabled module sees the plain text message that is not a command.
+    async fn on_message(&self, _bot: &Bot, _data: &AppData, _msg: &Message) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Modules opt in to see the plain text message again when it is edited, except the edits
+    /// made by the bot itself.
+    async fn on_edited_message(
+        &self,
+        _bot: &Bot,
+        _data: &AppData,
+        _msg: &Message,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Every enabled module sees the reactions changed by the users in the chat.
+    async fn on_reaction(
+        &self,
+        _bot: &Bot,
+        _data: &AppData,
+        _reaction: &MessageReactionUpdated,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Every module sees the answers to the non-anonymous polls. The answer doesn't tell the
+    /// chat, so the module finds its own polls by the poll id.
+    async fn on_poll_answer(
+        &self,
+        _bot: &Bot,
+        _data: &AppData,
+        _answer: &PollAnswer,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Collect the albums for [`BotModule::on_album`]. Each album is held for a moment until all
+    /// its parts arrive, so it is only done when a module asks for it.
+    fn wants_albums(&self) -> bool {
+        false
+    }
+
+    /// Every enabled module wanting the albums sees the album once all its parts arrive, instead
+    /// of the separate messages of each photo or video.
+    async fn on_album(&self, _bot: &Bot, _data: &AppData, _album: &Album) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Clean up what the module keeps for the chat which blocks or removes the bot, the event
+    /// subscriptions are already removed. See [`crate::api`].
+    async fn on_chat_unreachable(&self, _data: &AppData, _chat_id: ChatId) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn callbacks(&self, router: CallbackRouter) -> CallbackRouter {
+        router
+    }
+
+    fn inline_queries(&self, router: InlineRouter) -> InlineRouter {
+        router
+    }
+
+    fn dialogues(&self, router: DialogueRouter) -> DialogueRouter {
+        router
+    }
+
+    /// Start the background tasks like the event watchers.
+    fn spawn_watchers(&self, _bot: &Bot, _data: &AppData, _config: &Config) {}
+
+    /// Problems of the config the module needs, like a missing API key. Only the modules not in
+    /// `disabled_modules` are checked.
+    fn check_config(&self, _config: &Config) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// All the modules of the bot, collected at startup.
+#[derive(Default)]
+pub struct ModuleRegistry {
+    modules: Vec<Box<dyn BotModule>>,
+    commands: Vec<Command>,
+    command_registry: CommandRegistry,
+}
+
+impl ModuleRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(mut self, module: impl BotModule) -> Self {
+        if self.get(module.name()).is_some() {
+            panic!("module {} is registered twice", module.name());
+        }
+        for mut command in module.commands() {
+            if module.description().is_some() {
+                command.info.module = Some(module.name().to_string());
+            }
+            self.command_registry.register(command.info.clone());
+            self.commands.push(command);
+        }
+        self.modules.push(Box::new(module));
+        self
+    }
+
+    /// Make the registry available to [`ModuleRegistry::global`].
+    pub fn install(self) -> &'static Self {
+        if REGISTRY.set(self).is_err() {
+            panic!("module registry is installed twice");
+        }
+        Self::global()
+    }
+
+    pub fn global() -> &'static Self {
+        Self::try_global().expect("module registry is not installed")
+    }
+
+    pub fn try_global() -> Option<&'static Self> {
+        REGISTRY.get()
+    }
+
+    fn get(&self, name: &str) -> Option<&dyn BotModule> {
+        self.modules
+            .iter()
+            .find(|module| module.name() == name)
+            .map(|module| module.as_ref())
+    }
+
+    pub fn modules(&self) -> impl Iterator<Item = &dyn BotModule> {
+        self.modules.iter().map(|module| module.as_ref())
+    }
+
+    /// The modules that can be turned on or off, in the order of registration.
+    pub fn toggleable(&self) -> Vec<ModuleInfo> {
+        self.modules()
+            .filter_map(|module| {
+                Some(ModuleInfo {
+                    name: module.name(),
+                    description: module.description()?,
+                    opt_in: module.opt_in(),
+                })
+            })
+            .collect()
+    }
+
+    /// The settings of all the modules with the module names, in the order of registration.
+    pub fn settings(&self) -> Vec<(&'static str, SettingSchema)> {
+        self.modules()
+            .flat_map(|module| {
+                module
+                    .settings()
+                    .into_iter()
+                    .map(move |setting| (module.name(), setting))
+            })
+            .collect()
+    }
+
+    pub fn command_registry(&self) -> &CommandRegistry {
+        &self.command_registry
+    }
+
+    /// Build the handler for the commands that are stateful or not.
+    pub fn command_handler(&self, stateful: bool) -> UpdateHandler<anyhow::Error> {
+        self.commands
+            .iter()
+            .filter(|command| command.stateful == stateful)
+            .fold(dptree::entry(), |handler, command| {
+                let name = command.info.name.clone();
+                handler.branch(
+                    dptree::filter(move |msg: Message, me: Me| {
+                        msg.text()
+                            .is_some_and(|text| is_command(text, &name, me.username()))
+                    })
+                    .chain(command.handler.clone()),
+                )
+            })
+    }
+
+    /// The modules not turned off by `disabled_modules` in the config.
+    fn enabled_in<'a>(&'a self, config: &'a Config) -> impl Iterator<Item = &'a dyn BotModule> {
+        self.modules()
+            .filter(|module| !config.module_disabled(module.name()))
+    }
+
+    /// Build the router with the callbacks of the enabled modules. The routes remember their
+    /// module, so that they are skipped once the module is disabled by a reload.
+    pub fn callback_router(&self, router: CallbackRouter) -> CallbackRouter {
+        let config = Config::get_global_config();
+        self.enabled_in(&config).fold(router, |router, module| {
+            module.callbacks(router.owned_by(module.name()))
+        })
+    }
+
+    pub fn inline_router(&self, router: InlineRouter) -> InlineRouter {
+        let config = Config::get_global_config();
+        self.enabled_in(&config).fold(router, |router, module| {
+            module.inline_queries(router.owned_by(module.name()))
+        })
+    }
+
+    pub fn dialogue_router(&self, router: DialogueRouter) -> DialogueRouter {
+        let config = Config::get_global_config();
+        self.enabled_in(&config).fold(router, |router, module| {
+            module.dialogues(router.owned_by(module.name()))
+        })
+    }
+
+    /// Start the watchers of the enabled modules. Enabling a module by a reload doesn't start
+    /// them, the bot should be restarted.
+    pub fn spawn_watchers(&self, bot: &Bot, data: &AppData, config: &Config) {
+        for module in self.enabled_in(config) {
+            module.spawn_watchers(bot, data, config);
+        }
+    }
+}
+
+/// The command the text calls, like `weather` of `/weather`, `/weather 上海` or
+/// `/weather@this_bot`. Commands mentioning other bots are ignored.
+pub fn called_command<'a>(text: &'a str, bot_username: &str) -> Option<&'a str> {
+    let called = text
+        .strip_prefix('/')
+        .and_then(|text| text.split([' ', '\n']).next())?;
+    let (called, mention) = called.split_once('@').unwrap_or((called, bot_username));
+    mention.eq_ignore_ascii_case(bot_username).then_some(called)
+}
+
+/// Return true if the text calls the command, the name is case-insensitive.
+fn is_command(text: &str, name: &str, bot_username: &str) -> bool {
+    called_command(text, bot_username).is_some_and(|called| called.eq_ignore_ascii_case(name))
+}
+
+#[test]
+fn test_is_command() {
+    assert!(is_command("/weather", "weather", "maid_bot"));
+    assert!(is_command("/Weather 上海", "weather", "maid_bot"));
+    assert!(is_command(
+        "/weather@Maid_Bot\nhello",
+        "weather",
+        "maid_bot"
+    ));
+    assert!(!is_command("/weather@other_bot", "weather", "maid_bot"));
+    assert!(!is_command("/weathers", "weather", "maid_bot"));
+    assert!(!is_command("weather", "weather", "maid_bot"));
+    assert_eq!(called_command("/Ban@maid_bot 42", "maid_bot"), Some("Ban"));
+    assert_eq!(called_command("/ban@other_bot", "maid_bot"), None);
+}
+
+#[test]
+fn test_update_filter() {
+    let message = |chat_id, is_bot| {
+        let mut update = crate::testkit::text_update(chat_id, 42, "hello");
+        update["message"]["from"]["is_bot"] = serde_json::json!(is_bot);
+        serde_json::from_value::<Message>(update["message"].clone()).unwrap()
+    };
+
+    assert!(UpdateFilter::ALL.matches(&message(42, false)));
+    assert!(UpdateFilter::ALL.matches(&message(-100, true)));
+    assert!(!UpdateFilter::GROUP_MEMBERS.matches(&message(42, false)));
+    assert!(!UpdateFilter::GROUP_MEMBERS.matches(&message(-100, true)));
+    assert!(UpdateFilter::GROUP_MEMBERS.matches(&message(-100, false)));
+}
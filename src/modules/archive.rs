@@ -0,0 +1,395 @@
+use std::fmt::Write;
+
+use chrono::{DateTime, Local, TimeZone};
+use redis::Commands;
+use serde::Serialize;
+use teloxide::{types::Message, utils::html};
+
+use crate::{app::AppData, storage::Storage};
+
+/// Name of the module, it is opt-in so nothing is archived until the chat admin turns it on
+pub const MODULE: &str = "archive";
+/// Days to keep the messages when the chat doesn't set the retention
+pub const DEFAULT_RETENTION_DAYS: u32 = 30;
+/// The longest retention a chat can set
+pub const MAX_RETENTION_DAYS: u32 = 365;
+/// At most this many messages are exported at once
+pub const MAX_EXPORT_MESSAGES: i64 = 50_000;
+/// Characters of the message shown in the search result
+const SNIPPET_LEN: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ArchivedMessage {
+    pub message_id: i64,
+    pub user_id: Option<i64>,
+    pub username: String,
+    pub text: String,
+    /// Unix timestamp in seconds
+    pub sent_at: i64,
+}
+
+/// `message_id, user_id, username, text, sent_at` selected from the table
+type ArchivedRow = (i64, Option<i64>, String, String, i64);
+
+impl From<ArchivedRow> for ArchivedMessage {
+    fn from((message_id, user_id, username, text, sent_at): ArchivedRow) -> Self {
+        Self {
+            message_id,
+            user_id,
+            username,
+            text,
+            sent_at,
+        }
+    }
+}
+
+impl ArchivedMessage {
+    /// Hide who sent the message.
+    pub fn anonymize(mut self) -> Self {
+        self.user_id = None;
+        self.username = "anonymous".to_string();
+        self
+    }
+
+    /// The start of the text for the search result.
+    pub fn snippet(&self) -> String {
+        let mut snippet = self.text.chars().take(SNIPPET_LEN).collect::<String>();
+        if self.text.chars().count() > SNIPPET_LEN {
+            snippet.push('…');
+        }
+        snippet.replace('\n', " ")
+    }
+
+    pub fn time(&self) -> String {
+        Local
+            .timestamp_opt(self.sent_at, 0)
+            .single()
+            .map_or_else(String::new, |time| {
+                time.format("%Y-%m-%d %H:%M").to_string()
+            })
+    }
+}
+
+/// How active the chat is since some time.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChatStats {
+    pub messages: i64,
+    pub senders: i64,
+    /// Name and message count of the most active senders
+    pub top_senders: Vec<(String, i64)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Html,
+    Json,
+}
+
+fn retention_key(chat_id: i64) -> String {
+    format!("ARCHIVE_RETENTION:{chat_id}")
+}
+
+fn opt_out_key(chat_id: i64) -> String {
+    format!("ARCHIVE_OPT_OUT:{chat_id}")
+}
+
+pub fn get_retention(data: &AppData, chat_id: i64) -> anyhow::Result<u32> {
+    let days: Option<u32> = data.cacher.get_conn().get(retention_key(chat_id))?;
+    Ok(days.unwrap_or(DEFAULT_RETENTION_DAYS))
+}
+
+pub fn set_retention(data: &AppData, chat_id: i64, days: u32) -> anyhow::Result<()> {
+    if days == 0 || days > MAX_RETENTION_DAYS {
+        anyhow::bail!("retention should be 1 to {MAX_RETENTION_DAYS} days");
+    }
+    let () = data.cacher.get_conn().set(retention_key(chat_id), days)?;
+    Ok(())
+}
+
+pub fn is_opted_out(data: &AppData, chat_id: i64, user_id: u64) -> anyhow::Result<bool> {
+    Ok(data
+        .cacher
+        .get_conn()
+        .sismember(opt_out_key(chat_id), user_id)?)
+}
+
+/// Stop or resume archiving the messages of the user in the chat. The archived messages are
+/// deleted when the user opts out. Return true if the user is opted out now.
+pub async fn toggle_opt_out(data: &AppData, chat_id: i64, user_id: u64) -> anyhow::Result<bool> {
+    let key = opt_out_key(chat_id);
+    let removed: bool = data.cacher.get_conn().srem(&key, user_id)?;
+    if removed {
+        return Ok(false);
+    }
+    let () = data.cacher.get_conn().sadd(&key, user_id)?;
+    sqlx::query("DELETE FROM archived_messages WHERE chat_id = $1 AND user_id = $2")
+        .bind(chat_id)
+        .bind(user_id as i64)
+        .execute(data.storage.pool())
+        .await?;
+    Ok(true)
+}
+
+/// Keep the text message, an edited message replaces the archived one.
+pub async fn archive(storage: &Storage, msg: &Message) -> anyhow::Result<()> {
+    let Some(text) = msg.text() else {
+        return Ok(());
+    };
+    let (user_id, username) = match msg.from.as_ref() {
+        Some(user) => (Some(user.id.0 as i64), user.full_name()),
+        None => (None, msg.chat.title().unwrap_or_default().to_string()),
+    };
+    sqlx::query(
+        "INSERT INTO archived_messages (chat_id, message_id, user_id, username, text, sent_at) \
+         VALUES ($1, $2, $3, $4, $5, $6) \
+         ON CONFLICT (chat_id, message_id) DO UPDATE SET text = excluded.text",
+    )
+    .bind(msg.chat.id.0)
+    .bind(msg.id.0 as i64)
+    .bind(user_id)
+    .bind(username)
+    .bind(text)
+    .bind(msg.date.timestamp())
+    .execute(storage.pool())
+    .await?;
+    Ok(())
+}
+
+/// The archived messages sent in `[from, to)`, oldest first.
+pub async fn messages(
+    storage: &Storage,
+    chat_id: i64,
+    from: DateTime<Local>,
+    to: DateTime<Local>,
+) -> anyhow::Result<Vec<ArchivedMessage>> {
+    let rows: Vec<ArchivedRow> = sqlx::query_as(
+        "SELECT message_id, user_id, username, text, sent_at FROM archived_messages \
+         WHERE chat_id = $1 AND sent_at >= $2 AND sent_at < $3 \
+         ORDER BY sent_at, message_id LIMIT $4",
+    )
+    .bind(chat_id)
+    .bind(from.timestamp())
+    .bind(to.timestamp())
+    .bind(MAX_EXPORT_MESSAGES)
+    .fetch_all(storage.pool())
+    .await?;
+    Ok(rows.into_iter().map(ArchivedMessage::from).collect())
+}
+
+/// The archived messages containing the query, newest first. The query is matched case
+/// insensitively for the ASCII letters.
+pub async fn search(
+    storage: &Storage,
+    chat_id: i64,
+    query: &str,
+    limit: i64,
+) -> anyhow::Result<Vec<ArchivedMessage>> {
+    let pattern = format!(
+        "%{}%",
+        query
+            .to_lowercase()
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    );
+    let rows: Vec<ArchivedRow> = sqlx::query_as(
+        "SELECT message_id, user_id, username, text, sent_at FROM archived_messages \
+         WHERE chat_id = $1 AND LOWER(text) LIKE $2 ESCAPE '\\' \
+         ORDER BY sent_at DESC, message_id DESC LIMIT $3",
+    )
+    .bind(chat_id)
+    .bind(pattern)
+    .bind(limit)
+    .fetch_all(storage.pool())
+    .await?;
+    Ok(rows.into_iter().map(ArchivedMessage::from).collect())
+}
+
+/// Count the archived messages since the time, with the `top` most active senders.
+pub async fn stats(
+    storage: &Storage,
+    chat_id: i64,
+    since: DateTime<Local>,
+    top: i64,
+) -> anyhow::Result<ChatStats> {
+    let (messages, senders): (i64, i64) = sqlx::query_as(
+        "SELECT COUNT(*), COUNT(DISTINCT user_id) FROM archived_messages \
+         WHERE chat_id = $1 AND sent_at >= $2",
+    )
+    .bind(chat_id)
+    .bind(since.timestamp())
+    .fetch_one(storage.pool())
+    .await?;
+    let top_senders: Vec<(String, i64)> = sqlx::query_as(
+        "SELECT MAX(username), COUNT(*) AS sent FROM archived_messages \
+         WHERE chat_id = $1 AND sent_at >= $2 \
+         GROUP BY user_id ORDER BY sent DESC LIMIT $3",
+    )
+    .bind(chat_id)
+    .bind(since.timestamp())
+    .bind(top)
+    .fetch_all(storage.pool())
+    .await?;
+    Ok(ChatStats {
+        messages,
+        senders,
+        top_senders,
+    })
+}
+
+/// Count the archived messages of the chat.
+pub async fn count(storage: &Storage, chat_id: i64) -> anyhow::Result<i64> {
+    let (count,): (i64,) =
+        sqlx::query_as("SELECT COUNT(*) FROM archived_messages WHERE chat_id = $1")
+            .bind(chat_id)
+            .fetch_one(storage.pool())
+            .await?;
+    Ok(count)
+}
+
+/// Delete every archived message of the chat, return how many are deleted.
+pub async fn purge_chat(storage: &Storage, chat_id: i64) -> anyhow::Result<u64> {
+    let result = sqlx::query("DELETE FROM archived_messages WHERE chat_id = $1")
+        .bind(chat_id)
+        .execute(storage.pool())
+        .await?;
+    Ok(result.rows_affected())
+}
+
+/// Link to the message, only the supergroups and channels have one for the members.
+pub fn message_link(chat_id: i64, message_id: i64) -> Option<String> {
+    let chat = chat_id.to_string();
+    let internal_id = chat.strip_prefix("-100")?;
+    Some(format!("https://t.me/c/{internal_id}/{message_id}"))
+}
+
+/// Delete the messages older than the retention of each chat, return how many are deleted.
+pub async fn purge_expired(data: &AppData) -> anyhow::Result<u64> {
+    let chats: Vec<(i64,)> = sqlx::query_as("SELECT DISTINCT chat_id FROM archived_messages")
+        .fetch_all(data.storage.pool())
+        .await?;
+    let now = chrono::Utc::now().timestamp();
+    let mut purged = 0;
+    for (chat_id,) in chats {
+        let retention = get_retention(data, chat_id)? as i64;
+        let result =
+            sqlx::query("DELETE FROM archived_messages WHERE chat_id = $1 AND sent_at < $2")
+                .bind(chat_id)
+                .bind(now - retention * 60 * 60 * 24)
+                .execute(data.storage.pool())
+                .await?;
+        purged += result.rows_affected();
+    }
+    Ok(purged)
+}
+
+/// Render the messages as the file content to send.
+pub fn render(title: &str, messages: &[ArchivedMessage], format: ExportFormat) -> Vec<u8> {
+    match format {
+        ExportFormat::Json => serde_json::to_vec_pretty(&serde_json::json!({
+            "chat": title,
+            "messages": messages,
+        }))
+        .expect("archived messages are always serializable"),
+        ExportFormat::Html => {
+            let mut page = format!(
+                "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{0}</title></head>\n\
+                 <body>\n<h1>{0}</h1>\n",
+                html::escape(title)
+            );
+            for msg in messages {
+                writeln!(
+                    page,
+                    "<p><small>{}</small> <b>{}</b><br>{}</p>",
+                    msg.time(),
+                    html::escape(&msg.username),
+                    html::escape(&msg.text).replace('\n', "<br>")
+                )
+                .unwrap();
+            }
+            page.push_str("</body>\n</html>\n");
+            page.into_bytes()
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_archive() {
+    use crate::testkit;
+
+    let data = testkit::app_data(teloxide::Bot::new("1000:fake-token")).await;
+    let mut update = testkit::text_update(-100, 42, "hello <world>");
+    let msg: Message = serde_json::from_value(update["message"].clone()).unwrap();
+    archive(&data.storage, &msg).await.unwrap();
+    update["message"]["text"] = serde_json::json!("hello again");
+    let edited: Message = serde_json::from_value(update["message"].clone()).unwrap();
+    archive(&data.storage, &edited).await.unwrap();
+
+    let (from, to) = (
+        msg.date - chrono::Duration::hours(1),
+        msg.date + chrono::Duration::hours(1),
+    );
+    let archived = messages(&data.storage, -100, from.into(), to.into())
+        .await
+        .unwrap();
+    assert_eq!(archived.len(), 1);
+    assert_eq!(archived[0].text, "hello again");
+    let page = render("test", &archived, ExportFormat::Html);
+    assert!(String::from_utf8(page).unwrap().contains("hello again"));
+
+    assert!(toggle_opt_out(&data, -100, 42).await.unwrap());
+    assert!(is_opted_out(&data, -100, 42).unwrap());
+    let archived = messages(&data.storage, -100, from.into(), to.into())
+        .await
+        .unwrap();
+    assert!(archived.is_empty());
+    assert!(!toggle_opt_out(&data, -100, 42).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_search_and_stats() {
+    use crate::testkit;
+
+    let data = testkit::app_data(teloxide::Bot::new("1000:fake-token")).await;
+    for (message_id, user_id, text) in [
+        (1, 42, "Rust 1.80 is released"),
+        (2, 43, "who uses rust?"),
+        (3, 42, "100% sure"),
+        (4, 43, "lunch"),
+        (5, 43, "dinner"),
+    ] {
+        let mut update = testkit::text_update(-100123, user_id, text);
+        update["message"]["message_id"] = serde_json::json!(message_id);
+        let msg: Message = serde_json::from_value(update["message"].clone()).unwrap();
+        archive(&data.storage, &msg).await.unwrap();
+    }
+
+    let found = search(&data.storage, -100123, "RUST", 10).await.unwrap();
+    assert_eq!(
+        found.iter().map(|msg| msg.message_id).collect::<Vec<_>>(),
+        [2, 1]
+    );
+    let found = search(&data.storage, -100123, "0%", 10).await.unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].text, "100% sure");
+    assert!(search(&data.storage, -100, "rust", 10)
+        .await
+        .unwrap()
+        .is_empty());
+
+    let since = Local::now() - chrono::Duration::days(1);
+    let stats = stats(&data.storage, -100123, since, 1).await.unwrap();
+    assert_eq!(stats.messages, 5);
+    assert_eq!(stats.senders, 2);
+    assert_eq!(stats.top_senders, [("User".to_string(), 3)]);
+
+    assert_eq!(
+        message_link(-100123, 7).as_deref(),
+        Some("https://t.me/c/123/7")
+    );
+    assert_eq!(message_link(-123, 7), None);
+
+    assert_eq!(count(&data.storage, -100123).await.unwrap(), 5);
+    assert_eq!(purge_chat(&data.storage, -100123).await.unwrap(), 5);
+    assert_eq!(count(&data.storage, -100123).await.unwrap(), 0);
+}
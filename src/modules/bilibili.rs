@@ -1,9 +1,12 @@
+use crate::http::HttpClient;
+use crate::quiet_hours::{self, Notification};
+use crate::send_queue::Priority;
+use crate::topic::{ChatTarget, SendTo};
 use crate::{app::AppData, config::Config, event::EventWatcher};
 use redis::Commands;
 use serde::Deserialize;
 use std::collections::HashMap;
-use teloxide::{payloads::SendPhotoSetters, prelude::Requester, types as tg_type};
-use crate::http::HttpClient;
+use teloxide::{payloads::SendPhotoSetters, types as tg_type};
 
 pub struct BiliApi;
 impl BiliApi {
@@ -37,7 +40,9 @@ pub fn spawn_bilibili_live_room_listener(bot: teloxide::Bot, data: AppData, conf
         .bot(bot)
         .data(data)
         .client(client)
-        .heartbeat_interval(120) // 2mins
+        .heartbeat_interval(config.watcher.bilibili_interval)
+        .interval_of(|config| config.watcher.bilibili_interval)
+        .bot_of(|config| config.watcher.bilibili_bot.as_deref())
         .build()
         .setup_subscribe_registry(config.bili_live_room_event.iter())
         .start_with_task(watch_and_response);
@@ -58,23 +63,28 @@ pub struct RoomInfo {
 }
 
 impl RoomInfo {
-    fn to_captions(&self, status: u8) -> Option<String> {
-        match status {
-            0 => Some(format!(
-                "{} 下播了！",
-                self.username,
-            )),
-            1 => Some(format!(
-                "<a href=\"{}\">{}</a> 开播了！已有 {} 人正在观看\n直播: <a href=\"{}\">{}</a>\n分区: #{}\n",
-                format_args!("https://space.bilibili.com/{}/", self.uid),
-                self.username,
-                self.online,
-                format_args!("https://live.bilibili.com/{}/", self.room_id),
-                self.title,
-                self.area_v2_name
-            )),
-            _ => None,
-        }
+    /// Render the notification of the live status in the language, see [`crate::template`].
+    fn to_captions(&self, status: u8, lang: &str) -> anyhow::Result<Option<String>> {
+        let template = match status {
+            0 => "bilibili_offline.html",
+            1 => "bilibili_live.html",
+            _ => return Ok(None),
+        };
+        let caption = crate::template::render(
+            template,
+            lang,
+            minijinja::context! {
+                username => self.username,
+                uid => self.uid,
+                room_id => self.room_id,
+                title => self.title,
+                area => self.area_v2_name,
+                online => self.online,
+                space_url => format!("https://space.bilibili.com/{}/", self.uid),
+                room_url => format!("https://live.bilibili.com/{}/", self.room_id),
+            },
+        )?;
+        Ok(Some(caption))
     }
 }
 
@@ -128,9 +138,13 @@ async fn watch_and_response(ctx: EventWatcher<()>) -> anyhow::Result<()> {
             continue;
         }
 
-        let subscribers = ctx.get_subscribers(&room_info.uid)?;
-        for chat_id in subscribers {
-            if let Err(err) = notify_live_room_changes(&ctx, chat_id, &room_info).await {
+        let subscribers: Vec<String> = ctx.get_subscribers(&room_info.uid)?;
+        for target in subscribers {
+            let Ok(target) = target.parse::<ChatTarget>() else {
+                tracing::error!("[BiliLiveRoom] invalid subscriber {target}");
+                continue;
+            };
+            if let Err(err) = notify_live_room_changes(&ctx, target, &room_info).await {
                 tracing::error!("[BiliLiveRoom] fail to notify changes: {err}")
             }
         }
@@ -141,7 +155,7 @@ async fn watch_and_response(ctx: EventWatcher<()>) -> anyhow::Result<()> {
 
 async fn notify_live_room_changes(
     ctx: &EventWatcher<()>,
-    chat_id: i64,
+    target: ChatTarget,
     room_info: &RoomInfo,
 ) -> anyhow::Result<()> {
     let cover = if room_info.live_status == 0 {
@@ -154,15 +168,23 @@ async fn notify_live_room_changes(
         reqwest::Url::parse(&room_info.cover_from_user)?
     };
 
-    let caption = room_info.to_captions(room_info.live_status);
-    if caption.is_none() {
+    let lang = crate::i18n::chat_language(&ctx.data, target.chat_id.0, None);
+    let Some(caption) = room_info.to_captions(room_info.live_status, lang)? else {
+        return Ok(());
+    };
+    // The cover is dropped from the digest
+    let notification = Notification::html(caption);
+    if quiet_hours::hold(&ctx.bot, &ctx.data, target, &notification)? {
         return Ok(());
     }
-    let caption = caption.unwrap();
-    ctx.bot
-        .send_photo(tg_type::ChatId(chat_id), tg_type::InputFile::url(cover))
-        .caption(caption)
-        .parse_mode(tg_type::ParseMode::Html)
+    let caption = notification.text;
+    let bot = ctx.bot.clone();
+    ctx.data
+        .submit(target.chat_id, Priority::Background, move || {
+            bot.send_photo_to(target, tg_type::InputFile::url(cover.clone()))
+                .caption(&caption)
+                .parse_mode(tg_type::ParseMode::Html)
+        })
         .await?;
     Ok(())
 }
@@ -0,0 +1,104 @@
+use redis::Commands;
+use teloxide::prelude::*;
+
+use crate::{api, app::AppData, send_queue::Priority, settings};
+
+const KNOWN_CHATS: &str = "KNOWN_CHATS";
+/// Name of the module, chats turning it off in `/settings` don't receive the broadcast
+pub const MODULE: &str = "broadcast";
+
+/// Remember the chat that the bot has seen a message from.
+pub fn remember_chat(data: &AppData, chat_id: i64) -> anyhow::Result<()> {
+    let () = data.cacher.get_conn().sadd(KNOWN_CHATS, chat_id)?;
+    Ok(())
+}
+
+pub fn known_chats(data: &AppData) -> anyhow::Result<Vec<i64>> {
+    let mut chats: Vec<i64> = data.cacher.get_conn().smembers(KNOWN_CHATS)?;
+    chats.sort();
+    Ok(chats)
+}
+
+/// Stop broadcasting to the chat which blocks or removes the bot.
+pub fn forget_chat(data: &AppData, chat_id: i64) -> anyhow::Result<()> {
+    let () = data.cacher.get_conn().srem(KNOWN_CHATS, chat_id)?;
+    Ok(())
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BroadcastReport {
+    pub total: usize,
+    pub sent: usize,
+    pub failed: usize,
+    /// Chats that blocked or removed the bot, they are forgotten
+    pub unreachable: usize,
+}
+
+impl BroadcastReport {
+    pub fn done(&self) -> usize {
+        self.sent + self.failed + self.unreachable
+    }
+}
+
+/// Send the text to every known chat that has the broadcast module enabled, through the send
+/// queue. `progress` is called after each chat with the report so far.
+pub async fn broadcast(
+    data: &AppData,
+    bot: &Bot,
+    text: &str,
+    mut progress: impl FnMut(&BroadcastReport),
+) -> anyhow::Result<BroadcastReport> {
+    let chats = known_chats(data)?
+        .into_iter()
+        .filter(|chat_id| settings::is_enabled(data, *chat_id, MODULE).unwrap_or(true))
+        .collect::<Vec<_>>();
+    let mut report = BroadcastReport {
+        total: chats.len(),
+        ..Default::default()
+    };
+
+    for chat_id in chats {
+        let (bot, text, chat) = (bot.clone(), text.to_string(), ChatId(chat_id));
+        let sent = data
+            .submit(chat, Priority::Background, move || {
+                bot.send_message(chat, &text)
+            })
+            .await;
+        match sent {
+            Ok(_) => report.sent += 1,
+            // The chat is forgotten by the module hook
+            Err(err) if err.downcast_ref().is_some_and(api::is_unreachable) => {
+                report.unreachable += 1;
+            }
+            Err(err) => {
+                tracing::error!("[Broadcast] fail to send to {chat_id}: {err}");
+                report.failed += 1;
+            }
+        }
+        progress(&report);
+    }
+
+    Ok(report)
+}
+
+#[tokio::test]
+async fn test_broadcast() {
+    use crate::testkit::{self, FakeTelegram};
+
+    let telegram = FakeTelegram::start();
+    let bot = telegram.bot();
+    let data = testkit::app_data(bot.clone()).await;
+    for chat_id in [-100, 42, 42] {
+        remember_chat(&data, chat_id).unwrap();
+    }
+
+    let mut updates = 0;
+    let report = broadcast(&data, &bot, "hello", |_| updates += 1)
+        .await
+        .unwrap();
+    assert_eq!(report.total, 2);
+    assert_eq!(report.sent, 2);
+    assert_eq!(updates, 2);
+    let sent = telegram.calls_to("sendMessage");
+    assert!(sent.iter().all(|call| call.text() == Some("hello")));
+}
@@ -0,0 +1,481 @@
+//! Calculator and unit conversion, entirely offline so they work when the upstream APIs are down.
+
+/// Longer expressions are rejected instead of being evaluated
+const MAX_EXPRESSION_LEN: usize = 256;
+/// Nesting deeper than this is rejected, so a crafted expression can't overflow the stack
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Op(char),
+    LParen,
+    RParen,
+    Comma,
+}
+
+fn tokenize(expr: &str) -> anyhow::Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = expr.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '0'..='9' | '.' => {
+                let mut number = String::new();
+                while let Some(&c) = chars.peek() {
+                    let exponent_sign = (c == '-' || c == '+') && number.ends_with(['e', 'E']);
+                    if c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E' || exponent_sign {
+                        number.push(c);
+                        chars.next();
+                    } else if c == '_' {
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                let value = number
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("invalid number {number}"))?;
+                tokens.push(Token::Number(value));
+            }
+            c if c.is_alphabetic() => {
+                let mut ident = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !c.is_alphanumeric() {
+                        break;
+                    }
+                    ident.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Ident(ident.to_lowercase()));
+            }
+            '*' => {
+                chars.next();
+                // `**` is the same as `^`
+                if chars.peek() == Some(&'*') {
+                    chars.next();
+                    tokens.push(Token::Op('^'));
+                } else {
+                    tokens.push(Token::Op('*'));
+                }
+            }
+            '+' | '-' | '/' | '%' | '^' => {
+                chars.next();
+                tokens.push(Token::Op(c));
+            }
+            '×' | '÷' => {
+                chars.next();
+                tokens.push(Token::Op(if c == '×' { '*' } else { '/' }));
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::LParen);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::RParen);
+            }
+            ',' => {
+                chars.next();
+                tokens.push(Token::Comma);
+            }
+            c => anyhow::bail!("unexpected character {c}"),
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> anyhow::Result<()> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            _ => anyhow::bail!("expect {expected:?}"),
+        }
+    }
+
+    fn nested<T>(&mut self, f: impl FnOnce(&mut Self) -> anyhow::Result<T>) -> anyhow::Result<T> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            anyhow::bail!("expression is nested too deep");
+        }
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// expr := term (('+' | '-') term)*
+    fn expr(&mut self) -> anyhow::Result<f64> {
+        let mut value = self.term()?;
+        while let Some(Token::Op(op @ ('+' | '-'))) = self.peek().cloned() {
+            self.next();
+            let rhs = self.term()?;
+            value = if op == '+' { value + rhs } else { value - rhs };
+        }
+        Ok(value)
+    }
+
+    /// term := unary (('*' | '/' | '%') unary)*
+    fn term(&mut self) -> anyhow::Result<f64> {
+        let mut value = self.unary()?;
+        while let Some(Token::Op(op @ ('*' | '/' | '%'))) = self.peek().cloned() {
+            self.next();
+            let rhs = self.unary()?;
+            value = match op {
+                '*' => value * rhs,
+                '/' if rhs == 0.0 => anyhow::bail!("division by zero"),
+                '/' => value / rhs,
+                _ if rhs == 0.0 => anyhow::bail!("division by zero"),
+                _ => value % rhs,
+            };
+        }
+        Ok(value)
+    }
+
+    /// unary := ('+' | '-') unary | power
+    fn unary(&mut self) -> anyhow::Result<f64> {
+        match self.peek() {
+            Some(Token::Op('-')) => {
+                self.next();
+                self.nested(|parser| parser.unary()).map(|value| -value)
+            }
+            Some(Token::Op('+')) => {
+                self.next();
+                self.nested(|parser| parser.unary())
+            }
+            _ => self.power(),
+        }
+    }
+
+    /// power := atom ('^' unary)?, so `2^3^2` is `2^(3^2)` and `-2^2` is `-(2^2)`
+    fn power(&mut self) -> anyhow::Result<f64> {
+        let base = self.atom()?;
+        if self.peek() == Some(&Token::Op('^')) {
+            self.next();
+            let exponent = self.nested(|parser| parser.unary())?;
+            return Ok(base.powf(exponent));
+        }
+        Ok(base)
+    }
+
+    /// atom := number | constant | function '(' args ')' | '(' expr ')'
+    fn atom(&mut self) -> anyhow::Result<f64> {
+        match self.next() {
+            Some(Token::Number(value)) => Ok(value),
+            Some(Token::LParen) => {
+                let value = self.nested(|parser| parser.expr())?;
+                self.expect(Token::RParen)?;
+                Ok(value)
+            }
+            Some(Token::Ident(name)) => {
+                if self.peek() != Some(&Token::LParen) {
+                    return constant(&name);
+                }
+                self.next();
+                let mut args = Vec::new();
+                if self.peek() != Some(&Token::RParen) {
+                    loop {
+                        args.push(self.nested(|parser| parser.expr())?);
+                        if self.peek() != Some(&Token::Comma) {
+                            break;
+                        }
+                        self.next();
+                    }
+                }
+                self.expect(Token::RParen)?;
+                function(&name, &args)
+            }
+            Some(token) => anyhow::bail!("unexpected {token:?}"),
+            None => anyhow::bail!("unexpected end of expression"),
+        }
+    }
+}
+
+fn constant(name: &str) -> anyhow::Result<f64> {
+    match name {
+        "pi" | "π" => Ok(std::f64::consts::PI),
+        "e" => Ok(std::f64::consts::E),
+        "tau" => Ok(std::f64::consts::TAU),
+        _ => anyhow::bail!("unknown constant {name}"),
+    }
+}
+
+fn function(name: &str, args: &[f64]) -> anyhow::Result<f64> {
+    let value = match (name, args) {
+        ("sqrt", [x]) => x.sqrt(),
+        ("cbrt", [x]) => x.cbrt(),
+        ("abs", [x]) => x.abs(),
+        ("sin", [x]) => x.sin(),
+        ("cos", [x]) => x.cos(),
+        ("tan", [x]) => x.tan(),
+        ("asin", [x]) => x.asin(),
+        ("acos", [x]) => x.acos(),
+        ("atan", [x]) => x.atan(),
+        ("ln", [x]) => x.ln(),
+        ("log", [x]) | ("log10", [x]) => x.log10(),
+        ("log", [x, base]) => x.log(*base),
+        ("log2", [x]) => x.log2(),
+        ("exp", [x]) => x.exp(),
+        ("floor", [x]) => x.floor(),
+        ("ceil", [x]) => x.ceil(),
+        ("round", [x]) => x.round(),
+        ("min", [first, rest @ ..]) => rest.iter().fold(*first, |min, x| min.min(*x)),
+        ("max", [first, rest @ ..]) => rest.iter().fold(*first, |max, x| max.max(*x)),
+        _ => anyhow::bail!("unknown function {name} with {} arguments", args.len()),
+    };
+    Ok(value)
+}
+
+/// Evaluate the arithmetic expression, like `2^10 / (3 + sqrt(16))`.
+pub fn evaluate(expr: &str) -> anyhow::Result<f64> {
+    if expr.len() > MAX_EXPRESSION_LEN {
+        anyhow::bail!("expression is longer than {MAX_EXPRESSION_LEN} characters");
+    }
+    let mut parser = Parser {
+        tokens: tokenize(expr)?,
+        pos: 0,
+        depth: 0,
+    };
+    if parser.tokens.is_empty() {
+        anyhow::bail!("empty expression");
+    }
+    let value = parser.expr()?;
+    if let Some(token) = parser.peek() {
+        anyhow::bail!("unexpected {token:?}");
+    }
+    if !value.is_finite() {
+        anyhow::bail!("the result is not a finite number");
+    }
+    Ok(value)
+}
+
+/// Print the number without the floating point noise, like `0.3` for `0.1 + 0.2`.
+pub fn format_number(value: f64) -> String {
+    let rounded: f64 = format!("{value:.11e}").parse().unwrap_or(value);
+    if rounded == 0.0 {
+        return "0".to_string();
+    }
+    rounded.to_string()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dimension {
+    Length,
+    Mass,
+    Time,
+    Data,
+    Speed,
+    Volume,
+    Temperature,
+}
+
+struct Unit {
+    names: &'static [&'static str],
+    dimension: Dimension,
+    /// How many base units of the dimension is one of this unit. Temperatures are converted
+    /// by [`to_kelvin`] and [`from_kelvin`] instead.
+    factor: f64,
+}
+
+macro_rules! units {
+    ($($dimension:ident: $([$($name:literal),+] = $factor:expr),+;)+) => {
+        &[$($(Unit {
+            names: &[$($name),+],
+            dimension: Dimension::$dimension,
+            factor: $factor,
+        }),+),+]
+    };
+}
+
+const UNITS: &[Unit] = units! {
+    Length:
+        ["mm", "millimeter", "millimeters"] = 0.001,
+        ["cm", "centimeter", "centimeters"] = 0.01,
+        ["m", "meter", "meters", "metre", "metres"] = 1.0,
+        ["km", "kilometer", "kilometers", "公里"] = 1000.0,
+        ["in", "inch", "inches"] = 0.0254,
+        ["ft", "foot", "feet"] = 0.3048,
+        ["yd", "yard", "yards"] = 0.9144,
+        ["mi", "mile", "miles"] = 1609.344,
+        ["nmi", "海里"] = 1852.0;
+    Mass:
+        ["mg", "milligram", "milligrams"] = 1e-6,
+        ["g", "gram", "grams", "克"] = 0.001,
+        ["kg", "kilogram", "kilograms", "公斤"] = 1.0,
+        ["t", "ton", "tons", "吨"] = 1000.0,
+        ["oz", "ounce", "ounces"] = 0.028349523125,
+        ["lb", "lbs", "pound", "pounds"] = 0.45359237,
+        ["斤"] = 0.5;
+    Time:
+        ["ms", "millisecond", "milliseconds"] = 0.001,
+        ["s", "sec", "second", "seconds", "秒"] = 1.0,
+        ["min", "minute", "minutes", "分钟"] = 60.0,
+        ["h", "hr", "hour", "hours", "小时"] = 3600.0,
+        ["d", "day", "days", "天"] = 86400.0,
+        ["week", "weeks", "周"] = 604800.0,
+        ["year", "years", "年"] = 31557600.0;
+    Data:
+        ["bit", "bits"] = 0.125,
+        ["B", "byte", "bytes"] = 1.0,
+        ["KB"] = 1e3,
+        ["MB"] = 1e6,
+        ["GB"] = 1e9,
+        ["TB"] = 1e12,
+        ["KiB"] = 1024.0,
+        ["MiB"] = 1048576.0,
+        ["GiB"] = 1073741824.0,
+        ["TiB"] = 1099511627776.0,
+        ["Kb", "kbit"] = 125.0,
+        ["Mb", "mbit"] = 125e3,
+        ["Gb", "gbit"] = 125e6;
+    Speed:
+        ["m/s", "mps"] = 1.0,
+        ["km/h", "kmh", "kph"] = 1.0 / 3.6,
+        ["mph"] = 0.44704,
+        ["kn", "knot", "knots"] = 1852.0 / 3600.0;
+    Volume:
+        ["ml", "milliliter", "milliliters"] = 0.001,
+        ["l", "liter", "liters", "litre", "litres", "升"] = 1.0,
+        ["gal", "gallon", "gallons"] = 3.785411784,
+        ["floz"] = 0.0295735295625,
+        ["cup", "cups"] = 0.2365882365;
+    Temperature:
+        ["c", "°c", "celsius", "℃", "摄氏度"] = 0.0,
+        ["f", "°f", "fahrenheit", "℉", "华氏度"] = 0.0,
+        ["k", "kelvin"] = 0.0;
+};
+
+/// Find the unit by name. The data units are case sensitive (`Mb` is not `MB`), the others are
+/// not.
+fn find_unit(name: &str) -> Option<&'static Unit> {
+    UNITS
+        .iter()
+        .find(|unit| unit.names.contains(&name))
+        .or_else(|| {
+            let name = name.to_lowercase();
+            UNITS.iter().find(|unit| {
+                unit.dimension != Dimension::Data && unit.names.contains(&name.as_str())
+            })
+        })
+}
+
+fn to_kelvin(value: f64, unit: &Unit) -> f64 {
+    match unit.names[0] {
+        "c" => value + 273.15,
+        "f" => (value - 32.0) * 5.0 / 9.0 + 273.15,
+        _ => value,
+    }
+}
+
+fn from_kelvin(value: f64, unit: &Unit) -> f64 {
+    match unit.names[0] {
+        "c" => value - 273.15,
+        "f" => (value - 273.15) * 9.0 / 5.0 + 32.0,
+        _ => value,
+    }
+}
+
+/// Convert the amount between the units of the same dimension, like `5 mi` to `km`.
+pub fn convert(amount: f64, from: &str, to: &str) -> anyhow::Result<f64> {
+    let from_unit = find_unit(from).ok_or_else(|| anyhow::anyhow!("unknown unit {from}"))?;
+    let to_unit = find_unit(to).ok_or_else(|| anyhow::anyhow!("unknown unit {to}"))?;
+    if from_unit.dimension != to_unit.dimension {
+        anyhow::bail!("can't convert {from} to {to}");
+    }
+    if from_unit.dimension == Dimension::Temperature {
+        return Ok(from_kelvin(to_kelvin(amount, from_unit), to_unit));
+    }
+    Ok(amount * from_unit.factor / to_unit.factor)
+}
+
+/// Parse the conversion like `5 mi to km`, `5mi km` or `100 F in C`.
+pub fn parse_conversion(text: &str) -> Option<(f64, String, String)> {
+    let mut words = text.split_whitespace().collect::<Vec<_>>();
+    if words.len() == 4 && ["to", "in", "as"].contains(&words[2]) {
+        words.remove(2);
+    } else if words.len() == 3 && ["to", "in", "as"].contains(&words[1]) {
+        words.remove(1);
+    }
+    let (amount, from, to) = match words.as_slice() {
+        [amount, from, to] => (amount.parse().ok()?, from.to_string(), to.to_string()),
+        // The amount and the unit are written together, like `5mi`
+        [quantity, to] => {
+            let split = quantity
+                .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+                .filter(|split| *split > 0)?;
+            let (amount, from) = quantity.split_at(split);
+            (amount.parse().ok()?, from.to_string(), to.to_string())
+        }
+        _ => return None,
+    };
+    Some((amount, from, to))
+}
+
+#[test]
+fn test_evaluate() {
+    assert_eq!(evaluate("1 + 2 * 3").unwrap(), 7.0);
+    assert_eq!(evaluate("(1 + 2) * 3").unwrap(), 9.0);
+    assert_eq!(evaluate("2^3^2").unwrap(), 512.0);
+    assert_eq!(evaluate("2 ** 10").unwrap(), 1024.0);
+    assert_eq!(evaluate("-2^2").unwrap(), -4.0);
+    assert_eq!(evaluate("sqrt(16) + abs(-1)").unwrap(), 5.0);
+    assert_eq!(evaluate("max(1, 5, 3) % 3").unwrap(), 2.0);
+    assert_eq!(evaluate("log(8, 2)").unwrap(), 3.0);
+    assert_eq!(evaluate("1e3 / 4").unwrap(), 250.0);
+    assert_eq!(evaluate("cos(pi)").unwrap(), -1.0);
+    assert_eq!(format_number(evaluate("0.1 + 0.2").unwrap()), "0.3");
+
+    assert!(evaluate("1 / 0").is_err());
+    assert!(evaluate("1 +").is_err());
+    assert!(evaluate("(1").is_err());
+    assert!(evaluate("foo(1)").is_err());
+    assert!(evaluate(&"(".repeat(100)).is_err());
+    assert!(evaluate("10^1000").is_err());
+}
+
+#[test]
+fn test_convert() {
+    let km = convert(5.0, "mi", "km").unwrap();
+    assert_eq!(format_number(km), "8.04672");
+    assert_eq!(format_number(convert(100.0, "C", "F").unwrap()), "212");
+    assert_eq!(
+        format_number(convert(0.0, "K", "celsius").unwrap()),
+        "-273.15"
+    );
+    assert_eq!(convert(1.0, "GiB", "MiB").unwrap(), 1024.0);
+    assert_eq!(convert(8.0, "Mb", "MB").unwrap(), 1.0);
+    assert!(convert(1.0, "kg", "km").is_err());
+    assert!(convert(1.0, "parsec", "km").is_err());
+
+    assert_eq!(
+        parse_conversion("5 mi to km"),
+        Some((5.0, "mi".to_string(), "km".to_string()))
+    );
+    assert_eq!(
+        parse_conversion("1.5GB in MiB"),
+        Some((1.5, "GB".to_string(), "MiB".to_string()))
+    );
+    assert_eq!(
+        parse_conversion("-40 F C"),
+        Some((-40.0, "F".to_string(), "C".to_string()))
+    );
+    assert_eq!(parse_conversion("five mi to km"), None);
+}
@@ -0,0 +1,298 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use redis::Commands;
+use tokio_rustls::rustls::{
+    self,
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::CryptoProvider,
+    pki_types::{CertificateDer, ServerName, UnixTime},
+    DigitallySignedStruct, SignatureScheme,
+};
+
+use crate::{
+    app::AppData,
+    event::EventWatcher,
+    i18n,
+    quiet_hours::{self, Notification},
+    topic::ChatTarget,
+};
+
+/// Name of the watcher, the watched hosts are kept in its subscribe registry
+const WATCHER: &str = "CertExpiryWatcher";
+/// The certificates are checked once a day
+const CHECK_INTERVAL: u64 = 60 * 60 * 24;
+/// Connecting and the handshake should finish in this time
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Warn the chat when the certificate expires in these many days, each only once
+const WARN_DAYS: [i64; 3] = [30, 7, 1];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CertInfo {
+    pub subject: String,
+    pub issuer: String,
+    pub not_before: DateTime<Utc>,
+    pub not_after: DateTime<Utc>,
+    /// DNS names in the subject alternative names
+    pub dns_names: Vec<String>,
+}
+
+impl CertInfo {
+    pub fn days_left(&self, now: DateTime<Utc>) -> i64 {
+        (self.not_after - now).num_days()
+    }
+}
+
+/// Accept any certificate, the expired and self-signed ones are exactly what should be reported.
+/// Nothing is sent over the connection, it is closed after the handshake.
+#[derive(Debug)]
+struct InspectOnly(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for InspectOnly {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// Parse `example.com` or `example.com:8443`, the internal hosts are rejected like the monitor.
+pub fn parse_host(input: &str) -> anyhow::Result<(String, u16)> {
+    let url = super::monitor::parse_url(&format!("https://{input}"))?;
+    if url.path() != "/" || url.query().is_some() {
+        anyhow::bail!("{input} should be a host like example.com:443");
+    }
+    let host = url.host_str().unwrap_or_default().to_string();
+    Ok((host, url.port_or_known_default().unwrap_or(443)))
+}
+
+fn parse_cert(der: &CertificateDer<'_>) -> anyhow::Result<CertInfo> {
+    let (_, cert) = x509_parser::parse_x509_certificate(der)?;
+    let name_of = |name: &x509_parser::x509::X509Name<'_>| {
+        name.iter_common_name()
+            .next()
+            .and_then(|cn| cn.as_str().ok())
+            .map_or_else(|| name.to_string(), str::to_string)
+    };
+    let time_of = |time: x509_parser::time::ASN1Time| {
+        DateTime::from_timestamp(time.timestamp(), 0).unwrap_or_default()
+    };
+    let dns_names = cert
+        .subject_alternative_name()
+        .ok()
+        .flatten()
+        .map(|san| {
+            san.value
+                .general_names
+                .iter()
+                .filter_map(|name| match name {
+                    x509_parser::extensions::GeneralName::DNSName(name) => Some(name.to_string()),
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(CertInfo {
+        subject: name_of(cert.subject()),
+        issuer: name_of(cert.issuer()),
+        not_before: time_of(cert.validity().not_before),
+        not_after: time_of(cert.validity().not_after),
+        dns_names,
+    })
+}
+
+/// Connect to the host and read the certificate chain it sends, the leaf first.
+pub async fn fetch_chain(host: &str, port: u16) -> anyhow::Result<Vec<CertInfo>> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let config = rustls::ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(InspectOnly(provider)))
+        .with_no_client_auth();
+    let connector = tokio_rustls::TlsConnector::from(Arc::new(config));
+    let server_name = ServerName::try_from(host.to_string())?;
+
+    let handshake = async {
+        let addrs = super::monitor::resolve_public(host, port).await?;
+        let stream = tokio::net::TcpStream::connect(&addrs[..]).await?;
+        anyhow::Ok(connector.connect(server_name, stream).await?)
+    };
+    let stream = tokio::time::timeout(CONNECT_TIMEOUT, handshake)
+        .await
+        .map_err(|_| anyhow::anyhow!("timeout when connecting to {host}:{port}"))??;
+    let chain = stream
+        .get_ref()
+        .1
+        .peer_certificates()
+        .ok_or_else(|| anyhow::anyhow!("{host} sends no certificate"))?;
+    chain.iter().map(parse_cert).collect()
+}
+
+/// The smallest of [`WARN_DAYS`] the certificate is within, 0 if it is already expired.
+pub fn warn_level(days_left: i64) -> Option<i64> {
+    if days_left < 0 {
+        return Some(0);
+    }
+    WARN_DAYS
+        .into_iter()
+        .filter(|days| days_left <= *days)
+        .min()
+}
+
+fn event_of(host: &str, port: u16) -> String {
+    format!("{host}:{port}")
+}
+
+/// Warn the chat before the certificate of the host expires, return false if it is already
+/// watched.
+pub async fn watch(data: &AppData, target: ChatTarget, input: &str) -> anyhow::Result<bool> {
+    let (host, port) = parse_host(input)?;
+    // Make sure the host is reachable before watching it
+    fetch_chain(&host, port).await?;
+    data.cacher
+        .subscribe(WATCHER, &target.to_string(), &event_of(&host, port))
+}
+
+/// Stop warning the chat, return false if it is not watched.
+pub fn unwatch(data: &AppData, target: ChatTarget, input: &str) -> anyhow::Result<bool> {
+    let (host, port) = parse_host(input)?;
+    data.cacher
+        .unsubscribe(WATCHER, &target.to_string(), &event_of(&host, port))
+}
+
+pub fn spawn_cert_watcher(bot: teloxide::Bot, data: AppData) {
+    EventWatcher::builder()
+        .name(WATCHER)
+        .bot(bot)
+        .data(data)
+        .client(None)
+        .heartbeat_interval(CHECK_INTERVAL)
+        .build()
+        .start_with_task(check_expiry);
+}
+
+async fn check_expiry(ctx: EventWatcher<()>) -> anyhow::Result<()> {
+    let events: Vec<String> = ctx.event_pool()?;
+    let now = Utc::now();
+    for event in events {
+        let Some((host, port)) = event
+            .rsplit_once(':')
+            .and_then(|(host, port)| Some((host, port.parse::<u16>().ok()?)))
+        else {
+            tracing::error!("[CertExpiry] invalid event {event}");
+            continue;
+        };
+        let leaf = match fetch_chain(host, port).await {
+            Ok(chain) if !chain.is_empty() => chain.into_iter().next().unwrap(),
+            Ok(_) => continue,
+            Err(err) => {
+                tracing::warn!("[CertExpiry] fail to check {event}: {err}");
+                continue;
+            }
+        };
+        let days_left = leaf.days_left(now);
+        let Some(level) = warn_level(days_left) else {
+            continue;
+        };
+
+        // Each level is warned once for a certificate, the renewed one has another expiry
+        let key = format!("CERT_WARNED:{event}:{}", leaf.not_after.timestamp());
+        {
+            let mut conn = ctx.data.cacher.get_conn();
+            let warned: Option<i64> = conn.get(&key)?;
+            if warned.is_some_and(|warned| warned <= level) {
+                continue;
+            }
+            let () = conn.set_ex(&key, level, 60 * 60 * 24 * 60)?;
+        }
+
+        let expires = leaf.not_after.format("%Y-%m-%d %H:%M UTC").to_string();
+        let subscribers: Vec<String> = ctx.get_subscribers(&event)?;
+        for target in subscribers {
+            let Ok(target) = target.parse::<ChatTarget>() else {
+                tracing::error!("[CertExpiry] invalid subscriber {target}");
+                continue;
+            };
+            let lang = i18n::chat_language(&ctx.data, target.chat_id.0, None);
+            let text = if days_left < 0 {
+                crate::t!(lang, "cert.expired", host = &event, expires = &expires)
+            } else {
+                crate::t!(
+                    lang,
+                    "cert.expiring",
+                    host = &event,
+                    days = days_left,
+                    expires = &expires
+                )
+            };
+            let notification = Notification::text(text).digestable();
+            let sent = quiet_hours::notify(&ctx.bot, &ctx.data, target, notification).await;
+            if let Err(err) = sent {
+                tracing::error!("[CertExpiry] fail to notify {target}: {err}")
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_parse_host() {
+    assert_eq!(
+        parse_host("example.com").unwrap(),
+        ("example.com".to_string(), 443)
+    );
+    assert_eq!(
+        parse_host("example.com:8443").unwrap(),
+        ("example.com".to_string(), 8443)
+    );
+    assert!(parse_host("example.com/path").is_err());
+    assert!(parse_host("127.0.0.1").is_err());
+}
+
+#[test]
+fn test_warn_level() {
+    assert_eq!(warn_level(90), None);
+    assert_eq!(warn_level(30), Some(30));
+    assert_eq!(warn_level(12), Some(30));
+    assert_eq!(warn_level(7), Some(7));
+    assert_eq!(warn_level(0), Some(1));
+    assert_eq!(warn_level(-1), Some(0));
+}
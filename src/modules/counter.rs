@@ -0,0 +1,189 @@
+use crate::app::AppData;
+use crate::helper::Html;
+use crate::paginator::Paginator;
+use redis::Commands;
+use teloxide::utils::html;
+
+use super::Sendable;
+
+/// A counter defined by chat admin, like `hug` with template `has been hugged {n} times`.
+#[derive(Debug, PartialEq)]
+pub struct CounterDefinition {
+    pub name: String,
+    pub template: String,
+}
+
+impl CounterDefinition {
+    /// Parse the argument of `/counter new`, which looks like: `hug "has been hugged {n} times"`.
+    /// The quotes around the template are optional.
+    pub fn parse(args: &str, lang: &str) -> anyhow::Result<Self> {
+        let Some((name, template)) = args.trim().split_once(' ') else {
+            anyhow::bail!(crate::t!(lang, "counter.usage_new"));
+        };
+        let name = name.trim_start_matches('/').to_lowercase();
+        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            anyhow::bail!(crate::t!(lang, "counter.invalid_name"));
+        }
+
+        let template = template
+            .trim()
+            .trim_matches(|c| matches!(c, '"' | '“' | '”'))
+            .to_string();
+        if !template.contains("{n}") {
+            anyhow::bail!(crate::t!(lang, "counter.no_placeholder"));
+        }
+
+        Ok(Self { name, template })
+    }
+
+    /// Render the template with the target user name and current count. If the template doesn't
+    /// contain a `{user}` placeholder, the user name is prepended to the text.
+    pub fn render(&self, user: &str, n: i64) -> String {
+        let text = self.template.replace("{n}", &n.to_string());
+        if text.contains("{user}") {
+            text.replace("{user}", user)
+        } else {
+            format!("{user} {text}")
+        }
+    }
+}
+
+/// Extract the counter name from the trigger message: `hug`, `/hug` or `/hug@some_bot`.
+pub fn trigger_name(text: &str) -> Option<String> {
+    let text = text.trim();
+    if text.contains(char::is_whitespace) {
+        return None;
+    }
+    let name = text.trim_start_matches('/');
+    let name = name.split_once('@').map_or(name, |(name, _)| name);
+    if name.is_empty() {
+        return None;
+    }
+    Some(name.to_lowercase())
+}
+
+pub fn define(data: &AppData, chat_id: i64, def: &CounterDefinition) -> anyhow::Result<()> {
+    let () = data.cacher.get_conn().hset(
+        format!("COUNTER_DEFINITION:{chat_id}"),
+        &def.name,
+        &def.template,
+    )?;
+    Ok(())
+}
+
+pub fn remove(data: &AppData, chat_id: i64, name: &str) -> anyhow::Result<bool> {
+    let mut conn = data.cacher.get_conn();
+    let removed: u32 = conn.hdel(format!("COUNTER_DEFINITION:{chat_id}"), name)?;
+    let () = conn.del(format!("COUNTER:{chat_id}:{name}"))?;
+    Ok(removed > 0)
+}
+
+pub fn get_definition(
+    data: &AppData,
+    chat_id: i64,
+    name: &str,
+) -> anyhow::Result<Option<CounterDefinition>> {
+    let template: Option<String> = data
+        .cacher
+        .get_conn()
+        .hget(format!("COUNTER_DEFINITION:{chat_id}"), name)?;
+    Ok(template.map(|template| CounterDefinition {
+        name: name.to_string(),
+        template,
+    }))
+}
+
+pub fn list(data: &AppData, chat_id: i64, lang: &str) -> anyhow::Result<Sendable> {
+    let mut names: Vec<String> = data
+        .cacher
+        .get_conn()
+        .hkeys(format!("COUNTER_DEFINITION:{chat_id}"))?;
+    if names.is_empty() {
+        return Ok(Sendable::text(crate::t!(lang, "counter.none")));
+    }
+    names.sort();
+
+    let display = names
+        .iter()
+        .fold(crate::t!(lang, "counter.list"), |acc, name| {
+            format!("{acc}\n* /{name}")
+        });
+    Ok(Sendable::text(display))
+}
+
+/// Increase the count of target user for the given counter, returns the rendered text.
+pub fn hit(
+    data: &AppData,
+    chat_id: i64,
+    def: &CounterDefinition,
+    target: (u64, &str),
+) -> anyhow::Result<String> {
+    let (user_id, username) = target;
+    let () =
+        data.cacher
+            .get_conn()
+            .hset(format!("COUNTER_USERNAME:{chat_id}"), user_id, username)?;
+    let n = data
+        .cacher
+        .incr_counter(&format!("COUNTER:{chat_id}:{}", def.name), user_id, 1)?;
+    Ok(def.render(username, n))
+}
+
+/// The leaderboard of the counter in pages, none if nobody is counted yet.
+pub fn leaderboard(
+    data: &AppData,
+    chat_id: i64,
+    name: &str,
+    max: isize,
+    lang: &str,
+) -> anyhow::Result<Option<Paginator>> {
+    let board: Vec<(u64, i64)> = data
+        .cacher
+        .counter_top(&format!("COUNTER:{chat_id}:{name}"), max)?;
+    if board.is_empty() {
+        return Ok(None);
+    }
+
+    let ids: Vec<u64> = board.iter().map(|(id, _)| *id).collect();
+    let names: Vec<Option<String>> = redis::cmd("HMGET")
+        .arg(format!("COUNTER_USERNAME:{chat_id}"))
+        .arg(&ids)
+        .query(&mut data.cacher.get_conn())?;
+
+    let lines = board
+        .iter()
+        .zip(names)
+        .enumerate()
+        .map(|(i, ((id, n), username))| {
+            let username = username.map_or_else(|| id.to_string(), |name| html::escape(&name));
+            format!("{}. {} - {}", i + 1, username, Html::b(n))
+        })
+        .collect();
+
+    Ok(Some(Paginator::new(
+        crate::t!(lang, "counter.leaderboard", name = name),
+        lines,
+    )))
+}
+
+#[test]
+fn test_counter_definition() {
+    let def = CounterDefinition::parse(r#"Hug "has been hugged {n} times""#, "en").unwrap();
+    assert_eq!(def.name, "hug");
+    assert_eq!(def.template, "has been hugged {n} times");
+    assert_eq!(def.render("ksyx", 3), "ksyx has been hugged 3 times");
+
+    let def = CounterDefinition::parse("/pat {user} 被摸了 {n} 次头", "zh-hans").unwrap();
+    assert_eq!(def.render("ksyx", 1), "ksyx 被摸了 1 次头");
+
+    assert!(CounterDefinition::parse("hug", "en").is_err());
+    let err = CounterDefinition::parse("hug no placeholder", "en").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Template should contain a {n} placeholder for the count"
+    );
+
+    assert_eq!(trigger_name("/hug@maid_bot").as_deref(), Some("hug"));
+    assert_eq!(trigger_name(" Poke "), Some("poke".to_string()));
+    assert_eq!(trigger_name("hug me"), None);
+}
@@ -0,0 +1,277 @@
+use std::{fmt::Display, str::FromStr};
+
+use serde::Deserialize;
+
+use crate::app::AppData;
+
+/// DNS over HTTPS endpoint speaking the JSON API
+const DOH_API: &str = "https://cloudflare-dns.com/dns-query";
+/// RDAP, the JSON successor of WHOIS, redirects to the server of the TLD
+const RDAP_API: &str = "https://rdap.org/domain";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordType {
+    A,
+    Aaaa,
+    Cname,
+    Mx,
+    Ns,
+    Txt,
+}
+
+impl RecordType {
+    fn code(&self) -> u16 {
+        match self {
+            Self::A => 1,
+            Self::Ns => 2,
+            Self::Cname => 5,
+            Self::Mx => 15,
+            Self::Txt => 16,
+            Self::Aaaa => 28,
+        }
+    }
+}
+
+impl Display for RecordType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::A => "A",
+            Self::Aaaa => "AAAA",
+            Self::Cname => "CNAME",
+            Self::Mx => "MX",
+            Self::Ns => "NS",
+            Self::Txt => "TXT",
+        })
+    }
+}
+
+impl FromStr for RecordType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "A" => Ok(Self::A),
+            "AAAA" => Ok(Self::Aaaa),
+            "CNAME" => Ok(Self::Cname),
+            "MX" => Ok(Self::Mx),
+            "NS" => Ok(Self::Ns),
+            "TXT" => Ok(Self::Txt),
+            _ => anyhow::bail!("unsupported record type {s}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct DnsRecord {
+    #[serde(rename = "type")]
+    pub kind: u16,
+    #[serde(rename = "TTL")]
+    pub ttl: u32,
+    pub data: String,
+}
+
+#[derive(Deserialize)]
+struct DohResponse {
+    #[serde(rename = "Status")]
+    status: u8,
+    #[serde(rename = "Answer", default)]
+    answer: Vec<DnsRecord>,
+}
+
+/// Registration of the domain from RDAP, the dates are in RFC 3339
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Registration {
+    pub domain: String,
+    pub registrar: Option<String>,
+    pub registered: Option<String>,
+    pub expires: Option<String>,
+    pub updated: Option<String>,
+    pub status: Vec<String>,
+    pub nameservers: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RdapDomain {
+    ldh_name: String,
+    #[serde(default)]
+    events: Vec<RdapEvent>,
+    #[serde(default)]
+    status: Vec<String>,
+    #[serde(default)]
+    nameservers: Vec<RdapNameserver>,
+    #[serde(default)]
+    entities: Vec<RdapEntity>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RdapEvent {
+    event_action: String,
+    event_date: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RdapNameserver {
+    ldh_name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RdapEntity {
+    #[serde(default)]
+    roles: Vec<String>,
+    /// jCard, like `["vcard", [["fn", {}, "text", "Example Registrar"]]]`
+    vcard_array: Option<serde_json::Value>,
+}
+
+impl RdapEntity {
+    fn full_name(&self) -> Option<String> {
+        self.vcard_array
+            .as_ref()?
+            .get(1)?
+            .as_array()?
+            .iter()
+            .find(|prop| prop.get(0).and_then(|name| name.as_str()) == Some("fn"))?
+            .get(3)?
+            .as_str()
+            .map(str::to_string)
+    }
+}
+
+impl From<RdapDomain> for Registration {
+    fn from(rdap: RdapDomain) -> Self {
+        let event = |action: &str| {
+            rdap.events
+                .iter()
+                .find(|event| event.event_action == action)
+                .map(|event| event.event_date.clone())
+        };
+        Self {
+            registrar: rdap
+                .entities
+                .iter()
+                .find(|entity| entity.roles.iter().any(|role| role == "registrar"))
+                .and_then(RdapEntity::full_name),
+            registered: event("registration"),
+            expires: event("expiration"),
+            updated: event("last changed"),
+            domain: rdap.ldh_name.to_lowercase(),
+            status: rdap.status,
+            nameservers: rdap
+                .nameservers
+                .into_iter()
+                .map(|ns| ns.ldh_name.to_lowercase())
+                .collect(),
+        }
+    }
+}
+
+/// Accept the host names like `example.com`, so the user input can't reach other API paths.
+pub fn is_valid_domain(domain: &str) -> bool {
+    let domain = domain.strip_suffix('.').unwrap_or(domain);
+    domain.len() <= 253
+        && domain.contains('.')
+        && domain.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+}
+
+/// Resolve the records of the domain with DNS over HTTPS.
+pub async fn resolve(
+    data: &AppData,
+    domain: &str,
+    kind: RecordType,
+) -> anyhow::Result<Vec<DnsRecord>> {
+    if !is_valid_domain(domain) {
+        anyhow::bail!("{domain} is not a valid domain");
+    }
+    let request = data
+        .requester
+        .get(DOH_API)
+        .header(reqwest::header::ACCEPT, "application/dns-json")
+        .query(&[("name", domain), ("type", &kind.to_string())]);
+    let resp: DohResponse = data
+        .requester
+        .send(request)
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    match resp.status {
+        // NOERROR and NXDOMAIN, the latter just has no answer
+        0 | 3 => {}
+        status => anyhow::bail!("DNS query fails with rcode {status}"),
+    }
+    // The answer also has the CNAME chain, only keep the asked type
+    Ok(resp
+        .answer
+        .into_iter()
+        .filter(|record| record.kind == kind.code())
+        .collect())
+}
+
+/// Look up the registration of the domain.
+pub async fn whois(data: &AppData, domain: &str) -> anyhow::Result<Registration> {
+    if !is_valid_domain(domain) {
+        anyhow::bail!("{domain} is not a valid domain");
+    }
+    let url = format!("{RDAP_API}/{domain}");
+    let resp = data
+        .requester
+        .send(
+            data.requester
+                .get(&url)
+                .header(reqwest::header::ACCEPT, "application/rdap+json"),
+        )
+        .await?;
+    if resp.status() == reqwest::StatusCode::NOT_FOUND {
+        anyhow::bail!("{domain} is not registered, or its registry doesn't support RDAP");
+    }
+    let rdap: RdapDomain = resp.error_for_status()?.json().await?;
+    Ok(rdap.into())
+}
+
+#[test]
+fn test_is_valid_domain() {
+    assert!(is_valid_domain("example.com"));
+    assert!(is_valid_domain("_dmarc.example.com."));
+    assert!(!is_valid_domain("localhost"));
+    assert!(!is_valid_domain("example.com/../admin"));
+    assert!(!is_valid_domain("-bad.example.com"));
+    assert_eq!("aaaa".parse::<RecordType>().unwrap(), RecordType::Aaaa);
+}
+
+#[test]
+fn test_parse_rdap() {
+    let json = r#"{
+        "ldhName": "EXAMPLE.COM",
+        "status": ["client delete prohibited"],
+        "events": [
+            {"eventAction": "registration", "eventDate": "1995-08-14T04:00:00Z"},
+            {"eventAction": "expiration", "eventDate": "2025-08-13T04:00:00Z"}
+        ],
+        "nameservers": [{"ldhName": "A.IANA-SERVERS.NET"}],
+        "entities": [{
+            "roles": ["registrar"],
+            "vcardArray": ["vcard", [["version", {}, "text", "4.0"], ["fn", {}, "text", "RESERVED-IANA"]]]
+        }]
+    }"#;
+    let rdap: RdapDomain = serde_json::from_str(json).unwrap();
+    let registration = Registration::from(rdap);
+    assert_eq!(registration.domain, "example.com");
+    assert_eq!(registration.registrar.as_deref(), Some("RESERVED-IANA"));
+    assert_eq!(
+        registration.expires.as_deref(),
+        Some("2025-08-13T04:00:00Z")
+    );
+    assert!(registration.updated.is_none());
+    assert_eq!(registration.nameservers, ["a.iana-servers.net"]);
+}
@@ -1,35 +1,156 @@
-use tokio::{io::AsyncWriteExt, net::TcpListener};
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use axum::{extract::State, http::StatusCode, routing::get, Router};
+use teloxide::prelude::Requester;
+
+use crate::app::AppData;
+
+/// Give up the probe if the dependency doesn't respond in time
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+type ProbeFuture = Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send>>;
+type Probe = Box<dyn Fn() -> ProbeFuture + Send + Sync>;
+
+/// Dependencies checked by `/healthz` and `/readyz`. `/readyz` also fails until the bot
+/// finishes starting up.
+#[derive(Default)]
+pub struct HealthCheck {
+    probes: Vec<(&'static str, Probe)>,
+    ready: Arc<AtomicBool>,
+}
+
+impl HealthCheck {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn probe<F, Fut>(mut self, name: &'static str, probe: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        self.probes
+            .push((name, Box::new(move || Box::pin(probe()))));
+        self
+    }
+
+    /// Check Redis is reachable.
+    pub fn redis(self, data: AppData) -> Self {
+        self.probe("redis", move || {
+            let data = data.clone();
+            async move {
+                // The redis client is blocking
+                tokio::task::spawn_blocking(move || data.cacher.ping(PROBE_TIMEOUT)).await?
+            }
+        })
+    }
+
+    /// Check the SQL database is reachable.
+    pub fn database(self, data: AppData) -> Self {
+        self.probe("database", move || {
+            let data = data.clone();
+            async move { data.storage.ping().await }
+        })
+    }
+
+    /// Check the Telegram API accepts our token.
+    pub fn telegram(self, bot: teloxide::Bot) -> Self {
+        self.probe("telegram", move || {
+            let bot = bot.clone();
+            async move {
+                bot.get_me().await?;
+                Ok(())
+            }
+        })
+    }
+
+    /// The flag to set when the bot is ready to handle updates.
+    pub fn ready_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.ready)
+    }
+
+    /// Run all the probes, and describe the failures in the response body.
+    async fn report(&self) -> (StatusCode, String) {
+        let mut failures = Vec::new();
+        for (name, probe) in &self.probes {
+            match tokio::time::timeout(PROBE_TIMEOUT, probe()).await {
+                Ok(Ok(())) => (),
+                Ok(Err(err)) => failures.push(format!("{name}: {err}")),
+                Err(_) => failures.push(format!("{name}: timeout")),
+            }
+        }
+
+        if failures.is_empty() {
+            (StatusCode::OK, "OK".to_string())
+        } else {
+            (StatusCode::SERVICE_UNAVAILABLE, failures.join("\n"))
+        }
+    }
+}
+
+async fn healthz(State(health): State<Arc<HealthCheck>>) -> (StatusCode, String) {
+    health.report().await
+}
+
+async fn readyz(State(health): State<Arc<HealthCheck>>) -> (StatusCode, String) {
+    if !health.ready.load(Ordering::Relaxed) {
+        return (StatusCode::SERVICE_UNAVAILABLE, "starting".to_string());
+    }
+    health.report().await
+}
+
+/// Spawn the health check HTTP server in a non-blocking task for Docker HEALTHCHECK and
+/// Kubernetes probes. `/` only tells the process is up, `/healthz` checks the dependencies, and
+/// `/readyz` additionally waits for the startup. Prometheus metrics are served at `/metrics`.
+pub fn spawn_healthcheck_listner(bind: &str, port: u16, health: HealthCheck) {
+    let app = Router::new()
+        .route("/", get(|| async { "OK" }))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/metrics", get(|| async { crate::metrics::render() }))
+        .with_state(Arc::new(health));
+    let bind = bind.to_string();
 
-/// Spawn a health check listener in a non-blocking task for Docker HEALTHCHECK task
-pub fn spawn_healthcheck_listner(port: u16) {
     tokio::task::spawn(async move {
-        let listener = TcpListener::bind(("127.0.0.1", port))
+        let listener = tokio::net::TcpListener::bind((bind.as_str(), port))
             .await
             .expect("fail to bind docker health listener");
 
-        tracing::info!("Docker health check listening on port {port}");
+        tracing::info!("Health check listening on {bind}:{port}");
 
-        while let Ok((mut stream, _)) = listener.accept().await {
-            tracing::debug!("New Stream Incoming");
-            let res = stream.write_all(b"OK").await;
-            if let Err(err) = res {
-                tracing::error!("fail to response to health checker: {err}")
-            }
+        if let Err(err) = axum::serve(listener, app).await {
+            tracing::error!("health check server stopped: {err}")
         }
     });
 }
 
 #[tokio::test]
 async fn test_healthcheck() {
-    spawn_healthcheck_listner(11451);
+    let health = HealthCheck::new()
+        .probe("pass", || async { Ok(()) })
+        .probe("fail", || async { anyhow::bail!("broken") });
+    let ready = health.ready_flag();
+    spawn_healthcheck_listner("127.0.0.1", 11451, health);
 
     tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
 
-    use tokio::process::Command;
-    let op = Command::new("nc")
-        .args(["127.0.0.1", "11451"])
-        .output()
-        .await
-        .expect("fail to execute netcat");
-    assert_eq!("OK", String::from_utf8(op.stdout).unwrap());
+    let get = |path: &'static str| async move {
+        let resp = reqwest::get(format!("http://127.0.0.1:11451{path}"))
+            .await
+            .unwrap();
+        (resp.status().as_u16(), resp.text().await.unwrap())
+    };
+    assert_eq!(get("/").await, (200, "OK".to_string()));
+    assert_eq!(get("/healthz").await, (503, "fail: broken".to_string()));
+    assert_eq!(get("/readyz").await, (503, "starting".to_string()));
+    ready.store(true, Ordering::Relaxed);
+    assert_eq!(get("/readyz").await, (503, "fail: broken".to_string()));
 }
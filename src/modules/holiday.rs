@@ -0,0 +1,317 @@
+use crate::{app::AppData, config::Config, event::EventWatcher};
+use chrono::{Datelike, Duration, Local, NaiveDate, Timelike};
+use redis::Commands;
+use serde::{Deserialize, Serialize};
+
+use super::Sendable;
+use crate::quiet_hours::{self, Notification};
+use crate::topic::ChatTarget;
+
+/// Region with the official holiday and make-up workday dataset
+pub const DEFAULT_REGION: &str = "CN";
+/// Local hour to send the reminder for tomorrow
+const REMIND_HOUR: u32 = 20;
+/// ISO 3166-1 alpha-2 codes of the countries, the regions accepted by https://date.nager.at
+const COUNTRY_CODES: &str = "\
+AD AE AF AG AI AL AM AO AQ AR AS AT AU AW AX AZ BA BB BD BE BF BG BH BI \
+BJ BL BM BN BO BQ BR BS BT BV BW BY BZ CA CC CD CF CG CH CI CK CL CM CN \
+CO CR CU CV CW CX CY CZ DE DJ DK DM DO DZ EC EE EG EH ER ES ET FI FJ FK \
+FM FO FR GA GB GD GE GF GG GH GI GL GM GN GP GQ GR GS GT GU GW GY HK HM \
+HN HR HT HU ID IE IL IM IN IO IQ IR IS IT JE JM JO JP KE KG KH KI KM KN \
+KP KR KW KY KZ LA LB LC LI LK LR LS LT LU LV LY MA MC MD ME MF MG MH MK \
+ML MM MN MO MP MQ MR MS MT MU MV MW MX MY MZ NA NC NE NF NG NI NL NO NP \
+NR NU NZ OM PA PE PF PG PH PK PL PM PN PR PS PT PW PY QA RE RO RS RU RW \
+SA SB SC SD SE SG SH SI SJ SK SL SM SN SO SR SS ST SV SX SY SZ TC TD TF \
+TG TH TJ TK TL TM TN TO TR TT TV TW TZ UA UG UM US UY UZ VA VC VE VG VI \
+VN VU WF WS YE YT ZA ZM ZW";
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct HolidayDay {
+    pub name: String,
+    pub date: NaiveDate,
+    /// `false` means this is a shifted workday which need to go to work on weekend
+    #[serde(rename = "isOffDay")]
+    pub is_off_day: bool,
+}
+
+impl HolidayDay {
+    fn to_line(&self, lang: &str) -> String {
+        let date = self.date.format("%F %a");
+        if self.is_off_day {
+            crate::t!(lang, "holiday.off_day", date = date, name = self.name)
+        } else {
+            crate::t!(lang, "holiday.workday", date = date, name = self.name)
+        }
+    }
+}
+
+/// Uppercase the region, or none if it is not an ISO 3166-1 alpha-2 country code.
+pub fn normalize_region(region: &str) -> Option<String> {
+    let region = region.to_uppercase();
+    COUNTRY_CODES
+        .split_whitespace()
+        .any(|code| code == region)
+        .then_some(region)
+}
+
+/// The holiday or shifted workday tomorrow that needs a reminder tonight. A holiday is reminded
+/// only on the evening before it starts, not every evening during it. The weekend before a
+/// holiday still gets the reminder, as the datasets don't list the weekends.
+fn reminder_of(days: &[HolidayDay], today: NaiveDate) -> Option<&HolidayDay> {
+    let tomorrow = today + Duration::days(1);
+    let on_holiday = days.iter().any(|day| day.date == today && day.is_off_day);
+    days.iter()
+        .find(|day| day.date == tomorrow)
+        .filter(|day| !day.is_off_day || !on_holiday)
+}
+
+/// Response from https://github.com/NateScarlet/holiday-cn
+#[derive(Deserialize)]
+struct HolidayCnResponse {
+    days: Vec<HolidayDay>,
+}
+
+/// Response from https://date.nager.at
+#[derive(Deserialize)]
+struct NagerHoliday {
+    date: NaiveDate,
+    #[serde(rename = "localName")]
+    local_name: String,
+}
+
+async fn fetch_dataset(data: &AppData, region: &str, year: i32) -> anyhow::Result<Vec<HolidayDay>> {
+    if region == DEFAULT_REGION {
+        let url =
+            format!("https://raw.githubusercontent.com/NateScarlet/holiday-cn/master/{year}.json");
+        // Dataset for next year is not available until the government announce it
+        let resp = data.requester.send(data.requester.get(&url)).await?;
+        if resp.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(Vec::new());
+        }
+        let resp: HolidayCnResponse = resp.error_for_status()?.json().await?;
+        return Ok(resp.days);
+    }
+
+    let url = format!("https://date.nager.at/api/v3/PublicHolidays/{year}/{region}");
+    let resp: Vec<NagerHoliday> = data.requester.to_t(url).await?;
+    Ok(resp
+        .into_iter()
+        .map(|day| HolidayDay {
+            name: day.local_name,
+            date: day.date,
+            is_off_day: true,
+        })
+        .collect())
+}
+
+/// Get the holiday dataset of the given year, the dataset will be cached in redis for a year.
+pub async fn get_dataset(
+    data: &AppData,
+    region: &str,
+    year: i32,
+) -> anyhow::Result<Vec<HolidayDay>> {
+    let Some(region) = normalize_region(region) else {
+        anyhow::bail!("invalid region {region}");
+    };
+    let key = format!("HOLIDAY_DATASET:{region}:{year}");
+    let cache: Option<String> = data.cacher.get_conn().get(&key)?;
+    if let Some(cache) = cache {
+        return Ok(serde_json::from_str(&cache)?);
+    }
+
+    let mut dataset = fetch_dataset(data, &region, year).await?;
+    dataset.sort_by_key(|day| day.date);
+    // Retry a day later if the dataset is not published yet
+    let ttl = if dataset.is_empty() {
+        60 * 60 * 24
+    } else {
+        60 * 60 * 24 * 365
+    };
+    let () = data
+        .cacher
+        .get_conn()
+        .set_ex(&key, serde_json::to_string(&dataset)?, ttl)?;
+
+    Ok(dataset)
+}
+
+/// Get at most `max` holidays and shifted workdays since `since` (inclusive).
+pub async fn upcoming(
+    data: &AppData,
+    region: &str,
+    since: NaiveDate,
+    max: usize,
+) -> anyhow::Result<Vec<HolidayDay>> {
+    let mut days = get_dataset(data, region, since.year()).await?;
+    days.extend(get_dataset(data, region, since.year() + 1).await?);
+
+    Ok(days
+        .into_iter()
+        .filter(|day| day.date >= since)
+        .take(max)
+        .collect())
+}
+
+pub async fn next_holidays(data: AppData, region: &str, lang: &str) -> anyhow::Result<Sendable> {
+    let Some(region) = normalize_region(region) else {
+        return Ok(Sendable::text(crate::t!(
+            lang,
+            "holiday.invalid_region",
+            region = region
+        )));
+    };
+    let today = Local::now().date_naive();
+    let days = upcoming(&data, &region, today, 10).await?;
+    if days.is_empty() {
+        return Ok(Sendable::text(crate::t!(
+            lang,
+            "holiday.none",
+            region = region
+        )));
+    }
+
+    let title = crate::t!(lang, "holiday.upcoming", region = region);
+    let display = days.iter().fold(title + "\n", |acc, day| {
+        let line = crate::t!(
+            lang,
+            "holiday.days_left",
+            line = day.to_line(lang),
+            days = (day.date - today).num_days()
+        );
+        format!("{acc}\n{line}")
+    });
+
+    Ok(Sendable::text(display))
+}
+
+pub fn spawn_holiday_reminder(bot: teloxide::Bot, data: AppData, config: &Config) {
+    EventWatcher::builder()
+        .name("HolidayReminder")
+        .bot(bot)
+        .data(data)
+        .client(None)
+        .heartbeat_interval(config.watcher.holiday_interval)
+        .interval_of(|config| config.watcher.holiday_interval)
+        .bot_of(|config| config.watcher.holiday_bot.as_deref())
+        .build()
+        .setup_subscribe_registry(config.holiday_event.iter())
+        .start_with_task(remind_tomorrow);
+}
+
+async fn remind_tomorrow(ctx: EventWatcher<()>) -> anyhow::Result<()> {
+    let now = Local::now();
+    if now.hour() < REMIND_HOUR {
+        return Ok(());
+    }
+    let today = now.date_naive();
+    let tomorrow = today + Duration::days(1);
+
+    let regions: Vec<String> = ctx.event_pool()?;
+    for region in regions {
+        let days = upcoming(&ctx.data, &region, today, 10).await?;
+        let Some(day) = reminder_of(&days, today) else {
+            continue;
+        };
+
+        // Make sure each region only get notified once per day
+        let key = format!("HOLIDAY_REMINDED:{region}:{tomorrow}");
+        let unhandle: bool = redis::cmd("SET")
+            .arg(&key) // key
+            .arg(1) // val
+            .arg("NX") // NX
+            .arg("EX") // EX
+            .arg(60 * 60 * 24 * 2) // SECONDS
+            .query(&mut ctx.data.cacher.get_conn())?;
+        if !unhandle {
+            continue;
+        }
+
+        let subscribers: Vec<String> = ctx.get_subscribers(&region)?;
+        for target in subscribers {
+            let Ok(target) = target.parse::<ChatTarget>() else {
+                tracing::error!("[HolidayReminder] invalid subscriber {target}");
+                continue;
+            };
+            let lang = crate::i18n::chat_language(&ctx.data, target.chat_id.0, None);
+            let text = crate::template::render(
+                "holiday_reminder.txt",
+                lang,
+                minijinja::context! {
+                    name => day.name,
+                    date => day.date,
+                    is_off_day => day.is_off_day,
+                    line => day.to_line(lang),
+                },
+            )?;
+            let notification = Notification::text(text);
+            let sent = quiet_hours::notify(&ctx.bot, &ctx.data, target, notification).await;
+            if let Err(err) = sent {
+                tracing::error!("[HolidayReminder] fail to notify {target}: {err}")
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_parse_holiday_cn() {
+    let json = r#"{
+        "year": 2024,
+        "papers": [],
+        "days": [
+            { "name": "国庆节", "date": "2024-10-01", "isOffDay": true },
+            { "name": "国庆节", "date": "2024-10-12", "isOffDay": false }
+        ]
+    }"#;
+    let resp: HolidayCnResponse = serde_json::from_str(json).unwrap();
+    assert_eq!(resp.days.len(), 2);
+    assert!(resp.days[0].is_off_day);
+    assert_eq!(
+        resp.days[1].to_line("zh-hans"),
+        "💼 2024-10-12 Sat 国庆节 (补班)"
+    );
+    assert_eq!(
+        resp.days[1].to_line("en"),
+        "💼 2024-10-12 Sat 国庆节 (workday)"
+    );
+}
+
+#[test]
+fn test_normalize_region() {
+    assert_eq!(normalize_region("cn").as_deref(), Some("CN"));
+    assert_eq!(normalize_region("US").as_deref(), Some("US"));
+    assert_eq!(normalize_region("XX"), None);
+    assert_eq!(normalize_region("../CN"), None);
+    assert_eq!(normalize_region("CN:2024"), None);
+}
+
+#[test]
+fn test_reminder_of() {
+    let day = |date: &str, is_off_day| HolidayDay {
+        name: "国庆节".to_string(),
+        date: date.parse().unwrap(),
+        is_off_day,
+    };
+    let days = [
+        day("2024-10-01", true),
+        day("2024-10-02", true),
+        day("2024-10-03", true),
+        day("2024-10-12", false),
+    ];
+    let date = |date: &str| date.parse::<NaiveDate>().unwrap();
+
+    assert_eq!(reminder_of(&days, date("2024-09-30")), Some(&days[0]));
+    assert_eq!(reminder_of(&days, date("2024-10-01")), None);
+    assert_eq!(reminder_of(&days, date("2024-10-02")), None);
+    assert_eq!(reminder_of(&days, date("2024-10-11")), Some(&days[3]));
+
+    let weekend = [day("2024-10-06", true), day("2024-10-07", true)];
+    assert_eq!(reminder_of(&weekend, date("2024-10-06")), None);
+    let sunday = [day("2024-10-13", false)];
+    assert_eq!(reminder_of(&sunday, date("2024-10-12")), Some(&sunday[0]));
+    // Memorial Day on Monday, reminded on the Sunday evening
+    let monday = [day("2025-05-26", true)];
+    assert_eq!(reminder_of(&monday, date("2025-05-25")), Some(&monday[0]));
+    assert_eq!(reminder_of(&monday, date("2025-05-23")), None);
+}
@@ -0,0 +1,228 @@
+use std::path::Path;
+
+use redis::Commands;
+use sysinfo::{Disks, ProcessesToUpdate, System};
+
+use crate::{
+    app::AppData,
+    config::Config,
+    event::EventWatcher,
+    i18n,
+    send_queue::Priority,
+    topic::{ChatTarget, SendTo},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostReport {
+    pub cpu_percent: f32,
+    pub cpus: usize,
+    pub memory_used: u64,
+    pub memory_total: u64,
+    /// Mount point of the disk holding the asked path
+    pub disk_mount: String,
+    pub disk_used: u64,
+    pub disk_total: u64,
+    /// Load average of the last 1, 5 and 15 minutes
+    pub load: [f64; 3],
+    /// Seconds since the host booted
+    pub host_uptime: u64,
+    /// Seconds since the bot started, the container uptime when running in one
+    pub bot_uptime: u64,
+}
+
+impl HostReport {
+    pub fn memory_percent(&self) -> f32 {
+        percent(self.memory_used, self.memory_total)
+    }
+
+    pub fn disk_percent(&self) -> f32 {
+        percent(self.disk_used, self.disk_total)
+    }
+}
+
+fn percent(used: u64, total: u64) -> f32 {
+    if total == 0 {
+        return 0.0;
+    }
+    used as f32 / total as f32 * 100.0
+}
+
+/// Show the bytes in the binary units, like `1.5 GiB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+/// Show the seconds like `3d 4h 5m`.
+pub fn format_uptime(seconds: u64) -> String {
+    let (days, hours, minutes) = (seconds / 86400, seconds % 86400 / 3600, seconds % 3600 / 60);
+    match (days, hours) {
+        (0, 0) => format!("{minutes}m"),
+        (0, _) => format!("{hours}h {minutes}m"),
+        _ => format!("{days}d {hours}h {minutes}m"),
+    }
+}
+
+/// Find the disk holding the path, it is the one with the longest matching mount point.
+fn disk_of<'a>(
+    mounts: impl Iterator<Item = (&'a Path, u64, u64)>,
+    path: &Path,
+) -> Option<(String, u64, u64)> {
+    mounts
+        .filter(|(mount, _, _)| path.starts_with(mount))
+        .max_by_key(|(mount, _, _)| mount.as_os_str().len())
+        .map(|(mount, total, available)| {
+            (
+                mount.display().to_string(),
+                total.saturating_sub(available),
+                total,
+            )
+        })
+}
+
+fn collect(disk_path: &str) -> HostReport {
+    let mut system = System::new();
+    system.refresh_cpu_usage();
+    // The CPU usage is measured between two refreshes
+    std::thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
+    system.refresh_cpu_usage();
+    system.refresh_memory();
+
+    let bot_uptime = sysinfo::get_current_pid()
+        .ok()
+        .and_then(|pid| {
+            system.refresh_processes(ProcessesToUpdate::Some(&[pid]), true);
+            system.process(pid).map(|process| process.run_time())
+        })
+        .unwrap_or_default();
+
+    let disks = Disks::new_with_refreshed_list();
+    let (disk_mount, disk_used, disk_total) = disk_of(
+        disks.iter().map(|disk| {
+            (
+                disk.mount_point(),
+                disk.total_space(),
+                disk.available_space(),
+            )
+        }),
+        Path::new(disk_path),
+    )
+    .unwrap_or_default();
+
+    let load = System::load_average();
+    HostReport {
+        cpu_percent: system.global_cpu_usage(),
+        cpus: system.cpus().len(),
+        memory_used: system.total_memory() - system.available_memory(),
+        memory_total: system.total_memory(),
+        disk_mount,
+        disk_used,
+        disk_total,
+        load: [load.one, load.five, load.fifteen],
+        host_uptime: System::uptime(),
+        bot_uptime,
+    }
+}
+
+/// Measure the resources of the host, `disk_path` is any path on the disk to report.
+pub async fn report(disk_path: &str) -> anyhow::Result<HostReport> {
+    let disk_path = disk_path.to_string();
+    // Measuring the CPU blocks for a moment
+    Ok(tokio::task::spawn_blocking(move || collect(&disk_path)).await?)
+}
+
+pub fn spawn_resource_watcher(bot: teloxide::Bot, data: AppData, config: &Config) {
+    EventWatcher::builder()
+        .name("ResourceWatcher")
+        .bot(bot)
+        .data(data)
+        .client(None)
+        .heartbeat_interval(config.watcher.resource_interval)
+        .interval_of(|config| config.watcher.resource_interval)
+        .bot_of(|config| {
+            config
+                .resource_alert
+                .as_ref()
+                .and_then(|alert| alert.bot.as_deref())
+        })
+        .build()
+        .start_with_task(check_resources);
+}
+
+async fn check_resources(ctx: EventWatcher<()>) -> anyhow::Result<()> {
+    let config = Config::get_global_config();
+    let Some(alert) = &config.resource_alert else {
+        return Ok(());
+    };
+    let report = report(&alert.disk_path).await?;
+    let target = ChatTarget::from(teloxide::types::ChatId(alert.chat_id));
+    let lang = i18n::chat_language(&ctx.data, alert.chat_id, None);
+
+    for (resource, usage, threshold) in [
+        ("disk", report.disk_percent(), alert.disk_percent),
+        ("memory", report.memory_percent(), alert.memory_percent),
+    ] {
+        // Alert once when going above the threshold, and once when going back
+        let key = format!("RESOURCE_ALERTING:{resource}");
+        let over = usage >= threshold;
+        let alerting: bool = ctx.data.cacher.get_conn().exists(&key)?;
+        if over == alerting {
+            continue;
+        }
+        let text = if over {
+            let () = ctx.data.cacher.get_conn().set(&key, usage)?;
+            crate::t!(
+                lang,
+                "host.over_threshold",
+                resource = resource,
+                usage = format!("{usage:.1}"),
+                threshold = threshold
+            )
+        } else {
+            let () = ctx.data.cacher.get_conn().del(&key)?;
+            crate::t!(
+                lang,
+                "host.back_to_normal",
+                resource = resource,
+                usage = format!("{usage:.1}")
+            )
+        };
+        let bot = ctx.bot.clone();
+        ctx.data
+            .submit(target.chat_id, Priority::Background, move || {
+                bot.send_message_to(target, &text)
+            })
+            .await?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_format() {
+    assert_eq!(format_bytes(512), "512 B");
+    assert_eq!(format_bytes(1536), "1.5 KiB");
+    assert_eq!(format_bytes(5 * 1024 * 1024 * 1024), "5.0 GiB");
+    assert_eq!(format_uptime(59), "0m");
+    assert_eq!(format_uptime(3 * 3600 + 120), "3h 2m");
+    assert_eq!(format_uptime(2 * 86400 + 3600), "2d 1h 0m");
+}
+
+#[test]
+fn test_disk_of() {
+    let mounts = [(Path::new("/"), 100, 40), (Path::new("/data"), 1000, 900)];
+    let disk = |path: &str| disk_of(mounts.iter().copied(), Path::new(path));
+    assert_eq!(disk("/data/redis"), Some(("/data".to_string(), 100, 1000)));
+    assert_eq!(disk("/home"), Some(("/".to_string(), 60, 100)));
+    assert_eq!(disk("relative"), None);
+}
@@ -0,0 +1,180 @@
+use crate::app::AppData;
+use crate::helper::Html;
+use crate::storage::Storage;
+use teloxide::utils::html;
+
+use super::Sendable;
+
+/// Check if the given reply text is one of the karma trigger words. The comparison ignore case,
+/// surrounding whitespace and trailing punctuation, so "Thanks!" and "+1" both match.
+pub fn is_trigger(text: &str, triggers: &[String]) -> bool {
+    let text = text
+        .trim()
+        .trim_end_matches(|c: char| c.is_ascii_punctuation() || "！。～~".contains(c))
+        .to_lowercase();
+    if text.is_empty() {
+        return false;
+    }
+
+    triggers.iter().any(|t| t.to_lowercase() == text)
+}
+
+/// Give one karma point from `giver` to `receiver` in the given chat. Returns `None` if the
+/// giver is still in cooldown for this receiver, or the new karma of the receiver.
+pub async fn give(
+    data: &AppData,
+    chat_id: i64,
+    giver: u64,
+    receiver: (u64, &str),
+    cooldown: u64,
+) -> anyhow::Result<Option<i64>> {
+    let (receiver_id, receiver_name) = receiver;
+
+    // The cooldown is short-lived, so it stays in Redis
+    let cooldown_key = format!("KARMA_COOLDOWN:{chat_id}:{giver}:{receiver_id}");
+    let unhandle: bool = redis::cmd("SET")
+        .arg(&cooldown_key) // key
+        .arg(1) // val
+        .arg("NX") // NX
+        .arg("EX") // EX
+        .arg(cooldown) // SECONDS
+        .query(&mut data.cacher.get_conn())?;
+    if !unhandle {
+        return Ok(None);
+    }
+
+    let karma = add_karma(&data.storage, chat_id, receiver_id, receiver_name, 1).await?;
+    Ok(Some(karma))
+}
+
+/// Get the karma and the rank of the given user in current chat.
+pub async fn query(
+    data: &AppData,
+    chat_id: i64,
+    user_id: u64,
+    lang: &str,
+) -> anyhow::Result<Sendable> {
+    let display = match karma_rank(&data.storage, chat_id, user_id).await? {
+        Some((karma, rank)) => crate::t!(lang, "karma.rank", karma = karma, rank = rank + 1),
+        None => crate::t!(lang, "karma.none"),
+    };
+
+    Ok(Sendable::text(display))
+}
+
+/// Render the top `max` users of the karma leaderboard in current chat.
+pub async fn leaderboard(
+    data: &AppData,
+    chat_id: i64,
+    max: i64,
+    lang: &str,
+) -> anyhow::Result<Sendable> {
+    let board = karma_top(&data.storage, chat_id, max).await?;
+    if board.is_empty() {
+        return Ok(Sendable::text(crate::t!(lang, "karma.nobody")));
+    }
+
+    let display = board.iter().enumerate().fold(
+        crate::t!(lang, "karma.leaderboard") + "\n",
+        |mut acc, (i, (name, karma))| {
+            acc.push_str(&format!(
+                "\n{}. {} - {}",
+                i + 1,
+                html::escape(name),
+                Html::b(karma)
+            ));
+            acc
+        },
+    );
+
+    Ok(Sendable::text(display))
+}
+
+/// Add `delta` to the karma of the user and remember the latest name, return the new karma.
+async fn add_karma(
+    storage: &Storage,
+    chat_id: i64,
+    user_id: u64,
+    username: &str,
+    delta: i64,
+) -> anyhow::Result<i64> {
+    let (karma,): (i64,) = sqlx::query_as(
+        "INSERT INTO karma (chat_id, user_id, username, karma) VALUES ($1, $2, $3, $4) \
+         ON CONFLICT (chat_id, user_id) \
+         DO UPDATE SET username = excluded.username, karma = karma.karma + excluded.karma \
+         RETURNING karma",
+    )
+    .bind(chat_id)
+    .bind(user_id as i64)
+    .bind(username)
+    .bind(delta)
+    .fetch_one(storage.pool())
+    .await?;
+    Ok(karma)
+}
+
+/// Get the karma and the zero-based rank (highest karma first) of the user. Users with the same
+/// karma share the rank.
+async fn karma_rank(
+    storage: &Storage,
+    chat_id: i64,
+    user_id: u64,
+) -> anyhow::Result<Option<(i64, u64)>> {
+    let rank: Option<(i64, i64)> = sqlx::query_as(
+        "SELECT k.karma, \
+         (SELECT COUNT(*) FROM karma o WHERE o.chat_id = k.chat_id AND o.karma > k.karma) \
+         FROM karma k WHERE k.chat_id = $1 AND k.user_id = $2",
+    )
+    .bind(chat_id)
+    .bind(user_id as i64)
+    .fetch_optional(storage.pool())
+    .await?;
+    Ok(rank.map(|(karma, rank)| (karma, rank as u64)))
+}
+
+/// Get the name and karma of the top `max` users, highest karma first.
+async fn karma_top(
+    storage: &Storage,
+    chat_id: i64,
+    max: i64,
+) -> anyhow::Result<Vec<(String, i64)>> {
+    let top = sqlx::query_as(
+        "SELECT username, karma FROM karma WHERE chat_id = $1 \
+         ORDER BY karma DESC, user_id LIMIT $2",
+    )
+    .bind(chat_id)
+    .bind(max)
+    .fetch_all(storage.pool())
+    .await?;
+    Ok(top)
+}
+
+#[test]
+fn test_karma_trigger() {
+    let triggers = ["+1", "thanks", "谢谢"].map(String::from);
+
+    assert!(is_trigger("+1", &triggers));
+    assert!(is_trigger("  Thanks! ", &triggers));
+    assert!(is_trigger("谢谢！", &triggers));
+    assert!(!is_trigger("thanks for nothing", &triggers));
+    assert!(!is_trigger("!!!", &triggers));
+}
+
+#[tokio::test]
+async fn test_karma_storage() {
+    let storage = crate::testkit::memory_storage().await;
+
+    assert_eq!(add_karma(&storage, 1, 10, "alice", 1).await.unwrap(), 1);
+    assert_eq!(add_karma(&storage, 1, 10, "Alice", 1).await.unwrap(), 2);
+    assert_eq!(add_karma(&storage, 1, 20, "bob", 1).await.unwrap(), 1);
+    assert_eq!(add_karma(&storage, 2, 20, "bob", 5).await.unwrap(), 5);
+
+    assert_eq!(karma_rank(&storage, 1, 10).await.unwrap(), Some((2, 0)));
+    assert_eq!(karma_rank(&storage, 1, 20).await.unwrap(), Some((1, 1)));
+    assert_eq!(karma_rank(&storage, 1, 30).await.unwrap(), None);
+    assert_eq!(
+        karma_top(&storage, 1, 10).await.unwrap(),
+        vec![("Alice".to_string(), 2), ("bob".to_string(), 1)]
+    );
+    assert_eq!(karma_top(&storage, 2, 10).await.unwrap().len(), 1);
+}
@@ -0,0 +1,245 @@
+use chinese_lunisolar_calendar::{LunisolarDate, SolarDate};
+use chrono::{Datelike, Duration, Local, NaiveDate};
+
+use super::Sendable;
+
+const SOLAR_TERMS: [&str; 24] = [
+    "小寒", "大寒", "立春", "雨水", "惊蛰", "春分", "清明", "谷雨", "立夏", "小满", "芒种", "夏至",
+    "小暑", "大暑", "立秋", "处暑", "白露", "秋分", "寒露", "霜降", "立冬", "小雪", "大雪", "冬至",
+];
+
+/// The `C` constant of the 21st century for each solar term, used by the formula
+/// `[Y * D + C] - L` to calculate the day of solar term.
+const SOLAR_TERM_C: [f64; 24] = [
+    5.4055, 20.12, 3.87, 18.73, 5.63, 20.646, 4.81, 20.1, 5.52, 21.04, 5.678, 21.37, 7.108, 22.83,
+    7.5, 23.13, 7.646, 23.042, 8.318, 23.438, 7.438, 22.36, 7.18, 21.94,
+];
+
+/// Known years that the formula is off by one day: (year, solar term index, offset)
+const SOLAR_TERM_EXCEPTIONS: [(i32, usize, i64); 10] = [
+    (2002, 14, 1),
+    (2008, 9, 1),
+    (2016, 12, 1),
+    (2019, 0, -1),
+    (2021, 23, -1),
+    (2026, 3, -1),
+    (2082, 1, 1),
+    (2084, 5, 1),
+    (2089, 19, 1),
+    (2089, 20, 1),
+];
+
+const LUNAR_FESTIVALS: [(u8, u8, &str); 10] = [
+    (1, 1, "春节"),
+    (1, 15, "元宵节"),
+    (2, 2, "龙抬头"),
+    (5, 5, "端午节"),
+    (7, 7, "七夕节"),
+    (7, 15, "中元节"),
+    (8, 15, "中秋节"),
+    (9, 9, "重阳节"),
+    (12, 8, "腊八节"),
+    (12, 23, "小年"),
+];
+
+const SOLAR_FESTIVALS: [(u32, u32, &str); 9] = [
+    (1, 1, "元旦"),
+    (2, 14, "情人节"),
+    (3, 8, "妇女节"),
+    (4, 1, "愚人节"),
+    (5, 1, "劳动节"),
+    (6, 1, "儿童节"),
+    (10, 1, "国庆节"),
+    (12, 24, "平安夜"),
+    (12, 25, "圣诞节"),
+];
+
+/// Calculate the date of the solar term by its index in [`SOLAR_TERMS`]. Only year 2001 to
+/// 2099 is supported by the formula.
+pub fn solar_term_date(year: i32, index: usize) -> Option<NaiveDate> {
+    if !(2001..=2099).contains(&year) || index >= SOLAR_TERMS.len() {
+        return None;
+    }
+
+    const D: f64 = 0.2422;
+    let y = year % 100;
+    // Solar terms in January and February counts leap year before this year
+    let l = if index < 4 { (y - 1) / 4 } else { y / 4 };
+    let day = (y as f64 * D + SOLAR_TERM_C[index]).floor() as i64 - l as i64;
+    let offset = SOLAR_TERM_EXCEPTIONS
+        .iter()
+        .find(|(y, i, _)| *y == year && *i == index)
+        .map_or(0, |(_, _, offset)| *offset);
+
+    NaiveDate::from_ymd_opt(year, index as u32 / 2 + 1, (day + offset) as u32)
+}
+
+/// Get the solar term of the given date if it has.
+pub fn solar_term_of(date: NaiveDate) -> Option<&'static str> {
+    let first = (date.month0() * 2) as usize;
+    (first..first + 2)
+        .find(|i| solar_term_date(date.year(), *i) == Some(date))
+        .map(|i| SOLAR_TERMS[i])
+}
+
+/// Find the next solar term after the given date (exclusive).
+pub fn next_solar_term(date: NaiveDate) -> Option<(&'static str, NaiveDate)> {
+    (date.year()..=date.year() + 1)
+        .flat_map(|year| (0..SOLAR_TERMS.len()).map(move |i| (year, i)))
+        .filter_map(|(year, i)| Some((SOLAR_TERMS[i], solar_term_date(year, i)?)))
+        .find(|(_, term)| *term > date)
+}
+
+/// Get the traditional and public festivals of the given date.
+pub fn festivals_of(date: NaiveDate) -> Vec<&'static str> {
+    let mut festivals: Vec<_> = SOLAR_FESTIVALS
+        .iter()
+        .filter(|(m, d, _)| *m == date.month() && *d == date.day())
+        .map(|(_, _, name)| *name)
+        .collect();
+
+    if let Ok(lunar) = to_lunar(date) {
+        let (month, day) = (lunar.to_lunar_month(), lunar.to_lunar_day().to_u8());
+        if !month.is_leap_month() {
+            festivals.extend(
+                LUNAR_FESTIVALS
+                    .iter()
+                    .filter(|(m, d, _)| *m == month.to_u8() && *d == day)
+                    .map(|(_, _, name)| *name),
+            );
+        }
+
+        // The last day of the year can be the 29th or 30th of the 12th month
+        let is_new_year_eve = to_lunar(date + Duration::days(1))
+            .map(|next| next.to_lunar_month().to_u8() == 1 && next.to_lunar_day().to_u8() == 1)
+            .unwrap_or(false);
+        if is_new_year_eve {
+            festivals.push("除夕");
+        }
+    }
+
+    if let Some("清明") = solar_term_of(date) {
+        festivals.push("清明节");
+    }
+
+    festivals
+}
+
+pub fn to_lunar(date: NaiveDate) -> anyhow::Result<LunisolarDate> {
+    let solar = SolarDate::from_date(date)
+        .map_err(|err| anyhow::anyhow!("{date} is not supported: {err}"))?;
+    LunisolarDate::from_solar_date(solar)
+        .map_err(|err| anyhow::anyhow!("{date} is not supported: {err}"))
+}
+
+/// Convert the lunar date to Gregorian date, `year` is the Gregorian year when the lunar year
+/// starts.
+pub fn to_solar(year: u16, month: u8, leap: bool, day: u8) -> anyhow::Result<NaiveDate> {
+    let lunar = LunisolarDate::from_ymd(year, month, leap, day)
+        .map_err(|err| anyhow::anyhow!("invalid lunar date: {err}"))?;
+    Ok(lunar.to_naive_date())
+}
+
+/// Parse the user input into a Gregorian date. Accept `2024-10-01` as Gregorian date, and
+/// `L2024-08-15` or `L2023-闰2-15` as lunar date.
+pub fn parse_date(input: &str) -> anyhow::Result<NaiveDate> {
+    let input = input.trim();
+    let Some(lunar) = input
+        .strip_prefix(['L', 'l'])
+        .or_else(|| input.strip_prefix("农历"))
+    else {
+        return NaiveDate::parse_from_str(input, "%Y-%m-%d")
+            .map_err(|_| anyhow::anyhow!("invalid date {input}, expect format like 2024-10-01"));
+    };
+
+    let invalid = || anyhow::anyhow!("invalid lunar date {input}, expect format like L2024-08-15");
+    let mut parts = lunar.split('-');
+    let (Some(year), Some(month), Some(day), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid());
+    };
+    let (leap, month) = match month.strip_prefix(['闰', '閏', 'r', 'R']) {
+        Some(month) => (true, month),
+        None => (false, month),
+    };
+
+    to_solar(
+        year.parse().map_err(|_| invalid())?,
+        month.parse().map_err(|_| invalid())?,
+        leap,
+        day.parse().map_err(|_| invalid())?,
+    )
+}
+
+pub fn lookup(input: Option<&str>, lang: &str) -> anyhow::Result<Sendable> {
+    let date = match input {
+        Some(input) => parse_date(input)?,
+        None => Local::now().date_naive(),
+    };
+    let lunar = to_lunar(date)?;
+
+    let weekdays = crate::t!(lang, "lunar.weekdays");
+    let weekday = weekdays
+        .split(' ')
+        .nth(date.weekday().num_days_from_monday() as usize)
+        .unwrap_or_default();
+    let mut lines = vec![
+        crate::t!(
+            lang,
+            "lunar.solar",
+            date = date.format("%F"),
+            weekday = weekday
+        ),
+        crate::t!(lang, "lunar.lunar", date = format!("{lunar:#}")),
+    ];
+    if let Some(term) = solar_term_of(date) {
+        lines.push(crate::t!(lang, "lunar.term", term = term));
+    }
+    if let Some((term, term_date)) = next_solar_term(date) {
+        lines.push(crate::t!(
+            lang,
+            "lunar.next_term",
+            term = term,
+            date = term_date.format("%m-%d"),
+            days = (term_date - date).num_days()
+        ));
+    }
+
+    let festivals = festivals_of(date);
+    if !festivals.is_empty() {
+        let festivals = festivals.join(&crate::t!(lang, "lunar.separator"));
+        lines.push(crate::t!(lang, "lunar.festivals", festivals = festivals));
+    }
+
+    Ok(Sendable::text(lines.join("\n")))
+}
+
+#[test]
+fn test_solar_term() {
+    let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+
+    assert_eq!(solar_term_of(date(2024, 2, 4)), Some("立春"));
+    assert_eq!(solar_term_of(date(2024, 4, 4)), Some("清明"));
+    assert_eq!(solar_term_of(date(2024, 12, 21)), Some("冬至"));
+    assert_eq!(solar_term_of(date(2021, 12, 21)), Some("冬至"));
+    assert_eq!(solar_term_of(date(2024, 12, 22)), None);
+    assert_eq!(
+        next_solar_term(date(2024, 12, 25)),
+        Some(("小寒", date(2025, 1, 5)))
+    );
+}
+
+#[test]
+fn test_lunar_conversion() {
+    let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+
+    assert_eq!(parse_date("L2024-08-15").unwrap(), date(2024, 9, 17));
+    assert_eq!(parse_date("L2023-闰2-01").unwrap(), date(2023, 3, 22));
+    assert_eq!(parse_date("2024-10-01").unwrap(), date(2024, 10, 1));
+    assert!(parse_date("L2024-13-01").is_err());
+
+    assert_eq!(festivals_of(date(2024, 9, 17)), ["中秋节"]);
+    assert_eq!(festivals_of(date(2024, 2, 9)), ["除夕"]);
+    assert_eq!(festivals_of(date(2024, 10, 1)), ["国庆节"]);
+}
@@ -1,17 +1,39 @@
 // Provider Module
+pub mod archive;
 pub mod archlinux;
 pub mod bilibili;
+pub mod broadcast;
+pub mod calc;
+pub mod cert;
 pub mod collect;
+pub mod counter;
 pub mod currency;
+pub mod dns;
 pub mod ehentai;
 pub mod health;
+pub mod holiday;
+pub mod host;
+pub mod karma;
 pub mod ksyx;
+pub mod lunar;
+pub mod monitor;
 pub mod nsfw;
+pub mod package;
 pub mod piggy;
 pub mod price;
+pub mod qr;
+pub mod quiz;
+pub mod reaction;
+pub mod roll;
+pub mod secret;
+pub mod spam;
 pub mod steam;
+pub mod sticker_pack;
+pub mod summary;
+pub mod translate;
 pub mod video_dl;
 pub mod weather;
+pub mod wiki;
 pub mod ytd;
 
 // Every module should provide a function that turn user input to [`Sendable`]
@@ -19,10 +41,16 @@ use reqwest::IntoUrl;
 use std::fmt::Display;
 use teloxide::{
     payloads::SendPhotoSetters,
-    prelude::{Bot, Message, Requester},
+    prelude::{Bot, Message},
     types::InputFile,
 };
 
+use crate::{
+    app::AppData,
+    send_queue::Priority,
+    topic::{ChatTarget, SendTo},
+};
+
 pub enum Sendable {
     Text(String),
     File(InputFile, Option<String>),
@@ -30,63 +58,67 @@ pub enum Sendable {
 
 #[macro_export]
 macro_rules! sendable {
-    ($bot:expr, $msg:expr, $sendable:expr) => {
+    ($bot:expr, $data:expr, $msg:expr, $sendable:expr) => {{
+        use $crate::topic::SendTo as _;
+        let target = $crate::topic::ChatTarget::of(&$msg);
         match $sendable {
             Sendable::Text(msg) => {
-                $bot.send_message($msg.chat.id, msg).await?;
+                $data
+                    .reply(target.chat_id, $bot.send_message_to(target, msg))
+                    .await?;
             }
             Sendable::File(file, caption) => {
-                if let Some(caption) = caption {
-                    $bot.send_photo($msg.chat.id, file).caption(caption).await?;
-                } else {
-                    $bot.send_photo($msg.chat.id, file).await?;
-                }
+                let request = $bot.send_photo_to(target, file);
+                let request = match caption {
+                    Some(caption) => request.caption(caption),
+                    None => request,
+                };
+                $data.reply(target.chat_id, request).await?;
             }
         }
-    };
+    }};
 
-    ($bot:expr, $msg:expr, $sendable:expr, format=$format:ident) => {
+    ($bot:expr, $data:expr, $msg:expr, $sendable:expr, format=$format:ident) => {{
+        use $crate::topic::SendTo as _;
+        let target = $crate::topic::ChatTarget::of(&$msg);
         match $sendable {
             Sendable::Text(msg) => {
-                $bot.send_message($msg.chat.id, msg)
-                    .parse_mode(ParseMode::$format)
-                    .await?;
+                let request = $bot
+                    .send_message_to(target, msg)
+                    .parse_mode(ParseMode::$format);
+                $data.reply(target.chat_id, request).await?;
             }
             Sendable::File(file, caption) => {
-                if let Some(caption) = caption {
-                    $bot.send_photo($msg.chat.id, file)
-                        .caption(caption)
-                        .parse_mode(ParseMode::$format)
-                        .await?;
-                } else {
-                    $bot.send_photo($msg.chat.id, file).await?;
-                }
+                let request = $bot.send_photo_to(target, file);
+                let request = match caption {
+                    Some(caption) => request.caption(caption).parse_mode(ParseMode::$format),
+                    None => request,
+                };
+                $data.reply(target.chat_id, request).await?;
             }
         }
-    };
+    }};
 
-    ($bot:expr, $msg:expr, $sendable:expr, format=$format:ident, spoiler=on) => {
+    ($bot:expr, $data:expr, $msg:expr, $sendable:expr, format=$format:ident, spoiler=on) => {{
+        use $crate::topic::SendTo as _;
+        let target = $crate::topic::ChatTarget::of(&$msg);
         match $sendable {
             Sendable::Text(msg) => {
-                $bot.send_message($msg.chat.id, msg)
-                    .parse_mode(ParseMode::$format)
-                    .await?;
+                let request = $bot
+                    .send_message_to(target, msg)
+                    .parse_mode(ParseMode::$format);
+                $data.reply(target.chat_id, request).await?;
             }
             Sendable::File(file, caption) => {
-                if let Some(caption) = caption {
-                    $bot.send_photo($msg.chat.id, file)
-                        .caption(caption)
-                        .parse_mode(ParseMode::$format)
-                        .has_spoiler(true)
-                        .await?;
-                } else {
-                    $bot.send_photo($msg.chat.id, file)
-                        .has_spoiler(true)
-                        .await?;
-                }
+                let request = $bot.send_photo_to(target, file).has_spoiler(true);
+                let request = match caption {
+                    Some(caption) => request.caption(caption).parse_mode(ParseMode::$format),
+                    None => request,
+                };
+                $data.reply(target.chat_id, request).await?;
             }
         }
-    };
+    }};
 }
 
 impl Sendable {
@@ -102,18 +134,27 @@ impl Sendable {
         Self::Text(s.to_string())
     }
 
-    pub async fn send(self, bot: &Bot, msg: &Message) -> anyhow::Result<()> {
-        let chat = msg.chat.id;
+    /// Reply the message through the send queue.
+    pub async fn send(self, data: &AppData, bot: &Bot, msg: &Message) -> anyhow::Result<()> {
+        let target = ChatTarget::of(msg);
+        let chat = target.chat_id;
+        let bot = bot.clone();
         match self {
             Sendable::Text(msg) => {
-                bot.send_message(chat, msg).await?;
+                data.submit(chat, Priority::Interactive, move || {
+                    bot.send_message_to(target, &msg)
+                })
+                .await?;
             }
             Sendable::File(file, caption) => {
-                if let Some(caption) = caption {
-                    bot.send_photo(chat, file).caption(caption).await?;
-                } else {
-                    bot.send_photo(chat, file).await?;
-                }
+                data.submit(chat, Priority::Interactive, move || {
+                    let request = bot.send_photo_to(target, file.clone());
+                    match &caption {
+                        Some(caption) => request.caption(caption),
+                        None => request,
+                    }
+                })
+                .await?;
             }
         }
 
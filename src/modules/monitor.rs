@@ -0,0 +1,440 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::{Arc, OnceLock},
+    time::{Duration, Instant},
+};
+
+use redis::Commands;
+use serde::{Deserialize, Serialize};
+use teloxide::types::ChatId;
+
+use crate::{
+    app::AppData,
+    event::EventWatcher,
+    http::HttpClient,
+    i18n,
+    send_queue::Priority,
+    topic::{ChatTarget, SendTo},
+};
+
+/// Name of the watcher, the monitored URLs are kept in its subscribe registry
+const WATCHER: &str = "UptimeMonitor";
+/// Seconds between each round of probes
+const PROBE_INTERVAL: u64 = 60;
+/// A probe taking longer than this is a failure
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+/// The URL is only down after failing this many probes in a row, so one lost packet doesn't alert
+const FAILURES_TO_DOWN: u32 = 2;
+/// Each chat monitors at most this many URLs
+pub const MAX_MONITORS_PER_CHAT: usize = 10;
+/// Follow at most this many redirects of the public URLs
+const MAX_REDIRECTS: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MonitorState {
+    pub up: bool,
+    /// Unix timestamp when the URL went up or down
+    pub since: i64,
+    /// Unix timestamp of the last probe
+    pub checked_at: i64,
+    /// Response time of the last successful probe
+    pub latency_ms: Option<u64>,
+    pub error: Option<String>,
+    /// Failed probes in a row
+    #[serde(default)]
+    pub failures: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Probe {
+    pub latency_ms: u64,
+    pub error: Option<String>,
+}
+
+fn state_key(url: &str) -> String {
+    format!("MONITOR_STATE:{url}")
+}
+
+fn chat_key(target: &ChatTarget) -> String {
+    format!("MONITOR_URLS:{target}")
+}
+
+/// Only the public http(s) URLs can be monitored, the bot shouldn't probe its own network.
+pub fn parse_url(url: &str) -> anyhow::Result<reqwest::Url> {
+    let url = reqwest::Url::parse(url)?;
+    if !matches!(url.scheme(), "http" | "https") {
+        anyhow::bail!("only http and https URLs can be monitored");
+    }
+    let host = url.host_str().unwrap_or_default();
+    let internal = match host.trim_start_matches('[').trim_end_matches(']').parse() {
+        Ok(ip) => is_internal(ip),
+        Err(_) => {
+            host.is_empty()
+                || host == "localhost"
+                || host.ends_with(".localhost")
+                || host.ends_with(".local")
+        }
+    };
+    if internal {
+        anyhow::bail!("{url} is not a public address");
+    }
+    Ok(url)
+}
+
+fn is_internal(ip: IpAddr) -> bool {
+    if ip.is_loopback() || ip.is_unspecified() || ip.is_multicast() {
+        return true;
+    }
+    match ip {
+        IpAddr::V4(ip) => is_internal_v4(ip),
+        IpAddr::V6(ip) => {
+            // The IPv4 addresses mapped into IPv6 like `::ffff:127.0.0.1`, or translated by NAT64
+            let [a, b, c, d, e, f, g, h] = ip.segments();
+            let embedded = match (a, b, c, d, e, f) {
+                (0, 0, 0, 0, 0, 0xffff) | (0x64, 0xff9b, 0, 0, 0, 0) => {
+                    Some(Ipv4Addr::from((u32::from(g) << 16) | u32::from(h)))
+                }
+                _ => None,
+            };
+            // Unique local fc00::/7 and link local fe80::/10
+            embedded.is_some_and(|ip| IpAddr::V4(ip).is_loopback() || is_internal_v4(ip))
+                || (a & 0xfe00) == 0xfc00
+                || (a & 0xffc0) == 0xfe80
+        }
+    }
+}
+
+fn is_internal_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_private()
+        // Link local 169.254.0.0/16, with the metadata service of the clouds
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        // Shared address space 100.64.0.0/10 of the carrier-grade NAT
+        || (a == 100 && (b & 0xc0) == 64)
+        // "This network" 0.0.0.0/8 and the reserved 240.0.0.0/4
+        || a == 0
+        || a >= 240
+}
+
+/// Resolve the host to its public addresses, fail if it resolves to an internal one, so that a
+/// public name pointing to the bot's own network is rejected like the internal address itself.
+pub async fn resolve_public(host: &str, port: u16) -> anyhow::Result<Vec<SocketAddr>> {
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port)).await?.collect();
+    if addrs.is_empty() {
+        anyhow::bail!("{host} has no address");
+    }
+    if let Some(addr) = addrs.iter().find(|addr| is_internal(addr.ip())) {
+        anyhow::bail!("{host} resolves to the internal address {}", addr.ip());
+    }
+    Ok(addrs)
+}
+
+struct PublicResolver;
+
+impl reqwest::dns::Resolve for PublicResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        Box::pin(async move {
+            let addrs = resolve_public(name.as_str(), 0).await?;
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+/// The HTTP client for the URLs from the users. It only connects to the public addresses, and
+/// checks each redirect with [`parse_url`] too. The proxies are skipped, they would resolve the
+/// hosts by themselves.
+pub fn public_client() -> &'static HttpClient {
+    static CLIENT: OnceLock<HttpClient> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        let redirect = reqwest::redirect::Policy::custom(|attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else if let Err(err) = parse_url(attempt.url().as_str()) {
+                attempt.error(err.to_string())
+            } else {
+                attempt.follow()
+            }
+        });
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .no_proxy()
+            .dns_resolver(Arc::new(PublicResolver))
+            .redirect(redirect)
+            .build()
+            .expect("fail to build the HTTP client");
+        HttpClient(client)
+    })
+}
+
+/// Monitor the URL for the chat, return false if the chat already monitors it.
+pub fn add(data: &AppData, target: ChatTarget, url: &str) -> anyhow::Result<bool> {
+    let url = parse_url(url)?.to_string();
+    let key = chat_key(&target);
+    let mut conn = data.cacher.get_conn();
+    let monitored: usize = conn.scard(&key)?;
+    let exists: bool = conn.sismember(&key, &url)?;
+    if exists {
+        return Ok(false);
+    }
+    if monitored >= MAX_MONITORS_PER_CHAT {
+        anyhow::bail!("a chat can monitor at most {MAX_MONITORS_PER_CHAT} URLs");
+    }
+    let () = conn.sadd(&key, &url)?;
+    data.cacher.subscribe(WATCHER, &target.to_string(), &url)
+}
+
+/// Stop monitoring the URL for the chat, return false if the chat doesn't monitor it.
+pub fn remove(data: &AppData, target: ChatTarget, url: &str) -> anyhow::Result<bool> {
+    let url = reqwest::Url::parse(url)?.to_string();
+    let removed: bool = data.cacher.get_conn().srem(chat_key(&target), &url)?;
+    if !removed {
+        return Ok(false);
+    }
+    data.cacher
+        .unsubscribe(WATCHER, &target.to_string(), &url)?;
+    let subscribers: Vec<String> = data.cacher.get_subscribers(WATCHER, &url)?;
+    if subscribers.is_empty() {
+        let () = data.cacher.get_conn().del(state_key(&url))?;
+    }
+    Ok(true)
+}
+
+/// Drop the monitor list of the chat and its topics, the subscriptions are removed already.
+pub fn forget_chat(data: &AppData, chat_id: ChatId) -> anyhow::Result<()> {
+    let mut conn = data.cacher.get_conn();
+    let mut keys: Vec<String> = conn.keys(format!("MONITOR_URLS:{chat_id}:*"))?;
+    keys.push(chat_key(&ChatTarget::from(chat_id)));
+    let () = conn.del(keys)?;
+    Ok(())
+}
+
+/// The URLs monitored by the chat with their last state, the state is none before the first probe.
+pub fn status(
+    data: &AppData,
+    target: ChatTarget,
+) -> anyhow::Result<Vec<(String, Option<MonitorState>)>> {
+    let mut conn = data.cacher.get_conn();
+    let mut urls: Vec<String> = conn.smembers(chat_key(&target))?;
+    urls.sort();
+    let mut status = Vec::with_capacity(urls.len());
+    for url in urls {
+        let state: Option<String> = conn.get(state_key(&url))?;
+        let state = state.and_then(|state| serde_json::from_str(&state).ok());
+        status.push((url, state));
+    }
+    Ok(status)
+}
+
+async fn probe(url: &str) -> Probe {
+    let start = Instant::now();
+    let client = public_client();
+    let result = client.send(client.get(url).timeout(PROBE_TIMEOUT)).await;
+    let latency_ms = start.elapsed().as_millis() as u64;
+    let error = match result {
+        Ok(resp) if resp.status().is_success() || resp.status().is_redirection() => None,
+        Ok(resp) => Some(format!("HTTP {}", resp.status())),
+        Err(err) if err.is_timeout() => Some("timeout".to_string()),
+        Err(err) => Some(err.without_url().to_string()),
+    };
+    Probe { latency_ms, error }
+}
+
+/// Apply the probe to the previous state. Return the new state, and whether it went up or down.
+pub fn transit(previous: Option<MonitorState>, probe: &Probe, now: i64) -> (MonitorState, bool) {
+    let mut state = previous.unwrap_or(MonitorState {
+        up: true,
+        since: now,
+        checked_at: now,
+        latency_ms: None,
+        error: None,
+        failures: 0,
+    });
+    state.checked_at = now;
+    state.error = probe.error.clone();
+    if probe.error.is_none() {
+        state.failures = 0;
+        state.latency_ms = Some(probe.latency_ms);
+    } else {
+        state.failures += 1;
+    }
+
+    let up = state.failures < FAILURES_TO_DOWN;
+    let changed = up != state.up;
+    if changed {
+        state.up = up;
+        state.since = now;
+    }
+    (state, changed)
+}
+
+pub fn spawn_uptime_monitor(bot: teloxide::Bot, data: AppData) {
+    EventWatcher::builder()
+        .name(WATCHER)
+        .bot(bot)
+        .data(data)
+        .client(None)
+        .heartbeat_interval(PROBE_INTERVAL)
+        .build()
+        .start_with_task(probe_all);
+}
+
+async fn probe_all(ctx: EventWatcher<()>) -> anyhow::Result<()> {
+    let urls: Vec<String> = ctx.event_pool()?;
+    // Probe at the same time, so the slow URLs don't delay the others
+    let mut tasks = tokio::task::JoinSet::new();
+    for url in urls {
+        tasks.spawn(async move {
+            let probe = probe(&url).await;
+            (url, probe)
+        });
+    }
+    let probes = tasks.join_all().await;
+
+    let now = chrono::Utc::now().timestamp();
+    for (url, probe) in probes {
+        let key = state_key(&url);
+        let previous: Option<String> = ctx.data.cacher.get_conn().get(&key)?;
+        let previous = previous.and_then(|state| serde_json::from_str(&state).ok());
+        let downtime = previous
+            .as_ref()
+            .filter(|state: &&MonitorState| !state.up)
+            .map(|state| now - state.since);
+        let (state, changed) = transit(previous, &probe, now);
+        let () = ctx
+            .data
+            .cacher
+            .get_conn()
+            .set(&key, serde_json::to_string(&state)?)?;
+        if !changed {
+            continue;
+        }
+
+        let subscribers: Vec<String> = ctx.get_subscribers(&url)?;
+        for target in subscribers {
+            let Ok(target) = target.parse::<ChatTarget>() else {
+                tracing::error!("[UptimeMonitor] invalid subscriber {target}");
+                continue;
+            };
+            let lang = i18n::chat_language(&ctx.data, target.chat_id.0, None);
+            let text = if state.up {
+                crate::t!(
+                    lang,
+                    "monitor.recovered",
+                    url = &url,
+                    minutes = downtime.unwrap_or_default() / 60
+                )
+            } else {
+                crate::t!(
+                    lang,
+                    "monitor.down",
+                    url = &url,
+                    error = state.error.as_deref().unwrap_or_default()
+                )
+            };
+            let bot = ctx.bot.clone();
+            let sent = ctx
+                .data
+                .submit(target.chat_id, Priority::Background, move || {
+                    bot.send_message_to(target, &text)
+                })
+                .await;
+            if let Err(err) = sent {
+                tracing::error!("[UptimeMonitor] fail to notify {target}: {err}")
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_parse_url() {
+    assert!(parse_url("https://example.com/health").is_ok());
+    assert!(parse_url("ftp://example.com").is_err());
+    assert!(parse_url("http://localhost:8080").is_err());
+    assert!(parse_url("http://127.0.0.1").is_err());
+    assert!(parse_url("http://192.168.1.1").is_err());
+    assert!(parse_url("http://[::1]/").is_err());
+    assert!(parse_url("http://[fd00::1]/").is_err());
+    assert!(parse_url("http://[::ffff:127.0.0.1]/").is_err());
+    assert!(parse_url("http://[::ffff:169.254.169.254]/").is_err());
+    assert!(parse_url("http://[64:ff9b::10.0.0.1]/").is_err());
+    assert!(parse_url("http://[fe80::1]/").is_err());
+    assert!(parse_url("http://169.254.169.254/latest/meta-data/").is_err());
+    assert!(parse_url("http://100.64.0.1").is_err());
+    assert!(parse_url("http://100.127.255.255").is_err());
+    assert!(parse_url("http://2130706433/").is_err());
+    assert!(parse_url("http://100.128.0.1").is_ok());
+    assert!(parse_url("http://[2606:4700::1111]/").is_ok());
+}
+
+#[tokio::test]
+async fn test_resolve_public() {
+    assert!(resolve_public("localhost", 80).await.is_err());
+    assert!(resolve_public("127.0.0.1", 443).await.is_err());
+    assert!(resolve_public("169.254.169.254", 80).await.is_err());
+    assert!(resolve_public("1.1.1.1", 443).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_public_client_redirect() {
+    use axum::{response::Redirect, routing::get, Router};
+
+    // The IP literals are not resolved, so the local server is reachable and only the redirect
+    // to the metadata service is rejected
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = Router::new().route(
+        "/",
+        get(|| async { Redirect::temporary("http://169.254.169.254/latest/meta-data/") }),
+    );
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let err = public_client()
+        .get(format!("http://{addr}/"))
+        .send()
+        .await
+        .unwrap_err();
+    assert!(err.is_redirect(), "{err}");
+}
+
+#[test]
+fn test_transit() {
+    let ok = Probe {
+        latency_ms: 120,
+        error: None,
+    };
+    let failed = Probe {
+        latency_ms: 10_000,
+        error: Some("timeout".to_string()),
+    };
+
+    let (state, changed) = transit(None, &ok, 0);
+    assert!(state.up && !changed);
+    // One failure is not enough to be down
+    let (state, changed) = transit(Some(state), &failed, 60);
+    assert!(state.up && !changed);
+    let (state, changed) = transit(Some(state), &failed, 120);
+    assert!(!state.up && changed);
+    assert_eq!(state.since, 120);
+    assert_eq!(state.latency_ms, Some(120));
+    let (state, changed) = transit(Some(state), &ok, 180);
+    assert!(state.up && changed);
+    assert_eq!(state.error, None);
+}
+
+#[tokio::test]
+async fn test_monitor_registry() {
+    let data = crate::testkit::app_data(teloxide::Bot::new("1000:fake-token")).await;
+    let target = ChatTarget::from(ChatId(-100));
+    assert!(add(&data, target, "https://example.com").unwrap());
+    assert!(!add(&data, target, "https://example.com/").unwrap());
+    let status = status(&data, target).unwrap();
+    assert_eq!(status, [("https://example.com/".to_string(), None)]);
+    assert!(remove(&data, target, "https://example.com").unwrap());
+    assert!(!remove(&data, target, "https://example.com").unwrap());
+}
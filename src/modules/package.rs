@@ -0,0 +1,334 @@
+use std::{fmt::Display, str::FromStr};
+
+use redis::Commands;
+use serde::Deserialize;
+
+use crate::{
+    app::AppData,
+    event::EventWatcher,
+    i18n,
+    quiet_hours::{self, Notification},
+    topic::ChatTarget,
+};
+
+/// Name of the watcher, the watched packages are kept in its subscribe registry
+const WATCHER: &str = "PackageReleaseWatcher";
+/// Seconds between each check of the watched packages
+const WATCH_INTERVAL: u64 = 60 * 60;
+/// crates.io rejects the requests without a User-Agent
+const USER_AGENT: &str = concat!("rusty-maid/", env!("CARGO_PKG_VERSION"));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Registry {
+    Crates,
+    PyPI,
+    Npm,
+}
+
+impl Display for Registry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Crates => "crates",
+            Self::PyPI => "pypi",
+            Self::Npm => "npm",
+        })
+    }
+}
+
+impl FromStr for Registry {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "crates" => Ok(Self::Crates),
+            "pypi" => Ok(Self::PyPI),
+            "npm" => Ok(Self::Npm),
+            _ => anyhow::bail!("unknown registry {s}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Downloads {
+    Total(u64),
+    LastWeek(u64),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageInfo {
+    pub name: String,
+    pub version: String,
+    pub description: Option<String>,
+    pub downloads: Option<Downloads>,
+    pub docs: String,
+}
+
+#[derive(Deserialize)]
+struct CratesResponse {
+    #[serde(rename = "crate")]
+    krate: Crate,
+}
+
+#[derive(Deserialize)]
+struct Crate {
+    name: String,
+    max_stable_version: Option<String>,
+    max_version: String,
+    description: Option<String>,
+    downloads: u64,
+    documentation: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PyPIResponse {
+    info: PyPIInfo,
+}
+
+#[derive(Deserialize)]
+struct PyPIInfo {
+    name: String,
+    version: String,
+    summary: Option<String>,
+    docs_url: Option<String>,
+    package_url: String,
+}
+
+#[derive(Deserialize)]
+struct NpmPackage {
+    name: String,
+    version: String,
+    description: Option<String>,
+    homepage: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct NpmDownloads {
+    downloads: u64,
+}
+
+/// Package names are letters, digits and `-_.`, npm also has the scoped names like `@types/node`.
+pub fn is_valid_name(registry: Registry, name: &str) -> bool {
+    let is_name = |s: &str| {
+        !s.is_empty()
+            && s.len() <= 214
+            && s.chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    };
+    match (registry, name.strip_prefix('@')) {
+        (Registry::Npm, Some(scoped)) => scoped
+            .split_once('/')
+            .is_some_and(|(scope, name)| is_name(scope) && is_name(name)),
+        _ => is_name(name),
+    }
+}
+
+/// Append the name as one path segment, the `/` in the scoped npm names is escaped.
+fn api_url(base: &str, name: &str, rest: &[&str]) -> anyhow::Result<reqwest::Url> {
+    let mut url = reqwest::Url::parse(base)?;
+    url.path_segments_mut()
+        .map_err(|_| anyhow::anyhow!("{base} can't be a base url"))?
+        .push(name)
+        .extend(rest);
+    Ok(url)
+}
+
+async fn get_json<T: serde::de::DeserializeOwned>(
+    data: &AppData,
+    url: reqwest::Url,
+) -> anyhow::Result<T> {
+    let request = data
+        .requester
+        .get(url)
+        .header(reqwest::header::USER_AGENT, USER_AGENT);
+    let resp = data.requester.send(request).await?;
+    if resp.status() == reqwest::StatusCode::NOT_FOUND {
+        anyhow::bail!("no such package");
+    }
+    Ok(resp.error_for_status()?.json().await?)
+}
+
+/// Look up the latest release of the package.
+pub async fn lookup(data: &AppData, registry: Registry, name: &str) -> anyhow::Result<PackageInfo> {
+    if !is_valid_name(registry, name) {
+        anyhow::bail!("{name} is not a valid package name");
+    }
+    let info = match registry {
+        Registry::Crates => {
+            let url = api_url("https://crates.io/api/v1/crates", name, &[])?;
+            let Crate {
+                name,
+                max_stable_version,
+                max_version,
+                description,
+                downloads,
+                documentation,
+            } = get_json::<CratesResponse>(data, url).await?.krate;
+            PackageInfo {
+                docs: documentation.unwrap_or_else(|| format!("https://docs.rs/{name}")),
+                version: max_stable_version.unwrap_or(max_version),
+                description,
+                downloads: Some(Downloads::Total(downloads)),
+                name,
+            }
+        }
+        Registry::PyPI => {
+            let url = api_url("https://pypi.org/pypi", name, &["json"])?;
+            let info = get_json::<PyPIResponse>(data, url).await?.info;
+            PackageInfo {
+                name: info.name,
+                version: info.version,
+                description: info.summary.filter(|summary| !summary.is_empty()),
+                // PyPI doesn't count the downloads anymore
+                downloads: None,
+                docs: info.docs_url.unwrap_or(info.package_url),
+            }
+        }
+        Registry::Npm => {
+            let url = api_url("https://registry.npmjs.org", name, &["latest"])?;
+            let package = get_json::<NpmPackage>(data, url).await?;
+            let url = api_url("https://api.npmjs.org/downloads/point/last-week", name, &[])?;
+            // The download count is nice to have, the lookup doesn't fail without it
+            let downloads = get_json::<NpmDownloads>(data, url)
+                .await
+                .map(|downloads| Downloads::LastWeek(downloads.downloads))
+                .ok();
+            PackageInfo {
+                docs: package
+                    .homepage
+                    .unwrap_or_else(|| format!("https://www.npmjs.com/package/{}", package.name)),
+                name: package.name,
+                version: package.version,
+                description: package.description,
+                downloads,
+            }
+        }
+    };
+    Ok(info)
+}
+
+fn event_of(registry: Registry, name: &str) -> String {
+    format!("{registry}/{}", name.to_lowercase())
+}
+
+fn version_key(event: &str) -> String {
+    format!("PACKAGE_VERSION:{event}")
+}
+
+/// Notify the chat when the package has a new release. Return false if it is already watched.
+pub async fn watch(
+    data: &AppData,
+    target: ChatTarget,
+    registry: Registry,
+    name: &str,
+) -> anyhow::Result<bool> {
+    // Make sure the package exists, and remember the current version to compare with
+    let info = lookup(data, registry, name).await?;
+    let event = event_of(registry, name);
+    let () = data
+        .cacher
+        .get_conn()
+        .set_nx(version_key(&event), &info.version)?;
+    data.cacher.subscribe(WATCHER, &target.to_string(), &event)
+}
+
+/// Stop notifying the chat, return false if it is not watched.
+pub fn unwatch(
+    data: &AppData,
+    target: ChatTarget,
+    registry: Registry,
+    name: &str,
+) -> anyhow::Result<bool> {
+    data.cacher
+        .unsubscribe(WATCHER, &target.to_string(), &event_of(registry, name))
+}
+
+pub fn spawn_release_watcher(bot: teloxide::Bot, data: AppData) {
+    EventWatcher::builder()
+        .name(WATCHER)
+        .bot(bot)
+        .data(data)
+        .client(None)
+        .heartbeat_interval(WATCH_INTERVAL)
+        .build()
+        .start_with_task(check_releases);
+}
+
+async fn check_releases(ctx: EventWatcher<()>) -> anyhow::Result<()> {
+    let events: Vec<String> = ctx.event_pool()?;
+    for event in events {
+        let Some((registry, name)) = event
+            .split_once('/')
+            .and_then(|(registry, name)| Some((registry.parse::<Registry>().ok()?, name)))
+        else {
+            tracing::error!("[PackageRelease] invalid event {event}");
+            continue;
+        };
+        let info = match lookup(&ctx.data, registry, name).await {
+            Ok(info) => info,
+            Err(err) => {
+                tracing::warn!("[PackageRelease] fail to look up {event}: {err}");
+                continue;
+            }
+        };
+
+        let previous: Option<String> = ctx
+            .data
+            .cacher
+            .get_conn()
+            .getset(version_key(&event), &info.version)?;
+        if previous.is_none_or(|previous| previous == info.version) {
+            continue;
+        }
+
+        let subscribers: Vec<String> = ctx.get_subscribers(&event)?;
+        for target in subscribers {
+            let Ok(target) = target.parse::<ChatTarget>() else {
+                tracing::error!("[PackageRelease] invalid subscriber {target}");
+                continue;
+            };
+            let lang = i18n::chat_language(&ctx.data, target.chat_id.0, None);
+            let text = crate::t!(
+                lang,
+                "package.released",
+                registry = registry,
+                name = info.name,
+                version = info.version,
+                docs = info.docs
+            );
+            let notification = Notification::text(text).digestable();
+            let sent = quiet_hours::notify(&ctx.bot, &ctx.data, target, notification).await;
+            if let Err(err) = sent {
+                tracing::error!("[PackageRelease] fail to notify {target}: {err}")
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_package_name() {
+    assert!(is_valid_name(Registry::Crates, "serde_json"));
+    assert!(is_valid_name(Registry::PyPI, "zope.interface"));
+    assert!(is_valid_name(Registry::Npm, "@types/node"));
+    assert!(!is_valid_name(Registry::Crates, "@types/node"));
+    assert!(!is_valid_name(Registry::Npm, "../secret"));
+    assert!(!is_valid_name(Registry::Npm, "@types/"));
+
+    let url = api_url("https://registry.npmjs.org", "@types/node", &["latest"]).unwrap();
+    assert_eq!(
+        url.as_str(),
+        "https://registry.npmjs.org/@types%2Fnode/latest"
+    );
+    assert_eq!("pypi".parse::<Registry>().unwrap(), Registry::PyPI);
+}
+
+#[test]
+fn test_parse_crate() {
+    let json = r#"{"crate": {"id": "serde", "name": "serde", "max_version": "2.0.0-rc.1",
+        "max_stable_version": "1.0.210", "description": "A serialization framework",
+        "downloads": 400000000, "documentation": null}}"#;
+    let resp: CratesResponse = serde_json::from_str(json).unwrap();
+    assert_eq!(resp.krate.max_stable_version.as_deref(), Some("1.0.210"));
+    assert!(resp.krate.documentation.is_none());
+}
@@ -0,0 +1,136 @@
+use std::{io::Cursor, net::IpAddr};
+
+use image::{GrayImage, ImageFormat, Luma};
+use qrcode::{Color, QrCode};
+
+use crate::modules::monitor;
+
+/// The generated image is at least this wide, so it stays sharp in the Telegram preview
+const MIN_IMAGE_SIZE: u32 = 512;
+/// Blank modules around the code, the QR spec asks for 4
+const QUIET_ZONE: u32 = 4;
+
+/// Render the text as a QR code in PNG.
+pub fn encode(text: &str) -> anyhow::Result<Vec<u8>> {
+    let code = QrCode::new(text.as_bytes())?;
+    let width = code.width() as u32;
+    let colors = code.to_colors();
+    let modules = width + QUIET_ZONE * 2;
+    let scale = MIN_IMAGE_SIZE.div_ceil(modules);
+
+    let image = GrayImage::from_fn(modules * scale, modules * scale, |x, y| {
+        let (x, y) = (x / scale, y / scale);
+        let dark = (QUIET_ZONE..QUIET_ZONE + width).contains(&x)
+            && (QUIET_ZONE..QUIET_ZONE + width).contains(&y)
+            && colors[((y - QUIET_ZONE) * width + x - QUIET_ZONE) as usize] == Color::Dark;
+        Luma([if dark { 0 } else { 255 }])
+    });
+    let mut png = Cursor::new(Vec::new());
+    image.write_to(&mut png, ImageFormat::Png)?;
+    Ok(png.into_inner())
+}
+
+/// Contents of every QR code readable in the image. Decoding a large photo takes a while, run it
+/// in a blocking task.
+pub fn decode(image: &[u8]) -> anyhow::Result<Vec<String>> {
+    let image = image::load_from_memory(image)?.to_luma8();
+    let mut prepared = rqrr::PreparedImage::prepare_from_greyscale(
+        image.width() as usize,
+        image.height() as usize,
+        |x, y| image.get_pixel(x as u32, y as u32).0[0],
+    );
+    Ok(prepared
+        .detect_grids()
+        .into_iter()
+        .filter_map(|grid| grid.decode().ok())
+        .map(|(_, content)| content)
+        .collect())
+}
+
+/// Why the link in the QR code might not go where it looks like. QR codes are a common way to
+/// hide phishing links, since nobody reads the link before scanning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkWarning {
+    /// Like `https://bank.com@evil.com`, where the real host is after the `@`
+    Credentials,
+    /// Internationalized domain name, which can look the same as a well-known one
+    Punycode,
+    IpAddress,
+    /// Points to the local network of the one scanning it
+    PrivateAddress,
+    Unencrypted,
+}
+
+impl LinkWarning {
+    /// Locale key of the warning
+    pub fn key(&self) -> &'static str {
+        match self {
+            Self::Credentials => "qr.warn_credentials",
+            Self::Punycode => "qr.warn_punycode",
+            Self::IpAddress => "qr.warn_ip_address",
+            Self::PrivateAddress => "qr.warn_private_address",
+            Self::Unencrypted => "qr.warn_unencrypted",
+        }
+    }
+}
+
+/// Check the web link for the usual tricks, the content not being an http or https URL is not a
+/// link.
+pub fn check_link(content: &str) -> Option<Vec<LinkWarning>> {
+    let url = reqwest::Url::parse(content).ok()?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return None;
+    }
+    let mut warnings = Vec::new();
+    if !url.username().is_empty() || url.password().is_some() {
+        warnings.push(LinkWarning::Credentials);
+    }
+    let host = url.host_str().unwrap_or_default();
+    if host.trim_matches(['[', ']']).parse::<IpAddr>().is_ok() {
+        warnings.push(LinkWarning::IpAddress);
+    } else if host.split('.').any(|label| label.starts_with("xn--")) {
+        warnings.push(LinkWarning::Punycode);
+    }
+    if monitor::parse_url(content).is_err() {
+        warnings.push(LinkWarning::PrivateAddress);
+    }
+    if url.scheme() == "http" {
+        warnings.push(LinkWarning::Unencrypted);
+    }
+    Some(warnings)
+}
+
+#[test]
+fn test_encode_decode() {
+    let png = encode("https://example.com/?q=rusty maid").unwrap();
+    let image = image::load_from_memory(&png).unwrap();
+    assert!(image.width() >= MIN_IMAGE_SIZE);
+    assert_eq!(decode(&png).unwrap(), ["https://example.com/?q=rusty maid"]);
+
+    let mut blank = Cursor::new(Vec::new());
+    GrayImage::from_pixel(64, 64, Luma([255]))
+        .write_to(&mut blank, ImageFormat::Png)
+        .unwrap();
+    assert!(decode(blank.get_ref()).unwrap().is_empty());
+}
+
+#[test]
+fn test_check_link() {
+    use LinkWarning::*;
+
+    assert_eq!(check_link("WIFI:S:home;T:WPA;P:secret;;"), None);
+    assert_eq!(check_link("plain text"), None);
+    assert_eq!(check_link("https://example.com/login"), Some(vec![]));
+    assert_eq!(
+        check_link("https://bank.com@evil.com/"),
+        Some(vec![Credentials])
+    );
+    assert_eq!(
+        check_link("https://xn--80ak6aa92e.com/"),
+        Some(vec![Punycode])
+    );
+    assert_eq!(
+        check_link("http://192.168.1.1/admin"),
+        Some(vec![IpAddress, PrivateAddress, Unencrypted])
+    );
+}
@@ -0,0 +1,459 @@
+use std::time::Duration;
+
+use base64::Engine;
+use rand::seq::SliceRandom;
+use redis::Commands;
+use serde::{Deserialize, Serialize};
+use teloxide::{
+    payloads::{SendMessageSetters, SendPollSetters},
+    prelude::*,
+    types::{InputPollOption, MaybeAnonymousUser, MessageId, ParseMode, PollAnswer, PollType},
+    utils::html,
+};
+
+use crate::{
+    app::AppData,
+    delayed_task::{self, DelayedTask},
+    topic::{ChatTarget, SendTo},
+};
+
+const TRIVIA_API: &str = "https://opentdb.com/api.php";
+const CATEGORIES_API: &str = "https://opentdb.com/api_category.php";
+const QUESTIONS_PER_GAME: usize = 10;
+/// Time to answer each question
+const QUESTION_TIME: Duration = Duration::from_secs(30);
+/// The game is dropped when nothing happens for this long, like the bot is down for a while
+const GAME_TTL: u64 = 60 * 60;
+/// The categories of Open Trivia DB rarely change
+const CATEGORIES_TTL: u64 = 60 * 60 * 24;
+/// Telegram limits of the poll
+const MAX_QUESTION_CHARS: usize = 300;
+const MAX_OPTION_CHARS: usize = 100;
+const LEADERBOARD_SIZE: isize = 10;
+
+fn game_key(chat_id: ChatId) -> String {
+    format!("QUIZ:{chat_id}")
+}
+
+/// The chat of the quiz poll, the poll answers don't tell it
+fn poll_key(poll_id: &str) -> String {
+    format!("QUIZ_POLL:{poll_id}")
+}
+
+fn scores_key(chat_id: ChatId) -> String {
+    format!("QUIZ_SCORES:{chat_id}")
+}
+
+fn players_key(chat_id: ChatId) -> String {
+    format!("QUIZ_PLAYERS:{chat_id}")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Category {
+    pub id: u32,
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+struct Categories {
+    trivia_categories: Vec<Category>,
+}
+
+#[derive(Deserialize)]
+struct TriviaResponse {
+    response_code: u32,
+    results: Vec<TriviaQuestion>,
+}
+
+/// The fields are base64 encoded, the API escapes them in HTML by default
+#[derive(Deserialize)]
+struct TriviaQuestion {
+    question: String,
+    correct_answer: String,
+    incorrect_answers: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Question {
+    pub text: String,
+    pub options: Vec<String>,
+    /// Index of the correct option
+    pub correct: u8,
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let cut = text.chars().take(max - 1).collect::<String>();
+    format!("{cut}…")
+}
+
+fn decode_field(field: &str) -> anyhow::Result<String> {
+    let decoded = base64::engine::general_purpose::STANDARD.decode(field)?;
+    Ok(String::from_utf8(decoded)?)
+}
+
+impl Question {
+    /// Decode the question and shuffle the correct answer into the options.
+    fn from_trivia(trivia: &TriviaQuestion) -> anyhow::Result<Self> {
+        let correct = truncate(&decode_field(&trivia.correct_answer)?, MAX_OPTION_CHARS);
+        let mut options = trivia
+            .incorrect_answers
+            .iter()
+            .map(|answer| Ok(truncate(&decode_field(answer)?, MAX_OPTION_CHARS)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        options.push(correct.clone());
+        options.shuffle(&mut rand::thread_rng());
+        let correct = options
+            .iter()
+            .position(|option| *option == correct)
+            .unwrap();
+        Ok(Self {
+            text: decode_field(&trivia.question)?,
+            options,
+            correct: correct as u8,
+        })
+    }
+}
+
+/// The running game of the chat.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Game {
+    /// Where the questions are asked, in the format of [`ChatTarget`]
+    target: String,
+    lang: String,
+    questions: Vec<Question>,
+    /// Index of the question being asked
+    current: usize,
+    /// The poll of the current question
+    poll_id: String,
+    message_id: i32,
+}
+
+fn load_game(data: &AppData, chat_id: ChatId) -> anyhow::Result<Option<Game>> {
+    let encoded: Option<String> = data.cacher.get_conn().get(game_key(chat_id))?;
+    Ok(encoded
+        .map(|encoded| serde_json::from_str(&encoded))
+        .transpose()?)
+}
+
+fn save_game(data: &AppData, chat_id: ChatId, game: &Game) -> anyhow::Result<()> {
+    let () =
+        data.cacher
+            .get_conn()
+            .set_ex(game_key(chat_id), serde_json::to_string(game)?, GAME_TTL)?;
+    Ok(())
+}
+
+/// The categories to pick the questions from.
+pub async fn categories(data: &AppData) -> anyhow::Result<Vec<Category>> {
+    let key = "QUIZ_CATEGORIES";
+    let cache: Option<String> = data.cacher.get_conn().get(key)?;
+    if let Some(cache) = cache {
+        return Ok(serde_json::from_str(&cache)?);
+    }
+
+    let categories: Categories = data.requester.to_t(CATEGORIES_API).await?;
+    let categories = categories.trivia_categories;
+    let () =
+        data.cacher
+            .get_conn()
+            .set_ex(key, serde_json::to_string(&categories)?, CATEGORIES_TTL)?;
+    Ok(categories)
+}
+
+/// Find the category by its id or a part of its name.
+fn find_category<'a>(categories: &'a [Category], query: &str) -> Option<&'a Category> {
+    let query = query.trim().to_lowercase();
+    categories
+        .iter()
+        .find(|category| category.id.to_string() == query)
+        .or_else(|| {
+            categories
+                .iter()
+                .find(|category| category.name.to_lowercase().contains(&query))
+        })
+}
+
+async fn fetch_questions(data: &AppData, category: Option<u32>) -> anyhow::Result<Vec<Question>> {
+    let mut request = data.requester.get(TRIVIA_API).query(&[
+        ("amount", QUESTIONS_PER_GAME.to_string()),
+        ("encode", "base64".to_string()),
+    ]);
+    if let Some(category) = category {
+        request = request.query(&[("category", category)]);
+    }
+    let resp: TriviaResponse = data
+        .requester
+        .send(request)
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    match resp.response_code {
+        0 => resp.results.iter().map(Question::from_trivia).collect(),
+        1 => anyhow::bail!("not enough questions in the category"),
+        5 => anyhow::bail!("the trivia API is busy, try again in a few seconds"),
+        code => anyhow::bail!("the trivia API returns code {code}"),
+    }
+}
+
+/// Start a game in the chat with the questions of the category, false when there is a game
+/// running already.
+pub async fn start(
+    bot: &Bot,
+    data: &AppData,
+    target: ChatTarget,
+    lang: &str,
+    category: Option<&str>,
+) -> anyhow::Result<bool> {
+    let chat_id = target.chat_id;
+    if load_game(data, chat_id)?.is_some() {
+        return Ok(false);
+    }
+    let category = match category {
+        Some(query) => {
+            let categories = categories(data).await?;
+            let Some(category) = find_category(&categories, query) else {
+                anyhow::bail!("unknown category {query}");
+            };
+            Some(category.id)
+        }
+        None => None,
+    };
+    let questions = fetch_questions(data, category).await?;
+
+    let () = data
+        .cacher
+        .get_conn()
+        .del(&[scores_key(chat_id), players_key(chat_id)])?;
+    let mut game = Game {
+        target: target.to_string(),
+        lang: lang.to_string(),
+        questions,
+        current: 0,
+        poll_id: String::new(),
+        message_id: 0,
+    };
+    if let Err(err) = ask(bot, data, &mut game).await {
+        let () = data.cacher.get_conn().del(game_key(chat_id))?;
+        return Err(err);
+    }
+    Ok(true)
+}
+
+/// Post the current question of the game as a quiz poll, and close it after [`QUESTION_TIME`].
+async fn ask(bot: &Bot, data: &AppData, game: &mut Game) -> anyhow::Result<()> {
+    let target: ChatTarget = game.target.parse()?;
+    let question = &game.questions[game.current];
+    let text = format!(
+        "[{}/{}] {}",
+        game.current + 1,
+        game.questions.len(),
+        question.text
+    );
+    let sent = bot
+        .send_poll_to(
+            target,
+            truncate(&text, MAX_QUESTION_CHARS),
+            question.options.iter().cloned().map(InputPollOption::new),
+        )
+        .type_(PollType::Quiz)
+        .correct_option_id(question.correct)
+        // The answers of the anonymous polls are not sent to the bot
+        .is_anonymous(false)
+        .await?;
+    let Some(poll) = sent.poll() else {
+        anyhow::bail!("the poll is not sent");
+    };
+
+    game.poll_id = poll.id.to_string();
+    game.message_id = sent.id.0;
+    save_game(data, target.chat_id, game)?;
+    let () = data
+        .cacher
+        .get_conn()
+        .set_ex(poll_key(&game.poll_id), target.chat_id.0, GAME_TTL)?;
+    let timeout = DelayedTask::QuizTimeout {
+        chat_id: target.chat_id.0,
+        question: game.current,
+    };
+    delayed_task::schedule(data, &timeout, QUESTION_TIME)
+}
+
+/// Score the answer to the quiz poll, the answers to the other polls are ignored.
+pub fn answer(data: &AppData, answer: &PollAnswer) -> anyhow::Result<()> {
+    let poll_id = answer.poll_id.to_string();
+    let chat_id: Option<i64> = data.cacher.get_conn().get(poll_key(&poll_id))?;
+    let Some(chat_id) = chat_id.map(ChatId) else {
+        return Ok(());
+    };
+    let MaybeAnonymousUser::User(user) = &answer.voter else {
+        return Ok(());
+    };
+    // The answer to the closed question might come late
+    let Some(game) = load_game(data, chat_id)?.filter(|game| game.poll_id == poll_id) else {
+        return Ok(());
+    };
+
+    let () = data
+        .cacher
+        .get_conn()
+        .hset(players_key(chat_id), user.id.0, user.full_name())?;
+    if answer.option_ids == [game.questions[game.current].correct] {
+        data.cacher
+            .incr_counter(&scores_key(chat_id), user.id.0, 1)?;
+    }
+    Ok(())
+}
+
+/// Close the question when its time is up, and ask the next one or end the game.
+pub async fn timeout(
+    bot: &Bot,
+    data: &AppData,
+    chat_id: ChatId,
+    question: usize,
+) -> anyhow::Result<()> {
+    let Some(mut game) = load_game(data, chat_id)?.filter(|game| game.current == question) else {
+        return Ok(());
+    };
+    close_poll(bot, chat_id, &game).await;
+    game.current += 1;
+    if game.current == game.questions.len() {
+        return finish(bot, data, chat_id, &game).await;
+    }
+    if let Err(err) = ask(bot, data, &mut game).await {
+        finish(bot, data, chat_id, &game).await?;
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// End the game before all the questions are asked, false when there is no game.
+pub async fn stop(bot: &Bot, data: &AppData, chat_id: ChatId) -> anyhow::Result<bool> {
+    let Some(game) = load_game(data, chat_id)? else {
+        return Ok(false);
+    };
+    close_poll(bot, chat_id, &game).await;
+    finish(bot, data, chat_id, &game).await?;
+    Ok(true)
+}
+
+async fn close_poll(bot: &Bot, chat_id: ChatId, game: &Game) {
+    if let Err(err) = bot.stop_poll(chat_id, MessageId(game.message_id)).await {
+        tracing::debug!("[Quiz] fail to close the poll in {chat_id}: {err}");
+    }
+}
+
+/// Post the leaderboard and forget the game.
+async fn finish(bot: &Bot, data: &AppData, chat_id: ChatId, game: &Game) -> anyhow::Result<()> {
+    let lang = game.lang.as_str();
+    let top: Vec<(u64, i64)> = data
+        .cacher
+        .counter_top(&scores_key(chat_id), LEADERBOARD_SIZE)?;
+    let text = if top.is_empty() {
+        crate::t!(lang, "quiz.nobody")
+    } else {
+        let ids = top.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+        let names: Vec<Option<String>> = redis::cmd("HMGET")
+            .arg(players_key(chat_id))
+            .arg(&ids)
+            .query(&mut data.cacher.get_conn())?;
+        let lines = top
+            .iter()
+            .zip(names)
+            .enumerate()
+            .map(|(i, ((id, score), name))| {
+                let name = name.map_or_else(|| id.to_string(), |name| html::escape(&name));
+                format!("{}. {name} - <b>{score}</b>", i + 1)
+            })
+            .collect::<Vec<_>>();
+        format!(
+            "{}\n\n{}",
+            crate::t!(lang, "quiz.leaderboard", total = game.questions.len()),
+            lines.join("\n")
+        )
+    };
+
+    let () = data.cacher.get_conn().del(&[
+        game_key(chat_id),
+        scores_key(chat_id),
+        players_key(chat_id),
+    ])?;
+    let target: ChatTarget = game.target.parse()?;
+    data.reply(
+        target.chat_id,
+        bot.send_message_to(target, text)
+            .parse_mode(ParseMode::Html),
+    )
+    .await?;
+    Ok(())
+}
+
+#[test]
+fn test_question_from_trivia() {
+    let encode = |text: &str| base64::engine::general_purpose::STANDARD.encode(text);
+    let trivia = TriviaQuestion {
+        question: encode("What is \"1 + 1\"?"),
+        correct_answer: encode("2"),
+        incorrect_answers: vec![encode("1"), encode("3"), encode("11")],
+    };
+    let question = Question::from_trivia(&trivia).unwrap();
+    assert_eq!(question.text, "What is \"1 + 1\"?");
+    assert_eq!(question.options.len(), 4);
+    assert_eq!(question.options[question.correct as usize], "2");
+
+    let categories = [
+        Category {
+            id: 9,
+            name: "General Knowledge".to_string(),
+        },
+        Category {
+            id: 18,
+            name: "Science: Computers".to_string(),
+        },
+    ];
+    assert_eq!(find_category(&categories, "18").unwrap().id, 18);
+    assert_eq!(find_category(&categories, "computers").unwrap().id, 18);
+    assert!(find_category(&categories, "sports").is_none());
+}
+
+#[tokio::test]
+async fn test_answer() {
+    let data = crate::testkit::app_data(Bot::new("1000:fake-token")).await;
+    let chat_id = ChatId(-100);
+    let game = Game {
+        target: "-100".to_string(),
+        lang: "en".to_string(),
+        questions: vec![Question {
+            text: "1 + 1?".to_string(),
+            options: vec!["1".to_string(), "2".to_string()],
+            correct: 1,
+        }],
+        current: 0,
+        poll_id: "poll".to_string(),
+        message_id: 1,
+    };
+    save_game(&data, chat_id, &game).unwrap();
+    let () = data
+        .cacher
+        .get_conn()
+        .set(poll_key("poll"), chat_id.0)
+        .unwrap();
+
+    let vote = |user: u64, option: u8| -> PollAnswer {
+        serde_json::from_value(serde_json::json!({
+            "poll_id": "poll",
+            "user": { "id": user, "is_bot": false, "first_name": format!("user{user}") },
+            "option_ids": [option],
+        }))
+        .unwrap()
+    };
+    answer(&data, &vote(1, 1)).unwrap();
+    answer(&data, &vote(2, 0)).unwrap();
+    let top: Vec<(u64, i64)> = data.cacher.counter_top(&scores_key(chat_id), 10).unwrap();
+    assert_eq!(top, [(1, 1)]);
+    let players: usize = data.cacher.get_conn().hlen(players_key(chat_id)).unwrap();
+    assert_eq!(players, 2);
+}
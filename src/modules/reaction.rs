@@ -0,0 +1,210 @@
+use std::{fmt::Display, str::FromStr};
+
+use teloxide::types::{MessageReactionUpdated, ReactionType};
+
+use crate::{app::AppData, storage::Storage};
+
+/// The reactions on the messages older than this are forgotten
+const REACTION_TTL: i64 = 60 * 60 * 24 * 7;
+
+/// What happens when the message gets enough reactions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReactionAction {
+    /// Pin the message, counting the reactions from everyone
+    Pin,
+    /// Delete the message sent by the bot, counting the reactions from the chat admins only
+    Delete,
+}
+
+impl FromStr for ReactionAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pin" => Ok(Self::Pin),
+            "delete" => Ok(Self::Delete),
+            _ => anyhow::bail!("unknown reaction action {s}, expect pin or delete"),
+        }
+    }
+}
+
+impl Display for ReactionAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let action = match self {
+            Self::Pin => "pin",
+            Self::Delete => "delete",
+        };
+        write!(f, "{action}")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReactionRule {
+    pub emoji: String,
+    pub action: ReactionAction,
+    pub threshold: u64,
+}
+
+/// Add the rule to the chat, replacing the rule of the same emoji.
+pub async fn set_rule(storage: &Storage, chat_id: i64, rule: &ReactionRule) -> anyhow::Result<()> {
+    sqlx::query(
+        "INSERT INTO reaction_rules (chat_id, emoji, action, threshold) VALUES ($1, $2, $3, $4) \
+         ON CONFLICT (chat_id, emoji) \
+         DO UPDATE SET action = excluded.action, threshold = excluded.threshold",
+    )
+    .bind(chat_id)
+    .bind(&rule.emoji)
+    .bind(rule.action.to_string())
+    .bind(rule.threshold as i64)
+    .execute(storage.pool())
+    .await?;
+    Ok(())
+}
+
+/// Remove the rule of the emoji, return false if there is no such rule.
+pub async fn remove_rule(storage: &Storage, chat_id: i64, emoji: &str) -> anyhow::Result<bool> {
+    let result = sqlx::query("DELETE FROM reaction_rules WHERE chat_id = $1 AND emoji = $2")
+        .bind(chat_id)
+        .bind(emoji)
+        .execute(storage.pool())
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn rules(storage: &Storage, chat_id: i64) -> anyhow::Result<Vec<ReactionRule>> {
+    let rows: Vec<(String, String, i64)> = sqlx::query_as(
+        "SELECT emoji, action, threshold FROM reaction_rules WHERE chat_id = $1 ORDER BY emoji",
+    )
+    .bind(chat_id)
+    .fetch_all(storage.pool())
+    .await?;
+    let rules = rows
+        .into_iter()
+        .filter_map(|(emoji, action, threshold)| {
+            Some(ReactionRule {
+                emoji,
+                action: action.parse().ok()?,
+                threshold: threshold as u64,
+            })
+        })
+        .collect();
+    Ok(rules)
+}
+
+fn emojis(reactions: &[ReactionType]) -> impl Iterator<Item = &str> {
+    reactions.iter().filter_map(|reaction| match reaction {
+        ReactionType::Emoji { emoji } => Some(emoji.as_str()),
+        _ => None,
+    })
+}
+
+/// The emojis that the user added and removed in the update. Custom emojis are ignored.
+pub fn changed_emojis(update: &MessageReactionUpdated) -> (Vec<String>, Vec<String>) {
+    let old = emojis(&update.old_reaction).collect::<Vec<_>>();
+    let new = emojis(&update.new_reaction).collect::<Vec<_>>();
+    let added = new
+        .iter()
+        .filter(|emoji| !old.contains(emoji))
+        .map(|emoji| emoji.to_string())
+        .collect();
+    let removed = old
+        .iter()
+        .filter(|emoji| !new.contains(emoji))
+        .map(|emoji| emoji.to_string())
+        .collect();
+    (added, removed)
+}
+
+fn reactors_key(chat_id: i64, message_id: i32, emoji: &str) -> String {
+    format!("REACTION_USERS:{chat_id}:{message_id}:{emoji}")
+}
+
+/// Record that the user reacted the message with the emoji or took it back, return how many
+/// users are reacting with it now.
+pub fn count_reaction(
+    data: &AppData,
+    chat_id: i64,
+    message_id: i32,
+    emoji: &str,
+    user_id: u64,
+    reacted: bool,
+) -> anyhow::Result<u64> {
+    let key = reactors_key(chat_id, message_id, emoji);
+    let mut conn = data.cacher.get_conn();
+    let (count,): (u64,) = if reacted {
+        redis::pipe()
+            .sadd(&key, user_id)
+            .ignore()
+            .expire(&key, REACTION_TTL)
+            .ignore()
+            .scard(&key)
+            .query(&mut conn)?
+    } else {
+        redis::pipe()
+            .srem(&key, user_id)
+            .ignore()
+            .scard(&key)
+            .query(&mut conn)?
+    };
+    Ok(count)
+}
+
+/// Return true only for the first time the rule fires on the message, so that the message is
+/// not pinned again when someone else reacts.
+pub fn fire_once(
+    data: &AppData,
+    chat_id: i64,
+    message_id: i32,
+    rule: &ReactionRule,
+) -> anyhow::Result<bool> {
+    let key = format!("REACTION_FIRED:{chat_id}:{message_id}:{}", rule.emoji);
+    let first: bool = redis::cmd("SET")
+        .arg(&key)
+        .arg(rule.action.to_string())
+        .arg("NX")
+        .arg("EX")
+        .arg(REACTION_TTL)
+        .query(&mut data.cacher.get_conn())?;
+    Ok(first)
+}
+
+#[test]
+fn test_changed_emojis() {
+    let update: MessageReactionUpdated = serde_json::from_value(serde_json::json!({
+        "chat": { "id": -100, "type": "supergroup", "title": "test" },
+        "message_id": 7,
+        "user": { "id": 42, "is_bot": false, "first_name": "alice" },
+        "date": 0,
+        "old_reaction": [{ "type": "emoji", "emoji": "👍" }, { "type": "emoji", "emoji": "🔥" }],
+        "new_reaction": [{ "type": "emoji", "emoji": "🔥" }, { "type": "emoji", "emoji": "👎" }]
+    }))
+    .unwrap();
+    let (added, removed) = changed_emojis(&update);
+    assert_eq!(added, ["👎"]);
+    assert_eq!(removed, ["👍"]);
+}
+
+#[tokio::test]
+async fn test_reaction_rules() {
+    let storage = crate::testkit::memory_storage().await;
+    let pin = ReactionRule {
+        emoji: "👍".to_string(),
+        action: ReactionAction::Pin,
+        threshold: 3,
+    };
+    set_rule(&storage, -100, &pin).await.unwrap();
+    set_rule(
+        &storage,
+        -100,
+        &ReactionRule {
+            threshold: 5,
+            ..pin.clone()
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(rules(&storage, -100).await.unwrap()[0].threshold, 5);
+    assert!(rules(&storage, -200).await.unwrap().is_empty());
+    assert!(remove_rule(&storage, -100, "👍").await.unwrap());
+    assert!(!remove_rule(&storage, -100, "👍").await.unwrap());
+}
@@ -0,0 +1,100 @@
+use rand::Rng;
+
+use super::Sendable;
+
+/// Represent the dice notation like `2d6`, which means rolling two six-sided dice.
+#[derive(Debug, PartialEq)]
+pub struct Dice {
+    pub count: u32,
+    pub sides: u64,
+}
+
+impl Dice {
+    const MAX_COUNT: u32 = 100;
+    const MAX_SIDES: u64 = 1_000_000_000;
+
+    pub fn parse(s: &str) -> anyhow::Result<Self> {
+        let s = s.trim().to_lowercase();
+        let Some((count, sides)) = s.split_once('d') else {
+            anyhow::bail!("invalid dice {s}, expect format like 2d6");
+        };
+        let count = if count.is_empty() {
+            1
+        } else {
+            count
+                .parse::<u32>()
+                .map_err(|_| anyhow::anyhow!("invalid dice count {count}"))?
+        };
+        let sides = sides
+            .parse::<u64>()
+            .map_err(|_| anyhow::anyhow!("invalid dice sides {sides}"))?;
+        if count == 0 || count > Self::MAX_COUNT || sides == 0 || sides > Self::MAX_SIDES {
+            anyhow::bail!(
+                "dice count should between 1 and {}, and sides between 1 and {}",
+                Self::MAX_COUNT,
+                Self::MAX_SIDES
+            );
+        }
+
+        Ok(Self { count, sides })
+    }
+
+    pub fn roll(&self) -> Vec<u64> {
+        let mut rng = rand::thread_rng();
+        (0..self.count)
+            .map(|_| rng.gen_range(1..=self.sides))
+            .collect()
+    }
+}
+
+/// Roll by the given argument: empty for a random number, a number for range `[0, max)`, or a
+/// dice notation like `2d6`.
+pub fn roll(args: Option<&str>) -> anyhow::Result<Sendable> {
+    let Some(args) = args.map(str::trim).filter(|args| !args.is_empty()) else {
+        return Ok(Sendable::text(rand::random::<u64>()));
+    };
+
+    if let Ok(max) = args.parse::<u64>() {
+        if max == 0 {
+            anyhow::bail!("expect a positive number");
+        }
+        return Ok(Sendable::text(rand::thread_rng().gen_range(0..max)));
+    }
+
+    let dice = Dice::parse(args).map_err(|err| anyhow::anyhow!("expect number or dice: {err}"))?;
+    let points = dice.roll();
+    if points.len() == 1 {
+        return Ok(Sendable::text(points[0]));
+    }
+
+    let display = format!(
+        "{} = {}",
+        points
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>()
+            .join(" + "),
+        points.iter().fold(0u64, |sum, p| sum.saturating_add(*p))
+    );
+    Ok(Sendable::text(display))
+}
+
+#[test]
+fn test_parse_dice() {
+    assert_eq!(Dice::parse("2d6").unwrap(), Dice { count: 2, sides: 6 });
+    assert_eq!(
+        Dice::parse("D20").unwrap(),
+        Dice {
+            count: 1,
+            sides: 20
+        }
+    );
+    assert!(Dice::parse("0d6").is_err());
+    assert!(Dice::parse("2d").is_err());
+    assert!(Dice::parse("abc").is_err());
+    assert!(Dice::parse("100d18446744073709551615").is_err());
+
+    let points = Dice::parse("3d6").unwrap().roll();
+    assert_eq!(points.len(), 3);
+    assert!(points.iter().all(|p| (1..=6).contains(p)));
+}
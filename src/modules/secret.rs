@@ -0,0 +1,162 @@
+use std::str::FromStr;
+
+use base64::Engine;
+use rand::{rngs::OsRng, Rng, RngCore};
+
+pub const DEFAULT_PASSWORD_LEN: usize = 16;
+const MIN_PASSWORD_LEN: usize = 8;
+const MAX_PASSWORD_LEN: usize = 128;
+pub const DEFAULT_TOKEN_BYTES: usize = 32;
+const MAX_TOKEN_BYTES: usize = 256;
+
+const LOWER: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPER: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &str = "0123456789";
+const SYMBOLS: &str = "!#$%&*+-=?@^_~";
+/// Letters and digits without the look-alikes like `0O` and `1lI`
+const SAFE: &str = "abcdefghijkmnopqrstuvwxyzABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    /// Letters, digits and symbols
+    Full,
+    Alnum,
+    Safe,
+    Digits,
+    Hex,
+}
+
+impl Charset {
+    /// Every password has at least one character of each class
+    fn classes(&self) -> &'static [&'static str] {
+        match self {
+            Self::Full => &[LOWER, UPPER, DIGITS, SYMBOLS],
+            Self::Alnum => &[LOWER, UPPER, DIGITS],
+            Self::Safe => &[SAFE],
+            Self::Digits => &[DIGITS],
+            Self::Hex => &["0123456789abcdef"],
+        }
+    }
+}
+
+impl FromStr for Charset {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "full" => Ok(Self::Full),
+            "alnum" => Ok(Self::Alnum),
+            "safe" => Ok(Self::Safe),
+            "digits" | "pin" => Ok(Self::Digits),
+            "hex" => Ok(Self::Hex),
+            _ => anyhow::bail!("unknown charset {s}, expect full, alnum, safe, digits or hex"),
+        }
+    }
+}
+
+/// Generate the password from the OS random source.
+pub fn password(len: usize, charset: Charset) -> anyhow::Result<String> {
+    if !(MIN_PASSWORD_LEN..=MAX_PASSWORD_LEN).contains(&len) {
+        anyhow::bail!(
+            "password length should be between {MIN_PASSWORD_LEN} and {MAX_PASSWORD_LEN}"
+        );
+    }
+    let classes = charset.classes();
+    let chars = classes.concat().chars().collect::<Vec<_>>();
+    // Drawing again instead of patching the missing classes in keeps every valid password
+    // equally likely
+    loop {
+        let password = (0..len)
+            .map(|_| chars[OsRng.gen_range(0..chars.len())])
+            .collect::<String>();
+        if classes
+            .iter()
+            .all(|class| password.chars().any(|c| class.contains(c)))
+        {
+            return Ok(password);
+        }
+    }
+}
+
+/// Random UUID of version 4.
+pub fn uuid() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = bytes
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenFormat {
+    Hex,
+    /// URL-safe base64 without padding
+    Base64,
+}
+
+impl FromStr for TokenFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "hex" => Ok(Self::Hex),
+            "base64" | "b64" => Ok(Self::Base64),
+            _ => anyhow::bail!("unknown token format {s}, expect hex or base64"),
+        }
+    }
+}
+
+/// Random token of `bytes` bytes.
+pub fn token(format: TokenFormat, bytes: usize) -> anyhow::Result<String> {
+    if bytes == 0 || bytes > MAX_TOKEN_BYTES {
+        anyhow::bail!("token size should be between 1 and {MAX_TOKEN_BYTES} bytes");
+    }
+    let mut random = vec![0u8; bytes];
+    OsRng.fill_bytes(&mut random);
+    Ok(match format {
+        TokenFormat::Hex => random.iter().map(|byte| format!("{byte:02x}")).collect(),
+        TokenFormat::Base64 => base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(&random),
+    })
+}
+
+#[test]
+fn test_password() {
+    let full = password(DEFAULT_PASSWORD_LEN, Charset::Full).unwrap();
+    assert_eq!(full.chars().count(), DEFAULT_PASSWORD_LEN);
+    for class in [LOWER, UPPER, DIGITS, SYMBOLS] {
+        assert!(full.chars().any(|c| class.contains(c)));
+    }
+    let pin = password(8, Charset::Digits).unwrap();
+    assert!(pin.chars().all(|c| c.is_ascii_digit()));
+    assert!(password(8, Charset::Safe)
+        .unwrap()
+        .chars()
+        .all(|c| SAFE.contains(c)));
+    assert!(password(4, Charset::Full).is_err());
+    assert!(password(1000, Charset::Full).is_err());
+    assert_eq!("PIN".parse::<Charset>().unwrap(), Charset::Digits);
+}
+
+#[test]
+fn test_uuid_and_token() {
+    let id = uuid();
+    assert_eq!(id.len(), 36);
+    assert_eq!(id.as_bytes()[14], b'4');
+    assert!(matches!(id.as_bytes()[19], b'8' | b'9' | b'a' | b'b'));
+    assert_ne!(uuid(), id);
+
+    assert_eq!(token(TokenFormat::Hex, 32).unwrap().len(), 64);
+    assert_eq!(token(TokenFormat::Base64, 3).unwrap().len(), 4);
+    assert!(token(TokenFormat::Hex, 0).is_err());
+}
@@ -0,0 +1,255 @@
+use std::{collections::BTreeSet, fmt::Display, str::FromStr};
+
+use redis::Commands;
+
+use crate::app::AppData;
+
+/// Messages scoring at least this are treated as spam
+pub const SPAM_THRESHOLD: i64 = 6;
+/// Users with no more messages than this in the chat are new
+const NEW_MEMBER_MESSAGES: i64 = 3;
+/// Each word learned by `/spam` and `/ham` scores in this range
+const WORD_SCORE_LIMIT: i64 = 5;
+/// Only the first words of a long message are scored and learned
+const MAX_WORDS: usize = 50;
+
+lazy_static::lazy_static!(
+    static ref INVITE_LINK: regex::Regex = regex::Regex::new(
+        r"(?i)(t\.me|telegram\.(me|dog))/(\+|joinchat/)[\w-]+"
+    ).unwrap();
+    static ref LINK: regex::Regex = regex::Regex::new(
+        r"(?i)(https?://|t\.me/|@\w{5,})"
+    ).unwrap();
+    static ref GIFT_SCAM: regex::Regex = regex::Regex::new(
+        r"(?i)(free|gift|giveaway|airdrop|claim|免费|赠送|领取|空投).{0,30}(premium|stars|nft|usdt|ton|会员|红包)"
+    ).unwrap();
+);
+
+/// What to do with the spam in the group
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SpamAction {
+    /// Delete the message
+    Delete,
+    /// Delete the message and restrict the sender from sending for a day
+    Mute,
+    /// Keep the message and mention the chat admins
+    #[default]
+    Report,
+}
+
+impl FromStr for SpamAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "delete" => Ok(Self::Delete),
+            "mute" => Ok(Self::Mute),
+            "report" => Ok(Self::Report),
+            _ => anyhow::bail!("unknown spam action {s}, expect delete, mute or report"),
+        }
+    }
+}
+
+impl Display for SpamAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let action = match self {
+            Self::Delete => "delete",
+            Self::Mute => "mute",
+            Self::Report => "report",
+        };
+        write!(f, "{action}")
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Verdict {
+    pub score: i64,
+    /// Locale keys of why the message is scored
+    pub reasons: Vec<&'static str>,
+}
+
+impl Verdict {
+    pub fn is_spam(&self) -> bool {
+        self.score >= SPAM_THRESHOLD
+    }
+
+    fn add(&mut self, score: i64, reason: &'static str) {
+        self.score += score;
+        self.reasons.push(reason);
+    }
+}
+
+fn policy_key(chat_id: i64) -> String {
+    format!("SPAM_POLICY:{chat_id}")
+}
+
+fn words_key(chat_id: i64) -> String {
+    format!("SPAM_WORDS:{chat_id}")
+}
+
+fn seen_key(chat_id: i64) -> String {
+    format!("SPAM_SEEN:{chat_id}")
+}
+
+/// Split the text into the lowercase words that are scored, without duplication.
+pub fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= 2)
+        .map(str::to_lowercase)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .take(MAX_WORDS)
+        .collect()
+}
+
+fn is_emoji(c: char) -> bool {
+    matches!(c as u32, 0x1F000..=0x1FAFF | 0x2600..=0x27BF | 0x2B00..=0x2BFF)
+}
+
+/// Score the message by the heuristics that don't need any state.
+pub fn heuristic_verdict(text: &str, new_member: bool) -> Verdict {
+    let mut verdict = Verdict::default();
+    if INVITE_LINK.is_match(text) {
+        if new_member {
+            verdict.add(SPAM_THRESHOLD, "spam.reason_new_invite");
+        } else {
+            verdict.add(2, "spam.reason_invite");
+        }
+    }
+    if GIFT_SCAM.is_match(text) && LINK.is_match(text) {
+        verdict.add(5, "spam.reason_gift");
+    }
+
+    let visible = text.chars().filter(|c| !c.is_whitespace()).count();
+    let emojis = text.chars().filter(|c| is_emoji(*c)).count();
+    if emojis >= 10 && emojis * 2 >= visible {
+        verdict.add(4, "spam.reason_emoji_wall");
+    }
+
+    if new_member && LINK.is_match(text) && !verdict.reasons.is_empty() {
+        verdict.add(1, "spam.reason_new_link");
+    }
+    verdict
+}
+
+/// Count the message of the user, and return true if the user is new to the chat.
+pub fn record_member_message(data: &AppData, chat_id: i64, user_id: u64) -> anyhow::Result<bool> {
+    let count: i64 = data
+        .cacher
+        .get_conn()
+        .hincr(seen_key(chat_id), user_id, 1)?;
+    Ok(count <= NEW_MEMBER_MESSAGES)
+}
+
+/// Return true if the user is new to the chat, without counting a message.
+pub fn is_new_member(data: &AppData, chat_id: i64, user_id: u64) -> anyhow::Result<bool> {
+    let count: Option<i64> = data.cacher.get_conn().hget(seen_key(chat_id), user_id)?;
+    Ok(count.unwrap_or(0) <= NEW_MEMBER_MESSAGES)
+}
+
+/// Score the message with the heuristics and the words learned in the chat.
+pub fn check(
+    data: &AppData,
+    chat_id: i64,
+    text: &str,
+    new_member: bool,
+) -> anyhow::Result<Verdict> {
+    let mut verdict = heuristic_verdict(text, new_member);
+    let words = words(text);
+    if words.is_empty() {
+        return Ok(verdict);
+    }
+
+    let scores: Vec<Option<i64>> = redis::cmd("HMGET")
+        .arg(words_key(chat_id))
+        .arg(&words)
+        .query(&mut data.cacher.get_conn())?;
+    let learned: i64 = scores.into_iter().flatten().sum();
+    if learned > 0 {
+        verdict.add(learned, "spam.reason_learned");
+    } else {
+        verdict.score += learned;
+    }
+    Ok(verdict)
+}
+
+/// Learn the words of the message as spam or not, for `/spam` and `/ham`.
+pub fn train(data: &AppData, chat_id: i64, text: &str, spam: bool) -> anyhow::Result<()> {
+    let key = words_key(chat_id);
+    let delta = if spam { 1 } else { -1 };
+    let mut conn = data.cacher.get_conn();
+    for word in words(text) {
+        let score: i64 = conn.hincr(&key, &word, delta)?;
+        let clamped = score.clamp(-WORD_SCORE_LIMIT, WORD_SCORE_LIMIT);
+        if clamped != score {
+            let () = conn.hset(&key, &word, clamped)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn get_action(data: &AppData, chat_id: i64) -> anyhow::Result<SpamAction> {
+    let action: Option<String> = data.cacher.get_conn().get(policy_key(chat_id))?;
+    Ok(action
+        .and_then(|action| action.parse().ok())
+        .unwrap_or_default())
+}
+
+pub fn set_action(data: &AppData, chat_id: i64, action: SpamAction) -> anyhow::Result<()> {
+    let () = data
+        .cacher
+        .get_conn()
+        .set(policy_key(chat_id), action.to_string())?;
+    Ok(())
+}
+
+#[test]
+fn test_heuristic_verdict() {
+    let invite = "Join us https://t.me/+AbCdEf123 for signals";
+    assert!(heuristic_verdict(invite, true).is_spam());
+    assert!(!heuristic_verdict(invite, false).is_spam());
+
+    let gift = "🎁 Free Telegram Premium gift for everyone! Claim at https://example.com";
+    let verdict = heuristic_verdict(gift, false);
+    assert_eq!(verdict.reasons, ["spam.reason_gift"]);
+    assert!(heuristic_verdict(gift, true).is_spam());
+
+    let wall = "🔥🔥🔥🔥🔥🚀🚀🚀🚀🚀💰💰 buy now";
+    assert_eq!(
+        heuristic_verdict(wall, false).reasons,
+        ["spam.reason_emoji_wall"]
+    );
+
+    assert_eq!(
+        heuristic_verdict("see https://github.com/rust-lang/rust", true),
+        Verdict::default()
+    );
+}
+
+#[test]
+fn test_words() {
+    assert_eq!(words("Buy BTC now, buy BTC! a"), ["btc", "buy", "now"]);
+}
+
+#[tokio::test]
+async fn test_train() {
+    use crate::testkit;
+
+    let data = testkit::app_data(teloxide::Bot::new("1000:fake-token")).await;
+    let text = "cheap followers instant delivery";
+    assert_eq!(check(&data, -100, text, false).unwrap().score, 0);
+    for _ in 0..10 {
+        train(&data, -100, text, true).unwrap();
+    }
+    let verdict = check(&data, -100, text, false).unwrap();
+    assert_eq!(verdict.score, 4 * WORD_SCORE_LIMIT);
+    assert!(verdict.is_spam());
+    assert!(!check(&data, -200, text, false).unwrap().is_spam());
+
+    train(&data, -100, "instant noodles", false).unwrap();
+    assert!(record_member_message(&data, -100, 42).unwrap());
+    assert!(is_new_member(&data, -100, 42).unwrap());
+    assert_eq!(get_action(&data, -100).unwrap(), SpamAction::Report);
+    set_action(&data, -100, SpamAction::Mute).unwrap();
+    assert_eq!(get_action(&data, -100).unwrap(), SpamAction::Mute);
+}
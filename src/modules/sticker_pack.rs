@@ -0,0 +1,123 @@
+use std::io::Cursor;
+
+use image::{imageops::FilterType, ImageFormat};
+use redis::Commands;
+use serde::{Deserialize, Serialize};
+
+use crate::app::AppData;
+
+/// One side of the static sticker must be exactly this, and the other one no longer
+const STICKER_SIDE: u32 = 512;
+/// Largest static sticker file Telegram accepts
+const MAX_STICKER_BYTES: usize = 512 * 1024;
+/// Longest sticker set name and title Telegram accepts
+const MAX_SET_NAME_LEN: usize = 64;
+
+/// The sticker set the user is adding to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StickerPack {
+    /// Full name of the set, ends with `_by_<bot username>`
+    pub name: String,
+    pub title: String,
+}
+
+impl StickerPack {
+    /// Name the pack of the user. The user id is in the set name, so that packs of different
+    /// users never clash, and the bot can tell who owns the set.
+    pub fn new(name: &str, title: &str, user_id: u64, bot_username: &str) -> anyhow::Result<Self> {
+        let valid = name.starts_with(|c: char| c.is_ascii_alphabetic())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            && !name.contains("__")
+            && !name.ends_with('_');
+        if !valid {
+            anyhow::bail!("{name} should be letters, digits and single underscores");
+        }
+        let full_name = format!("{name}_u{user_id}_by_{bot_username}");
+        if full_name.len() > MAX_SET_NAME_LEN {
+            anyhow::bail!("{name} is too long");
+        }
+        let title = if title.is_empty() { name } else { title };
+        if title.chars().count() > MAX_SET_NAME_LEN {
+            anyhow::bail!("the title is longer than {MAX_SET_NAME_LEN} characters");
+        }
+        Ok(Self {
+            name: full_name,
+            title: title.to_string(),
+        })
+    }
+
+    pub fn link(&self) -> String {
+        format!("https://t.me/addstickers/{}", self.name)
+    }
+}
+
+/// The user owning the sticker set, none when the set is not made by [`StickerPack`].
+pub fn owner_of(set_name: &str, bot_username: &str) -> Option<u64> {
+    let name = set_name.strip_suffix(&format!("_by_{bot_username}"))?;
+    name.rsplit_once("_u")?.1.parse().ok()
+}
+
+fn current_key(user_id: u64) -> String {
+    format!("STICKER_PACK:{user_id}")
+}
+
+/// The pack `/pack add` adds to.
+pub fn current(data: &AppData, user_id: u64) -> anyhow::Result<Option<StickerPack>> {
+    let encoded: Option<String> = data.cacher.get_conn().get(current_key(user_id))?;
+    Ok(encoded
+        .map(|encoded| serde_json::from_str(&encoded))
+        .transpose()?)
+}
+
+pub fn set_current(data: &AppData, user_id: u64, pack: &StickerPack) -> anyhow::Result<()> {
+    let () = data
+        .cacher
+        .get_conn()
+        .set(current_key(user_id), serde_json::to_string(pack)?)?;
+    Ok(())
+}
+
+/// Resize the image to fit the sticker in PNG. This takes a while for the large image, run it in
+/// a blocking task.
+pub fn legalize(image: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let image = image::load_from_memory(image)?;
+    // Scales up the small image as well, so that the longer side is exactly 512
+    let image = image.resize(STICKER_SIDE, STICKER_SIDE, FilterType::Lanczos3);
+    let mut png = Cursor::new(Vec::new());
+    image.write_to(&mut png, ImageFormat::Png)?;
+    let png = png.into_inner();
+    if png.len() > MAX_STICKER_BYTES {
+        anyhow::bail!(
+            "the image is {} KiB after resizing, larger than the 512 KiB limit",
+            png.len() / 1024
+        );
+    }
+    Ok(png)
+}
+
+#[test]
+fn test_sticker_pack() {
+    let pack = StickerPack::new("cats", "", 42, "maid_bot").unwrap();
+    assert_eq!(pack.name, "cats_u42_by_maid_bot");
+    assert_eq!(pack.title, "cats");
+    assert_eq!(pack.link(), "https://t.me/addstickers/cats_u42_by_maid_bot");
+    assert_eq!(owner_of(&pack.name, "maid_bot"), Some(42));
+    assert_eq!(owner_of("quoting_42_by_maid_bot", "maid_bot"), None);
+    assert_eq!(owner_of("cats_u42_by_other_bot", "maid_bot"), None);
+
+    for invalid in ["1cats", "my__cats", "cats_", "cats!", ""] {
+        assert!(StickerPack::new(invalid, "", 42, "maid_bot").is_err());
+    }
+    assert!(StickerPack::new(&"a".repeat(60), "", 42, "maid_bot").is_err());
+}
+
+#[test]
+fn test_legalize() {
+    let mut small = Cursor::new(Vec::new());
+    image::RgbaImage::new(100, 50)
+        .write_to(&mut small, ImageFormat::Png)
+        .unwrap();
+    let sticker = image::load_from_memory(&legalize(small.get_ref()).unwrap()).unwrap();
+    assert_eq!((sticker.width(), sticker.height()), (512, 256));
+    assert!(legalize(b"not an image").is_err());
+}
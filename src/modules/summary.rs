@@ -0,0 +1,300 @@
+use std::{collections::HashMap, str::FromStr};
+
+use redis::Commands;
+use serde::{Deserialize, Serialize};
+
+use crate::{app::AppData, modules::monitor};
+
+/// The summaries are cached for a day
+const CACHE_TTL: u64 = 60 * 60 * 24;
+/// Stop reading the page after this many bytes
+const MAX_PAGE_BYTES: usize = 2 * 1024 * 1024;
+/// Shorter paragraphs are mostly captions and buttons
+const MIN_PARAGRAPH_CHARS: usize = 20;
+/// Words too common to tell what the article is about
+const STOPWORDS: &[&str] = &[
+    "the", "and", "for", "are", "but", "not", "you", "all", "any", "can", "had", "her", "was",
+    "one", "our", "out", "has", "have", "him", "his", "how", "its", "may", "who", "did", "she",
+    "they", "them", "this", "that", "with", "from", "were", "been", "will", "would", "there",
+    "their", "what", "when", "which", "than", "then", "into", "also", "more", "some", "such",
+    "only", "other", "about", "after", "could", "should", "these", "those", "being", "over", "的",
+    "了", "是", "在", "和", "有", "我", "也", "就", "不", "这", "那", "都", "而", "与", "及", "或",
+    "被", "把", "对", "中", "上", "为", "以", "之", "其", "他", "她", "它", "们",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SummaryLength {
+    Short,
+    Medium,
+    Long,
+}
+
+impl SummaryLength {
+    /// Sentences picked for the summary
+    fn sentences(&self) -> usize {
+        match self {
+            Self::Short => 3,
+            Self::Medium => 5,
+            Self::Long => 8,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Short => "short",
+            Self::Medium => "medium",
+            Self::Long => "long",
+        }
+    }
+}
+
+impl FromStr for SummaryLength {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "short" => Ok(Self::Short),
+            "medium" => Ok(Self::Medium),
+            "long" => Ok(Self::Long),
+            _ => anyhow::bail!("unknown summary length {s}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Summary {
+    pub title: Option<String>,
+    /// In the order of the article
+    pub sentences: Vec<String>,
+    pub url: String,
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The title and the paragraphs of the article. The paragraphs are looked for in `<article>`
+/// first, then `<main>`, then the whole page.
+fn extract_article(html: &str) -> (Option<String>, Vec<String>) {
+    let document = scraper::Html::parse_document(html);
+    let select = |selector: &str| scraper::Selector::parse(selector).unwrap();
+
+    let title = document
+        .select(&select(r#"meta[property="og:title"]"#))
+        .find_map(|meta| meta.value().attr("content"))
+        .map(collapse_whitespace)
+        .or_else(|| {
+            document
+                .select(&select("title"))
+                .next()
+                .map(|title| collapse_whitespace(&title.text().collect::<String>()))
+        })
+        .filter(|title| !title.is_empty());
+
+    for container in ["article p", "main p", "p"] {
+        let paragraphs = document
+            .select(&select(container))
+            .map(|p| collapse_whitespace(&p.text().collect::<String>()))
+            .filter(|p| p.chars().count() >= MIN_PARAGRAPH_CHARS)
+            .collect::<Vec<_>>();
+        if !paragraphs.is_empty() {
+            return (title, paragraphs);
+        }
+    }
+    (title, Vec::new())
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c, '\u{3040}'..='\u{30ff}' | '\u{3400}'..='\u{4dbf}' | '\u{4e00}'..='\u{9fff}')
+}
+
+fn is_closing(c: char) -> bool {
+    matches!(c, '"' | '\'' | '”' | '’' | '」' | '』' | ')' | '）')
+}
+
+/// Split the paragraph at the end of each sentence. The ASCII marks only end a sentence before
+/// a space, so that `3.14` and `example.com` are kept.
+fn split_sentences(paragraph: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut current = String::new();
+    let mut chars = paragraph.chars().peekable();
+    while let Some(c) = chars.next() {
+        current.push(c);
+        let end = match c {
+            '。' | '！' | '？' => true,
+            '.' | '!' | '?' => chars
+                .peek()
+                .is_none_or(|&next| next.is_whitespace() || is_closing(next)),
+            _ => false,
+        };
+        if end {
+            // Keep the closing quote with the sentence
+            while let Some(quote) = chars.next_if(|&next| is_closing(next)) {
+                current.push(quote);
+            }
+            sentences.push(current.trim().to_string());
+            current.clear();
+        }
+    }
+    sentences.push(current.trim().to_string());
+    sentences.retain(|sentence| !sentence.is_empty());
+    sentences
+}
+
+/// The words of the text in lowercase, every CJK character is a word.
+fn words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    for c in text.chars() {
+        if is_cjk(c) {
+            words.extend((!word.is_empty()).then(|| std::mem::take(&mut word)));
+            words.push(c.to_string());
+        } else if c.is_alphanumeric() {
+            word.extend(c.to_lowercase());
+        } else {
+            words.extend((!word.is_empty()).then(|| std::mem::take(&mut word)));
+        }
+    }
+    words.extend((!word.is_empty()).then_some(word));
+    words.retain(|word| {
+        let cjk = word.chars().next().is_some_and(is_cjk);
+        (cjk || word.chars().count() > 2) && !STOPWORDS.contains(&word.as_str())
+    });
+    words
+}
+
+/// Pick the sentences made of the most frequent words in the article, kept in their order.
+fn summarize(paragraphs: &[String], count: usize) -> Vec<String> {
+    let sentences = paragraphs
+        .iter()
+        .flat_map(|paragraph| split_sentences(paragraph))
+        .collect::<Vec<_>>();
+    let sentence_words = sentences
+        .iter()
+        .map(|sentence| words(sentence))
+        .collect::<Vec<_>>();
+
+    let mut frequency = HashMap::<&str, usize>::new();
+    for word in sentence_words.iter().flatten() {
+        *frequency.entry(word.as_str()).or_default() += 1;
+    }
+
+    let mut scored = sentence_words
+        .iter()
+        .enumerate()
+        .filter(|(_, words)| !words.is_empty())
+        .map(|(index, words)| {
+            let total = words
+                .iter()
+                .map(|word| frequency[word.as_str()])
+                .sum::<usize>();
+            // Averaged so the long sentences don't always win, and the lead usually tells the
+            // point of the article
+            let mut score = total as f64 / words.len() as f64;
+            if index == 0 {
+                score *= 1.5;
+            }
+            (index, score)
+        })
+        .collect::<Vec<_>>();
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+    scored.truncate(count);
+    scored.sort_by_key(|(index, _)| *index);
+    scored
+        .into_iter()
+        .map(|(index, _)| sentences[index].clone())
+        .collect()
+}
+
+async fn fetch_page(url: reqwest::Url) -> anyhow::Result<String> {
+    let client = monitor::public_client();
+    let mut resp = client.send(client.get(url)).await?.error_for_status()?;
+    let mut body = Vec::new();
+    while let Some(chunk) = resp.chunk().await? {
+        body.extend_from_slice(&chunk);
+        if body.len() >= MAX_PAGE_BYTES {
+            break;
+        }
+    }
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+/// Summarize the article of the page by picking its key sentences.
+pub async fn summarize_url(
+    data: &AppData,
+    url: &str,
+    length: SummaryLength,
+) -> anyhow::Result<Summary> {
+    let url = monitor::parse_url(url)?;
+    let key = format!("SUMMARY:{}:{url}", length.name());
+    let cache: Option<String> = data.cacher.get_conn().get(&key)?;
+    if let Some(cache) = cache {
+        return Ok(serde_json::from_str(&cache)?);
+    }
+
+    let html = fetch_page(url.clone()).await?;
+    let (title, paragraphs) = extract_article(&html);
+    let sentences = summarize(&paragraphs, length.sentences());
+    if sentences.is_empty() {
+        anyhow::bail!("no article is found in {url}");
+    }
+    let summary = Summary {
+        title,
+        sentences,
+        url: url.to_string(),
+    };
+    let () = data
+        .cacher
+        .get_conn()
+        .set_ex(&key, serde_json::to_string(&summary)?, CACHE_TTL)?;
+    Ok(summary)
+}
+
+#[test]
+fn test_split_sentences() {
+    assert_eq!(
+        split_sentences("Pi is 3.14 roughly. See example.com! Really? \"Yes.\" Tail"),
+        [
+            "Pi is 3.14 roughly.",
+            "See example.com!",
+            "Really?",
+            "\"Yes.\"",
+            "Tail"
+        ]
+    );
+    assert_eq!(
+        split_sentences("今天下雨了。「真的吗？」明天呢"),
+        ["今天下雨了。", "「真的吗？」", "明天呢"]
+    );
+    assert_eq!(
+        words("The Rust compiler, 编译器!"),
+        ["rust", "compiler", "编", "译", "器"]
+    );
+}
+
+#[test]
+fn test_summarize() {
+    let html = r#"<html><head><title>Ignored</title>
+        <meta property="og:title" content="Rust  news"></head><body>
+        <nav><p>Home | About | Contact us here now</p></nav>
+        <article>
+            <p>Rust is a language for building reliable and efficient software.</p>
+            <p>Short</p>
+            <p>The weather was nice yesterday. Rust releases a new version every six weeks.
+            Many developers love Rust for its reliable tooling and efficient software.</p>
+        </article></body></html>"#;
+    let (title, paragraphs) = extract_article(html);
+    assert_eq!(title.as_deref(), Some("Rust news"));
+    assert_eq!(paragraphs.len(), 2);
+
+    let sentences = summarize(&paragraphs, 2);
+    assert_eq!(
+        sentences,
+        [
+            "Rust is a language for building reliable and efficient software.",
+            "Many developers love Rust for its reliable tooling and efficient software."
+        ]
+    );
+    assert_eq!(summarize(&paragraphs, 10).len(), 4);
+    assert!(summarize(&[], 3).is_empty());
+}
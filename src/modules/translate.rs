@@ -0,0 +1,98 @@
+use crate::{app::AppData, usage};
+use base64::Engine;
+use deepl::Lang;
+use redis::Commands;
+use sha2::{Digest, Sha256};
+use teloxide::types::UserId;
+
+/// Module name the translated characters are counted under, see [`usage`]
+const USAGE_MODULE: &str = "tr";
+/// The inline query asks for the same text again while the user edits it, keep the translation
+/// for a day so that it is only paid once
+const CACHE_TTL: u64 = 60 * 60 * 24;
+
+fn cache_key(text: &str, source: Option<&Lang>, target: &Lang) -> String {
+    let hash = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(Sha256::digest(text));
+    let source = source.map(|lang| lang.to_string()).unwrap_or_default();
+    format!("TRANSLATION:{source}:{target}:{hash}")
+}
+
+pub fn parse_lang(code: &str) -> anyhow::Result<Lang> {
+    Lang::try_from(&code.to_uppercase())
+        .map_err(|_| anyhow::anyhow!("invalid language code {code}"))
+}
+
+/// Translate text by DeepL. The API is paid, so the translation stop working when one third of
+/// the character limit are used, or the `tr` quota of the user is met. The same text is
+/// translated from the cache for free.
+pub async fn translate(
+    data: &AppData,
+    user: UserId,
+    text: &str,
+    source: Option<Lang>,
+    target: Lang,
+) -> anyhow::Result<String> {
+    let key = cache_key(text, source.as_ref(), &target);
+    let cached: Option<String> = data.cacher.get_conn().get(&key)?;
+    if let Some(translated) = cached {
+        return Ok(translated);
+    }
+
+    let characters = text.chars().count() as u64;
+    usage::charge(data, USAGE_MODULE, user, characters)?;
+    let translated = request_deepl(data, text, source, target).await;
+    if translated.is_err() {
+        usage::refund(data, USAGE_MODULE, user, characters)?;
+    }
+    let translated = translated?;
+    let () = data
+        .cacher
+        .get_conn()
+        .set_ex(&key, &translated, CACHE_TTL)?;
+    Ok(translated)
+}
+
+async fn request_deepl(
+    data: &AppData,
+    text: &str,
+    source: Option<Lang>,
+    target: Lang,
+) -> anyhow::Result<String> {
+    let deepl = data.deepl.borrow().clone();
+    let current_usage = deepl
+        .get_usage()
+        .await
+        .map_err(|err| anyhow::anyhow!("fail to get current api usage: {err}"))?;
+    if current_usage.character_count > current_usage.character_limit / 3 {
+        anyhow::bail!("API usage limit are met, this command is temporary unusable.");
+    }
+
+    let result = if let Some(src) = source {
+        deepl.translate_text(text, target).source_lang(src).await
+    } else {
+        deepl.translate_text(text, target).await
+    };
+    let resp = result.map_err(|err| anyhow::anyhow!("fail to translate: {err:?}"))?;
+
+    Ok(resp
+        .translations
+        .iter()
+        .map(|rp| rp.text.as_str())
+        .collect::<String>())
+}
+
+/// Split the optional target language from the text like `en hello`. If the text doesn't start
+/// with a language code, translate non-ASCII text into English and others into Chinese.
+pub fn split_target_lang(args: &str) -> (Lang, &str) {
+    if let Some((code, text)) = args.split_once(' ') {
+        if let Ok(lang) = parse_lang(code) {
+            return (lang, text.trim());
+        }
+    }
+
+    if args.is_ascii() {
+        (Lang::ZH, args)
+    } else {
+        (Lang::EN, args)
+    }
+}
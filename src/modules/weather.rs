@@ -2,18 +2,25 @@ use anyhow::Result;
 
 use crate::app::AppData;
 
-use super::Sendable;
-
 const WTTR_IN_URL: &str = "https://wttr.in";
 
-pub async fn fetch_weather(data: AppData, city: &str) -> Result<Sendable> {
+/// Get the one line weather description of the given city.
+pub async fn fetch_weather_text(data: &AppData, city: &str) -> Result<String> {
     let url = reqwest::Url::parse_with_params(
         &format!("{WTTR_IN_URL}/{city}"),
         &[("format", "%l的天气:+%c+温度:%t+湿度:%h+降雨量:%p")],
     )?;
-    let resp = data.requester.get_text(url).await?;
-    Ok(Sendable::builder()
-        .url(format!("{WTTR_IN_URL}/{city}.png"))
-        .caption(resp)
-        .build())
+    data.requester.get_text(url).await
+}
+
+/// Get the weather image of the given city. The image is the same until the weather changes, so
+/// it is sent through the media cache.
+pub async fn fetch_weather_image(data: &AppData, city: &str) -> Result<Vec<u8>> {
+    let url = format!("{WTTR_IN_URL}/{city}.png");
+    let resp = data
+        .requester
+        .send(data.requester.get(url))
+        .await?
+        .error_for_status()?;
+    Ok(resp.bytes().await?.to_vec())
 }
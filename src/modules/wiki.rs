@@ -0,0 +1,218 @@
+use redis::Commands;
+use serde::{Deserialize, Serialize};
+
+use crate::app::AppData;
+
+/// The summaries are cached for a day
+const CACHE_TTL: u64 = 60 * 60 * 24;
+/// Long lead paragraphs are cut at this many characters
+const MAX_SUMMARY_CHARS: usize = 800;
+/// Wikipedia asks the API clients to identify themselves
+const USER_AGENT: &str = concat!("rusty-maid/", env!("CARGO_PKG_VERSION"));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WikiSite {
+    Wikipedia,
+    Moegirl,
+}
+
+impl WikiSite {
+    fn api(&self, lang: &str) -> String {
+        match self {
+            Self::Wikipedia => format!("https://{lang}.wikipedia.org/w/api.php"),
+            // Moegirlpedia is Chinese only
+            Self::Moegirl => "https://zh.moegirl.org.cn/api.php".to_string(),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Wikipedia => "wikipedia",
+            Self::Moegirl => "moegirl",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WikiResult {
+    Page {
+        title: String,
+        summary: String,
+        url: String,
+    },
+    /// The page is not found, these are the titles the search suggests
+    Suggestions(Vec<String>),
+}
+
+#[derive(Deserialize)]
+struct QueryResponse<T> {
+    query: Option<T>,
+}
+
+#[derive(Deserialize)]
+struct Pages {
+    pages: Vec<Page>,
+}
+
+#[derive(Deserialize)]
+struct Page {
+    title: String,
+    #[serde(default)]
+    missing: bool,
+    extract: Option<String>,
+    fullurl: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Search {
+    search: Vec<SearchHit>,
+}
+
+#[derive(Deserialize)]
+struct SearchHit {
+    title: String,
+}
+
+/// Wikipedia subdomain for the language of the chat
+pub fn site_language(lang: &str) -> &'static str {
+    if lang.starts_with("zh") {
+        "zh"
+    } else {
+        "en"
+    }
+}
+
+/// The first paragraph of the extract, cut at [`MAX_SUMMARY_CHARS`].
+fn lead_paragraph(extract: &str) -> String {
+    let lead = extract
+        .split('\n')
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or_default();
+    if lead.chars().count() <= MAX_SUMMARY_CHARS {
+        return lead.to_string();
+    }
+    let cut = lead.chars().take(MAX_SUMMARY_CHARS).collect::<String>();
+    format!("{cut}…")
+}
+
+async fn query<T: serde::de::DeserializeOwned>(
+    data: &AppData,
+    api: &str,
+    params: &[(&str, &str)],
+) -> anyhow::Result<Option<T>> {
+    let request = data
+        .requester
+        .get(api)
+        .header(reqwest::header::USER_AGENT, USER_AGENT)
+        .query(&[
+            ("action", "query"),
+            ("format", "json"),
+            ("formatversion", "2"),
+        ])
+        .query(params);
+    let resp: QueryResponse<T> = data
+        .requester
+        .send(request)
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(resp.query)
+}
+
+async fn fetch(
+    data: &AppData,
+    site: WikiSite,
+    lang: &str,
+    term: &str,
+) -> anyhow::Result<WikiResult> {
+    let api = site.api(lang);
+    // Show the Chinese pages in simplified Chinese
+    let variant = if lang == "zh" { "zh-cn" } else { lang };
+    let pages: Option<Pages> = query(
+        data,
+        &api,
+        &[
+            ("prop", "extracts|info"),
+            ("exintro", "1"),
+            ("explaintext", "1"),
+            ("inprop", "url"),
+            ("redirects", "1"),
+            ("variant", variant),
+            ("titles", term),
+        ],
+    )
+    .await?;
+    let page = pages.and_then(|pages| pages.pages.into_iter().next());
+    if let Some(Page {
+        title,
+        missing: false,
+        extract: Some(extract),
+        fullurl: Some(url),
+    }) = page
+    {
+        if !extract.trim().is_empty() {
+            return Ok(WikiResult::Page {
+                title,
+                summary: lead_paragraph(&extract),
+                url,
+            });
+        }
+    }
+
+    let search: Option<Search> = query(
+        data,
+        &api,
+        &[("list", "search"), ("srlimit", "5"), ("srsearch", term)],
+    )
+    .await?;
+    let titles = search
+        .map(|search| search.search.into_iter().map(|hit| hit.title).collect())
+        .unwrap_or_default();
+    Ok(WikiResult::Suggestions(titles))
+}
+
+/// Look up the summary of the term, or the search suggestions when there is no such page.
+pub async fn lookup(
+    data: &AppData,
+    site: WikiSite,
+    lang: &str,
+    term: &str,
+) -> anyhow::Result<WikiResult> {
+    let key = format!(
+        "WIKI_SUMMARY:{}:{lang}:{}",
+        site.name(),
+        term.to_lowercase()
+    );
+    let cache: Option<String> = data.cacher.get_conn().get(&key)?;
+    if let Some(cache) = cache {
+        return Ok(serde_json::from_str(&cache)?);
+    }
+
+    let result = fetch(data, site, lang, term).await?;
+    let () = data
+        .cacher
+        .get_conn()
+        .set_ex(&key, serde_json::to_string(&result)?, CACHE_TTL)?;
+    Ok(result)
+}
+
+#[test]
+fn test_lead_paragraph() {
+    let extract = "\nRust is a programming language.\n\nIt is fast.";
+    assert_eq!(lead_paragraph(extract), "Rust is a programming language.");
+
+    let long = "字".repeat(MAX_SUMMARY_CHARS + 1);
+    assert_eq!(lead_paragraph(&long).chars().count(), MAX_SUMMARY_CHARS + 1);
+    assert!(lead_paragraph(&long).ends_with('…'));
+}
+
+#[test]
+fn test_parse_pages() {
+    let json = r#"{"batchcomplete": true, "query": {"pages": [
+        {"ns": 0, "title": "Nope", "missing": true}
+    ]}}"#;
+    let resp: QueryResponse<Pages> = serde_json::from_str(json).unwrap();
+    assert!(resp.query.unwrap().pages[0].missing);
+}
@@ -58,7 +58,8 @@ impl YtdlpVideo {
             .arg(&video_format)
             .arg("--restrict-filenames")
             .arg("-j");
-        if let Some(proxy_url) = Config::get_global_config().proxy.yt_dlp() {
+        let config = Config::get_global_config();
+        if let Some(proxy_url) = config.proxy.yt_dlp() {
             info.arg("--proxy").arg(proxy_url);
         }
         let info = info
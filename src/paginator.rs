@@ -0,0 +1,191 @@
+use std::sync::Arc;
+
+use redis::Commands;
+use serde::{Deserialize, Serialize};
+use teloxide::{
+    payloads::{EditMessageTextSetters, SendMessageSetters},
+    prelude::*,
+    types::{InlineKeyboardMarkup, ParseMode},
+};
+
+use crate::{
+    app::AppData,
+    callback::{CallbackAnswer, CallbackContext, CallbackRouter},
+    i18n,
+    topic::{ChatTarget, SendTo},
+};
+
+/// Module name of the page buttons in the callback router
+pub const MODULE: &str = "page";
+/// The buttons stop working after a day
+const PAGES_TTL: u64 = 60 * 60 * 24;
+const DEFAULT_PAGE_SIZE: usize = 10;
+
+fn pages_key(id: &str) -> String {
+    format!("PAGINATOR:{id}")
+}
+
+/// A long list sent as pages, with the buttons to turn the page by editing the message. The
+/// items are kept in Redis for the buttons.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Paginator {
+    /// Shown above the items on every page, in HTML
+    title: String,
+    /// Each item is a line in HTML
+    items: Vec<String>,
+    page_size: usize,
+}
+
+impl Paginator {
+    pub fn new(title: impl Into<String>, items: Vec<String>) -> Self {
+        Self {
+            title: title.into(),
+            items,
+            page_size: DEFAULT_PAGE_SIZE,
+        }
+    }
+
+    pub fn page_size(mut self, page_size: usize) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.items.len().div_ceil(self.page_size).max(1)
+    }
+
+    /// The text of the page, counted from 0.
+    pub fn render(&self, page: usize) -> String {
+        let lines = self
+            .items
+            .iter()
+            .skip(page * self.page_size)
+            .take(self.page_size)
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        format!("{}\n\n{}", self.title, lines.join("\n"))
+    }
+
+    /// The previous and next buttons of the page, none when everything fits in one page.
+    fn keyboard(
+        &self,
+        router: &CallbackRouter,
+        id: &str,
+        page: usize,
+    ) -> anyhow::Result<Option<InlineKeyboardMarkup>> {
+        let count = self.page_count();
+        if count <= 1 {
+            return Ok(None);
+        }
+        let mut row = Vec::new();
+        if page > 0 {
+            row.push(router.button("«", MODULE, "go", &(id, page - 1))?);
+        }
+        row.push(router.button(format!("{}/{count}", page + 1), MODULE, "stay", &(id, page))?);
+        if page + 1 < count {
+            row.push(router.button("»", MODULE, "go", &(id, page + 1))?);
+        }
+        Ok(Some(InlineKeyboardMarkup::new([row])))
+    }
+
+    fn save(&self, data: &AppData) -> anyhow::Result<String> {
+        let id = format!("{:016x}", rand::random::<u64>());
+        let () = data.cacher.get_conn().set_ex(
+            pages_key(&id),
+            serde_json::to_string(self)?,
+            PAGES_TTL,
+        )?;
+        Ok(id)
+    }
+
+    fn load(data: &AppData, id: &str) -> anyhow::Result<Option<Self>> {
+        let encoded: Option<String> = data.cacher.get_conn().get(pages_key(id))?;
+        Ok(encoded
+            .map(|encoded| serde_json::from_str(&encoded))
+            .transpose()?)
+    }
+
+    /// Send the first page to the chat, the router signs the page buttons.
+    pub async fn send(
+        &self,
+        bot: &Bot,
+        data: &AppData,
+        router: &CallbackRouter,
+        to: impl Into<ChatTarget>,
+    ) -> anyhow::Result<Message> {
+        let to = to.into();
+        let mut request = bot
+            .send_message_to(to, self.render(0))
+            .parse_mode(ParseMode::Html);
+        if self.page_count() > 1 {
+            let id = self.save(data)?;
+            if let Some(keyboard) = self.keyboard(router, &id, 0)? {
+                request = request.reply_markup(keyboard);
+            }
+        }
+        data.reply(to.chat_id, request).await
+    }
+}
+
+/// Handle the page buttons. Every router creating a [`Paginator`] should route it.
+pub fn route(router: CallbackRouter) -> CallbackRouter {
+    let buttons = Arc::new(router.signer());
+    router.route(MODULE, move |ctx| {
+        let buttons = Arc::clone(&buttons);
+        async move { turn_page(ctx, &buttons).await }
+    })
+}
+
+async fn turn_page(
+    ctx: CallbackContext,
+    buttons: &CallbackRouter,
+) -> anyhow::Result<CallbackAnswer> {
+    if ctx.action != "go" {
+        return Ok(None);
+    }
+    let (id, page): (String, usize) = ctx.payload()?;
+    let Some(msg) = ctx.query.regular_message() else {
+        anyhow::bail!(crate::t!(i18n::DEFAULT_LANG, "paginator.expired"));
+    };
+    let lang = i18n::chat_language(
+        &ctx.data,
+        msg.chat.id.0,
+        ctx.query.from.language_code.as_deref(),
+    );
+    let Some(paginator) = Paginator::load(&ctx.data, &id)? else {
+        anyhow::bail!(crate::t!(lang, "paginator.expired"));
+    };
+
+    let page = page.min(paginator.page_count() - 1);
+    let mut request = ctx
+        .bot
+        .edit_message_text(msg.chat.id, msg.id, paginator.render(page))
+        .parse_mode(ParseMode::Html);
+    if let Some(keyboard) = paginator.keyboard(buttons, &id, page)? {
+        request = request.reply_markup(keyboard);
+    }
+    request.await?;
+    Ok(None)
+}
+
+#[tokio::test]
+async fn test_paginator() {
+    let data = crate::testkit::app_data(Bot::new("1000:fake-token")).await;
+    let router = route(CallbackRouter::new("secret"));
+    let items = (1..=25).map(|n| n.to_string()).collect();
+    let paginator = Paginator::new("<b>Numbers</b>", items).page_size(10);
+
+    assert_eq!(paginator.page_count(), 3);
+    assert_eq!(paginator.render(2), "<b>Numbers</b>\n\n21\n22\n23\n24\n25");
+    let keyboard = |page| paginator.keyboard(&router, "id", page).unwrap().unwrap();
+    assert_eq!(keyboard(0).inline_keyboard[0].len(), 2);
+    assert_eq!(keyboard(1).inline_keyboard[0].len(), 3);
+    assert_eq!(keyboard(2).inline_keyboard[0][1].text, "3/3");
+
+    let short = Paginator::new("title", vec!["only".to_string()]);
+    assert!(short.keyboard(&router, "id", 0).unwrap().is_none());
+
+    let id = paginator.save(&data).unwrap();
+    assert_eq!(Paginator::load(&data, &id).unwrap(), Some(paginator));
+    assert_eq!(Paginator::load(&data, "missing").unwrap(), None);
+}
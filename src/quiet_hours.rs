@@ -0,0 +1,301 @@
+//! Quiet hours of the chats. The watcher notifications sent by [`notify`] during the quiet hours
+//! are held in Redis, and sent together as a digest when the quiet hours end. The command
+//! replies and the critical alerts like the uptime monitor are sent by
+//! [`crate::app::AppData::submit`] directly, so they are never held.
+
+use std::{fmt::Display, str::FromStr, time::Duration};
+
+use chrono::{Local, NaiveTime, Timelike};
+use redis::Commands;
+use serde::{Deserialize, Serialize};
+use teloxide::{prelude::*, types::ParseMode, utils::html};
+
+use crate::{
+    app::AppData,
+    config::MAIN_BOT,
+    delayed_task::{self, DelayedTask},
+    send_queue::Priority,
+    topic::{ChatTarget, SendTo},
+};
+
+/// Hash of the chat id and its quiet hours like `23:00-08:00`
+const QUIET_HOURS_KEY: &str = "QUIET_HOURS";
+/// Telegram rejects message longer than 4096 characters
+const MAX_DIGEST_LEN: usize = 4000;
+const DIGEST_SEPARATOR: &str = "\n\n";
+
+/// The time of the day the chat doesn't want to be disturbed, in the local time of the bot host.
+/// The end is before the start when it runs over midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl QuietHours {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+
+    /// Time left before the quiet hours end.
+    pub fn remaining(&self, time: NaiveTime) -> Duration {
+        let left = (self.end - time).num_seconds().rem_euclid(24 * 60 * 60);
+        Duration::from_secs(left as u64)
+    }
+}
+
+impl FromStr for QuietHours {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((start, end)) = s.split_once('-') else {
+            anyhow::bail!("quiet hours should look like 23:00-08:00");
+        };
+        let parse = |time: &str| {
+            NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                .map_err(|_| anyhow::anyhow!("{time} is not a time like 08:00"))
+        };
+        let (start, end) = (parse(start)?, parse(end)?);
+        if start == end {
+            anyhow::bail!("quiet hours should not start and end at the same time");
+        }
+        Ok(Self { start, end })
+    }
+}
+
+impl Display for QuietHours {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M")
+        )
+    }
+}
+
+pub fn get(data: &AppData, chat_id: i64) -> anyhow::Result<Option<QuietHours>> {
+    let stored: Option<String> = data.cacher.get_conn().hget(QUIET_HOURS_KEY, chat_id)?;
+    Ok(stored.and_then(|stored| stored.parse().ok()))
+}
+
+/// Set the quiet hours of the chat, or turn them off with `None`.
+pub fn set(data: &AppData, chat_id: i64, hours: Option<QuietHours>) -> anyhow::Result<()> {
+    let mut conn = data.cacher.get_conn();
+    let () = match hours {
+        Some(hours) => conn.hset(QUIET_HOURS_KEY, chat_id, hours.to_string())?,
+        None => conn.hdel(QUIET_HOURS_KEY, chat_id)?,
+    };
+    Ok(())
+}
+
+/// A watcher notification which can be held for the digest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Notification {
+    pub text: String,
+    /// The text is in the Telegram HTML
+    #[serde(default)]
+    pub html: bool,
+    /// The notification can wait for the digest of the chat, see [`crate::digest`]
+    #[serde(default)]
+    pub digestable: bool,
+}
+
+impl Notification {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            html: false,
+            digestable: false,
+        }
+    }
+
+    pub fn html(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            html: true,
+            digestable: false,
+        }
+    }
+
+    pub fn digestable(mut self) -> Self {
+        self.digestable = true;
+        self
+    }
+}
+
+pub(crate) fn held_key(bot: &str, target: &str) -> String {
+    format!("QUIET_HELD:{bot}:{target}")
+}
+
+/// Hold the notification for the digest of the chat, or for the end of its quiet hours. Return
+/// false if it should be sent now.
+pub fn hold(
+    bot: &Bot,
+    data: &AppData,
+    target: ChatTarget,
+    notification: &Notification,
+) -> anyhow::Result<bool> {
+    Ok(crate::digest::hold(bot, data, target, notification)?
+        || hold_all(bot, data, target, std::slice::from_ref(notification))?)
+}
+
+/// Hold the notifications if the chat is in its quiet hours, return false if they should be sent
+/// now. The first held notification schedules the digest at the end of the quiet hours.
+pub(crate) fn hold_all(
+    bot: &Bot,
+    data: &AppData,
+    target: ChatTarget,
+    notifications: &[Notification],
+) -> anyhow::Result<bool> {
+    let Some(hours) = get(data, target.chat_id.0)? else {
+        return Ok(false);
+    };
+    let now = Local::now().time().with_nanosecond(0).unwrap_or_default();
+    if !hours.contains(now) {
+        return Ok(false);
+    }
+
+    let bot = data.bots.name_of(bot).unwrap_or(MAIN_BOT).to_string();
+    let target = target.to_string();
+    let encoded = notifications
+        .iter()
+        .map(serde_json::to_string)
+        .collect::<Result<Vec<_>, _>>()?;
+    let held: usize = data
+        .cacher
+        .get_conn()
+        .rpush(held_key(&bot, &target), encoded)?;
+    if held == notifications.len() {
+        let task = DelayedTask::QuietDigest { bot, target };
+        delayed_task::schedule(data, &task, hours.remaining(now))?;
+    }
+    Ok(true)
+}
+
+/// Take the notifications held in the list.
+pub(crate) fn take_held(data: &AppData, key: &str) -> anyhow::Result<Vec<Notification>> {
+    let (held, ()): (Vec<String>, ()) = redis::pipe()
+        .atomic()
+        .lrange(key, 0, -1)
+        .del(key)
+        .query(&mut data.cacher.get_conn())?;
+    Ok(held
+        .iter()
+        .filter_map(|held| serde_json::from_str(held).ok())
+        .collect())
+}
+
+/// Send the notification, or hold it during the quiet hours of the chat.
+pub async fn notify(
+    bot: &Bot,
+    data: &AppData,
+    target: ChatTarget,
+    notification: Notification,
+) -> anyhow::Result<()> {
+    if hold(bot, data, target, &notification)? {
+        return Ok(());
+    }
+    let bot = bot.clone();
+    data.submit(target.chat_id, Priority::Background, move || {
+        let request = bot.send_message_to(target, &notification.text);
+        if notification.html {
+            request.parse_mode(ParseMode::Html)
+        } else {
+            request
+        }
+    })
+    .await?;
+    Ok(())
+}
+
+/// Join the notifications into the digest messages, each of them fits in one message.
+pub(crate) fn digest(header: &str, notifications: &[Notification]) -> Vec<String> {
+    let mut messages = Vec::new();
+    let mut current = html::escape(header);
+    for notification in notifications {
+        let text = if notification.html {
+            notification.text.clone()
+        } else {
+            html::escape(&notification.text)
+        };
+        let len = current.chars().count() + DIGEST_SEPARATOR.len() + text.chars().count();
+        if len > MAX_DIGEST_LEN && !current.is_empty() {
+            messages.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push_str(DIGEST_SEPARATOR);
+        }
+        current.push_str(&text);
+    }
+    if !current.is_empty() {
+        messages.push(current);
+    }
+    messages
+}
+
+/// Send the notifications held for the chat target by the bot as a digest.
+pub async fn send_digest(data: &AppData, bot: &str, target: &str) -> anyhow::Result<()> {
+    let notifications = take_held(data, &held_key(bot, target))?;
+    if notifications.is_empty() {
+        return Ok(());
+    }
+
+    let target = target.parse::<ChatTarget>()?;
+    let lang = crate::i18n::chat_language(data, target.chat_id.0, None);
+    let header = crate::t!(lang, "quiet.digest", count = notifications.len());
+    let bot = data.bots.pick(Some(bot)).clone();
+    for text in digest(&header, &notifications) {
+        let bot = bot.clone();
+        data.submit(target.chat_id, Priority::Background, move || {
+            bot.send_message_to(target, &text)
+                .parse_mode(ParseMode::Html)
+        })
+        .await?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_quiet_hours() {
+    let time = |s| NaiveTime::parse_from_str(s, "%H:%M").unwrap();
+    let night: QuietHours = "23:00-08:00".parse().unwrap();
+    assert_eq!(night.to_string(), "23:00-08:00");
+    assert!(night.contains(time("23:30")));
+    assert!(night.contains(time("07:59")));
+    assert!(!night.contains(time("08:00")));
+    assert!(!night.contains(time("12:00")));
+    assert_eq!(
+        night.remaining(time("23:00")),
+        Duration::from_secs(9 * 3600)
+    );
+    assert_eq!(night.remaining(time("07:30")), Duration::from_secs(1800));
+
+    let noon: QuietHours = "12:00 - 13:30".parse().unwrap();
+    assert!(noon.contains(time("13:00")));
+    assert!(!noon.contains(time("23:00")));
+    assert!("08:00".parse::<QuietHours>().is_err());
+    assert!("25:00-08:00".parse::<QuietHours>().is_err());
+    assert!("08:00-08:00".parse::<QuietHours>().is_err());
+}
+
+#[test]
+fn test_digest() {
+    let notifications = [
+        Notification::text("a < b"),
+        Notification::html("<b>bold</b>"),
+    ];
+    assert_eq!(
+        digest("Digest", &notifications),
+        ["Digest\n\na &lt; b\n\n<b>bold</b>"]
+    );
+
+    let long = vec![Notification::text("x".repeat(3000)); 2];
+    let messages = digest("Digest", &long);
+    assert_eq!(messages.len(), 2);
+    assert!(messages[1].starts_with('x'));
+}
@@ -0,0 +1,125 @@
+use redis::Commands;
+use teloxide::{
+    prelude::{Bot, Requester},
+    types::{Chat, UserId},
+};
+
+use crate::{app::AppData, command::Permission, config::PermissionConfig};
+
+/// Seconds to cache the chat administrator list fetched from Telegram
+const CHAT_ADMINS_CACHE_SECS: u64 = 10 * 60;
+
+fn chat_admins_key(chat_id: i64) -> String {
+    format!("CHAT_ADMINS:{chat_id}")
+}
+
+/// Resolve the role that doesn't depend on the chat: the owner and global admins come from the
+/// config, and global admins can also be added at runtime by `/admin add`.
+fn static_role(config: &PermissionConfig, runtime_admins: &[u64], user_id: u64) -> Permission {
+    if config.owner == Some(user_id) {
+        Permission::Owner
+    } else if config.admins.contains(&user_id) || runtime_admins.contains(&user_id) {
+        Permission::GlobalAdmin
+    } else {
+        Permission::User
+    }
+}
+
+pub async fn global_admins(data: &AppData) -> anyhow::Result<Vec<u64>> {
+    let admins: Vec<(i64,)> = sqlx::query_as("SELECT user_id FROM global_admins ORDER BY added_at")
+        .fetch_all(data.storage.pool())
+        .await?;
+    Ok(admins.into_iter().map(|(id,)| id as u64).collect())
+}
+
+/// Add the global admin. Return false if the user is already an admin.
+pub async fn add_global_admin(data: &AppData, user_id: u64) -> anyhow::Result<bool> {
+    let added = sqlx::query(
+        "INSERT INTO global_admins (user_id, added_at) VALUES ($1, $2) \
+         ON CONFLICT (user_id) DO NOTHING",
+    )
+    .bind(user_id as i64)
+    .bind(chrono::Utc::now().timestamp())
+    .execute(data.storage.pool())
+    .await?;
+    Ok(added.rows_affected() > 0)
+}
+
+/// Remove the global admin added by [`add_global_admin`], admins in the config can't be removed.
+pub async fn remove_global_admin(data: &AppData, user_id: u64) -> anyhow::Result<bool> {
+    let removed = sqlx::query("DELETE FROM global_admins WHERE user_id = $1")
+        .bind(user_id as i64)
+        .execute(data.storage.pool())
+        .await?;
+    Ok(removed.rows_affected() > 0)
+}
+
+/// Get the administrators of the group. The list is cached for a while to avoid hitting the
+/// Telegram API on every command.
+async fn chat_admins(bot: &Bot, data: &AppData, chat: &Chat) -> anyhow::Result<Vec<u64>> {
+    let key = chat_admins_key(chat.id.0);
+    let cached: Option<String> = data.cacher.get_conn().get(&key)?;
+    if let Some(cached) = cached {
+        return Ok(serde_json::from_str(&cached)?);
+    }
+
+    let admins = bot
+        .get_chat_administrators(chat.id)
+        .await?
+        .into_iter()
+        .map(|member| member.user.id.0)
+        .collect::<Vec<_>>();
+    let () = data.cacher.get_conn().set_ex(
+        &key,
+        serde_json::to_string(&admins)?,
+        CHAT_ADMINS_CACHE_SECS,
+    )?;
+    Ok(admins)
+}
+
+/// Get the role of the user in the chat. User is always the chat admin of the private chat.
+pub async fn role_of(
+    bot: &Bot,
+    data: &AppData,
+    chat: &Chat,
+    user_id: UserId,
+) -> anyhow::Result<Permission> {
+    let config = crate::config::Config::get_global_config();
+    let role = static_role(&config.permission, &global_admins(data).await?, user_id.0);
+    if role > Permission::User {
+        return Ok(role);
+    }
+
+    if chat.is_private() || chat_admins(bot, data, chat).await?.contains(&user_id.0) {
+        Ok(Permission::ChatAdmin)
+    } else {
+        Ok(Permission::User)
+    }
+}
+
+/// Return true if the user has the required permission in the chat.
+pub async fn has_permission(
+    bot: &Bot,
+    data: &AppData,
+    chat: &Chat,
+    user_id: UserId,
+    required: Permission,
+) -> anyhow::Result<bool> {
+    if required == Permission::User {
+        return Ok(true);
+    }
+    Ok(role_of(bot, data, chat, user_id).await? >= required)
+}
+
+#[test]
+fn test_static_role() {
+    let config = PermissionConfig {
+        owner: Some(1),
+        admins: vec![2],
+    };
+    assert_eq!(static_role(&config, &[3], 1), Permission::Owner);
+    assert_eq!(static_role(&config, &[3], 2), Permission::GlobalAdmin);
+    assert_eq!(static_role(&config, &[3], 3), Permission::GlobalAdmin);
+    assert_eq!(static_role(&config, &[3], 4), Permission::User);
+    assert!(Permission::GlobalAdmin > Permission::ChatAdmin);
+}
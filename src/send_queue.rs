@@ -0,0 +1,308 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    future::{Future, IntoFuture},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use teloxide::{types::ChatId, RequestError};
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::Instant,
+};
+
+/// Telegram allows about 30 messages per second for each bot
+const GLOBAL_INTERVAL: Duration = Duration::from_millis(35);
+/// About one message per second in private chat
+const PRIVATE_CHAT_INTERVAL: Duration = Duration::from_secs(1);
+/// 20 messages per minute in group
+const GROUP_CHAT_INTERVAL: Duration = Duration::from_secs(3);
+/// Give up after Telegram asks us to retry for this many times
+const MAX_ATTEMPTS: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    /// Reply to the user, sent before any background job
+    Interactive,
+    /// Notification from the event watcher
+    Background,
+}
+
+/// Run the request once, and return the time to wait if Telegram asks for retry. The argument
+/// tells whether this is the last attempt, so the error should be delivered instead.
+type JobFuture = Pin<Box<dyn Future<Output = Option<Duration>> + Send>>;
+
+struct Job {
+    chat_id: ChatId,
+    priority: Priority,
+    attempts: u32,
+    run: Box<dyn FnMut(bool) -> JobFuture + Send>,
+}
+
+/// Serialize all the outgoing API calls, so that the bot doesn't hit the Telegram flood limit
+/// when notifying lots of chats.
+pub struct SendQueue {
+    tx: mpsc::UnboundedSender<Job>,
+    pending: Arc<AtomicUsize>,
+}
+
+impl SendQueue {
+    /// Create the queue and spawn its worker, must be called inside the tokio runtime.
+    pub fn spawn() -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let pending = Arc::new(AtomicUsize::new(0));
+        tokio::spawn(Worker::new(rx, Arc::clone(&pending)).run());
+        Self { tx, pending }
+    }
+
+    /// Requests queued or being sent.
+    pub fn pending(&self) -> usize {
+        self.pending.load(Ordering::Relaxed)
+    }
+
+    /// Queue the request and wait for its response. The `request` might be called more than once
+    /// when Telegram asks to retry later.
+    ///
+    /// ```ignore
+    /// data.send_queue
+    ///     .submit(chat_id, Priority::Background, move || bot.send_message(chat_id, text.clone()))
+    ///     .await?;
+    /// ```
+    pub async fn submit<T, F, Req>(
+        &self,
+        chat_id: ChatId,
+        priority: Priority,
+        request: F,
+    ) -> anyhow::Result<T>
+    where
+        T: Send + 'static,
+        F: Fn() -> Req + Send + 'static,
+        Req: IntoFuture<Output = Result<T, RequestError>>,
+        Req::IntoFuture: Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        let tx = Arc::new(Mutex::new(Some(tx)));
+        let run = move |last_attempt: bool| -> JobFuture {
+            let response = request().into_future();
+            let tx = Arc::clone(&tx);
+            Box::pin(async move {
+                let result = response.await;
+                if let Err(RequestError::RetryAfter(secs)) = &result {
+                    if !last_attempt {
+                        return Some(secs.duration());
+                    }
+                }
+                if let Some(tx) = tx.lock().unwrap().take() {
+                    // The caller might not care about the response anymore
+                    tx.send(result).ok();
+                }
+                None
+            })
+        };
+
+        // Count it before the worker can finish it
+        self.pending.fetch_add(1, Ordering::Relaxed);
+        self.tx
+            .send(Job {
+                chat_id,
+                priority,
+                attempts: 0,
+                run: Box::new(run),
+            })
+            .map_err(|_| {
+                self.pending.fetch_sub(1, Ordering::Relaxed);
+                anyhow::anyhow!("send queue is closed")
+            })?;
+        let response = rx
+            .await
+            .map_err(|_| anyhow::anyhow!("send queue dropped the request"))?;
+        Ok(response?)
+    }
+}
+
+struct Worker {
+    rx: mpsc::UnboundedReceiver<Job>,
+    interactive: VecDeque<Job>,
+    background: VecDeque<Job>,
+    last_sent: HashMap<ChatId, Instant>,
+    next_global: Instant,
+    pending: Arc<AtomicUsize>,
+}
+
+impl Worker {
+    fn new(rx: mpsc::UnboundedReceiver<Job>, pending: Arc<AtomicUsize>) -> Self {
+        Self {
+            rx,
+            interactive: VecDeque::new(),
+            background: VecDeque::new(),
+            last_sent: HashMap::new(),
+            next_global: Instant::now(),
+            pending,
+        }
+    }
+
+    fn push(&mut self, job: Job) {
+        match job.priority {
+            Priority::Interactive => self.interactive.push_back(job),
+            Priority::Background => self.background.push_back(job),
+        }
+    }
+
+    fn chat_ready_at(&self, chat_id: ChatId, now: Instant) -> Instant {
+        let interval = if chat_id.is_user() {
+            PRIVATE_CHAT_INTERVAL
+        } else {
+            GROUP_CHAT_INTERVAL
+        };
+        self.last_sent
+            .get(&chat_id)
+            .map_or(now, |last| (*last + interval).max(now))
+    }
+
+    /// Take the first job that can be sent now, interactive job first. Return the time when the
+    /// next job is ready if none of them are, or `None` if the queue is empty.
+    fn pop_ready(&mut self, now: Instant) -> Result<Job, Option<Instant>> {
+        if self.interactive.is_empty() && self.background.is_empty() {
+            return Err(None);
+        }
+        if now < self.next_global {
+            return Err(Some(self.next_global));
+        }
+
+        let mut wake = None::<Instant>;
+        for priority in [Priority::Interactive, Priority::Background] {
+            let queue = match priority {
+                Priority::Interactive => &self.interactive,
+                Priority::Background => &self.background,
+            };
+            let mut found = None;
+            for (index, job) in queue.iter().enumerate() {
+                let ready_at = self.chat_ready_at(job.chat_id, now);
+                if ready_at <= now {
+                    found = Some(index);
+                    break;
+                }
+                wake = Some(wake.map_or(ready_at, |wake| wake.min(ready_at)));
+            }
+            if let Some(index) = found {
+                let queue = match priority {
+                    Priority::Interactive => &mut self.interactive,
+                    Priority::Background => &mut self.background,
+                };
+                return Ok(queue.remove(index).unwrap());
+            }
+        }
+
+        Err(wake)
+    }
+
+    async fn execute(&mut self, mut job: Job) {
+        let now = Instant::now();
+        self.next_global = now + GLOBAL_INTERVAL;
+        self.last_sent.insert(job.chat_id, now);
+        if self.last_sent.len() > 1024 {
+            self.last_sent
+                .retain(|_, last| now.duration_since(*last) < GROUP_CHAT_INTERVAL);
+        }
+
+        job.attempts += 1;
+        let Some(wait) = (job.run)(job.attempts >= MAX_ATTEMPTS).await else {
+            self.pending.fetch_sub(1, Ordering::Relaxed);
+            return;
+        };
+
+        tracing::warn!(
+            "hit flood limit when sending to chat {}, retry after {}s",
+            job.chat_id,
+            wait.as_secs()
+        );
+        // The flood limit applies to the whole bot, so pause everything
+        self.next_global = Instant::now() + wait;
+        match job.priority {
+            Priority::Interactive => self.interactive.push_front(job),
+            Priority::Background => self.background.push_front(job),
+        }
+    }
+
+    async fn run(mut self) {
+        let mut closed = false;
+        loop {
+            while let Ok(job) = self.rx.try_recv() {
+                self.push(job);
+            }
+
+            match self.pop_ready(Instant::now()) {
+                Ok(job) => self.execute(job).await,
+                Err(None) if closed => return,
+                Err(None) => match self.rx.recv().await {
+                    Some(job) => self.push(job),
+                    None => return,
+                },
+                Err(Some(wake)) => {
+                    tokio::select! {
+                        job = self.rx.recv(), if !closed => match job {
+                            Some(job) => self.push(job),
+                            None => closed = true,
+                        },
+                        _ = tokio::time::sleep_until(wake) => (),
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_pop_ready() {
+    let (_tx, rx) = mpsc::unbounded_channel();
+    let mut worker = Worker::new(rx, Arc::default());
+    let job = |chat_id, priority| Job {
+        chat_id: ChatId(chat_id),
+        priority,
+        attempts: 0,
+        run: Box::new(|_| Box::pin(async { None })),
+    };
+    let now = Instant::now();
+
+    worker.push(job(-1001, Priority::Background));
+    worker.push(job(-1001, Priority::Interactive));
+    worker.push(job(1, Priority::Interactive));
+
+    // Interactive job is sent first
+    let first = worker.pop_ready(now).unwrap();
+    assert_eq!(
+        (first.chat_id, first.priority),
+        (ChatId(-1001), Priority::Interactive)
+    );
+
+    // The group just received a message, so the private chat goes first
+    worker.last_sent.insert(ChatId(-1001), now);
+    let second = worker.pop_ready(now).unwrap();
+    assert_eq!(second.chat_id, ChatId(1));
+
+    // The background job should wait for the group interval
+    assert_eq!(
+        worker.pop_ready(now).err(),
+        Some(Some(now + GROUP_CHAT_INTERVAL))
+    );
+    assert!(worker.pop_ready(now + GROUP_CHAT_INTERVAL).is_ok());
+    assert!(matches!(worker.pop_ready(now), Err(None)));
+}
+
+#[tokio::test]
+async fn test_pending() {
+    let queue = SendQueue::spawn();
+    assert_eq!(queue.pending(), 0);
+    let sent = queue
+        .submit(ChatId(1), Priority::Interactive, || {
+            std::future::ready(Ok::<_, RequestError>(42))
+        })
+        .await
+        .unwrap();
+    assert_eq!(sent, 42);
+    assert_eq!(queue.pending(), 0);
+}
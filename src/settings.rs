@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+
+use redis::Commands;
+
+use crate::{app::AppData, module::ModuleRegistry};
+
+/// A module that can be turned on or off for each chat by the chat admin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModuleInfo {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Off until the chat admin turns it on, like the modules keeping the chat history
+    pub opt_in: bool,
+}
+
+/// A setting of the module besides on and off. The module keeps the value where it likes, and
+/// the settings menu reads and writes it by `get` and `set`.
+#[derive(Clone, Copy)]
+pub struct SettingSchema {
+    /// Unique in the module
+    pub key: &'static str,
+    pub description: &'static str,
+    /// The values to choose from, the menu switches to the next one when it is pressed
+    pub choices: &'static [&'static str],
+    pub get: fn(&AppData, i64) -> anyhow::Result<String>,
+    pub set: fn(&AppData, i64, &str) -> anyhow::Result<()>,
+}
+
+/// The settings declared by the modules in the [`ModuleRegistry`], with the module names.
+pub fn module_settings() -> Vec<(&'static str, SettingSchema)> {
+    ModuleRegistry::try_global().map_or_else(Vec::new, ModuleRegistry::settings)
+}
+
+fn next_choice<'a>(choices: &[&'a str], current: &str) -> &'a str {
+    let next = choices
+        .iter()
+        .position(|choice| *choice == current)
+        .map_or(0, |index| index + 1);
+    choices[next % choices.len()]
+}
+
+/// Switch the setting of the module to the next choice, and return the new value.
+pub fn cycle_setting(
+    data: &AppData,
+    chat_id: i64,
+    module: &str,
+    key: &str,
+) -> anyhow::Result<&'static str> {
+    let Some((_, setting)) = module_settings()
+        .into_iter()
+        .find(|(name, setting)| *name == module && setting.key == key)
+    else {
+        anyhow::bail!("unknown setting {module}.{key}");
+    };
+    let current = (setting.get)(data, chat_id)?;
+    let next = next_choice(setting.choices, &current);
+    (setting.set)(data, chat_id, next)?;
+    Ok(next)
+}
+
+/// The toggleable modules declared by the [`ModuleRegistry`].
+pub fn modules() -> Vec<ModuleInfo> {
+    ModuleRegistry::try_global().map_or_else(Vec::new, ModuleRegistry::toggleable)
+}
+
+/// Find the module by name.
+pub fn get_module(name: &str) -> Option<ModuleInfo> {
+    modules().into_iter().find(|module| module.name == name)
+}
+
+fn settings_key(chat_id: i64) -> String {
+    format!("CHAT_SETTINGS:{chat_id}")
+}
+
+/// Whether the module is turned off in every chat by `disabled_modules` in the latest config.
+pub fn globally_disabled(module: &str) -> bool {
+    crate::config::Config::get_global_config().module_disabled(module)
+}
+
+fn enabled_by_default(module: &str) -> bool {
+    get_module(module).is_none_or(|module| !module.opt_in)
+}
+
+/// Return whether the module is enabled in the chat. Every module except the opt-in ones is
+/// enabled by default, unless it is turned off by `disabled_modules` in the config.
+pub fn is_enabled(data: &AppData, chat_id: i64, module: &str) -> anyhow::Result<bool> {
+    if globally_disabled(module) {
+        return Ok(false);
+    }
+    let enabled: Option<bool> = data.cacher.get_conn().hget(settings_key(chat_id), module)?;
+    Ok(enabled.unwrap_or_else(|| enabled_by_default(module)))
+}
+
+pub fn set_enabled(
+    data: &AppData,
+    chat_id: i64,
+    module: &str,
+    enabled: bool,
+) -> anyhow::Result<()> {
+    if get_module(module).is_none() {
+        anyhow::bail!("unknown module {module}");
+    }
+    let () = data
+        .cacher
+        .get_conn()
+        .hset(settings_key(chat_id), module, enabled)?;
+    Ok(())
+}
+
+/// Flip the module status, and return the new status.
+pub fn toggle(data: &AppData, chat_id: i64, module: &str) -> anyhow::Result<bool> {
+    if globally_disabled(module) {
+        anyhow::bail!("module {module} is disabled by the bot owner");
+    }
+    let enabled = !is_enabled(data, chat_id, module)?;
+    set_enabled(data, chat_id, module, enabled)?;
+    Ok(enabled)
+}
+
+/// Get the status of all the modules in the chat.
+pub fn list(data: &AppData, chat_id: i64) -> anyhow::Result<Vec<(ModuleInfo, bool)>> {
+    let stored: HashMap<String, bool> = data.cacher.get_conn().hgetall(settings_key(chat_id))?;
+    let flags = merge_flags(modules(), &stored)
+        .into_iter()
+        .map(|(module, enabled)| (module, enabled && !globally_disabled(module.name)))
+        .collect();
+    Ok(flags)
+}
+
+fn merge_flags(
+    modules: Vec<ModuleInfo>,
+    stored: &HashMap<String, bool>,
+) -> Vec<(ModuleInfo, bool)> {
+    modules
+        .into_iter()
+        .map(|module| {
+            let enabled = stored.get(module.name).copied().unwrap_or(!module.opt_in);
+            (module, enabled)
+        })
+        .collect()
+}
+
+#[test]
+fn test_next_choice() {
+    let choices = ["report", "delete", "mute"];
+    assert_eq!(next_choice(&choices, "report"), "delete");
+    assert_eq!(next_choice(&choices, "mute"), "report");
+    assert_eq!(next_choice(&choices, "removed"), "report");
+}
+
+#[test]
+fn test_merge_flags() {
+    let stored = HashMap::from([
+        ("weather".to_string(), false),
+        ("removed_module".to_string(), false),
+    ]);
+    let modules = ["weather", "karma", "archive"].map(|name| ModuleInfo {
+        name,
+        description: name,
+        opt_in: name == "archive",
+    });
+    let flags = merge_flags(modules.to_vec(), &stored);
+    assert_eq!(flags.len(), modules.len());
+    assert!(flags
+        .iter()
+        .all(|(module, enabled)| *enabled == (module.name == "karma")));
+}
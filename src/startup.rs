@@ -0,0 +1,171 @@
+//! Checks of the dependencies when the bot starts. The transient failures, like Redis still
+//! booting in the same compose file, are retried with backoff instead of crashing the bot, while
+//! the permanent ones like a revoked token fail at once.
+
+use std::{future::Future, time::Duration};
+
+use teloxide::{prelude::*, types::Me, RequestError};
+
+use crate::{cache::Cacher, config::Config, event, module::ModuleRegistry};
+
+/// Tries of each dependency before giving up, about two minutes with the backoff
+const MAX_ATTEMPTS: u32 = 8;
+const FIRST_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// Give up one try of Redis if it doesn't respond in time
+const REDIS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Return false for the errors that won't go away by retrying, like the Telegram API rejecting
+/// the token or Redis rejecting the password.
+pub fn is_transient(err: &anyhow::Error) -> bool {
+    if let Some(err) = err.downcast_ref::<RequestError>() {
+        return matches!(
+            err,
+            RequestError::Network(_) | RequestError::RetryAfter(_) | RequestError::Io(_)
+        );
+    }
+    if let Some(err) = err.downcast_ref::<redis::RedisError>() {
+        return err.is_io_error()
+            || err.is_timeout()
+            || err.is_connection_refusal()
+            || err.is_connection_dropped();
+    }
+    true
+}
+
+/// Run the check until it succeeds, retrying the transient failures with backoff.
+pub async fn retry<T, F, Fut>(name: &str, check: F) -> anyhow::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = anyhow::Result<T>>,
+{
+    retry_with(name, MAX_ATTEMPTS, FIRST_BACKOFF, check).await
+}
+
+async fn retry_with<T, F, Fut>(
+    name: &str,
+    max_attempts: u32,
+    mut backoff: Duration,
+    mut check: F,
+) -> anyhow::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = anyhow::Result<T>>,
+{
+    let mut attempt = 1;
+    loop {
+        match check().await {
+            Ok(value) => return Ok(value),
+            Err(err) if attempt < max_attempts && is_transient(&err) => {
+                tracing::warn!(
+                    "{name} is not ready ({attempt}/{max_attempts}), retry in {backoff:?}: {err:#}"
+                );
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                attempt += 1;
+            }
+            Err(err) => return Err(err.context(format!("fail to reach {name}"))),
+        }
+    }
+}
+
+/// Wait for Redis to respond.
+pub async fn redis(cacher: &Cacher) -> anyhow::Result<()> {
+    // The redis client is blocking
+    retry("redis", || async {
+        tokio::task::block_in_place(|| cacher.ping(REDIS_TIMEOUT))
+    })
+    .await
+}
+
+/// Wait for the Telegram API to accept the token, and return who the bot is.
+pub async fn telegram(bot: &Bot) -> anyhow::Result<Me> {
+    retry("telegram", || async { Ok(bot.get_me().await?) }).await
+}
+
+/// Describe what the bot runs with, logged once it is started.
+pub fn summary(me: &Me, config: &Config, dry_run: bool) -> String {
+    let mut bots = config.bots.keys().map(String::as_str).collect::<Vec<_>>();
+    bots.sort_unstable();
+    let (mut enabled, mut disabled) = (Vec::new(), Vec::new());
+    for module in ModuleRegistry::global().modules() {
+        if config
+            .disabled_modules
+            .iter()
+            .any(|name| name == module.name())
+        {
+            disabled.push(module.name());
+        } else {
+            enabled.push(module.name());
+        }
+    }
+    let updates = match &config.webhook {
+        Some(webhook) => format!("webhook {}", webhook.url),
+        None => "long polling".to_string(),
+    };
+    let mut bot_api = config.bot_api.url.clone();
+    if config.bot_api.test_environment {
+        bot_api.push_str(" (test environment)");
+    }
+    if config.bot_api.local_mode {
+        bot_api.push_str(" (local mode)");
+    }
+    let list = |names: &[&str]| match names {
+        [] => "none".to_string(),
+        names => names.join(", "),
+    };
+
+    let mut lines = vec![
+        format!("started as @{}", me.username()),
+        format!("  updates: {updates}"),
+        format!("  bot api: {bot_api}"),
+        format!("  extra bots: {}", list(&bots)),
+        format!("  modules: {}", list(&enabled)),
+        format!("  disabled modules: {}", list(&disabled)),
+        format!(
+            "  watchers: {}",
+            list(
+                &event::started_watchers()
+                    .iter()
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()
+            )
+        ),
+    ];
+    if dry_run {
+        lines.push("  dry run: the messages are logged instead of sent".to_string());
+    }
+    lines.join("\n")
+}
+
+#[tokio::test]
+async fn test_retry() {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    let tries = AtomicU32::new(0);
+    let flaky = || async {
+        match tries.fetch_add(1, Ordering::Relaxed) {
+            0 | 1 => anyhow::bail!("connection refused"),
+            tried => Ok(tried),
+        }
+    };
+    assert_eq!(
+        retry_with("flaky", 3, Duration::ZERO, flaky).await.unwrap(),
+        2
+    );
+
+    tries.store(0, Ordering::Relaxed);
+    assert!(retry_with("flaky", 2, Duration::ZERO, flaky).await.is_err());
+    assert_eq!(tries.load(Ordering::Relaxed), 2);
+
+    tries.store(0, Ordering::Relaxed);
+    let rejected = || async {
+        tries.fetch_add(1, Ordering::Relaxed);
+        Err::<(), _>(RequestError::Api(teloxide::ApiError::InvalidToken).into())
+    };
+    let err = retry_with("telegram", 5, Duration::ZERO, rejected)
+        .await
+        .unwrap_err();
+    assert_eq!(tries.load(Ordering::Relaxed), 1);
+    assert_eq!(err.to_string(), "fail to reach telegram");
+}
@@ -0,0 +1,230 @@
+use std::{collections::HashMap, str::FromStr, time::Duration};
+
+use anyhow::Context;
+use redis::Commands;
+use sqlx::{
+    any::{AnyConnectOptions, AnyPoolOptions},
+    AnyPool, ConnectOptions,
+};
+
+use crate::{cache::Cacher, config::DatabaseConfig};
+
+/// Durable storage for the data that should survive a Redis flush: the karma, the admins, the
+/// reaction rules and the chat archive. The rest, like the spam filter and the counters, is
+/// still kept in Redis with the caches and queues.
+#[derive(Clone)]
+pub struct Storage(AnyPool);
+
+impl Storage {
+    /// Connect to the database and run the pending migrations.
+    pub async fn connect(config: &DatabaseConfig) -> anyhow::Result<Self> {
+        sqlx::any::install_default_drivers();
+        let options = AnyConnectOptions::from_str(&config.url)
+            .with_context(|| "invalid database url")?
+            .log_statements(tracing::log::LevelFilter::Debug);
+        let pool = AnyPoolOptions::new()
+            .max_connections(config.max_connections)
+            .acquire_timeout(Duration::from_secs(10))
+            .connect_with(options)
+            .await
+            .with_context(|| "fail to connect to the database")?;
+        sqlx::migrate!()
+            .run(&pool)
+            .await
+            .with_context(|| "fail to run the database migrations")?;
+        Ok(Self(pool))
+    }
+
+    pub fn pool(&self) -> &AnyPool {
+        &self.0
+    }
+
+    /// Check the database responds.
+    pub async fn ping(&self) -> anyhow::Result<()> {
+        sqlx::query("SELECT 1").execute(&self.0).await?;
+        Ok(())
+    }
+
+    /// Move the karma, the reaction rules and the archived messages of the group to its new id
+    /// after the upgrade to a supergroup, and return the rows moved. The rows the new chat
+    /// already has are kept. The supergroup numbers its messages again, so an archived message
+    /// whose id is taken by a new one is dropped.
+    pub async fn migrate_chat(&self, from: i64, to: i64) -> anyhow::Result<u64> {
+        let mut tx = self.0.begin().await?;
+        let mut moved = 0;
+        for (table, key) in [
+            ("karma", "user_id"),
+            ("reaction_rules", "emoji"),
+            ("archived_messages", "message_id"),
+        ] {
+            let updated = sqlx::query(&format!(
+                "UPDATE {table} SET chat_id = $2 WHERE chat_id = $1 AND {key} NOT IN \
+                 (SELECT {key} FROM {table} WHERE chat_id = $2)"
+            ))
+            .bind(from)
+            .bind(to)
+            .execute(&mut *tx)
+            .await?;
+            moved += updated.rows_affected();
+            sqlx::query(&format!("DELETE FROM {table} WHERE chat_id = $1"))
+                .bind(from)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(moved)
+    }
+
+    /// Dump the durable tables as JSON, for backing up or moving to another database.
+    pub async fn export(&self) -> anyhow::Result<serde_json::Value> {
+        let karma: Vec<(i64, i64, String, i64)> = sqlx::query_as(
+            "SELECT chat_id, user_id, username, karma FROM karma ORDER BY chat_id, karma DESC",
+        )
+        .fetch_all(&self.0)
+        .await?;
+        let admins: Vec<(i64, i64)> =
+            sqlx::query_as("SELECT user_id, added_at FROM global_admins ORDER BY user_id")
+                .fetch_all(&self.0)
+                .await?;
+
+        let karma = karma
+            .into_iter()
+            .map(|(chat_id, user_id, username, karma)| {
+                serde_json::json!({
+                    "chat_id": chat_id,
+                    "user_id": user_id,
+                    "username": username,
+                    "karma": karma,
+                })
+            })
+            .collect::<Vec<_>>();
+        let admins = admins
+            .into_iter()
+            .map(|(user_id, added_at)| serde_json::json!({ "user_id": user_id, "added_at": added_at }))
+            .collect::<Vec<_>>();
+        Ok(serde_json::json!({ "karma": karma, "global_admins": admins }))
+    }
+
+    /// Copy the karma and admins that were kept in Redis by the older version. At startup it
+    /// only runs when the tables are still empty, so running it on every start is fine. With
+    /// `merge`, like `redis migrate`, the rows missing in the tables are copied and the existing
+    /// ones are kept. Return the number of rows copied.
+    pub async fn import_from_redis(&self, cacher: &Cacher, merge: bool) -> anyhow::Result<u64> {
+        if !merge {
+            let (karma, admins): (i64, i64) = sqlx::query_as(
+                "SELECT (SELECT COUNT(*) FROM karma), (SELECT COUNT(*) FROM global_admins)",
+            )
+            .fetch_one(&self.0)
+            .await?;
+            if karma > 0 || admins > 0 {
+                return Ok(0);
+            }
+        }
+
+        // The redis client is blocking
+        let cacher = cacher.clone();
+        let (boards, admins) =
+            tokio::task::spawn_blocking(move || read_legacy_redis(&cacher)).await??;
+        if boards.is_empty() && admins.is_empty() {
+            return Ok(0);
+        }
+
+        let mut imported = 0;
+        let mut tx = self.0.begin().await?;
+        for (chat_id, users) in &boards {
+            for (user_id, username, karma) in users {
+                imported += sqlx::query(
+                    "INSERT INTO karma (chat_id, user_id, username, karma) VALUES ($1, $2, $3, $4)
+                    ON CONFLICT (chat_id, user_id) DO NOTHING",
+                )
+                .bind(chat_id)
+                .bind(*user_id as i64)
+                .bind(username)
+                .bind(karma)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+            }
+        }
+        let now = chrono::Utc::now().timestamp();
+        for user_id in &admins {
+            imported += sqlx::query(
+                "INSERT INTO global_admins (user_id, added_at) VALUES ($1, $2)
+                ON CONFLICT (user_id) DO NOTHING",
+            )
+            .bind(*user_id as i64)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+        tx.commit().await?;
+
+        tracing::info!(
+            "imported {imported} rows of karma in {} chats and {} global admins from Redis",
+            boards.len(),
+            admins.len()
+        );
+        Ok(imported)
+    }
+}
+
+type LegacyBoards = HashMap<i64, Vec<(u64, String, i64)>>;
+
+fn read_legacy_redis(cacher: &Cacher) -> anyhow::Result<(LegacyBoards, Vec<u64>)> {
+    let mut conn = cacher.get_conn();
+    let mut boards = LegacyBoards::new();
+    let keys: Vec<String> = conn.keys("KARMA_LEADERBOARD:*")?;
+    for key in keys {
+        let Some(chat_id) = key
+            .strip_prefix("KARMA_LEADERBOARD:")
+            .and_then(|id| id.parse::<i64>().ok())
+        else {
+            continue;
+        };
+        let scores: Vec<(u64, i64)> = conn.zrange_withscores(&key, 0, -1)?;
+        let names: HashMap<u64, String> = conn.hgetall(format!("KARMA_USERNAME:{chat_id}"))?;
+        let users = scores
+            .into_iter()
+            .map(|(user_id, karma)| {
+                let name = names
+                    .get(&user_id)
+                    .cloned()
+                    .unwrap_or_else(|| user_id.to_string());
+                (user_id, name, karma)
+            })
+            .collect();
+        boards.insert(chat_id, users);
+    }
+    let admins = conn.smembers("GLOBAL_ADMINS")?;
+    Ok((boards, admins))
+}
+
+#[tokio::test]
+async fn test_import_from_redis() {
+    let redis = crate::testkit::MemoryRedis::start();
+    let cacher = redis.cacher();
+    let mut conn = cacher.get_conn();
+    let () = conn.zadd("KARMA_LEADERBOARD:-100", 42, 3).unwrap();
+    let () = conn.hset("KARMA_USERNAME:-100", 42, "alice").unwrap();
+    let () = conn.sadd("GLOBAL_ADMINS", 7).unwrap();
+
+    let storage = crate::testkit::memory_storage().await;
+    assert_eq!(storage.import_from_redis(&cacher, false).await.unwrap(), 2);
+    // The tables are not empty anymore
+    let () = conn.zadd("KARMA_LEADERBOARD:-100", 43, 1).unwrap();
+    assert_eq!(storage.import_from_redis(&cacher, false).await.unwrap(), 0);
+
+    // Only the missing rows are copied, the karma given since the last import is kept
+    sqlx::query("UPDATE karma SET karma = 5 WHERE user_id = 42")
+        .execute(&storage.0)
+        .await
+        .unwrap();
+    assert_eq!(storage.import_from_redis(&cacher, true).await.unwrap(), 1);
+    let karma: Vec<(i64, i64)> =
+        sqlx::query_as("SELECT user_id, karma FROM karma ORDER BY user_id")
+            .fetch_all(&storage.0)
+            .await
+            .unwrap();
+    assert_eq!(karma, [(42, 5), (43, 1)]);
+}
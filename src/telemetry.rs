@@ -0,0 +1,70 @@
+use sentry::integrations::tracing::EventFilter;
+use teloxide::types::{ChatId, Update};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::config::SentryConfig;
+
+/// Start the Sentry client, errors are only sent while the returned guard is alive.
+pub fn init(config: &SentryConfig) -> sentry::ClientInitGuard {
+    sentry::init((
+        config.dsn.as_str(),
+        sentry::ClientOptions {
+            release: sentry::release_name!(),
+            environment: config.environment.clone().map(Into::into),
+            traces_sample_rate: config.traces_sample_rate,
+            ..Default::default()
+        },
+    ))
+}
+
+/// Turn the tracing spans into Sentry transactions, and the logs into breadcrumbs. Errors are
+/// captured explicitly by [`capture_handler_error`] and [`capture_watcher_error`], so the logged
+/// error is not sent twice.
+pub fn layer<S>() -> sentry::integrations::tracing::SentryLayer<S>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+{
+    sentry::integrations::tracing::layer().event_filter(|metadata| match *metadata.level() {
+        tracing::Level::TRACE => EventFilter::Ignore,
+        _ => EventFilter::Breadcrumb,
+    })
+}
+
+/// Capture the error returned by the update handler with the chat and user of the update.
+pub fn capture_handler_error(command: &str, update: &Update, err: &anyhow::Error) {
+    sentry::with_scope(
+        |scope| {
+            scope.set_tag("command", command);
+            scope.set_tag("update_id", update.id.0);
+            if let Some(chat) = update.chat() {
+                scope.set_tag("chat_id", chat.id);
+            }
+            if let Some(user) = update.from() {
+                scope.set_user(Some(sentry::User {
+                    id: Some(user.id.to_string()),
+                    username: user.username.clone(),
+                    ..Default::default()
+                }));
+            }
+        },
+        || sentry::integrations::anyhow::capture_anyhow(err),
+    );
+}
+
+/// Capture the error of the album, which is handled out of the update handler.
+pub fn capture_album_error(chat_id: ChatId, err: &anyhow::Error) {
+    sentry::with_scope(
+        |scope| {
+            scope.set_tag("command", "album");
+            scope.set_tag("chat_id", chat_id);
+        },
+        || sentry::integrations::anyhow::capture_anyhow(err),
+    );
+}
+
+pub fn capture_watcher_error(watcher: &str, err: &anyhow::Error) {
+    sentry::with_scope(
+        |scope| scope.set_tag("watcher", watcher),
+        || sentry::integrations::anyhow::capture_anyhow(err),
+    );
+}
@@ -0,0 +1,186 @@
+//! Templates of the notifications sent by the watchers, rendered by minijinja.
+//!
+//! Every template has a default one for each language here, and the operator overrides it with
+//! the file of the same name in [`Config::template_dir`], like `templates/bilibili_live.html` for
+//! all the languages or `templates/zh-hans/bilibili_live.html` for one of them. The overrides are
+//! read once at the first render, restart the bot to apply the changes.
+//!
+//! The extension of the template decides how the variables are escaped: `.html` for the Telegram
+//! HTML, `.md` for the Telegram MarkdownV2, and nothing for the others. Skip the escaping of a
+//! variable with `{{ var | safe }}`.
+
+use std::{fs, path::Path, sync::OnceLock};
+
+use minijinja::{AutoEscape, Environment, Error, Output, State, Value};
+use serde::Serialize;
+
+use crate::{config::Config, i18n};
+
+const MARKDOWN: &str = "markdown";
+
+/// Language, name and source of the default templates, with the variables each of them takes
+const DEFAULTS: &[(&str, &str, &str)] = &[
+    // username, uid, room_id, title, area, online, space_url, room_url
+    (
+        "en",
+        "bilibili_live.html",
+        "<a href=\"{{ space_url }}\">{{ username }}</a> is live! {{ online }} watching now\n\
+         Live: <a href=\"{{ room_url }}\">{{ title }}</a>\n\
+         Area: #{{ area }}",
+    ),
+    (
+        "zh-hans",
+        "bilibili_live.html",
+        "<a href=\"{{ space_url }}\">{{ username }}</a> 开播了！已有 {{ online }} 人正在观看\n\
+         直播: <a href=\"{{ room_url }}\">{{ title }}</a>\n\
+         分区: #{{ area }}",
+    ),
+    // username, uid, room_id, title, area, space_url, room_url
+    ("en", "bilibili_offline.html", "{{ username }} is offline."),
+    (
+        "zh-hans",
+        "bilibili_offline.html",
+        "{{ username }} 下播了！",
+    ),
+    // name, date, is_off_day, line
+    (
+        "en",
+        "holiday_reminder.txt",
+        "{% if is_off_day %}Tomorrow is {{ name }}, enjoy the day off!\
+         {% else %}Tomorrow is a workday, remember to set the alarm!{% endif %}\n{{ line }}",
+    ),
+    (
+        "zh-hans",
+        "holiday_reminder.txt",
+        "{% if is_off_day %}明天是 {{ name }}，好好休息吧！\
+         {% else %}明天要补班，记得定闹钟！{% endif %}\n{{ line }}",
+    ),
+];
+
+static TEMPLATES: OnceLock<Environment<'static>> = OnceLock::new();
+
+/// Render the template in the language with the variables, like
+/// `minijinja::context! { name => "foo" }`.
+pub fn render(name: &str, lang: &str, vars: impl Serialize) -> anyhow::Result<String> {
+    let templates = TEMPLATES.get_or_init(|| {
+        let dir = Config::template_dir().ok();
+        load(dir.as_deref())
+    });
+    let template = templates
+        .get_template(&format!("{lang}/{name}"))
+        .or_else(|_| templates.get_template(&format!("{}/{name}", i18n::DEFAULT_LANG)))?;
+    Ok(template.render(vars)?)
+}
+
+/// Load the overrides in the template directory, the broken ones are reported and replaced by
+/// the default templates.
+fn load(dir: Option<&Path>) -> Environment<'static> {
+    let mut env = environment();
+    for (lang, name, default) in DEFAULTS {
+        let key = format!("{lang}/{name}");
+        let path = dir.and_then(|dir| {
+            [dir.join(lang).join(name), dir.join(name)]
+                .into_iter()
+                .find(|path| path.exists())
+        });
+        if let Some(path) = path {
+            let loaded = fs::read_to_string(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|source| Ok(env.add_template_owned(key.clone(), source)?));
+            match loaded {
+                Ok(()) => {
+                    tracing::info!("use the template {path:?}");
+                    continue;
+                }
+                Err(err) => tracing::error!("fail to load the template {path:?}: {err}"),
+            }
+        }
+        env.add_template_owned(key, *default)
+            .unwrap_or_else(|err| panic!("invalid default template {lang}/{name}: {err}"));
+    }
+    env
+}
+
+fn environment() -> Environment<'static> {
+    let mut env = Environment::new();
+    env.set_auto_escape_callback(|name| match name.rsplit_once('.') {
+        Some((_, "html")) => AutoEscape::Html,
+        Some((_, "md")) => AutoEscape::Custom(MARKDOWN),
+        _ => AutoEscape::None,
+    });
+    env.set_formatter(format_value);
+    env
+}
+
+/// Escape the variables for Telegram, which only knows a few of the HTML entities.
+fn format_value(out: &mut Output, state: &State, value: &Value) -> Result<(), Error> {
+    let escape = match state.auto_escape() {
+        AutoEscape::Html => escape_html,
+        AutoEscape::Custom(MARKDOWN) => teloxide::utils::markdown::escape,
+        _ => return minijinja::escape_formatter(out, state, value),
+    };
+    if value.is_safe() || value.is_none() || value.is_undefined() {
+        return minijinja::escape_formatter(out, state, value);
+    }
+    out.write_str(&escape(&value.to_string()))?;
+    Ok(())
+}
+
+fn escape_html(s: &str) -> String {
+    teloxide::utils::html::escape(s).replace('"', "&quot;")
+}
+
+#[test]
+fn test_render() {
+    let mut env = environment();
+    env.add_template(
+        "test.html",
+        "<b>{{ name }}</b> {{ raw | safe }} {{ count }}",
+    )
+    .unwrap();
+    env.add_template("test.md", "*{{ name }}*").unwrap();
+    env.add_template("test.txt", "{{ name }}").unwrap();
+    let vars = minijinja::context! { name => "<a & \"b\">", raw => "<i>ok</i>", count => 3 };
+
+    let render = |name| env.get_template(name).unwrap().render(&vars).unwrap();
+    assert_eq!(
+        render("test.html"),
+        "<b>&lt;a &amp; &quot;b&quot;&gt;</b> <i>ok</i> 3"
+    );
+    assert_eq!(render("test.md"), "*<a & \"b\"\\>*");
+    assert_eq!(render("test.txt"), "<a & \"b\">");
+}
+
+#[test]
+fn test_default_templates() {
+    let env = load(None);
+    let render = |name, vars| env.get_template(name).unwrap().render(vars);
+    let vars = minijinja::context! {
+        username => "foo",
+        online => 42,
+        title => "<Title>",
+        area => "Game",
+        space_url => "https://space.bilibili.com/1/",
+        room_url => "https://live.bilibili.com/2/",
+    };
+    assert_eq!(
+        render("zh-hans/bilibili_live.html", &vars).unwrap(),
+        "<a href=\"https://space.bilibili.com/1/\">foo</a> 开播了！已有 42 人正在观看\n\
+         直播: <a href=\"https://live.bilibili.com/2/\">&lt;Title&gt;</a>\n分区: #Game"
+    );
+    assert_eq!(
+        render("en/bilibili_live.html", &vars).unwrap(),
+        "<a href=\"https://space.bilibili.com/1/\">foo</a> is live! 42 watching now\n\
+         Live: <a href=\"https://live.bilibili.com/2/\">&lt;Title&gt;</a>\nArea: #Game"
+    );
+
+    let vars = minijinja::context! { name => "国庆节", is_off_day => true, line => "🎉" };
+    assert_eq!(
+        render("zh-hans/holiday_reminder.txt", &vars).unwrap(),
+        "明天是 国庆节，好好休息吧！\n🎉"
+    );
+    assert_eq!(
+        render("en/holiday_reminder.txt", &vars).unwrap(),
+        "Tomorrow is 国庆节, enjoy the day off!\n🎉"
+    );
+}
@@ -0,0 +1,342 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::{header, HeaderMap},
+    routing::post,
+    Json, Router,
+};
+use serde_json::{json, Value};
+use teloxide::Bot;
+use tokio::sync::Notify;
+
+pub const BOT_TOKEN: &str = "1000:fake-token";
+pub const BOT_ID: u64 = 1000;
+pub const BOT_USERNAME: &str = "maid_bot";
+
+/// An API request sent by the bot.
+#[derive(Debug, Clone)]
+pub struct RecordedCall {
+    pub method: String,
+    /// The JSON parameters. Only the text fields of the multipart requests are kept, the
+    /// uploaded files are left out.
+    pub params: Value,
+}
+
+impl RecordedCall {
+    pub fn text(&self) -> Option<&str> {
+        self.params
+            .get("text")
+            .or_else(|| self.params.get("caption"))
+            .and_then(Value::as_str)
+    }
+
+    pub fn chat_id(&self) -> Option<i64> {
+        self.params.get("chat_id").and_then(Value::as_i64)
+    }
+}
+
+#[derive(Default)]
+struct Server {
+    calls: Mutex<Vec<RecordedCall>>,
+    updates: Mutex<VecDeque<Value>>,
+    responses: Mutex<HashMap<String, Value>>,
+    next_update_id: Mutex<i64>,
+    next_message_id: Mutex<i64>,
+    /// Woken up when a call is recorded or an update is queued
+    notify: Notify,
+}
+
+/// A fake Telegram Bot API server. It records every request, answers them with a plausible
+/// result, and replays the queued updates to `getUpdates`, so the dispatcher can run against it.
+#[derive(Clone)]
+pub struct FakeTelegram {
+    url: reqwest::Url,
+    server: Arc<Server>,
+}
+
+impl FakeTelegram {
+    /// Serve on its own thread and runtime, so that it keeps answering while the test runtime is
+    /// busy or blocked, like when the dispatcher is running.
+    pub fn start() -> Self {
+        let server = Arc::new(Server::default());
+        let app = Router::new()
+            .route("/{bot}/{method}", post(handle))
+            .with_state(Arc::clone(&server));
+        let listener =
+            std::net::TcpListener::bind("127.0.0.1:0").expect("fail to bind fake telegram");
+        listener.set_nonblocking(true).unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("fail to start fake telegram runtime");
+            runtime.block_on(async move {
+                let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+                axum::serve(listener, app).await
+            })
+        });
+
+        Self {
+            url: reqwest::Url::parse(&url).unwrap(),
+            server,
+        }
+    }
+
+    /// URL of the server, to use it as the Bot API upstream.
+    pub fn url(&self) -> &reqwest::Url {
+        &self.url
+    }
+
+    /// A bot talking to this server. The connections are not kept for reuse, as a connection is
+    /// driven by the runtime opening it, and the dispatcher runs on its own runtime while
+    /// blocking the one calling it.
+    pub fn bot(&self) -> Bot {
+        let client = teloxide::net::default_reqwest_settings()
+            .pool_max_idle_per_host(0)
+            .build()
+            .expect("fail to build the client");
+        Bot::with_client(BOT_TOKEN, client).set_api_url(self.url.clone())
+    }
+
+    /// Queue an update for `getUpdates`, the `update_id` field is filled in.
+    pub fn push_update(&self, mut update: Value) {
+        let mut id = self.server.next_update_id.lock().unwrap();
+        *id += 1;
+        update["update_id"] = json!(*id);
+        self.server.updates.lock().unwrap().push_back(update);
+        self.server.notify.notify_waiters();
+    }
+
+    /// Answer the method with the given result instead of the default one.
+    pub fn respond(&self, method: &str, result: Value) {
+        self.server
+            .responses
+            .lock()
+            .unwrap()
+            .insert(method.to_string(), result);
+    }
+
+    pub fn calls(&self) -> Vec<RecordedCall> {
+        self.server.calls.lock().unwrap().clone()
+    }
+
+    pub fn calls_to(&self, method: &str) -> Vec<RecordedCall> {
+        self.server
+            .calls
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|call| call.method == method)
+            .cloned()
+            .collect()
+    }
+
+    /// Wait until the bot has called the method `count` times, and return those calls. Panic
+    /// after the timeout, so a broken test doesn't hang.
+    pub async fn wait_for(
+        &self,
+        method: &str,
+        count: usize,
+        timeout: Duration,
+    ) -> Vec<RecordedCall> {
+        let wait = async {
+            loop {
+                let notified = self.server.notify.notified();
+                let calls = self.calls_to(method);
+                if calls.len() >= count {
+                    return calls;
+                }
+                notified.await;
+            }
+        };
+        tokio::time::timeout(timeout, wait)
+            .await
+            .unwrap_or_else(|_| {
+                panic!(
+                    "{method} is called {} times in {timeout:?}, expect {count}",
+                    self.calls_to(method).len()
+                )
+            })
+    }
+}
+
+/// A text message update, sent to the chat by the user.
+pub fn text_update(chat_id: i64, user_id: u64, text: &str) -> Value {
+    json!({
+        "message": {
+            "message_id": 1,
+            "date": chrono::Utc::now().timestamp(),
+            "chat": chat(chat_id),
+            "from": {
+                "id": user_id,
+                "is_bot": false,
+                "first_name": "User",
+                "username": format!("user{user_id}"),
+            },
+            "text": text,
+        }
+    })
+}
+
+fn chat(chat_id: i64) -> Value {
+    if chat_id > 0 {
+        json!({ "id": chat_id, "type": "private", "first_name": "User" })
+    } else {
+        json!({ "id": chat_id, "type": "supergroup", "title": "Group" })
+    }
+}
+
+fn bot_user() -> Value {
+    json!({
+        "id": BOT_ID,
+        "is_bot": true,
+        "first_name": "Maid",
+        "username": BOT_USERNAME,
+        "can_join_groups": true,
+        "can_read_all_group_messages": true,
+        "supports_inline_queries": true,
+    })
+}
+
+async fn handle(
+    State(server): State<Arc<Server>>,
+    Path((_bot, method)): Path<(String, String)>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Json<Value> {
+    // teloxide calls the methods like `SendMessage`, Telegram doesn't care about the case
+    let mut chars = method.chars();
+    let method: String = chars
+        .next()
+        .map(|first| first.to_ascii_lowercase())
+        .into_iter()
+        .chain(chars)
+        .collect();
+    let boundary = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split_once("boundary="))
+        .map(|(_, boundary)| boundary.trim_matches('"').to_string());
+    let params = match boundary {
+        Some(boundary) => multipart_fields(&body, &boundary),
+        None => serde_json::from_slice(&body).unwrap_or(Value::Null),
+    };
+    let result = if method == "getUpdates" {
+        updates(&server, &params).await
+    } else {
+        server.calls.lock().unwrap().push(RecordedCall {
+            method: method.clone(),
+            params: params.clone(),
+        });
+        server.notify.notify_waiters();
+        let canned = server.responses.lock().unwrap().get(&method).cloned();
+        canned.unwrap_or_else(|| default_result(&server, &method, &params))
+    };
+    Json(json!({ "ok": true, "result": result }))
+}
+
+/// The text fields of the multipart form, parsed as JSON when they are, like the `chat_id`.
+fn multipart_fields(body: &[u8], boundary: &str) -> Value {
+    let body = String::from_utf8_lossy(body);
+    let mut fields = serde_json::Map::new();
+    for part in body.split(&format!("--{boundary}")) {
+        let Some((head, value)) = part.split_once("\r\n\r\n") else {
+            continue;
+        };
+        if head.contains("filename=") {
+            continue;
+        }
+        let Some((_, name)) = head.split_once("name=\"") else {
+            continue;
+        };
+        let name = name.split('"').next().unwrap_or_default();
+        let value = value.strip_suffix("\r\n").unwrap_or(value);
+        let value = serde_json::from_str(value).unwrap_or_else(|_| json!(value));
+        fields.insert(name.to_string(), value);
+    }
+    Value::Object(fields)
+}
+
+/// Return the queued updates, or wait a while like the long polling when there is none.
+async fn updates(server: &Server, params: &Value) -> Value {
+    let offset = params.get("offset").and_then(Value::as_i64).unwrap_or(0);
+    let take = |server: &Server| {
+        let mut updates = server.updates.lock().unwrap();
+        updates.retain(|update| update["update_id"].as_i64() >= Some(offset));
+        updates.iter().cloned().collect::<Vec<_>>()
+    };
+    let notified = server.notify.notified();
+    let pending = take(server);
+    if !pending.is_empty() {
+        return json!(pending);
+    }
+    tokio::time::timeout(Duration::from_millis(200), notified)
+        .await
+        .ok();
+    json!(take(server))
+}
+
+fn default_result(server: &Server, method: &str, params: &Value) -> Value {
+    let message = || {
+        let mut id = server.next_message_id.lock().unwrap();
+        *id += 1;
+        let chat_id = params.get("chat_id").and_then(Value::as_i64).unwrap_or(0);
+        let text = params
+            .get("text")
+            .or_else(|| params.get("caption"))
+            .cloned()
+            .unwrap_or_else(|| json!(""));
+        json!({
+            "message_id": *id,
+            "date": chrono::Utc::now().timestamp(),
+            "chat": chat(chat_id),
+            "from": bot_user(),
+            "text": text,
+        })
+    };
+
+    match method {
+        "getMe" => bot_user(),
+        "sendChatAction" => json!(true),
+        "sendMediaGroup" => json!([message()]),
+        "copyMessage" => json!({ "message_id": message()["message_id"] }),
+        "getChatAdministrators" => json!([]),
+        _ if method.starts_with("send")
+            || method.starts_with("editMessage")
+            || method == "forwardMessage" =>
+        {
+            message()
+        }
+        _ => json!(true),
+    }
+}
+
+#[tokio::test]
+async fn test_fake_telegram() {
+    use teloxide::prelude::*;
+
+    let telegram = FakeTelegram::start();
+    let bot = telegram.bot();
+
+    let me = bot.get_me().await.unwrap();
+    assert_eq!(me.username(), BOT_USERNAME);
+
+    let sent = bot.send_message(ChatId(-100), "hello").await.unwrap();
+    assert_eq!(sent.text(), Some("hello"));
+    let calls = telegram.calls_to("sendMessage");
+    assert_eq!(calls[0].chat_id(), Some(-100));
+    assert_eq!(calls[0].text(), Some("hello"));
+
+    telegram.push_update(text_update(-100, 42, "/roll"));
+    let updates = bot.get_updates().await.unwrap();
+    assert_eq!(updates.len(), 1);
+    let offset = updates[0].id.0 as i32 + 1;
+    assert!(bot.get_updates().offset(offset).await.unwrap().is_empty());
+}
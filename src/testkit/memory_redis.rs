@@ -0,0 +1,825 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::cache::Cacher;
+
+/// A tiny Redis server keeping the data in memory, speaking enough RESP for the commands used
+/// by this bot. It runs on its own threads, so the blocking [`Cacher`] works in async tests.
+#[derive(Clone)]
+pub struct MemoryRedis {
+    addr: String,
+    db: Arc<Mutex<Db>>,
+}
+
+impl MemoryRedis {
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("fail to bind memory redis");
+        let addr = format!("redis://{}", listener.local_addr().unwrap());
+        let db = Arc::new(Mutex::new(Db::default()));
+
+        let shared = Arc::clone(&db);
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let db = Arc::clone(&shared);
+                std::thread::spawn(move || serve(stream, db));
+            }
+        });
+
+        Self { addr, db }
+    }
+
+    /// URL like `redis://127.0.0.1:12345`
+    pub fn addr(&self) -> &str {
+        &self.addr
+    }
+
+    pub fn cacher(&self) -> Cacher {
+        Cacher::new(redis::Client::open(self.addr.as_str()).unwrap())
+    }
+
+    /// All the live keys, for checking what a handler stored.
+    pub fn keys(&self) -> Vec<String> {
+        let mut db = self.db.lock().unwrap();
+        db.purge_expired();
+        let mut keys: Vec<String> = db.entries.keys().cloned().collect();
+        keys.sort();
+        keys
+    }
+
+    pub fn flush(&self) {
+        self.db.lock().unwrap().entries.clear();
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Value {
+    Str(Vec<u8>),
+    Hash(HashMap<Vec<u8>, Vec<u8>>),
+    Set(HashSet<Vec<u8>>),
+    /// Members with their score, ordered by (score, member) like Redis
+    ZSet(HashMap<Vec<u8>, f64>),
+    List(VecDeque<Vec<u8>>),
+}
+
+#[derive(Debug)]
+struct Entry {
+    value: Value,
+    expire_at: Option<Instant>,
+}
+
+#[derive(Default)]
+struct Db {
+    entries: HashMap<String, Entry>,
+}
+
+enum Reply {
+    Status(&'static str),
+    Error(String),
+    Int(i64),
+    Bulk(Vec<u8>),
+    Nil,
+    Array(Vec<Reply>),
+}
+
+impl Reply {
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        match self {
+            Reply::Status(status) => write!(out, "+{status}\r\n"),
+            Reply::Error(err) => write!(out, "-{err}\r\n"),
+            Reply::Int(int) => write!(out, ":{int}\r\n"),
+            Reply::Bulk(bulk) => {
+                write!(out, "${}\r\n", bulk.len())?;
+                out.write_all(bulk)?;
+                out.write_all(b"\r\n")
+            }
+            Reply::Nil => write!(out, "$-1\r\n"),
+            Reply::Array(items) => {
+                write!(out, "*{}\r\n", items.len())?;
+                items.iter().try_for_each(|item| item.write(out))
+            }
+        }
+    }
+}
+
+fn bulk(value: impl Into<Vec<u8>>) -> Reply {
+    Reply::Bulk(value.into())
+}
+
+fn serve(stream: TcpStream, db: Arc<Mutex<Db>>) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut writer = stream;
+    // Commands queued by MULTI, run together by EXEC
+    let mut queued: Option<Vec<Vec<Vec<u8>>>> = None;
+    while let Ok(Some(args)) = read_command(&mut reader) {
+        let name = String::from_utf8_lossy(&args[0]).to_uppercase();
+        let reply = match (name.as_str(), queued.as_mut()) {
+            ("MULTI", _) => {
+                queued = Some(Vec::new());
+                Reply::Status("OK")
+            }
+            ("EXEC", Some(_)) => {
+                let commands = queued.take().unwrap();
+                let mut db = db.lock().unwrap();
+                Reply::Array(commands.iter().map(|args| db.execute(args)).collect())
+            }
+            ("DISCARD", Some(_)) => {
+                queued = None;
+                Reply::Status("OK")
+            }
+            (_, Some(commands)) => {
+                commands.push(args);
+                Reply::Status("QUEUED")
+            }
+            _ => db.lock().unwrap().execute(&args),
+        };
+        if reply.write(&mut writer).is_err() {
+            break;
+        }
+    }
+}
+
+/// Read a command sent as RESP array of bulk strings. Return `None` when the client quits.
+fn read_command(reader: &mut impl BufRead) -> io::Result<Option<Vec<Vec<u8>>>> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid RESP");
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    let count: usize = line
+        .trim_end()
+        .strip_prefix('*')
+        .and_then(|count| count.parse().ok())
+        .ok_or_else(invalid)?;
+
+    let mut args = Vec::with_capacity(count);
+    for _ in 0..count {
+        line.clear();
+        reader.read_line(&mut line)?;
+        let len: usize = line
+            .trim_end()
+            .strip_prefix('$')
+            .and_then(|len| len.parse().ok())
+            .ok_or_else(invalid)?;
+        let mut arg = vec![0; len + 2];
+        reader.read_exact(&mut arg)?;
+        arg.truncate(len);
+        args.push(arg);
+    }
+    Ok(Some(args))
+}
+
+fn text(arg: &[u8]) -> String {
+    String::from_utf8_lossy(arg).into_owned()
+}
+
+fn int(arg: &[u8]) -> Result<i64, Reply> {
+    text(arg)
+        .parse()
+        .map_err(|_| Reply::Error("ERR value is not an integer or out of range".to_string()))
+}
+
+fn float(arg: &[u8]) -> Result<f64, Reply> {
+    match text(arg).as_str() {
+        "-inf" => Ok(f64::NEG_INFINITY),
+        "+inf" | "inf" => Ok(f64::INFINITY),
+        value => value
+            .trim_start_matches('(')
+            .parse()
+            .map_err(|_| Reply::Error("ERR value is not a valid float".to_string())),
+    }
+}
+
+/// Format the score the same way as Redis, integers don't have the decimal part.
+fn format_score(score: f64) -> String {
+    if score.fract() == 0.0 && score.abs() < 1e15 {
+        format!("{}", score as i64)
+    } else {
+        score.to_string()
+    }
+}
+
+/// Match the key with a glob pattern that only supports `*` and `?`.
+fn glob_match(pattern: &[u8], key: &[u8]) -> bool {
+    match (pattern.first(), key.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => {
+            glob_match(&pattern[1..], key) || (!key.is_empty() && glob_match(pattern, &key[1..]))
+        }
+        (Some(b'?'), Some(_)) => glob_match(&pattern[1..], &key[1..]),
+        (Some(p), Some(k)) if p == k => glob_match(&pattern[1..], &key[1..]),
+        _ => false,
+    }
+}
+
+/// Resolve the Redis style range with negative index to a slice range.
+fn index_range(start: i64, stop: i64, len: usize) -> Option<std::ops::Range<usize>> {
+    let len = len as i64;
+    let start = if start < 0 {
+        (len + start).max(0)
+    } else {
+        start
+    };
+    let stop = if stop < 0 {
+        len + stop
+    } else {
+        stop.min(len - 1)
+    };
+    (start <= stop && start < len).then(|| start as usize..stop as usize + 1)
+}
+
+fn wrong_type() -> Reply {
+    Reply::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())
+}
+
+macro_rules! value_of {
+    ($db:expr, $key:expr, $variant:ident, $default:expr) => {{
+        let entry = $db.entries.entry($key).or_insert_with(|| Entry {
+            value: Value::$variant($default),
+            expire_at: None,
+        });
+        match &mut entry.value {
+            Value::$variant(value) => value,
+            _ => return Err(wrong_type()),
+        }
+    }};
+}
+
+impl Db {
+    fn purge_expired(&mut self) {
+        let now = Instant::now();
+        self.entries
+            .retain(|_, entry| entry.expire_at.is_none_or(|at| at > now));
+    }
+
+    fn get(&self, key: &str) -> Option<&Value> {
+        self.entries.get(key).map(|entry| &entry.value)
+    }
+
+    fn remove_if_empty(&mut self, key: &str) {
+        let empty = match self.get(key) {
+            Some(Value::Hash(hash)) => hash.is_empty(),
+            Some(Value::Set(set)) => set.is_empty(),
+            Some(Value::ZSet(zset)) => zset.is_empty(),
+            Some(Value::List(list)) => list.is_empty(),
+            _ => false,
+        };
+        if empty {
+            self.entries.remove(key);
+        }
+    }
+
+    fn sorted_zset(&self, key: &str) -> Result<Vec<(Vec<u8>, f64)>, Reply> {
+        match self.get(key) {
+            None => Ok(Vec::new()),
+            Some(Value::ZSet(zset)) => {
+                let mut members: Vec<_> = zset.iter().map(|(m, s)| (m.clone(), *s)).collect();
+                members.sort_by(|(m1, s1), (m2, s2)| s1.total_cmp(s2).then_with(|| m1.cmp(m2)));
+                Ok(members)
+            }
+            Some(_) => Err(wrong_type()),
+        }
+    }
+
+    fn execute(&mut self, args: &[Vec<u8>]) -> Reply {
+        self.purge_expired();
+        let name = text(&args[0]).to_uppercase();
+        match self.run(&name, &args[1..]) {
+            Ok(reply) | Err(reply) => reply,
+        }
+    }
+
+    fn run(&mut self, name: &str, args: &[Vec<u8>]) -> Result<Reply, Reply> {
+        let arity = |min: usize| {
+            if args.len() < min {
+                Err(Reply::Error(format!(
+                    "ERR wrong number of arguments for '{}' command",
+                    name.to_lowercase()
+                )))
+            } else {
+                Ok(())
+            }
+        };
+        let key = || text(&args[0]);
+
+        let reply = match name {
+            "PING" => Reply::Status("PONG"),
+            "SELECT" | "CLIENT" | "READONLY" => Reply::Status("OK"),
+            "FLUSHALL" | "FLUSHDB" => {
+                self.entries.clear();
+                Reply::Status("OK")
+            }
+            "DBSIZE" => Reply::Int(self.entries.len() as i64),
+
+            "GET" => {
+                arity(1)?;
+                match self.get(&key()) {
+                    None => Reply::Nil,
+                    Some(Value::Str(value)) => bulk(value.clone()),
+                    Some(_) => return Err(wrong_type()),
+                }
+            }
+            "SET" => {
+                arity(2)?;
+                let (mut nx, mut xx, mut expire_at) = (false, false, None);
+                let mut options = args[2..].iter();
+                while let Some(option) = options.next() {
+                    match text(option).to_uppercase().as_str() {
+                        "NX" => nx = true,
+                        "XX" => xx = true,
+                        unit @ ("EX" | "PX") => {
+                            let amount = int(options.next().ok_or_else(syntax_error)?)? as u64;
+                            let ttl = if unit == "EX" {
+                                Duration::from_secs(amount)
+                            } else {
+                                Duration::from_millis(amount)
+                            };
+                            expire_at = Some(Instant::now() + ttl);
+                        }
+                        _ => return Err(syntax_error()),
+                    }
+                }
+                let exists = self.entries.contains_key(&key());
+                if (nx && exists) || (xx && !exists) {
+                    return Ok(Reply::Nil);
+                }
+                self.entries.insert(
+                    key(),
+                    Entry {
+                        value: Value::Str(args[1].clone()),
+                        expire_at,
+                    },
+                );
+                Reply::Status("OK")
+            }
+            "SETEX" | "PSETEX" => {
+                arity(3)?;
+                let amount = int(&args[1])? as u64;
+                let ttl = if name == "SETEX" {
+                    Duration::from_secs(amount)
+                } else {
+                    Duration::from_millis(amount)
+                };
+                self.entries.insert(
+                    key(),
+                    Entry {
+                        value: Value::Str(args[2].clone()),
+                        expire_at: Some(Instant::now() + ttl),
+                    },
+                );
+                Reply::Status("OK")
+            }
+            "INCR" | "INCRBY" | "DECR" | "DECRBY" => {
+                arity(1)?;
+                let delta = match name {
+                    "INCR" => 1,
+                    "DECR" => -1,
+                    "INCRBY" => int(args.get(1).ok_or_else(syntax_error)?)?,
+                    _ => -int(args.get(1).ok_or_else(syntax_error)?)?,
+                };
+                let value = value_of!(self, key(), Str, b"0".to_vec());
+                let new = int(value)? + delta;
+                *value = new.to_string().into_bytes();
+                Reply::Int(new)
+            }
+            "TYPE" => {
+                arity(1)?;
+                let kind = match self.get(&key()) {
+                    None => "none",
+                    Some(Value::Str(_)) => "string",
+                    Some(Value::Hash(_)) => "hash",
+                    Some(Value::Set(_)) => "set",
+                    Some(Value::ZSet(_)) => "zset",
+                    Some(Value::List(_)) => "list",
+                };
+                Reply::Status(kind)
+            }
+            "DEL" | "UNLINK" => {
+                arity(1)?;
+                let removed = args
+                    .iter()
+                    .filter(|key| self.entries.remove(&text(key)).is_some())
+                    .count();
+                Reply::Int(removed as i64)
+            }
+            "RENAME" => {
+                arity(2)?;
+                let entry = self
+                    .entries
+                    .remove(&key())
+                    .ok_or_else(|| Reply::Error("ERR no such key".to_string()))?;
+                self.entries.insert(text(&args[1]), entry);
+                Reply::Status("OK")
+            }
+            "EXISTS" => {
+                arity(1)?;
+                let exists = args
+                    .iter()
+                    .filter(|key| self.entries.contains_key(&text(key)))
+                    .count();
+                Reply::Int(exists as i64)
+            }
+            "EXPIRE" | "PEXPIRE" => {
+                arity(2)?;
+                let amount = int(&args[1])?.max(0) as u64;
+                let ttl = if name == "EXPIRE" {
+                    Duration::from_secs(amount)
+                } else {
+                    Duration::from_millis(amount)
+                };
+                match self.entries.get_mut(&key()) {
+                    Some(entry) => {
+                        entry.expire_at = Some(Instant::now() + ttl);
+                        Reply::Int(1)
+                    }
+                    None => Reply::Int(0),
+                }
+            }
+            "TTL" | "PTTL" => {
+                arity(1)?;
+                match self.entries.get(&key()) {
+                    None => Reply::Int(-2),
+                    Some(Entry {
+                        expire_at: None, ..
+                    }) => Reply::Int(-1),
+                    Some(Entry {
+                        expire_at: Some(at),
+                        ..
+                    }) => {
+                        let left = at.saturating_duration_since(Instant::now());
+                        if name == "TTL" {
+                            Reply::Int(left.as_secs_f64().ceil() as i64)
+                        } else {
+                            Reply::Int(left.as_millis() as i64)
+                        }
+                    }
+                }
+            }
+            "KEYS" => {
+                arity(1)?;
+                let mut keys: Vec<&String> = self
+                    .entries
+                    .keys()
+                    .filter(|key| glob_match(&args[0], key.as_bytes()))
+                    .collect();
+                keys.sort();
+                Reply::Array(keys.into_iter().map(|key| bulk(key.as_str())).collect())
+            }
+
+            "HSET" | "HMSET" => {
+                if args.len() < 3 || args.len().is_multiple_of(2) {
+                    return Err(syntax_error());
+                }
+                let hash = value_of!(self, key(), Hash, HashMap::new());
+                let added = args[1..]
+                    .chunks(2)
+                    .filter(|pair| hash.insert(pair[0].clone(), pair[1].clone()).is_none())
+                    .count();
+                if name == "HMSET" {
+                    Reply::Status("OK")
+                } else {
+                    Reply::Int(added as i64)
+                }
+            }
+            "HGET" => {
+                arity(2)?;
+                match self.get(&key()) {
+                    None => Reply::Nil,
+                    Some(Value::Hash(hash)) => hash.get(&args[1]).cloned().map_or(Reply::Nil, bulk),
+                    Some(_) => return Err(wrong_type()),
+                }
+            }
+            "HMGET" => {
+                arity(2)?;
+                let empty = HashMap::new();
+                let hash = match self.get(&key()) {
+                    None => &empty,
+                    Some(Value::Hash(hash)) => hash,
+                    Some(_) => return Err(wrong_type()),
+                };
+                Reply::Array(
+                    args[1..]
+                        .iter()
+                        .map(|field| hash.get(field).cloned().map_or(Reply::Nil, bulk))
+                        .collect(),
+                )
+            }
+            "HGETALL" | "HKEYS" | "HVALS" => {
+                arity(1)?;
+                let mut fields: Vec<(&Vec<u8>, &Vec<u8>)> = match self.get(&key()) {
+                    None => Vec::new(),
+                    Some(Value::Hash(hash)) => hash.iter().collect(),
+                    Some(_) => return Err(wrong_type()),
+                };
+                fields.sort();
+                let items = fields.into_iter().flat_map(|(field, value)| match name {
+                    "HKEYS" => vec![bulk(field.clone())],
+                    "HVALS" => vec![bulk(value.clone())],
+                    _ => vec![bulk(field.clone()), bulk(value.clone())],
+                });
+                Reply::Array(items.collect())
+            }
+            "HDEL" => {
+                arity(2)?;
+                let removed = match self.entries.get_mut(&key()).map(|entry| &mut entry.value) {
+                    None => 0,
+                    Some(Value::Hash(hash)) => args[1..]
+                        .iter()
+                        .filter(|field| hash.remove(*field).is_some())
+                        .count(),
+                    Some(_) => return Err(wrong_type()),
+                };
+                self.remove_if_empty(&key());
+                Reply::Int(removed as i64)
+            }
+            "HEXISTS" => {
+                arity(2)?;
+                match self.get(&key()) {
+                    None => Reply::Int(0),
+                    Some(Value::Hash(hash)) => Reply::Int(hash.contains_key(&args[1]) as i64),
+                    Some(_) => return Err(wrong_type()),
+                }
+            }
+            "HLEN" => {
+                arity(1)?;
+                match self.get(&key()) {
+                    None => Reply::Int(0),
+                    Some(Value::Hash(hash)) => Reply::Int(hash.len() as i64),
+                    Some(_) => return Err(wrong_type()),
+                }
+            }
+            "HINCRBY" => {
+                arity(3)?;
+                let delta = int(&args[2])?;
+                let hash = value_of!(self, key(), Hash, HashMap::new());
+                let field = hash.entry(args[1].clone()).or_insert_with(|| b"0".to_vec());
+                let new = int(field)? + delta;
+                *field = new.to_string().into_bytes();
+                Reply::Int(new)
+            }
+
+            "SADD" => {
+                arity(2)?;
+                let set = value_of!(self, key(), Set, HashSet::new());
+                let added = args[1..]
+                    .iter()
+                    .filter(|member| set.insert(member.to_vec()))
+                    .count();
+                Reply::Int(added as i64)
+            }
+            "SREM" => {
+                arity(2)?;
+                let removed = match self.entries.get_mut(&key()).map(|entry| &mut entry.value) {
+                    None => 0,
+                    Some(Value::Set(set)) => args[1..]
+                        .iter()
+                        .filter(|member| set.remove(*member))
+                        .count(),
+                    Some(_) => return Err(wrong_type()),
+                };
+                self.remove_if_empty(&key());
+                Reply::Int(removed as i64)
+            }
+            "SMEMBERS" => {
+                arity(1)?;
+                let members: BTreeSet<&Vec<u8>> = match self.get(&key()) {
+                    None => BTreeSet::new(),
+                    Some(Value::Set(set)) => set.iter().collect(),
+                    Some(_) => return Err(wrong_type()),
+                };
+                Reply::Array(members.into_iter().map(|m| bulk(m.clone())).collect())
+            }
+            "SISMEMBER" => {
+                arity(2)?;
+                match self.get(&key()) {
+                    None => Reply::Int(0),
+                    Some(Value::Set(set)) => Reply::Int(set.contains(&args[1]) as i64),
+                    Some(_) => return Err(wrong_type()),
+                }
+            }
+            "SCARD" => {
+                arity(1)?;
+                match self.get(&key()) {
+                    None => Reply::Int(0),
+                    Some(Value::Set(set)) => Reply::Int(set.len() as i64),
+                    Some(_) => return Err(wrong_type()),
+                }
+            }
+
+            "ZADD" => {
+                arity(3)?;
+                if args.len().is_multiple_of(2) {
+                    return Err(syntax_error());
+                }
+                let pairs = args[1..]
+                    .chunks(2)
+                    .map(|pair| Ok((float(&pair[0])?, pair[1].clone())))
+                    .collect::<Result<Vec<_>, Reply>>()?;
+                let zset = value_of!(self, key(), ZSet, HashMap::new());
+                let added = pairs
+                    .into_iter()
+                    .filter(|(score, member)| zset.insert(member.clone(), *score).is_none())
+                    .count();
+                Reply::Int(added as i64)
+            }
+            "ZINCRBY" => {
+                arity(3)?;
+                let delta = float(&args[1])?;
+                let zset = value_of!(self, key(), ZSet, HashMap::new());
+                let score = zset.entry(args[2].clone()).or_insert(0.0);
+                *score += delta;
+                bulk(format_score(*score))
+            }
+            "ZSCORE" => {
+                arity(2)?;
+                match self.get(&key()) {
+                    None => Reply::Nil,
+                    Some(Value::ZSet(zset)) => zset
+                        .get(&args[1])
+                        .map_or(Reply::Nil, |score| bulk(format_score(*score))),
+                    Some(_) => return Err(wrong_type()),
+                }
+            }
+            "ZRANK" | "ZREVRANK" => {
+                arity(2)?;
+                let mut members = self.sorted_zset(&key())?;
+                if name == "ZREVRANK" {
+                    members.reverse();
+                }
+                members
+                    .iter()
+                    .position(|(member, _)| *member == args[1])
+                    .map_or(Reply::Nil, |rank| Reply::Int(rank as i64))
+            }
+            "ZRANGE" | "ZREVRANGE" => {
+                arity(3)?;
+                let mut members = self.sorted_zset(&key())?;
+                if name == "ZREVRANGE" {
+                    members.reverse();
+                }
+                let with_scores = args
+                    .get(3)
+                    .is_some_and(|arg| text(arg).eq_ignore_ascii_case("WITHSCORES"));
+                let range = index_range(int(&args[1])?, int(&args[2])?, members.len());
+                let items = range
+                    .map(|range| members[range].to_vec())
+                    .unwrap_or_default()
+                    .into_iter()
+                    .flat_map(|(member, score)| {
+                        let mut items = vec![bulk(member)];
+                        if with_scores {
+                            items.push(bulk(format_score(score)));
+                        }
+                        items
+                    });
+                Reply::Array(items.collect())
+            }
+            "ZRANGEBYSCORE" => {
+                arity(3)?;
+                let (min, max) = (float(&args[1])?, float(&args[2])?);
+                let items = self
+                    .sorted_zset(&key())?
+                    .into_iter()
+                    .filter(|(_, score)| *score >= min && *score <= max)
+                    .map(|(member, _)| bulk(member));
+                Reply::Array(items.collect())
+            }
+            "ZREMRANGEBYSCORE" => {
+                arity(3)?;
+                let (min, max) = (float(&args[1])?, float(&args[2])?);
+                let removed = match self.entries.get_mut(&key()).map(|entry| &mut entry.value) {
+                    None => 0,
+                    Some(Value::ZSet(zset)) => {
+                        let before = zset.len();
+                        zset.retain(|_, score| *score < min || *score > max);
+                        before - zset.len()
+                    }
+                    Some(_) => return Err(wrong_type()),
+                };
+                self.remove_if_empty(&key());
+                Reply::Int(removed as i64)
+            }
+            "ZREM" => {
+                arity(2)?;
+                let removed = match self.entries.get_mut(&key()).map(|entry| &mut entry.value) {
+                    None => 0,
+                    Some(Value::ZSet(zset)) => args[1..]
+                        .iter()
+                        .filter(|member| zset.remove(*member).is_some())
+                        .count(),
+                    Some(_) => return Err(wrong_type()),
+                };
+                self.remove_if_empty(&key());
+                Reply::Int(removed as i64)
+            }
+            "ZCARD" => {
+                arity(1)?;
+                Reply::Int(self.sorted_zset(&key())?.len() as i64)
+            }
+
+            "RPUSH" | "LPUSH" => {
+                arity(2)?;
+                let list = value_of!(self, key(), List, VecDeque::new());
+                for value in &args[1..] {
+                    if name == "RPUSH" {
+                        list.push_back(value.clone());
+                    } else {
+                        list.push_front(value.clone());
+                    }
+                }
+                Reply::Int(list.len() as i64)
+            }
+            "LPOP" | "RPOP" => {
+                arity(1)?;
+                let popped = match self.entries.get_mut(&key()).map(|entry| &mut entry.value) {
+                    None => None,
+                    Some(Value::List(list)) if name == "LPOP" => list.pop_front(),
+                    Some(Value::List(list)) => list.pop_back(),
+                    Some(_) => return Err(wrong_type()),
+                };
+                self.remove_if_empty(&key());
+                popped.map_or(Reply::Nil, bulk)
+            }
+            "LRANGE" => {
+                arity(3)?;
+                let list: Vec<Vec<u8>> = match self.get(&key()) {
+                    None => Vec::new(),
+                    Some(Value::List(list)) => list.iter().cloned().collect(),
+                    Some(_) => return Err(wrong_type()),
+                };
+                let range = index_range(int(&args[1])?, int(&args[2])?, list.len());
+                let items = range.map(|range| list[range].to_vec()).unwrap_or_default();
+                Reply::Array(items.into_iter().map(bulk).collect())
+            }
+            "LLEN" => {
+                arity(1)?;
+                match self.get(&key()) {
+                    None => Reply::Int(0),
+                    Some(Value::List(list)) => Reply::Int(list.len() as i64),
+                    Some(_) => return Err(wrong_type()),
+                }
+            }
+
+            _ => Reply::Error(format!("ERR unknown command '{}'", name.to_lowercase())),
+        };
+        Ok(reply)
+    }
+}
+
+fn syntax_error() -> Reply {
+    Reply::Error("ERR syntax error".to_string())
+}
+
+#[test]
+fn test_memory_redis() {
+    use redis::Commands;
+
+    let redis = MemoryRedis::start();
+    let cacher = redis.cacher();
+    let mut conn = cacher.get_conn();
+
+    let () = conn.set("name", "maid").unwrap();
+    let name: String = conn.get("name").unwrap();
+    assert_eq!(name, "maid");
+    let set: bool = redis::cmd("SET")
+        .arg("name")
+        .arg("other")
+        .arg("NX")
+        .arg("EX")
+        .arg(60)
+        .query(&mut conn)
+        .unwrap();
+    assert!(!set);
+
+    assert_eq!(cacher.incr_counter("board", 1, 2).unwrap(), 2);
+    assert_eq!(cacher.incr_counter("board", 2, 5).unwrap(), 5);
+    assert_eq!(cacher.counter_rank("board", 1).unwrap(), Some((2, 1)));
+    let top: Vec<(u64, i64)> = cacher.counter_top("board", 10).unwrap();
+    assert_eq!(top, [(2, 5), (1, 2)]);
+
+    let window = Duration::from_secs(60);
+    assert!(cacher
+        .hit_sliding_window("limit", 1, window)
+        .unwrap()
+        .is_none());
+    assert!(cacher
+        .hit_sliding_window("limit", 1, window)
+        .unwrap()
+        .is_some());
+
+    let () = conn.hset("hash", "field", 1).unwrap();
+    let fields: HashMap<String, u32> = conn.hgetall("hash").unwrap();
+    assert_eq!(fields["field"], 1);
+    let keys: Vec<String> = conn.keys("h*").unwrap();
+    assert_eq!(keys, ["hash"]);
+
+    assert!(glob_match(
+        b"SUBSCRIBE_REGISTRY:*",
+        b"SUBSCRIBE_REGISTRY:a:b"
+    ));
+    assert!(!glob_match(b"KARMA:?", b"KARMA:10"));
+}
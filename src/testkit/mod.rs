@@ -0,0 +1,91 @@
+//! Fake services for testing the module handlers and watcher tasks end to end, without real
+//! tokens. Enabled by the `testkit` feature, which the tests of this crate always turn on.
+//!
+//! ```ignore
+//! let telegram = FakeTelegram::start();
+//! let data = testkit::app_data(telegram.bot()).await;
+//! telegram.push_update(testkit::text_update(-100, 42, "/roll"));
+//! // run the dispatcher or the watcher with the data...
+//! let sent = telegram.wait_for("sendMessage", 1, Duration::from_secs(5)).await;
+//! ```
+
+mod fake_telegram;
+mod memory_redis;
+
+use std::sync::Arc;
+
+use clearurl::UrlCleaner;
+
+use crate::{
+    app::{AppData, Bots, RuntimeData},
+    config::Config,
+    http::HttpClient,
+    send_queue::SendQueue,
+    storage::Storage,
+};
+
+pub use fake_telegram::{text_update, FakeTelegram, RecordedCall, BOT_ID, BOT_USERNAME};
+pub use memory_redis::MemoryRedis;
+
+const TEST_CONFIG: &str = r#"
+    bot_token = "1000:fake-token"
+    redis_addr = "redis://127.0.0.1"
+
+    [deepl]
+    api_key = "fake-key"
+
+    [bili_live_room_event]
+"#;
+
+/// A minimal valid config, with the defaults for everything optional.
+pub fn config() -> Config {
+    toml::from_str(TEST_CONFIG).expect("invalid test config")
+}
+
+/// Install [`config`] as the global config unless there is one, so the code reading
+/// [`Config::get_global_config`] works without a config file.
+pub fn install_config() -> Arc<Config> {
+    Config::install_global_config(config())
+}
+
+/// Connect to a fresh in-memory SQLite database.
+pub async fn memory_storage() -> Storage {
+    Storage::connect(&crate::config::DatabaseConfig {
+        url: "sqlite::memory:".to_string(),
+        // Every connection opens its own in-memory database
+        max_connections: 1,
+    })
+    .await
+    .expect("fail to open in-memory database")
+}
+
+/// Build the app data backed by a fresh [`MemoryRedis`] and in-memory database, with the given
+/// bot as the main bot. The test config is installed as well.
+pub async fn app_data(bot: teloxide::Bot) -> AppData {
+    app_data_with(bot, &MemoryRedis::start()).await
+}
+
+/// Like [`app_data`], but use the given Redis so the test can inspect it.
+pub async fn app_data_with(bot: teloxide::Bot, redis: &MemoryRedis) -> AppData {
+    let config = install_config();
+    let deepl = deepl::DeepLApi::with(&config.deepl.api_key).new();
+    let (_, deepl) = tokio::sync::watch::channel(deepl);
+    let quote_maker = make_quote::QuoteProducer::builder()
+        .font(
+            include_bytes!(env!("QUOTE_TEXT_FONT_PATH")),
+            include_bytes!(env!("QUOTE_USERNAME_FONT_PATH")),
+        )
+        .build();
+
+    RuntimeData::builder()
+        .bots(Bots::new(bot))
+        .cacher(redis.cacher())
+        .storage(memory_storage().await)
+        .requester(HttpClient::new())
+        .deepl(deepl)
+        .quote_maker(quote_maker)
+        .url_cleaner(UrlCleaner::from_toml("").unwrap())
+        .send_queue(SendQueue::spawn())
+        .build()
+        .into()
+}
@@ -0,0 +1,221 @@
+use std::{fmt::Display, str::FromStr};
+
+use teloxide::{
+    payloads::{
+        SendChatActionSetters, SendDocumentSetters, SendMessageSetters, SendPhotoSetters,
+        SendPollSetters, SendVideoSetters,
+    },
+    prelude::*,
+    types::{ChatAction, InputFile, InputPollOption, MessageId, ThreadId},
+};
+
+/// A chat, or a forum topic in the group. Written as `-100123` for the chat and `-100123:42`
+/// for the topic in the config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChatTarget {
+    pub chat_id: ChatId,
+    pub thread_id: Option<ThreadId>,
+}
+
+impl ChatTarget {
+    /// Where to reply the message. Only the messages in a forum topic have the thread, the
+    /// reply threads of the normal groups are ignored.
+    pub fn of(msg: &Message) -> Self {
+        Self {
+            chat_id: msg.chat.id,
+            thread_id: msg.thread_id.filter(|_| msg.is_topic_message),
+        }
+    }
+}
+
+impl From<ChatId> for ChatTarget {
+    fn from(chat_id: ChatId) -> Self {
+        Self {
+            chat_id,
+            thread_id: None,
+        }
+    }
+}
+
+impl From<&Message> for ChatTarget {
+    fn from(msg: &Message) -> Self {
+        Self::of(msg)
+    }
+}
+
+impl FromStr for ChatTarget {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (chat, thread) = match s.split_once(':') {
+            Some((chat, thread)) => (chat, Some(thread)),
+            None => (s, None),
+        };
+        let chat_id = chat
+            .trim()
+            .parse::<i64>()
+            .map_err(|_| anyhow::anyhow!("`{s}` is not a chat id"))?;
+        let thread_id = thread
+            .map(|thread| thread.trim().parse::<i32>())
+            .transpose()
+            .map_err(|_| anyhow::anyhow!("`{s}` has an invalid topic id"))?;
+        Ok(Self {
+            chat_id: ChatId(chat_id),
+            thread_id: thread_id.map(|id| ThreadId(MessageId(id))),
+        })
+    }
+}
+
+impl Display for ChatTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.thread_id {
+            Some(thread) => write!(f, "{}:{}", self.chat_id, thread.0 .0),
+            None => write!(f, "{}", self.chat_id),
+        }
+    }
+}
+
+/// Send into the chat and forum topic, so the replies to a topic don't end up in General.
+/// Every module should reply by these instead of the `chat_id` ones.
+///
+/// ```ignore
+/// bot.send_message_to(&msg, "pong").await?;
+/// ```
+pub trait SendTo {
+    fn send_message_to(
+        &self,
+        to: impl Into<ChatTarget>,
+        text: impl Into<String>,
+    ) -> <Bot as Requester>::SendMessage;
+
+    fn send_photo_to(
+        &self,
+        to: impl Into<ChatTarget>,
+        photo: InputFile,
+    ) -> <Bot as Requester>::SendPhoto;
+
+    fn send_video_to(
+        &self,
+        to: impl Into<ChatTarget>,
+        video: InputFile,
+    ) -> <Bot as Requester>::SendVideo;
+
+    fn send_document_to(
+        &self,
+        to: impl Into<ChatTarget>,
+        document: InputFile,
+    ) -> <Bot as Requester>::SendDocument;
+
+    fn send_chat_action_to(
+        &self,
+        to: impl Into<ChatTarget>,
+        action: ChatAction,
+    ) -> <Bot as Requester>::SendChatAction;
+
+    fn send_poll_to(
+        &self,
+        to: impl Into<ChatTarget>,
+        question: impl Into<String>,
+        options: impl IntoIterator<Item = InputPollOption>,
+    ) -> <Bot as Requester>::SendPoll;
+}
+
+macro_rules! in_thread {
+    ($request:expr, $target:expr) => {
+        match $target.thread_id {
+            Some(thread) => $request.message_thread_id(thread),
+            None => $request,
+        }
+    };
+}
+
+impl SendTo for Bot {
+    fn send_message_to(
+        &self,
+        to: impl Into<ChatTarget>,
+        text: impl Into<String>,
+    ) -> <Bot as Requester>::SendMessage {
+        let to = to.into();
+        in_thread!(self.send_message(to.chat_id, text), to)
+    }
+
+    fn send_photo_to(
+        &self,
+        to: impl Into<ChatTarget>,
+        photo: InputFile,
+    ) -> <Bot as Requester>::SendPhoto {
+        let to = to.into();
+        in_thread!(self.send_photo(to.chat_id, photo), to)
+    }
+
+    fn send_video_to(
+        &self,
+        to: impl Into<ChatTarget>,
+        video: InputFile,
+    ) -> <Bot as Requester>::SendVideo {
+        let to = to.into();
+        in_thread!(self.send_video(to.chat_id, video), to)
+    }
+
+    fn send_document_to(
+        &self,
+        to: impl Into<ChatTarget>,
+        document: InputFile,
+    ) -> <Bot as Requester>::SendDocument {
+        let to = to.into();
+        in_thread!(self.send_document(to.chat_id, document), to)
+    }
+
+    fn send_chat_action_to(
+        &self,
+        to: impl Into<ChatTarget>,
+        action: ChatAction,
+    ) -> <Bot as Requester>::SendChatAction {
+        let to = to.into();
+        in_thread!(self.send_chat_action(to.chat_id, action), to)
+    }
+
+    fn send_poll_to(
+        &self,
+        to: impl Into<ChatTarget>,
+        question: impl Into<String>,
+        options: impl IntoIterator<Item = InputPollOption>,
+    ) -> <Bot as Requester>::SendPoll {
+        let to = to.into();
+        in_thread!(self.send_poll(to.chat_id, question, options), to)
+    }
+}
+
+#[test]
+fn test_parse_chat_target() {
+    let target: ChatTarget = "-100123".parse().unwrap();
+    assert_eq!(target, ChatTarget::from(ChatId(-100123)));
+    assert_eq!(target.to_string(), "-100123");
+
+    let target: ChatTarget = "-100123:42".parse().unwrap();
+    assert_eq!(target.thread_id, Some(ThreadId(MessageId(42))));
+    assert_eq!(target.to_string(), "-100123:42");
+
+    assert!("group".parse::<ChatTarget>().is_err());
+    assert!("-100123:general".parse::<ChatTarget>().is_err());
+}
+
+#[tokio::test]
+async fn test_send_to_topic() {
+    use crate::testkit::{self, FakeTelegram};
+
+    let telegram = FakeTelegram::start();
+    let bot = telegram.bot();
+    let mut update = testkit::text_update(-100, 42, "/roll");
+    update["message"]["message_thread_id"] = serde_json::json!(7);
+    update["message"]["is_topic_message"] = serde_json::json!(true);
+    let msg: Message = serde_json::from_value(update["message"].clone()).unwrap();
+
+    bot.send_message_to(&msg, "in topic").await.unwrap();
+    bot.send_message_to(ChatId(-100), "in general")
+        .await
+        .unwrap();
+    let calls = telegram.calls_to("sendMessage");
+    assert_eq!(calls[0].params["message_thread_id"], 7);
+    assert!(calls[1].params.get("message_thread_id").is_none());
+}
@@ -0,0 +1,221 @@
+//! Usage accounting of the paid APIs like DeepL. Each call is counted per module and per user in
+//! the daily counters, and refused when it would go over the daily quota in `[quota]` config.
+//! Modules without a quota rule are still counted, so the owner can review the spend by `/usage`.
+
+use chrono::{Local, NaiveDate};
+use redis::Commands;
+use teloxide::types::UserId;
+
+use crate::{
+    app::AppData,
+    config::{Config, QuotaRule},
+};
+
+/// Days the counters are kept, enough to review the spend of the last month
+const KEEP_DAYS: i64 = 31;
+
+/// The call is refused as it would use more units than the quota allows today.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaExceeded {
+    pub module: String,
+    /// The quota of the user instead of the whole module is met
+    pub per_user: bool,
+    pub limit: u64,
+    pub used: u64,
+}
+
+impl std::fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let scope = if self.per_user { "user" } else { "daily" };
+        write!(
+            f,
+            "{} {scope} quota is met, {} of {} units are used today",
+            self.module, self.used, self.limit
+        )
+    }
+}
+
+impl std::error::Error for QuotaExceeded {}
+
+/// Units, requests and the top users of a module in one day.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleUsage {
+    pub module: String,
+    pub units: u64,
+    pub requests: u64,
+    pub rule: Option<QuotaRule>,
+    pub top_users: Vec<(u64, u64)>,
+}
+
+/// The date of the counters, in the local time of the bot host like the quiet hours.
+pub fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+/// Units used by each user of the module
+fn units_key(module: &str, date: NaiveDate) -> String {
+    format!("USAGE:{module}:{date}")
+}
+
+/// Units used by each module
+fn total_key(date: NaiveDate) -> String {
+    format!("USAGE_TOTAL:{date}")
+}
+
+/// Requests made by each module
+fn requests_key(date: NaiveDate) -> String {
+    format!("USAGE_REQUESTS:{date}")
+}
+
+/// Charge the user `amount` units on the module today, or return [`QuotaExceeded`] and leave the
+/// counters as they were. The units are added before comparing with the quota, so the calls made
+/// at the same time can't all pass the check.
+pub fn charge(data: &AppData, module: &str, user: UserId, amount: u64) -> anyhow::Result<()> {
+    let rule = Config::get_global_config().quota.get(module).copied();
+    charge_on(
+        data,
+        module,
+        user,
+        amount,
+        rule.unwrap_or_default(),
+        today(),
+    )
+}
+
+fn charge_on(
+    data: &AppData,
+    module: &str,
+    user: UserId,
+    amount: u64,
+    rule: QuotaRule,
+    date: NaiveDate,
+) -> anyhow::Result<()> {
+    let keep = KEEP_DAYS * 24 * 60 * 60;
+    let (units, total, requests) = (units_key(module, date), total_key(date), requests_key(date));
+    let (user_used, module_used): (u64, u64) = redis::pipe()
+        .atomic()
+        .zincr(&units, user.0, amount)
+        .expire(&units, keep)
+        .ignore()
+        .zincr(&total, module, amount)
+        .expire(&total, keep)
+        .ignore()
+        .zincr(&requests, module, 1)
+        .ignore()
+        .expire(&requests, keep)
+        .ignore()
+        .query(&mut data.cacher.get_conn())?;
+
+    let limits = [
+        (false, rule.daily, module_used),
+        (true, rule.per_user, user_used),
+    ];
+    for (per_user, limit, used) in limits {
+        let Some(limit) = limit else {
+            continue;
+        };
+        if used > limit {
+            refund_on(data, module, user, amount, date)?;
+            return Err(QuotaExceeded {
+                module: module.to_string(),
+                per_user,
+                limit,
+                used: used - amount,
+            }
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Give back the units charged for the call that failed.
+pub fn refund(data: &AppData, module: &str, user: UserId, amount: u64) -> anyhow::Result<()> {
+    refund_on(data, module, user, amount, today())
+}
+
+fn refund_on(
+    data: &AppData,
+    module: &str,
+    user: UserId,
+    amount: u64,
+    date: NaiveDate,
+) -> anyhow::Result<()> {
+    let amount = -(amount as i64);
+    let () = redis::pipe()
+        .atomic()
+        .zincr(units_key(module, date), user.0, amount)
+        .ignore()
+        .zincr(total_key(date), module, amount)
+        .ignore()
+        .zincr(requests_key(date), module, -1)
+        .ignore()
+        .query(&mut data.cacher.get_conn())?;
+    Ok(())
+}
+
+/// Usage of every module on the date, the most used first, with at most `top` users each.
+pub fn report(data: &AppData, date: NaiveDate, top: isize) -> anyhow::Result<Vec<ModuleUsage>> {
+    let config = Config::get_global_config();
+    let mut conn = data.cacher.get_conn();
+    let totals: Vec<(String, u64)> = conn.zrevrange_withscores(total_key(date), 0, -1)?;
+    let requests: Vec<(String, u64)> = conn.zrevrange_withscores(requests_key(date), 0, -1)?;
+
+    let mut report = Vec::new();
+    for (module, units) in totals {
+        let requests = requests
+            .iter()
+            .find(|(name, _)| *name == module)
+            .map_or(0, |(_, requests)| *requests);
+        let top_users: Vec<(u64, i64)> = data.cacher.counter_top(&units_key(&module, date), top)?;
+        let top_users = top_users
+            .into_iter()
+            .map(|(user, units)| (user, units.max(0) as u64))
+            .collect();
+        report.push(ModuleUsage {
+            rule: config.quota.get(&module).copied(),
+            module,
+            units,
+            requests,
+            top_users,
+        });
+    }
+    Ok(report)
+}
+
+#[tokio::test]
+async fn test_quota() {
+    let data = crate::testkit::app_data(teloxide::Bot::new("1000:fake-token")).await;
+    let date = NaiveDate::from_ymd_opt(2024, 10, 1).unwrap();
+    let rule = QuotaRule {
+        daily: Some(100),
+        per_user: Some(60),
+    };
+    let (alice, bob) = (UserId(1), UserId(2));
+
+    charge_on(&data, "tr", alice, 50, rule, date).unwrap();
+    let err = charge_on(&data, "tr", alice, 20, rule, date).unwrap_err();
+    let err = err.downcast::<QuotaExceeded>().unwrap();
+    assert!(err.per_user);
+    assert_eq!((err.limit, err.used), (60, 50));
+
+    charge_on(&data, "tr", bob, 40, rule, date).unwrap();
+    let err = charge_on(&data, "tr", bob, 20, rule, date).unwrap_err();
+    let err = err.downcast::<QuotaExceeded>().unwrap();
+    assert!(!err.per_user);
+    assert_eq!((err.limit, err.used), (100, 90));
+    charge_on(&data, "tr", bob, 5, rule, date).unwrap();
+
+    // The refused calls are not counted
+    assert_eq!(
+        report(&data, date, 1).unwrap(),
+        [ModuleUsage {
+            module: "tr".to_string(),
+            units: 95,
+            requests: 3,
+            rule: None,
+            top_users: vec![(1, 50)],
+        }]
+    );
+    let yesterday = date.pred_opt().unwrap();
+    assert!(report(&data, yesterday, 1).unwrap().is_empty());
+}